@@ -1,11 +1,18 @@
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
 use http::HeaderValue;
-use hyper::body::HttpBody;
+use hyper::body::{Bytes, HttpBody};
 
 pub use memory::*;
+pub use replay::ReplayableBody;
+pub use text::Utf8Bytes;
 
 use crate::error::ProtocolResult;
 
 mod memory;
+mod replay;
+mod text;
 
 #[derive(Debug)]
 pub enum Body {
@@ -26,37 +33,91 @@ impl Body {
             Body::InMemory(m) => Ok(m),
             Body::Hyper(hyper_body) => {
                 let bytes = hyper::body::to_bytes(hyper_body).await?;
-                Ok(InMemoryBody::Bytes(bytes.to_vec()))
+                Ok(InMemoryBody::Bytes(bytes))
             }
         }
     }
 
+    /// Read the body into memory, decoding text by its content type. JSON is kept as bytes and only parsed
+    /// when deserialized, e.g. by `json::<T>()`, or when a middleware asks for it with `InMemoryResponseExt::json_value_mut`.
     pub async fn into_content_type(self, content_type: Option<&HeaderValue>) -> ProtocolResult<InMemoryBody> {
         match self {
             Body::InMemory(m) => Ok(m),
             Body::Hyper(hyper_body) => {
                 let bytes = hyper::body::to_bytes(hyper_body).await?;
-                let content_type = content_type.and_then(|t| t.to_str().ok()).and_then(|t| t.split(';').next());
-                match content_type {
-                    Some("application/json") => {
-                        let value = serde_json::from_slice(&bytes)?;
-                        Ok(InMemoryBody::Json(value))
-                    }
-                    Some("application/octet-stream") => Ok(InMemoryBody::Bytes(bytes.to_vec())),
-                    _ if bytes.is_empty() => Ok(InMemoryBody::Empty),
-                    _ => match String::from_utf8(bytes.to_vec()) {
-                        Ok(text) => Ok(InMemoryBody::Text(text)),
-                        Err(e) => {
-                            let bytes = e.into_bytes();
-                            Ok(InMemoryBody::Bytes(bytes))
-                        }
-                    },
+                if is_json(content_type) {
+                    return Ok(InMemoryBody::Bytes(bytes));
                 }
+                decode(bytes, content_type)
             }
         }
     }
 }
 
+/// Decode raw body bytes by their content type: JSON is parsed, other text is kept as a string.
+pub(crate) fn decode(bytes: Bytes, content_type: Option<&HeaderValue>) -> ProtocolResult<InMemoryBody> {
+    match media_type(content_type) {
+        Some("application/json") => {
+            let value = serde_json::from_slice(&bytes)?;
+            Ok(InMemoryBody::Json(value))
+        }
+        Some("application/octet-stream") => Ok(InMemoryBody::Bytes(bytes)),
+        _ if bytes.is_empty() => Ok(InMemoryBody::Empty),
+        // Checked in place, so the text shares the received buffer.
+        _ => match Utf8Bytes::from_utf8(bytes.clone()) {
+            Ok(text) => Ok(InMemoryBody::Text(text)),
+            Err(_) => Ok(InMemoryBody::Bytes(bytes)),
+        },
+    }
+}
+
+pub(crate) fn media_type(content_type: Option<&HeaderValue>) -> Option<&str> {
+    content_type.and_then(|t| t.to_str().ok()).and_then(|t| t.split(';').next())
+}
+
+pub(crate) fn is_json(content_type: Option<&HeaderValue>) -> bool {
+    media_type(content_type) == Some("application/json")
+}
+
+/// Clone a value if possible. Bodies that are still streaming from the network can't be cloned;
+/// convert them to memory first, e.g. with `ResponseExt::into_memory`.
+pub trait TryClone: Sized {
+    fn try_clone(&self) -> Option<Self>;
+}
+
+impl TryClone for InMemoryBody {
+    fn try_clone(&self) -> Option<Self> {
+        Some(self.clone())
+    }
+}
+
+impl TryClone for Body {
+    fn try_clone(&self) -> Option<Self> {
+        match self {
+            Body::InMemory(m) => Some(Body::InMemory(m.clone())),
+            Body::Hyper(_) => None,
+        }
+    }
+}
+
+impl<B: TryClone> TryClone for http::Request<B> {
+    fn try_clone(&self) -> Option<Self> {
+        let mut b = http::Request::builder().method(self.method().clone()).uri(self.uri().clone()).version(self.version());
+        *b.headers_mut()? = self.headers().clone();
+        *b.extensions_mut()? = self.extensions().clone();
+        b.body(self.body().try_clone()?).ok()
+    }
+}
+
+impl<B: TryClone> TryClone for http::Response<B> {
+    fn try_clone(&self) -> Option<Self> {
+        let mut b = http::Response::builder().status(self.status()).version(self.version());
+        *b.headers_mut()? = self.headers().clone();
+        *b.extensions_mut()? = self.extensions().clone();
+        b.body(self.body().try_clone()?).ok()
+    }
+}
+
 impl Default for Body {
     fn default() -> Self {
         Body::InMemory(InMemoryBody::default())
@@ -82,7 +143,7 @@ impl From<InMemoryBody> for hyper::Body {
     fn from(val: InMemoryBody) -> Self {
         match val {
             InMemoryBody::Empty => hyper::Body::empty(),
-            InMemoryBody::Text(s) => hyper::Body::from(s),
+            InMemoryBody::Text(s) => hyper::Body::from(s.into_bytes()),
             InMemoryBody::Bytes(b) => hyper::Body::from(b),
             InMemoryBody::Json(value) => {
                 let b = serde_json::to_vec(&value).unwrap();
@@ -98,6 +159,41 @@ impl From<hyper::Body> for Body {
     }
 }
 
+/// The body's data chunks as they arrive. In-memory bodies are a single chunk.
+/// Trailers aren't part of the stream; read them with `ResponseExt::trailers`.
+impl futures::Stream for Body {
+    type Item = ProtocolResult<Bytes>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match self.get_mut() {
+            Body::Hyper(body) => loop {
+                match Pin::new(&mut *body).poll_data(cx) {
+                    // Empty frames carry nothing for the caller; wait for the next one instead.
+                    Poll::Ready(Some(Ok(chunk))) if chunk.is_empty() => {}
+                    poll => return poll.map(|chunk| chunk.map(|r| r.map_err(Into::into))),
+                }
+            },
+            Body::InMemory(body) => {
+                let body = std::mem::take(body);
+                if body.is_empty() {
+                    Poll::Ready(None)
+                } else {
+                    Poll::Ready(Some(body.into_wire_bytes().map_err(Into::into)))
+                }
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self {
+            Body::Hyper(body) if body.is_end_stream() => (0, Some(0)),
+            Body::Hyper(_) => (0, None),
+            Body::InMemory(body) if body.is_empty() => (0, Some(0)),
+            Body::InMemory(_) => (1, Some(1)),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -110,4 +206,82 @@ mod tests {
         }));
         assert_eq!(serde_json::to_string(&body).expect("Unable to deserialize JSON"), r#"{"foo":"bar"}"#);
     }
+
+    #[test]
+    fn test_http_interop() {
+        let req = http::Request::builder().method("POST").uri("/a").body(b"hello".to_vec()).unwrap();
+        let req: crate::InMemoryRequest = req.map(Into::into);
+        assert!(matches!(req.body(), InMemoryBody::Bytes(b) if b == &b"hello"[..]));
+
+        let res = http::Response::builder().status(201).body(InMemoryBody::Json(json!({"a": 1}))).unwrap();
+        let res: http::Response<Vec<u8>> = res.map(Into::into);
+        assert_eq!(res.status(), 201);
+        assert_eq!(res.body(), br#"{"a":1}"#);
+    }
+
+    #[tokio::test]
+    async fn test_into_memory_shares_buffer() {
+        let bytes = Bytes::from(vec![0; 1024]);
+        let body = Body::Hyper(hyper::Body::from(bytes.clone())).into_memory().await.unwrap();
+        let InMemoryBody::Bytes(b) = &body else { panic!("Expected bytes") };
+        assert_eq!(b.as_ptr(), bytes.as_ptr());
+        assert_eq!(body.bytes().unwrap().as_ptr(), bytes.as_ptr());
+    }
+
+    #[test]
+    fn test_try_clone() {
+        let res = http::Response::builder().header("x-a", "1").body(Body::InMemory(InMemoryBody::Text("hi".into()))).unwrap();
+        let cloned = res.try_clone().unwrap();
+        assert_eq!(cloned.headers()["x-a"], "1");
+        assert!(matches!(cloned.body(), Body::InMemory(InMemoryBody::Text(t)) if t == "hi"));
+
+        let streaming = http::Response::new(Body::Hyper(hyper::Body::empty()));
+        assert!(streaming.try_clone().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_json_parsed_lazily() {
+        let content_type = HeaderValue::from_static("application/json");
+        let body = Body::Hyper(hyper::Body::from(r#"{"b": 1, "a": 2.50}"#));
+        let body = body.into_content_type(Some(&content_type)).await.unwrap();
+        assert!(matches!(&body, InMemoryBody::Bytes(b) if b == &br#"{"b": 1, "a": 2.50}"#[..]));
+        assert_eq!(body.json::<serde_json::Value>().unwrap(), json!({"a": 2.5, "b": 1}));
+
+        let body = Body::Hyper(hyper::Body::from("{"));
+        assert!(body.into_content_type(Some(&content_type)).await.unwrap().json::<serde_json::Value>().is_err());
+    }
+
+    #[test]
+    fn test_content_sha256_matches_wire_bytes() {
+        let body = InMemoryBody::Json(json!({"foo": "bar"}));
+        let hash = body.content_sha256().unwrap();
+        let wire = body.into_wire_bytes().unwrap();
+        assert_eq!(wire.as_ref(), br#"{"foo":"bar"}"#);
+        assert_eq!(hash.as_slice(), ring::digest::digest(&ring::digest::SHA256, &wire).as_ref());
+    }
+
+    #[tokio::test]
+    async fn test_stream() {
+        use futures::{poll, Stream, StreamExt};
+
+        let (mut sender, body) = hyper::Body::channel();
+        let mut body = Body::Hyper(body);
+        assert_eq!(body.size_hint(), (0, None));
+        // Nothing is buffered ahead of the sender, so polling waits for it.
+        assert!(poll!(body.next()).is_pending());
+        tokio::spawn(async move {
+            sender.send_data(Bytes::from("a")).await.unwrap();
+            sender.send_data(Bytes::new()).await.unwrap();
+            sender.send_data(Bytes::from("b")).await.unwrap();
+        });
+        assert_eq!(body.next().await.unwrap().unwrap(), "a");
+        assert_eq!(body.next().await.unwrap().unwrap(), "b");
+        assert!(body.next().await.is_none());
+
+        let mut body = Body::InMemory(InMemoryBody::Json(json!({"a": 1})));
+        assert_eq!(body.size_hint(), (1, Some(1)));
+        assert_eq!(body.next().await.unwrap().unwrap(), r#"{"a":1}"#);
+        assert_eq!(body.size_hint(), (0, Some(0)));
+        assert!(body.next().await.is_none());
+    }
 }
@@ -1,6 +1,13 @@
+use crate::body::Utf8Bytes;
 use crate::sanitize::sanitize_value;
 use crate::InMemoryResult;
+#[cfg(feature = "recorder")]
+use base64::engine::general_purpose::STANDARD;
+#[cfg(feature = "recorder")]
+use base64::Engine;
 use hyper::body::Bytes;
+#[cfg(not(target_arch = "wasm32"))]
+use ring::digest;
 use serde::de::{DeserializeOwned, Error};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
@@ -15,8 +22,8 @@ pub enum InMemoryBody {
     // json must come before bytes, otherwise Recorder deserialization gets messed up, see
     // response::memory::test_deserialize
     Json(Value),
-    Bytes(Vec<u8>),
-    Text(String),
+    Bytes(Bytes),
+    Text(Utf8Bytes),
 }
 
 impl TryInto<String> for InMemoryBody {
@@ -25,8 +32,8 @@ impl TryInto<String> for InMemoryBody {
     fn try_into(self) -> InMemoryResult<String> {
         match self {
             InMemoryBody::Empty => Ok(String::new()),
-            InMemoryBody::Bytes(b) => String::from_utf8(b).map_err(std::convert::Into::into),
-            InMemoryBody::Text(s) => Ok(s),
+            InMemoryBody::Bytes(b) => String::from_utf8(b.into()).map_err(std::convert::Into::into),
+            InMemoryBody::Text(s) => Ok(s.into()),
             InMemoryBody::Json(val) => match val {
                 Value::String(s) => Ok(s),
                 _ => serde_json::to_string(&val).map_err(std::convert::Into::into),
@@ -41,8 +48,8 @@ impl TryInto<Bytes> for InMemoryBody {
     fn try_into(self) -> InMemoryResult<Bytes> {
         match self {
             InMemoryBody::Empty => Ok(Bytes::new()),
-            InMemoryBody::Bytes(b) => Ok(Bytes::from(b)),
-            InMemoryBody::Text(s) => Ok(Bytes::from(s)),
+            InMemoryBody::Bytes(b) => Ok(b),
+            InMemoryBody::Text(s) => Ok(s.into_bytes()),
             InMemoryBody::Json(val) => {
                 if let Value::Array(a) = &val {
                     if a.iter().all(|v| v.is_number()) {
@@ -53,7 +60,7 @@ impl TryInto<Bytes> for InMemoryBody {
                         return Ok(Bytes::from(bytes));
                     }
                 }
-                Ok(Bytes::from(serde_json::to_string(&val)?))
+                Ok(Bytes::from(serde_json::to_vec(&val)?))
             }
         }
     }
@@ -82,13 +89,93 @@ impl InMemoryBody {
         }
     }
 
+    /// Deserialize without taking ownership, so `T` can borrow strings from the body, e.g. `&str` fields.
+    /// Strings containing escapes can't be borrowed; use `Cow<str>` fields to accept those too.
+    pub fn json_borrowed<'de, T: Deserialize<'de>>(&'de self) -> serde_json::Result<T> {
+        match self {
+            InMemoryBody::Empty => Err(serde_json::Error::custom("Empty body")),
+            InMemoryBody::Bytes(b) => serde_json::from_slice(b),
+            InMemoryBody::Text(t) => serde_json::from_str(t),
+            InMemoryBody::Json(v) => T::deserialize(v),
+        }
+    }
+
     pub fn bytes(self) -> InMemoryResult<Bytes> {
         self.try_into()
     }
 
+    /// The exact bytes that are sent on the wire for this body.
+    pub fn into_wire_bytes(self) -> serde_json::Result<Bytes> {
+        Ok(match self {
+            InMemoryBody::Empty => Bytes::new(),
+            InMemoryBody::Bytes(b) => b,
+            InMemoryBody::Text(s) => s.into_bytes(),
+            InMemoryBody::Json(val) => Bytes::from(serde_json::to_vec(&val)?),
+        })
+    }
+
+    /// SHA-256 of the bytes that are sent on the wire. Because the body is serialized the same way
+    /// right before dispatch, signing middlewares can rely on this matching what the server receives.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn content_sha256(&self) -> serde_json::Result<[u8; 32]> {
+        let digest = match self {
+            InMemoryBody::Empty => digest::digest(&digest::SHA256, b""),
+            InMemoryBody::Bytes(b) => digest::digest(&digest::SHA256, b),
+            InMemoryBody::Text(s) => digest::digest(&digest::SHA256, s.as_bytes()),
+            InMemoryBody::Json(val) => digest::digest(&digest::SHA256, &serde_json::to_vec(val)?),
+        };
+        let mut out = [0u8; 32];
+        out.copy_from_slice(digest.as_ref());
+        Ok(out)
+    }
+
     pub fn sanitize(&mut self) {
-        if let InMemoryBody::Json(value) = self {
-            sanitize_value(value);
+        match self {
+            InMemoryBody::Json(value) => sanitize_value(value),
+            // JSON responses are kept as bytes until parsed, so sanitize those that parse as JSON too.
+            InMemoryBody::Bytes(bytes) => {
+                if let Ok(mut value) = serde_json::from_slice::<Value>(bytes) {
+                    let original = value.clone();
+                    sanitize_value(&mut value);
+                    if value != original {
+                        *bytes = serde_json::to_vec(&value).expect("A JSON value always serializes").into();
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The `body_encoding` of recorded bodies stored as base64.
+#[cfg(feature = "recorder")]
+pub(crate) const BASE64_ENCODING: &str = "base64";
+
+#[cfg(feature = "recorder")]
+impl InMemoryBody {
+    /// The body as base64, if recordings should store it that way: raw bytes, which JSON would otherwise hold as an
+    /// array of numbers that can't be told apart from a JSON body.
+    pub(crate) fn recorded_base64(&self) -> Option<String> {
+        match self {
+            InMemoryBody::Bytes(b) => Some(STANDARD.encode(b)),
+            _ => None,
+        }
+    }
+
+    /// Undo [`Self::recorded_base64`] for a body read from a recording with the given `body_encoding`.
+    pub(crate) fn decode_recorded<E: Error>(self, encoding: Option<&str>) -> Result<Self, E> {
+        match (encoding, self) {
+            (None, body) => Ok(body),
+            (Some(BASE64_ENCODING), InMemoryBody::Json(Value::String(s))) => {
+                let bytes = STANDARD.decode(s).map_err(|e| E::custom(format!("Invalid base64 body: {e}")))?;
+                Ok(InMemoryBody::Bytes(bytes.into()))
+            }
+            (Some(BASE64_ENCODING), InMemoryBody::Text(s)) => {
+                let bytes = STANDARD.decode(s.as_str()).map_err(|e| E::custom(format!("Invalid base64 body: {e}")))?;
+                Ok(InMemoryBody::Bytes(bytes.into()))
+            }
+            (Some(BASE64_ENCODING), InMemoryBody::Empty) => Ok(InMemoryBody::Bytes(Bytes::new())),
+            (Some(encoding), _) => Err(E::custom(format!("Unsupported body encoding `{encoding}`"))),
         }
     }
 }
@@ -100,7 +187,7 @@ impl std::hash::Hash for InMemoryBody {
             // InMemoryBody::Empty => state.write_u8(0),
             InMemoryBody::Bytes(b) => {
                 // state.write_u8(1);
-                state.write(b.as_slice());
+                state.write(b);
             }
             InMemoryBody::Text(s) => {
                 // state.write_u8(2);
@@ -118,14 +205,40 @@ impl std::hash::Hash for InMemoryBody {
     }
 }
 
-impl Into<InMemoryBody> for String {
-    fn into(self) -> InMemoryBody {
-        InMemoryBody::Text(self)
+impl From<String> for InMemoryBody {
+    fn from(value: String) -> Self {
+        InMemoryBody::Text(value.into())
     }
 }
 
-impl Into<InMemoryBody> for Vec<u8> {
-    fn into(self) -> InMemoryBody {
-        InMemoryBody::Bytes(self)
+impl From<Utf8Bytes> for InMemoryBody {
+    fn from(value: Utf8Bytes) -> Self {
+        InMemoryBody::Text(value)
+    }
+}
+
+/// Lets plain `http` types with byte bodies convert with `.map(Into::into)`,
+/// e.g. `http::Request<Vec<u8>>` into `InMemoryRequest`.
+impl From<Vec<u8>> for InMemoryBody {
+    fn from(value: Vec<u8>) -> Self {
+        InMemoryBody::Bytes(value.into())
+    }
+}
+
+impl From<Bytes> for InMemoryBody {
+    fn from(value: Bytes) -> Self {
+        InMemoryBody::Bytes(value)
+    }
+}
+
+/// The bytes sent on the wire. Lets `InMemoryResponse` convert to `http::Response<Vec<u8>>` with `.map(Into::into)`.
+impl From<InMemoryBody> for Vec<u8> {
+    fn from(value: InMemoryBody) -> Self {
+        match value {
+            InMemoryBody::Empty => Vec::new(),
+            InMemoryBody::Bytes(b) => b.into(),
+            InMemoryBody::Text(s) => s.into_bytes().into(),
+            InMemoryBody::Json(val) => serde_json::to_vec(&val).expect("A JSON value always serializes"),
+        }
     }
 }
@@ -0,0 +1,188 @@
+use std::fmt::{Debug, Formatter};
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+#[cfg(not(target_arch = "wasm32"))]
+use futures::stream;
+use futures::Stream;
+use hyper::body::Bytes;
+#[cfg(not(target_arch = "wasm32"))]
+use tokio::io::AsyncReadExt;
+
+use crate::error::{ProtocolError, ProtocolResult};
+
+/// Files are read in chunks of this size.
+#[cfg(not(target_arch = "wasm32"))]
+const CHUNK_SIZE: usize = 64 * 1024;
+
+type Factory = dyn Fn() -> hyper::Body + Send + Sync;
+
+#[derive(Clone)]
+enum Source {
+    Factory(Arc<Factory>),
+    Once(Arc<Mutex<Option<hyper::Body>>>),
+}
+
+/// A streamed request body that middlewares can send again, e.g. to retry or follow a redirect.
+/// Attach it with [`crate::RequestBuilder::replayable_body`]: unlike other streamed requests, the request then
+/// goes through the client's middlewares, and the transport makes a fresh stream for each attempt.
+///
+/// Middlewares see the request with an empty body, since the stream is only read by the transport.
+/// ```no_run
+/// # use httpclient::{Client, ReplayableBody};
+/// # async fn f(client: Client) -> httpclient::InMemoryResult<()> {
+/// let res = client.put("/uploads/video.mp4").replayable_body(ReplayableBody::file("video.mp4")).await?;
+/// # Ok(())
+/// # }
+/// ```
+/// A single-use stream can be sent with [`ReplayableBody::once`], but only through middlewares that don't
+/// resend requests; see [`crate::Middleware::replays_requests`].
+#[derive(Clone)]
+pub struct ReplayableBody {
+    source: Source,
+    len: Option<u64>,
+}
+
+impl Debug for ReplayableBody {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ReplayableBody")
+            .field("replayable", &self.is_replayable())
+            .field("len", &self.len)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ReplayableBody {
+    /// Make the stream to send by calling `f`, once per attempt.
+    pub fn new<F, S, O, E>(f: F) -> Self
+    where
+        F: Fn() -> S + Send + Sync + 'static,
+        S: Stream<Item = Result<O, E>> + Send + 'static,
+        O: Into<Bytes> + 'static,
+        E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    {
+        Self {
+            source: Source::Factory(Arc::new(move || hyper::Body::wrap_stream(f()))),
+            len: None,
+        }
+    }
+
+    /// Read the file at `path`, reopening it for each attempt. Its current size is sent as the `Content-Length`.
+    #[cfg(not(target_arch = "wasm32"))]
+    #[must_use]
+    pub fn file(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let len = std::fs::metadata(&path).ok().map(|m| m.len());
+        let body = Self::new(move || {
+            let path = path.clone();
+            stream::try_unfold(None, move |file: Option<tokio::fs::File>| {
+                let path = path.clone();
+                async move {
+                    let mut file = match file {
+                        Some(file) => file,
+                        None => tokio::fs::File::open(&path).await?,
+                    };
+                    let mut chunk = vec![0; CHUNK_SIZE];
+                    let n = file.read(&mut chunk).await?;
+                    if n == 0 {
+                        return Ok::<_, std::io::Error>(None);
+                    }
+                    chunk.truncate(n);
+                    Ok(Some((Bytes::from(chunk), Some(file))))
+                }
+            })
+        });
+        Self { len, ..body }
+    }
+
+    /// A stream that can only be sent once. Sending it through a middleware that resends requests fails
+    /// with [`ProtocolError::NotReplayable`] before anything is sent.
+    pub fn once<S, O, E>(stream: S) -> Self
+    where
+        S: Stream<Item = Result<O, E>> + Send + 'static,
+        O: Into<Bytes> + 'static,
+        E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    {
+        Self {
+            source: Source::Once(Arc::new(Mutex::new(Some(hyper::Body::wrap_stream(stream))))),
+            len: None,
+        }
+    }
+
+    /// Send a `Content-Length` of `len` instead of sending the body chunked. The stream must be exactly that long.
+    #[must_use]
+    pub fn content_length(mut self, len: u64) -> Self {
+        self.len = Some(len);
+        self
+    }
+
+    #[must_use]
+    pub fn is_replayable(&self) -> bool {
+        matches!(self.source, Source::Factory(_))
+    }
+
+    /// The stream for the next attempt, and its length if known.
+    pub(crate) fn body(&self) -> ProtocolResult<(hyper::Body, Option<u64>)> {
+        let body = match &self.source {
+            Source::Factory(f) => f(),
+            Source::Once(body) => body
+                .lock()
+                .expect("Replayable body lock poisoned")
+                .take()
+                .ok_or_else(|| ProtocolError::NotReplayable("Body was already sent".to_string()))?,
+        };
+        Ok((body, self.len))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use tokio::io::AsyncWriteExt;
+
+    use crate::middleware::Retry;
+    use crate::{Client, ResponseExt};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_replayable_body() {
+        // Answers the first request with a 503, and the second with the request it received.
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            for status in ["503 Service Unavailable", "200 OK"] {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut received = Vec::new();
+                while !received.ends_with(b"hello") {
+                    let mut buf = [0u8; 1024];
+                    let n = stream.read(&mut buf).await.unwrap();
+                    received.extend_from_slice(&buf[..n]);
+                }
+                let head = format!("HTTP/1.1 {status}\r\nconnection: close\r\ncontent-length: {}\r\n\r\n", received.len());
+                stream.write_all(head.as_bytes()).await.unwrap();
+                stream.write_all(&received).await.unwrap();
+            }
+        });
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let body = ReplayableBody::new(move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+            stream::iter([Ok::<_, std::io::Error>("hel"), Ok("lo")])
+        })
+        .content_length(5);
+        let client = Client::new().with_middleware(Retry::new());
+        let url = format!("http://127.0.0.1:{port}/upload");
+        let res = client.put(&url).replayable_body(body).send().await.unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        let text = res.text().await.unwrap();
+        assert!(text.contains("content-length: 5\r\n"));
+        assert!(text.ends_with("\r\n\r\nhello"));
+
+        let once = ReplayableBody::once(stream::iter([Ok::<_, std::io::Error>("hello")]));
+        let err = client.put(&url).replayable_body(once).send().await.unwrap_err();
+        assert!(matches!(err, ProtocolError::NotReplayable(_)));
+    }
+}
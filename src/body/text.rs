@@ -0,0 +1,155 @@
+use std::borrow::Borrow;
+use std::fmt::{Debug, Display, Formatter};
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+use std::str::Utf8Error;
+
+use hyper::body::Bytes;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// A string stored as [`Bytes`], the text of an [`crate::InMemoryBody::Text`] body. Cloning it, sending it,
+/// or receiving it from the network shares the buffer instead of copying it. Derefs to `str`.
+#[derive(Clone, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Utf8Bytes(Bytes);
+
+impl Utf8Bytes {
+    #[must_use]
+    pub const fn from_static(s: &'static str) -> Self {
+        Self(Bytes::from_static(s.as_bytes()))
+    }
+
+    /// Use `bytes` as text without copying them, if they are valid UTF-8.
+    pub fn from_utf8(bytes: Bytes) -> Result<Self, Utf8Error> {
+        std::str::from_utf8(&bytes)?;
+        Ok(Self(bytes))
+    }
+
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        // SAFETY: every constructor checks the bytes are UTF-8, or takes them from a `str`.
+        unsafe { std::str::from_utf8_unchecked(&self.0) }
+    }
+
+    #[must_use]
+    pub fn into_bytes(self) -> Bytes {
+        self.0
+    }
+}
+
+impl Deref for Utf8Bytes {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for Utf8Bytes {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<[u8]> for Utf8Bytes {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl Borrow<str> for Utf8Bytes {
+    fn borrow(&self) -> &str {
+        self.as_str()
+    }
+}
+
+/// Hashes like `str`, as `Borrow<str>` requires.
+impl Hash for Utf8Bytes {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state);
+    }
+}
+
+impl Debug for Utf8Bytes {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl Display for Utf8Bytes {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(self.as_str(), f)
+    }
+}
+
+impl From<String> for Utf8Bytes {
+    fn from(value: String) -> Self {
+        Self(Bytes::from(value))
+    }
+}
+
+impl From<&str> for Utf8Bytes {
+    fn from(value: &str) -> Self {
+        Self(Bytes::copy_from_slice(value.as_bytes()))
+    }
+}
+
+impl From<Utf8Bytes> for Bytes {
+    fn from(value: Utf8Bytes) -> Self {
+        value.0
+    }
+}
+
+impl From<Utf8Bytes> for String {
+    fn from(value: Utf8Bytes) -> Self {
+        // SAFETY: the bytes are UTF-8, see `Utf8Bytes::as_str`.
+        unsafe { String::from_utf8_unchecked(value.0.into()) }
+    }
+}
+
+impl PartialEq<str> for Utf8Bytes {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for Utf8Bytes {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl PartialEq<String> for Utf8Bytes {
+    fn eq(&self, other: &String) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl Serialize for Utf8Bytes {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Utf8Bytes {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Into::into)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_utf8_bytes() {
+        let bytes = Bytes::from("héllo");
+        let text = Utf8Bytes::from_utf8(bytes.clone()).unwrap();
+        assert_eq!(text, "héllo");
+        assert_eq!(text.as_ptr(), bytes.as_ptr());
+        assert!(Utf8Bytes::from_utf8(Bytes::from_static(&[0xff])).is_err());
+        assert_eq!(String::from(text.clone()), "héllo");
+        assert_eq!(format!("{text:?}"), r#""héllo""#);
+        assert_eq!(serde_json::to_string(&text).unwrap(), r#""héllo""#);
+        assert_eq!(serde_json::from_str::<Utf8Bytes>(r#""héllo""#).unwrap(), text);
+    }
+}
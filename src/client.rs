@@ -1,21 +1,101 @@
+#[cfg(not(target_arch = "wasm32"))]
+use std::collections::HashMap;
 use std::fmt::Formatter;
+#[cfg(not(target_arch = "wasm32"))]
+use std::net::IpAddr;
 use std::str::FromStr;
-use std::sync::{Arc, OnceLock};
+use std::sync::Arc;
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
 
-use http::{Method};
+use http::header::{ACCEPT, CONTENT_TYPE, USER_AGENT};
+use http::Method;
 use http::Uri;
+#[cfg(not(target_arch = "wasm32"))]
 use hyper::client::HttpConnector;
+#[cfg(not(target_arch = "wasm32"))]
 use hyper_rustls::HttpsConnector;
 
-use crate::middleware::{Middleware, MiddlewareStack};
-use crate::RequestBuilder;
+use crate::error::{ProtocolError, ProtocolResult};
+use crate::middleware::{Follow, Middleware, MiddlewareStack, Next, Retry};
+#[cfg(feature = "recorder")]
+use crate::middleware::{Recorder, RecorderMode};
+use crate::{InMemoryRequest, ReplayableBody, RequestBuilder, Response};
 
-static DEFAULT_HTTPS_CONNECTOR: OnceLock<HttpsConnector<HttpConnector>> = OnceLock::new();
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) use connector::blocked_reason;
+#[cfg(not(target_arch = "wasm32"))]
+pub use connector::AddressSelection;
+#[cfg(not(target_arch = "wasm32"))]
+use connector::{Addresses, Connector, TimedResolver};
+#[cfg(not(target_arch = "wasm32"))]
+pub use dns_cache::DnsCache;
+#[cfg(target_arch = "wasm32")]
+pub use fetch::Fetch;
+#[cfg(not(target_arch = "wasm32"))]
+pub use identity::ClientIdentity;
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) use pool::ConnectTiming;
+#[cfg(not(target_arch = "wasm32"))]
+use pool::InstrumentedConnector;
+#[cfg(not(target_arch = "wasm32"))]
+pub use pool::{ConnectionInfo, HostPoolStats, PoolMetrics};
+use profile::BuiltWith;
+pub use profile::Profile;
+#[cfg(all(feature = "tower", not(target_arch = "wasm32")))]
+pub use service::ServiceTransport;
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) use timeouts::idle_read_timeout;
+pub use timeouts::Timeouts;
+#[cfg(not(target_arch = "wasm32"))]
+pub use tls::TlsInfo;
+pub(crate) use transport::send_custom;
+pub use transport::Transport;
 
-fn default_https_connector() -> &'static HttpsConnector<HttpConnector> {
-    DEFAULT_HTTPS_CONNECTOR.get_or_init(|| hyper_rustls::HttpsConnectorBuilder::new().with_native_roots().https_or_http().enable_http1().build())
+#[cfg(not(target_arch = "wasm32"))]
+mod connector;
+#[cfg(not(target_arch = "wasm32"))]
+mod dns_cache;
+#[cfg(target_arch = "wasm32")]
+mod fetch;
+#[cfg(not(target_arch = "wasm32"))]
+mod identity;
+#[cfg(not(target_arch = "wasm32"))]
+mod pool;
+mod profile;
+#[cfg(all(feature = "tower", not(target_arch = "wasm32")))]
+mod service;
+mod timeouts;
+#[cfg(not(target_arch = "wasm32"))]
+mod tls;
+mod transport;
+
+#[cfg(not(target_arch = "wasm32"))]
+static DEFAULT_HTTPS_CONNECTOR: OnceLock<HttpsConnector<HttpConnector<TimedResolver>>> = OnceLock::new();
+
+#[cfg(not(target_arch = "wasm32"))]
+fn default_https_connector() -> &'static HttpsConnector<HttpConnector<TimedResolver>> {
+    DEFAULT_HTTPS_CONNECTOR.get_or_init(|| connector::https_connector(connector::default_http_connector(), None))
+}
+
+/// Join a base url and a path, so that exactly one slash separates them.
+/// A query or fragment on its own, like `?page=2`, is appended to the base as is.
+fn join_url(base: &str, path: &str) -> String {
+    if path.is_empty() || path.starts_with(['?', '#']) {
+        return format!("{base}{path}");
+    }
+    format!("{}/{}", base.trim_end_matches('/'), path.trim_start_matches('/'))
 }
 
+/// Binds a single request's connection to a local address, overriding the client's. See [`RequestBuilder::local_address`].
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct LocalAddress(pub IpAddr);
+
+#[cfg(not(target_arch = "wasm32"))]
+type HyperClient = hyper::Client<InstrumentedConnector<Connector>, hyper::Body>;
+
 static APP_USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"),);
 
 #[derive(Clone)]
@@ -23,7 +103,28 @@ pub struct Client {
     base_url: Option<String>,
     default_headers: Vec<(String, String)>,
     pub(crate) middlewares: MiddlewareStack,
-    pub(crate) inner: hyper::Client<HttpsConnector<HttpConnector>, hyper::Body>,
+    #[cfg(not(target_arch = "wasm32"))]
+    http_connector: HttpConnector<TimedResolver>,
+    #[cfg(not(target_arch = "wasm32"))]
+    /// A connector set with `with_tls_connector`, used instead of one built from `http_connector`.
+    tls_connector: Option<HttpsConnector<HttpConnector>>,
+    #[cfg(not(target_arch = "wasm32"))]
+    /// The first connector setting made after `with_tls_connector`, which couldn't be applied. Requests fail with it.
+    unapplied_setting: Option<&'static str>,
+    #[cfg(not(target_arch = "wasm32"))]
+    addresses: Addresses,
+    #[cfg(not(target_arch = "wasm32"))]
+    identity: Option<ClientIdentity>,
+    #[cfg(not(target_arch = "wasm32"))]
+    pool: PoolMetrics,
+    pub(crate) timeouts: Timeouts,
+    profiles: Vec<(String, Arc<Profile>)>,
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) inner: HyperClient,
+    #[cfg(not(target_arch = "wasm32"))]
+    /// Clients bound to other local addresses, for requests that override it, each with its own connections.
+    egress: Arc<Mutex<HashMap<IpAddr, HyperClient>>>,
+    pub(crate) transport: Option<Arc<dyn Transport>>,
 }
 
 /**
@@ -45,41 +146,303 @@ impl std::fmt::Debug for Client {
 impl Client {
     #[must_use]
     pub fn new() -> Self {
-        let https = default_https_connector().clone();
+        #[cfg(not(target_arch = "wasm32"))]
+        let https = Connector::Default(default_https_connector().clone(), Arc::default());
+        #[cfg(not(target_arch = "wasm32"))]
+        let pool = PoolMetrics::default();
         Client {
             base_url: None,
             default_headers: vec![("User-Agent".to_string(), APP_USER_AGENT.to_string())],
             middlewares: Vec::new(),
-            inner: hyper::Client::builder().build(https),
+            #[cfg(not(target_arch = "wasm32"))]
+            http_connector: connector::default_http_connector(),
+            #[cfg(not(target_arch = "wasm32"))]
+            tls_connector: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            unapplied_setting: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            addresses: Addresses::default(),
+            #[cfg(not(target_arch = "wasm32"))]
+            identity: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            inner: hyper::Client::builder().build(InstrumentedConnector::new(https, pool.clone())),
+            #[cfg(not(target_arch = "wasm32"))]
+            egress: Arc::default(),
+            #[cfg(not(target_arch = "wasm32"))]
+            pool,
+            timeouts: Timeouts::default(),
+            profiles: Vec::new(),
+            transport: None,
         }
     }
 
+    /// A client with conservative defaults: a 10 second connect timeout, a 30 second limit on each request
+    /// (see [`Timeouts`]), and no retries. It doesn't follow any redirects, not even to the same origin, so a redirect
+    /// can't send requests to another origin: 3xx responses are returned as they are. Add [`Follow`] to follow them.
+    /// Connections use TLS 1.2 or newer, like every client.
+    #[must_use]
+    pub fn strict() -> Self {
+        Client::new().timeouts(Timeouts {
+            connect: Some(Duration::from_secs(10)),
+            total: Some(Duration::from_secs(30)),
+            ..Timeouts::default()
+        })
+    }
+
+    /// A client that rides out transient failures: it follows redirects and retries throttled,
+    /// server-error, and stalled responses with backoff (see [`Retry`]), within a 2 minute limit per request.
+    #[must_use]
+    pub fn resilient() -> Self {
+        Client::new()
+            .timeouts(Timeouts {
+                connect: Some(Duration::from_secs(10)),
+                total: Some(Duration::from_secs(2 * 60)),
+                ..Timeouts::default()
+            })
+            .with_middleware(Follow)
+            .with_middleware(Retry::new().header_timeout(Duration::from_secs(30)))
+    }
+
+    /// A client for tests, which replays recorded responses and never touches the network.
+    /// Requests without a recording fail. See [`Recorder`].
+    #[cfg(feature = "recorder")]
+    #[must_use]
+    pub fn for_tests() -> Self {
+        Client::new().with_middleware(Recorder::new().mode(RecorderMode::ForceNoRequests))
+    }
+
     /// Set a `base_url` so you can pass relative paths instead of full URLs.
+    ///
+    /// # Panics
+    /// If `base_url` is not an absolute URL with a scheme and host. Use [`Client::try_base_url`] to handle the error.
     #[must_use]
-    pub fn base_url(mut self, base_url: &str) -> Self {
+    pub fn base_url(self, base_url: &str) -> Self {
+        self.try_base_url(base_url).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Set a `base_url`, returning an error if it is not an absolute URL with a scheme and host.
+    pub fn try_base_url(mut self, base_url: &str) -> ProtocolResult<Self> {
+        let uri = Uri::from_str(base_url).map_err(|e| ProtocolError::InvalidUrl(format!("base_url `{base_url}` is not a valid URL: {e}")))?;
+        if uri.scheme().is_none() || uri.authority().is_none() {
+            return Err(ProtocolError::InvalidUrl(format!(
+                "base_url `{base_url}` must be an absolute URL with a scheme and host, e.g. `https://{base_url}`"
+            )));
+        }
         self.base_url = Some(base_url.to_string());
+        Ok(self)
+    }
+
+    /// A derived client for a sub-path of this one's `base_url`, e.g. `client.scoped("/v2")`, or for another absolute URL.
+    /// It shares the connection pool and starts with this client's default headers and middlewares, which can then
+    /// be overridden with e.g. `set_default_header`, without affecting this client.
+    ///
+    /// # Panics
+    /// If this client has no `base_url` and `path` is not an absolute URL.
+    #[must_use]
+    pub fn scoped(&self, path: &str) -> Self {
+        let base_url = self.build_uri(path).to_string();
+        self.clone().base_url(&base_url)
+    }
+
+    #[must_use]
+    pub fn with_middleware<T: Middleware + 'static>(self, middleware: T) -> Self {
+        self.middleware(middleware)
+    }
+
+    #[must_use]
+    pub fn middleware<T: Middleware + 'static>(self, middleware: T) -> Self {
+        // Keep middlewares that check each redirect hop, e.g. from `https_only`, after the one following redirects.
+        let index = if middleware.follows_redirects() {
+            self.middlewares.iter().position(|m| m.checks_redirect_hops()).unwrap_or(self.middlewares.len())
+        } else {
+            self.middlewares.len()
+        };
+        self.insert_middleware(index, Arc::new(middleware))
+    }
+
+    /// Insert a middleware at `index` in the stack.
+    pub(crate) fn insert_middleware(mut self, index: usize, middleware: Arc<dyn Middleware>) -> Self {
+        // A guard like SsrfGuard also checks the addresses connections are made to, which only the connector sees.
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(guard) = middleware.guards_connections() {
+            self.addresses.guards.push(guard);
+            self = self.rebuild_inner();
+        }
+        self.middlewares.insert(index, middleware);
         self
     }
 
+    /// Send requests with `transport` instead of over the network. Middlewares still run first. See [`Transport`].
     #[must_use]
-    pub fn with_middleware<T: Middleware + 'static>(mut self, middleware: T) -> Self {
-        self.middlewares.push(Arc::new(middleware));
+    pub fn transport<T: Transport + 'static>(mut self, transport: T) -> Self {
+        self.transport = Some(Arc::new(transport));
         self
     }
 
+    /// Configure every timeout at once. See [`Timeouts`] for where each one applies.
     #[must_use]
-    pub fn middleware<T: Middleware + 'static>(mut self, middleware: T) -> Self {
-        self.middlewares.push(Arc::new(middleware));
+    pub fn timeouts(mut self, timeouts: Timeouts) -> Self {
+        #[cfg(not(target_arch = "wasm32"))]
+        let connect_changed = timeouts.connect != self.timeouts.connect;
+        self.timeouts = timeouts;
+        #[cfg(not(target_arch = "wasm32"))]
+        if connect_changed {
+            return self.configure_connector("timeouts", |c| c.set_connect_timeout(timeouts.connect));
+        }
         self
     }
+}
 
+/// Connection settings, which the browser controls instead on wasm32.
+#[cfg(not(target_arch = "wasm32"))]
+impl Client {
     #[must_use]
     /// Set a custom TLS connector to use for making requests.
+    ///
+    /// It replaces the built-in connector, so the socket and address settings made before, like `connect_timeout`,
+    /// `local_address`, `resolve_to` or `client_identity`, don't apply to it. Making them after it makes every request
+    /// fail with [`ProtocolError::InvalidInput`]. Configure the connector itself instead.
     pub fn with_tls_connector(mut self, connector: HttpsConnector<HttpConnector>) -> Self {
-        self.inner = hyper::Client::builder().build(connector);
+        self.tls_connector = Some(connector);
+        self.rebuild_inner()
+    }
+
+    /// Change the socket-level settings of the connector, and rebuild the underlying client.
+    /// If the client uses a connector set with `with_tls_connector`, which `setting` can't be applied to,
+    /// requests fail instead.
+    fn configure_connector(mut self, setting: &'static str, f: impl FnOnce(&mut HttpConnector<TimedResolver>)) -> Self {
+        if self.tls_connector.is_some() {
+            self.unapplied_setting.get_or_insert(setting);
+            return self;
+        }
+        f(&mut self.http_connector);
+        self.rebuild_inner()
+    }
+
+    /// Rebuild the underlying client, e.g. after changing the connector's settings.
+    fn rebuild_inner(mut self) -> Self {
+        self.inner = self.build_inner(self.http_connector.clone());
+        self.egress = Arc::default();
         self
     }
 
+    fn build_inner(&self, http: HttpConnector<TimedResolver>) -> HyperClient {
+        let addresses = Arc::new(self.addresses.clone());
+        let connector = match &self.tls_connector {
+            Some(custom) => Connector::Custom(custom.clone(), addresses),
+            None => Connector::Default(connector::https_connector(http, self.identity.as_ref()), addresses),
+        };
+        hyper::Client::builder().build(InstrumentedConnector::new(connector, self.pool.clone()))
+    }
+
+    /// The hyper client to send a request with, bound to `local` if the request overrides the local address.
+    /// Fails for a local address, or a connector setting, that can't be applied to a connector set with `with_tls_connector`.
+    pub(crate) fn hyper_client(&self, local: Option<LocalAddress>) -> ProtocolResult<HyperClient> {
+        if let Some(setting) = self.unapplied_setting {
+            return Err(ProtocolError::invalid_input(format!(
+                "`{setting}` can't be applied to a connector set with `with_tls_connector`. Configure that connector instead."
+            )));
+        }
+        let Some(LocalAddress(addr)) = local else {
+            return Ok(self.inner.clone());
+        };
+        if self.tls_connector.is_some() {
+            return Err(ProtocolError::invalid_input(
+                "A request's `local_address` can't be applied to a connector set with `with_tls_connector`",
+            ));
+        }
+        let mut egress = self.egress.lock().expect("Egress clients lock poisoned");
+        let client = egress.entry(addr).or_insert_with(|| {
+            let mut http = self.http_connector.clone();
+            http.set_local_address(Some(addr));
+            self.build_inner(http)
+        });
+        Ok(client.clone())
+    }
+
+    /// Per-host connection statistics for this client and its clones.
+    #[must_use]
+    pub fn pool_stats(&self) -> Vec<HostPoolStats> {
+        self.pool.stats()
+    }
+
+    /// Access the connection counters, e.g. to call `.spawn_reporter()` for periodic tracing events.
+    #[must_use]
+    pub fn pool_metrics(&self) -> &PoolMetrics {
+        &self.pool
+    }
+
+    /// Set the Happy Eyeballs (RFC 6555/8305) fallback delay. When a host resolves to both IPv6
+    /// and IPv4 addresses and the preferred family hasn't connected within `delay`, the other
+    /// family is raced in parallel. `None` disables racing. Defaults to 300ms.
+    #[must_use]
+    pub fn happy_eyeballs_timeout(self, delay: Option<Duration>) -> Self {
+        self.configure_connector("happy_eyeballs_timeout", |c| c.set_happy_eyeballs_timeout(delay))
+    }
+
+    /// Set the timeout for establishing a TCP connection. If a host resolves to multiple
+    /// addresses, the timeout is divided evenly across them.
+    #[must_use]
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.timeouts.connect = Some(timeout);
+        self.configure_connector("connect_timeout", |c| c.set_connect_timeout(Some(timeout)))
+    }
+
+    /// Bind outgoing connections to the given local address. Requests can override it with [`RequestBuilder::local_address`].
+    #[must_use]
+    pub fn local_address(self, addr: impl Into<IpAddr>) -> Self {
+        let addr = addr.into();
+        self.configure_connector("local_address", |c| c.set_local_address(Some(addr)))
+    }
+
+    /// Bind outgoing connections to the addresses of the given network interface, e.g. `eth0`.
+    ///
+    /// The interface's addresses are looked up once, when this is called.
+    /// Fails if the interface does not exist or has no addresses.
+    pub fn interface(self, name: &str) -> ProtocolResult<Self> {
+        let (v4, v6) = connector::interface_addresses(name);
+        if v4.is_none() && v6.is_none() {
+            return Err(ProtocolError::invalid_input(format!("Network interface `{name}` not found, or has no addresses")));
+        }
+        Ok(self.configure_connector("interface", |c| connector::bind_addresses(c, v4, v6)))
+    }
+
+    /// Choose how new connections pick among the addresses a hostname resolves to, e.g. to spread them across
+    /// a service's replicas. Pooled connections are still reused, so this only balances as connections are opened.
+    #[must_use]
+    pub fn address_selection(mut self, selection: AddressSelection) -> Self {
+        self.addresses.selection = selection;
+        self.configure_connector("address_selection", |_| {})
+    }
+
+    /// Connect to `addresses` for `host` instead of resolving it, e.g. a static list of backends behind one name.
+    /// The port still comes from the URL, and TLS and the `Host` header still use `host`.
+    /// Combine with [`Client::address_selection`] to balance across them.
+    #[must_use]
+    pub fn resolve_to(mut self, host: &str, addresses: &[IpAddr]) -> Self {
+        self.addresses.backends.retain(|(h, _)| !h.eq_ignore_ascii_case(host));
+        self.addresses.backends.push((host.to_string(), addresses.to_vec()));
+        self.configure_connector("resolve_to", |_| {})
+    }
+
+    /// Present a client certificate to servers that ask for one (mutual TLS). The certificate can be replaced later
+    /// through the [`ClientIdentity`], without rebuilding the client.
+    #[must_use]
+    pub fn client_identity(mut self, identity: ClientIdentity) -> Self {
+        self.identity = Some(identity);
+        self.configure_connector("client_identity", |_| {})
+    }
+
+    /// Cache hostname lookups for this client's new connections. See [`DnsCache`].
+    /// Static addresses from [`Client::resolve_to`] take precedence.
+    #[must_use]
+    pub fn dns_cache(mut self, cache: DnsCache) -> Self {
+        self.addresses.dns_cache = Some(cache);
+        self.configure_connector("dns_cache", |_| {})
+    }
+}
+
+impl Client {
     #[must_use]
     pub fn no_default_headers(mut self) -> Self {
         self.default_headers = Vec::new();
@@ -98,6 +461,51 @@ impl Client {
         self
     }
 
+    /// Set a default header, replacing any existing default values for it.
+    #[must_use]
+    pub fn set_default_header<S: AsRef<str>>(mut self, key: S, value: S) -> Self {
+        self.default_headers.retain(|(k, _)| !k.eq_ignore_ascii_case(key.as_ref()));
+        self.default_header(key, value)
+    }
+
+    /// Replace the default `User-Agent` (`httpclient/<version>`). A request's own `User-Agent` header still takes precedence.
+    #[must_use]
+    pub fn user_agent(self, user_agent: &str) -> Self {
+        self.set_default_header(USER_AGENT.as_str(), user_agent)
+    }
+
+    /// Append a product token to the default `User-Agent`, e.g. `my-sdk/1.2` gives `httpclient/0.23 my-sdk/1.2`.
+    #[must_use]
+    pub fn ua_extend(mut self, product: &str) -> Self {
+        match self.default_headers.iter_mut().find(|(k, _)| k.eq_ignore_ascii_case(USER_AGENT.as_str())) {
+            Some((_, ua)) => {
+                ua.push(' ');
+                ua.push_str(product);
+                self
+            }
+            None => self.default_header(USER_AGENT.as_str(), product),
+        }
+    }
+
+    /// Send and expect JSON by default: default `Accept` and `Content-Type` to `application/json`.
+    /// Headers set on a request, including by [`RequestBuilder::bytes`] or [`RequestBuilder::text`], still take precedence.
+    #[must_use]
+    pub fn json(self) -> Self {
+        self.accept("application/json").content_type("application/json")
+    }
+
+    /// Default the `Accept` header of every request, e.g. `text/csv`.
+    #[must_use]
+    pub fn accept(self, media_type: &str) -> Self {
+        self.set_default_header(ACCEPT.as_str(), media_type)
+    }
+
+    /// Default the `Content-Type` header of every request, e.g. `application/xml`.
+    #[must_use]
+    pub fn content_type(self, media_type: &str) -> Self {
+        self.set_default_header(CONTENT_TYPE.as_str(), media_type)
+    }
+
     #[must_use]
     fn build_uri(&self, uri_or_path: &str) -> Uri {
         if let Ok(uri) = Uri::from_str(uri_or_path) {
@@ -105,10 +513,24 @@ impl Client {
                 return uri;
             }
         }
-        let uri = self.base_url.as_ref().map_or_else(|| uri_or_path.to_string(), |s| s.clone() + uri_or_path);
+        let uri = self.base_url.as_ref().map_or_else(|| uri_or_path.to_string(), |base| join_url(base, uri_or_path));
         Uri::from_str(&uri).unwrap()
     }
 
+    /// Send `request` through `middlewares`, within the `total` timeout.
+    pub(crate) async fn run(&self, request: InMemoryRequest, middlewares: &[Arc<dyn Middleware>]) -> ProtocolResult<Response> {
+        let once = request.extensions().get::<ReplayableBody>().is_some_and(|body| !body.is_replayable());
+        if let Some(m) = middlewares.iter().find(|m| once && m.replays_requests(&request)) {
+            return Err(ProtocolError::NotReplayable(format!("{m:?} may send the request more than once")));
+        }
+        let total = self.timeouts_for(request.uri()).total;
+        let next = Next { client: self, middlewares };
+        match total {
+            Some(timeout) => crate::clock::timeout(timeout, next.run(request)).await.map_err(|_| ProtocolError::Timeout)?,
+            None => next.run(request).await,
+        }
+    }
+
     #[must_use]
     pub fn get(&self, url_or_path: impl AsRef<str>) -> RequestBuilder<Client> {
         self.request(Method::GET, url_or_path.as_ref())
@@ -134,12 +556,37 @@ impl Client {
         self.request(Method::PATCH, uri_or_path.as_ref())
     }
 
+    /// A `HEAD` request, to check a resource's headers, e.g. its [`crate::ResponseExt::content_length`], without
+    /// downloading it.
+    #[must_use]
+    pub fn head(&self, uri_or_path: impl AsRef<str>) -> RequestBuilder<'_> {
+        self.request(Method::HEAD, uri_or_path.as_ref())
+    }
+
+    /// An `OPTIONS` request, e.g. to see which methods and CORS headers a server allows.
+    #[must_use]
+    pub fn options(&self, uri_or_path: impl AsRef<str>) -> RequestBuilder<'_> {
+        self.request(Method::OPTIONS, uri_or_path.as_ref())
+    }
+
+    /// A `TRACE` request, which servers that allow it echo back, to see what reaches them through proxies.
+    #[must_use]
+    pub fn trace(&self, uri_or_path: impl AsRef<str>) -> RequestBuilder<'_> {
+        self.request(Method::TRACE, uri_or_path.as_ref())
+    }
+
     #[must_use]
     pub fn request(&self, method: Method, uri_or_path: impl AsRef<str>) -> RequestBuilder {
         let uri = self.build_uri(uri_or_path.as_ref());
-        RequestBuilder::new(self, method, uri)
-            .headers(self.default_headers.iter().map(|(k, v)| (k.as_str(), v.as_str())))
-            .set_middlewares(self.middlewares.clone())
+        let middlewares = self.middlewares_for(&uri);
+        let default_headers = self.default_headers_for(&uri);
+        let built_with = (!self.profiles.is_empty()).then(|| BuiltWith(self.profile(&uri).cloned()));
+        let mut builder = RequestBuilder::new(self, method, uri).set_middlewares(middlewares);
+        builder.default_headers = default_headers;
+        if let Some(built_with) = built_with {
+            builder.extensions.insert(built_with);
+        }
+        builder
     }
 }
 
@@ -151,15 +598,18 @@ impl Default for Client {
 
 #[cfg(test)]
 mod tests {
-    use std::collections::HashMap;
-
-    use crate::middleware::{Recorder, RecorderMode};
-    use crate::ResponseExt;
+    use crate::middleware::SsrfGuard;
+    use crate::{InMemoryBody, ResponseExt};
 
     use super::*;
 
+    #[cfg(feature = "recorder")]
     #[tokio::test]
     async fn test_make_request() {
+        use std::collections::HashMap;
+
+        use crate::ResponseExt;
+
         let client = Client::new()
             .base_url("https://www.jsonip.com")
             .no_default_headers()
@@ -173,4 +623,221 @@ mod tests {
             serde_json::json!({"ip":"70.107.97.117","geo-ip":"https://getjsonip.com/#plus","API Help":"https://getjsonip.com/#docs"})
         );
     }
+
+    #[test]
+    fn test_base_url_join() {
+        for base in ["https://example.com/v1", "https://example.com/v1/"] {
+            let client = Client::new().base_url(base);
+            assert_eq!(client.build_uri("/users").to_string(), "https://example.com/v1/users");
+            assert_eq!(client.build_uri("users").to_string(), "https://example.com/v1/users");
+        }
+        let client = Client::new().base_url("https://example.com");
+        assert_eq!(client.build_uri("/users?a=1").to_string(), "https://example.com/users?a=1");
+        assert_eq!(client.build_uri("https://other.com/x").to_string(), "https://other.com/x");
+
+        let client = Client::new().base_url("https://example.com/v1/search");
+        assert_eq!(client.build_uri("?q=a").to_string(), "https://example.com/v1/search?q=a");
+        assert_eq!(client.build_uri("").to_string(), "https://example.com/v1/search");
+        assert_eq!(client.build_uri("#top").to_string(), "https://example.com/v1/search");
+    }
+
+    #[test]
+    fn test_scoped() {
+        let client = Client::new().base_url("https://example.com/api").default_header("X-Tenant", "a");
+        let scoped = client.scoped("/v2").set_default_header("x-tenant", "b");
+        assert_eq!(scoped.build_uri("/users").to_string(), "https://example.com/api/v2/users");
+        assert_eq!(client.build_uri("/users").to_string(), "https://example.com/api/users");
+        let r = scoped.get("/users").build();
+        assert_eq!(r.headers().get_all("x-tenant").iter().collect::<Vec<_>>(), vec!["b"]);
+        assert_eq!(client.get("/users").build().headers()["x-tenant"], "a");
+        assert_eq!(client.scoped("https://other.com/").build_uri("/x").to_string(), "https://other.com/x");
+    }
+
+    #[test]
+    fn test_user_agent() {
+        let ua = |client: &Client| {
+            let headers = client.get("/").build().headers().clone();
+            headers.get_all(USER_AGENT).iter().map(|v| v.to_str().unwrap().to_string()).collect::<Vec<_>>()
+        };
+        let client = Client::new().base_url("https://example.com").ua_extend("sdk/1.0");
+        assert_eq!(ua(&client), vec![format!("{APP_USER_AGENT} sdk/1.0")]);
+        let client = client.user_agent("custom/2.0").ua_extend("extra/3");
+        assert_eq!(ua(&client), vec!["custom/2.0 extra/3"]);
+        let r = client.get("/").header(USER_AGENT, "per-request").build();
+        assert_eq!(r.headers().get_all(USER_AGENT).iter().collect::<Vec<_>>(), vec!["per-request"]);
+        assert_eq!(ua(&Client::new().base_url("https://example.com").no_default_headers().ua_extend("only/1")), vec!["only/1"]);
+    }
+
+    #[test]
+    fn test_content_profile() {
+        let client = Client::new().base_url("https://example.com").json();
+        let r = client.post("/").body(InMemoryBody::Text("{}".into())).build();
+        assert_eq!(r.headers()[ACCEPT], "application/json");
+        assert_eq!(r.headers()[CONTENT_TYPE], "application/json");
+        let r = client.accept("text/csv").post("/").bytes(vec![1]).build();
+        assert_eq!(r.headers()[ACCEPT], "text/csv");
+        assert_eq!(r.headers()[CONTENT_TYPE], "application/octet-stream");
+    }
+
+    #[test]
+    fn test_method_helpers() {
+        let client = Client::new().base_url("https://example.com/api").default_header("X-Tenant", "a");
+        let get = client.get("/users").build();
+        for r in [client.head("/users"), client.options("/users"), client.trace("/users")] {
+            let r = r.build();
+            assert!([Method::HEAD, Method::OPTIONS, Method::TRACE].contains(r.method()));
+            assert_eq!(r.uri(), get.uri());
+            assert_eq!(r.headers(), get.headers());
+        }
+    }
+
+    #[test]
+    fn test_base_url_requires_scheme() {
+        let err = Client::new().try_base_url("example.com").unwrap_err();
+        assert!(matches!(err, ProtocolError::InvalidUrl(_)));
+        assert!(Client::new().try_base_url("/api").is_err());
+        assert!(Client::new().try_base_url("http://localhost:8080").is_ok());
+    }
+
+    #[test]
+    fn test_presets() {
+        let strict = Client::strict();
+        assert!(strict.middlewares.is_empty());
+        assert_eq!(strict.timeouts.total, Some(Duration::from_secs(30)));
+        let resilient = Client::resilient();
+        assert_eq!(resilient.middlewares.len(), 2);
+        assert_eq!(resilient.timeouts.total, Some(Duration::from_secs(120)));
+        #[cfg(feature = "recorder")]
+        assert_eq!(Client::for_tests().middlewares.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_connection_info() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 1024];
+            let _ = tokio::io::AsyncReadExt::read(&mut stream, &mut buf).await;
+            let _ = tokio::io::AsyncWriteExt::write_all(&mut stream, b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n").await;
+        });
+        let client = Client::new();
+        let res = client.get(format!("http://localhost:{port}/")).send().await.unwrap();
+        let info = res.extensions().get::<ConnectionInfo>().unwrap();
+        assert_eq!(info.remote_addr.port(), port);
+        assert!(!info.is_ipv6());
+        assert!(res.extensions().get::<TlsInfo>().is_none());
+        assert!(info.resolve_time.is_some());
+        let stats = client.pool_stats();
+        assert_eq!(stats[0].ipv4_requests, 1);
+    }
+
+    #[tokio::test]
+    async fn test_head() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 1024];
+            let n = tokio::io::AsyncReadExt::read(&mut stream, &mut buf).await.unwrap();
+            assert!(buf[..n].starts_with(b"HEAD /file "));
+            let _ = tokio::io::AsyncWriteExt::write_all(&mut stream, b"HTTP/1.1 200 OK\r\ncontent-length: 1048576\r\n\r\n").await;
+        });
+        let res = Client::new().base_url(&format!("http://127.0.0.1:{port}")).head("/file").send().await.unwrap();
+        assert_eq!(res.content_length(), Some(1_048_576));
+        assert_eq!(res.bytes().await.unwrap().len(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_request_local_address() {
+        // Answers each connection with the address it came from.
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, peer) = listener.accept().await.unwrap();
+                let mut buf = [0u8; 1024];
+                let _ = tokio::io::AsyncReadExt::read(&mut stream, &mut buf).await;
+                let body = peer.ip().to_string();
+                let res = format!("HTTP/1.1 200 OK\r\nconnection: close\r\ncontent-length: {}\r\n\r\n{body}", body.len());
+                let _ = tokio::io::AsyncWriteExt::write_all(&mut stream, res.as_bytes()).await;
+            }
+        });
+        let client = Client::new().local_address([127, 0, 0, 1]);
+        let url = format!("http://127.0.0.1:{port}/");
+        let res = client.get(&url).send().await.unwrap();
+        assert_eq!(res.text().await.unwrap(), "127.0.0.1");
+        let res = client.get(&url).local_address([127, 0, 0, 2]).send().await.unwrap();
+        assert_eq!(res.text().await.unwrap(), "127.0.0.2");
+        assert_eq!(client.egress.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_timeouts() {
+        // Answers the first request with the start of a body that never finishes, and never answers the second.
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let mut streams = Vec::new();
+            for response in [&b"HTTP/1.1 200 OK\r\ncontent-length: 10\r\n\r\nab"[..], b""] {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut buf = [0u8; 1024];
+                let _ = tokio::io::AsyncReadExt::read(&mut stream, &mut buf).await;
+                let _ = tokio::io::AsyncWriteExt::write_all(&mut stream, response).await;
+                streams.push(stream);
+            }
+            tokio::time::sleep(Duration::from_secs(5)).await;
+        });
+        let client = Client::new().timeouts(Timeouts {
+            first_byte: Some(Duration::from_millis(100)),
+            idle_read: Some(Duration::from_millis(100)),
+            ..Timeouts::default()
+        });
+        let url = format!("http://localhost:{port}/");
+        let res = client.get(&url).send().await.unwrap();
+        assert!(res.into_body().into_memory().await.is_err());
+        let err = client.get(&url).send().await.unwrap_err();
+        assert!(matches!(err, ProtocolError::Timeout));
+    }
+
+    #[tokio::test]
+    async fn test_tls_connector_kept() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let config = rustls::ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(rustls::RootCertStore::empty())
+            .with_no_client_auth();
+        let https_only = hyper_rustls::HttpsConnectorBuilder::new().with_tls_config(config).https_only().enable_http1().build();
+        let client = Client::new()
+            .with_tls_connector(https_only)
+            .timeouts(Timeouts {
+                total: Some(Duration::from_secs(5)),
+                ..Timeouts::default()
+            })
+            .with_middleware(SsrfGuard::new().allow_internal(true));
+        // Only the custom connector refuses plain http, so it's still the one in use.
+        let url = format!("http://127.0.0.1:{port}/");
+        assert!(matches!(client.get(&url).send().await, Err(ProtocolError::ConnectionError(_))));
+        let err = client.get(&url).local_address([127, 0, 0, 1]).send().await.unwrap_err();
+        assert!(matches!(err, ProtocolError::InvalidInput(_)));
+    }
+
+    #[tokio::test]
+    async fn test_tls_connector_settings() {
+        let connector = connector::https_connector(HttpConnector::new(), None);
+        let client = Client::new()
+            .with_tls_connector(connector)
+            .connect_timeout(Duration::from_secs(1))
+            .base_url("http://127.0.0.1:1");
+        let Err(ProtocolError::InvalidInput(msg)) = client.get("/").send().await else {
+            panic!("expected the unapplied setting to fail the request");
+        };
+        assert!(msg.contains("`connect_timeout` can't be applied"));
+    }
+
+    #[test]
+    fn test_interface_not_found() {
+        assert!(matches!(Client::new().interface("does-not-exist0"), Err(ProtocolError::InvalidInput(_))));
+    }
 }
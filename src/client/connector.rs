@@ -0,0 +1,361 @@
+use std::cell::Cell;
+use std::future::Future;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use hyper::client::connect::dns::{GaiResolver, Name};
+use hyper::client::HttpConnector;
+use hyper_rustls::{ConfigBuilderExt, HttpsConnector, MaybeHttpsStream};
+use rand::seq::SliceRandom;
+use tokio::net::TcpStream;
+use tower_service::Service;
+
+use super::dns_cache::{DnsCache, Lookup};
+use super::identity::ClientIdentity;
+use crate::middleware::SsrfGuard;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+tokio::task_local! {
+    static RESOLVE_TIME: Cell<Option<Duration>>;
+    static ADDRESSES: Arc<Addresses>;
+}
+
+/// How to choose among the addresses a hostname resolves to. Set it with `Client::address_selection`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AddressSelection {
+    /// Try the addresses in the order the resolver returns them, so connections go to the first one that works.
+    #[default]
+    First,
+    /// Start from the next address for each new connection.
+    RoundRobin,
+    /// Start from a random address for each new connection.
+    Random,
+}
+
+/// A client's address settings, read by [`TimedResolver`] while its connections are made.
+#[derive(Debug, Default)]
+pub(crate) struct Addresses {
+    pub(crate) selection: AddressSelection,
+    pub(crate) backends: Vec<(String, Vec<IpAddr>)>,
+    pub(crate) dns_cache: Option<DnsCache>,
+    /// Guards every address must pass before it is connected to, installed with an [`SsrfGuard`] middleware.
+    pub(crate) guards: Vec<SsrfGuard>,
+    next: AtomicUsize,
+}
+
+impl Clone for Addresses {
+    fn clone(&self) -> Self {
+        Self {
+            selection: self.selection,
+            backends: self.backends.clone(),
+            dns_cache: self.dns_cache.clone(),
+            guards: self.guards.clone(),
+            next: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl Addresses {
+    /// The static addresses for `host`, if it has any. Ports are filled in by the connector.
+    fn backends(&self, host: &str) -> Option<Vec<SocketAddr>> {
+        let (_, ips) = self.backends.iter().find(|(h, _)| h.eq_ignore_ascii_case(host))?;
+        Some(ips.iter().map(|ip| SocketAddr::new(*ip, 0)).collect())
+    }
+
+    /// Refuse `ip` if any guard blocks it.
+    fn check(&self, ip: IpAddr) -> Result<(), Blocked> {
+        for guard in &self.guards {
+            guard.check_ip(ip).map_err(|e| Blocked(e.to_string()))?;
+        }
+        Ok(())
+    }
+
+    /// Reorder `addrs` so the one to try first comes first. The connector tries the rest in order if it fails.
+    fn order(&self, mut addrs: Vec<SocketAddr>) -> Vec<SocketAddr> {
+        match self.selection {
+            AddressSelection::First => {}
+            AddressSelection::RoundRobin => {
+                let start = self.next.fetch_add(1, Ordering::Relaxed).checked_rem(addrs.len()).unwrap_or(0);
+                addrs.rotate_left(start);
+            }
+            AddressSelection::Random => addrs.shuffle(&mut rand::thread_rng()),
+        }
+        addrs
+    }
+}
+
+/// A connection refused because a guard blocks the address, e.g. an internal IP.
+/// Surfaced to callers as `ProtocolError::Blocked`, see [`blocked_reason`].
+#[derive(Debug)]
+pub(crate) struct Blocked(String);
+
+impl std::fmt::Display for Blocked {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Blocked {}
+
+/// The reason a connection was refused by a guard, if that's why `err` failed.
+pub(crate) fn blocked_reason(err: &(dyn std::error::Error + 'static)) -> Option<String> {
+    let mut cur = Some(err);
+    while let Some(e) = cur {
+        if let Some(blocked) = e.downcast_ref::<Blocked>() {
+            return Some(blocked.0.clone());
+        }
+        // io::Error's source skips the error it wraps, so look inside it.
+        if let Some(blocked) = e.downcast_ref::<io::Error>().and_then(|e| e.get_ref()).and_then(|e| e.downcast_ref::<Blocked>()) {
+            return Some(blocked.0.clone());
+        }
+        cur = e.source();
+    }
+    None
+}
+
+/// The system resolver (hyper's default), timing each lookup for [`time_resolution`],
+/// ordering the results by the client's [`AddressSelection`], and dropping addresses its guards block.
+#[derive(Clone)]
+pub(crate) struct TimedResolver(GaiResolver);
+
+impl Service<Name> for TimedResolver {
+    type Response = std::vec::IntoIter<SocketAddr>;
+    type Error = io::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, io::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.0.poll_ready(cx)
+    }
+
+    fn call(&mut self, name: Name) -> Self::Future {
+        let mut gai = self.0.clone();
+        Box::pin(async move {
+            let addresses = ADDRESSES.try_with(Arc::clone).unwrap_or_default();
+            let host = name.as_str().to_string();
+            let lookup = addresses.dns_cache.as_ref().map_or(Lookup::Miss, |cache| cache.lookup(&host));
+            let addrs = if let Some(backends) = addresses.backends(&host) {
+                backends
+            } else if let Lookup::Hit(addrs) = lookup {
+                addrs
+            } else if let Lookup::Refresh(addrs) = lookup {
+                let cache = addresses.dns_cache.clone().expect("Refresh comes from a cache");
+                let host = host.clone();
+                tokio::spawn(async move {
+                    match gai.call(name).await {
+                        Ok(fresh) => cache.insert(&host, fresh.collect()),
+                        Err(_) => cache.refresh_failed(&host),
+                    }
+                });
+                addrs
+            } else {
+                let start = Instant::now();
+                let addrs = gai.call(name).await;
+                let _ = RESOLVE_TIME.try_with(|t| t.set(Some(start.elapsed())));
+                let addrs: Vec<SocketAddr> = addrs?.collect();
+                if let Some(cache) = &addresses.dns_cache {
+                    cache.insert(&host, addrs.clone());
+                }
+                addrs
+            };
+            // Only the addresses returned here are dialed, so a guard holds even if DNS answers differently
+            // than it did for an earlier check.
+            let total = addrs.len();
+            let addrs: Vec<SocketAddr> = addrs.into_iter().filter(|a| addresses.check(a.ip()).is_ok()).collect();
+            if addrs.is_empty() && total > 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    Blocked(format!("{host} resolves only to blocked addresses")),
+                ));
+            }
+            Ok(addresses.order(addrs).into_iter())
+        })
+    }
+}
+
+/// Run a connect future, returning how long DNS resolution took within it.
+/// `None` if no lookup happened (e.g. the host is an IP address or has static addresses) or the connector doesn't use [`TimedResolver`].
+pub(crate) async fn time_resolution<F: Future>(connecting: F) -> (F::Output, Option<Duration>) {
+    RESOLVE_TIME
+        .scope(Cell::new(None), async {
+            let out = connecting.await;
+            (out, RESOLVE_TIME.with(Cell::get))
+        })
+        .await
+}
+
+/// The built-in connector, or one set with `Client::with_tls_connector`.
+#[derive(Clone)]
+pub(crate) enum Connector {
+    Default(HttpsConnector<HttpConnector<TimedResolver>>, Arc<Addresses>),
+    Custom(HttpsConnector<HttpConnector>, Arc<Addresses>),
+}
+
+/// The address of the peer `stream` is connected to.
+fn peer_addr(stream: &MaybeHttpsStream<TcpStream>) -> io::Result<SocketAddr> {
+    match stream {
+        MaybeHttpsStream::Http(s) => s.peer_addr(),
+        MaybeHttpsStream::Https(s) => s.get_ref().0.peer_addr(),
+    }
+}
+
+impl Service<hyper::Uri> for Connector {
+    type Response = MaybeHttpsStream<TcpStream>;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, BoxError>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        match self {
+            Connector::Default(c, _) => c.poll_ready(cx),
+            Connector::Custom(c, _) => c.poll_ready(cx),
+        }
+    }
+
+    fn call(&mut self, uri: hyper::Uri) -> Self::Future {
+        let (connecting, addresses) = match self {
+            Connector::Default(c, addresses) => (Box::pin(ADDRESSES.scope(addresses.clone(), c.call(uri.clone()))) as Self::Future, addresses.clone()),
+            Connector::Custom(c, addresses) => (c.call(uri.clone()), addresses.clone()),
+        };
+        if addresses.guards.is_empty() {
+            return connecting;
+        }
+        Box::pin(async move {
+            // IP literals are never resolved, so check them before dialing.
+            if let Some(ip) = uri.host().and_then(|h| h.trim_start_matches('[').trim_end_matches(']').parse().ok()) {
+                addresses.check(ip)?;
+            }
+            let stream = connecting.await?;
+            // A custom connector resolves on its own, so check where it actually connected.
+            addresses.check(peer_addr(&stream)?.ip())?;
+            Ok(stream)
+        })
+    }
+}
+
+pub(crate) fn default_http_connector() -> HttpConnector<TimedResolver> {
+    let mut http = HttpConnector::new_with_resolver(TimedResolver(GaiResolver::new()));
+    // HttpConnector won't enforce scheme, but HttpsConnector will
+    http.enforce_http(false);
+    http
+}
+
+/// An HTTPS connector trusting the platform's roots, presenting `identity` to servers that ask for a client certificate.
+pub(crate) fn https_connector<R>(http: HttpConnector<R>, identity: Option<&ClientIdentity>) -> HttpsConnector<HttpConnector<R>> {
+    let builder = rustls::ClientConfig::builder().with_safe_defaults().with_native_roots();
+    let config = match identity {
+        Some(identity) => builder.with_client_cert_resolver(Arc::new(identity.clone())),
+        None => builder.with_no_client_auth(),
+    };
+    hyper_rustls::HttpsConnectorBuilder::new()
+        .with_tls_config(config)
+        .https_or_http()
+        .enable_http1()
+        .wrap_connector(http)
+}
+
+/// Look up the addresses assigned to a network interface, e.g. `eth0`.
+/// Returns the first IPv4 and first IPv6 address found.
+#[cfg(unix)]
+pub(crate) fn interface_addresses(name: &str) -> (Option<Ipv4Addr>, Option<Ipv6Addr>) {
+    use std::ffi::CStr;
+
+    let mut v4 = None;
+    let mut v6 = None;
+    let mut addrs: *mut libc::ifaddrs = std::ptr::null_mut();
+    // SAFETY: getifaddrs allocates a linked list that we walk read-only and release with freeifaddrs.
+    unsafe {
+        if libc::getifaddrs(&raw mut addrs) != 0 {
+            return (None, None);
+        }
+        let mut cur = addrs;
+        while let Some(ifa) = cur.as_ref() {
+            cur = ifa.ifa_next;
+            if ifa.ifa_addr.is_null() || CStr::from_ptr(ifa.ifa_name).to_bytes() != name.as_bytes() {
+                continue;
+            }
+            match i32::from((*ifa.ifa_addr).sa_family) {
+                libc::AF_INET if v4.is_none() => {
+                    let sin = ifa.ifa_addr.cast::<libc::sockaddr_in>().read_unaligned();
+                    v4 = Some(Ipv4Addr::from(u32::from_be(sin.sin_addr.s_addr)));
+                }
+                libc::AF_INET6 if v6.is_none() => {
+                    let sin6 = ifa.ifa_addr.cast::<libc::sockaddr_in6>().read_unaligned();
+                    v6 = Some(Ipv6Addr::from(sin6.sin6_addr.s6_addr));
+                }
+                _ => {}
+            }
+        }
+        libc::freeifaddrs(addrs);
+    }
+    (v4, v6)
+}
+
+#[cfg(not(unix))]
+pub(crate) fn interface_addresses(_name: &str) -> (Option<Ipv4Addr>, Option<Ipv6Addr>) {
+    (None, None)
+}
+
+/// Bind outgoing sockets to the given addresses. Either family may be missing.
+pub(crate) fn bind_addresses<R>(http: &mut HttpConnector<R>, v4: Option<Ipv4Addr>, v6: Option<Ipv6Addr>) {
+    match (v4, v6) {
+        (Some(v4), Some(v6)) => http.set_local_addresses(v4, v6),
+        (Some(v4), None) => http.set_local_address(Some(IpAddr::V4(v4))),
+        (None, Some(v6)) => http.set_local_address(Some(IpAddr::V6(v6))),
+        (None, None) => http.set_local_address(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(unix)]
+    fn test_interface_addresses() {
+        let (v4, _) = interface_addresses("lo");
+        if let Some(v4) = v4 {
+            assert!(v4.is_loopback());
+        }
+        assert_eq!(interface_addresses("does-not-exist0"), (None, None));
+    }
+
+    #[tokio::test]
+    async fn test_address_selection() {
+        let ips: Vec<IpAddr> = vec![[10, 0, 0, 1].into(), [10, 0, 0, 2].into(), [10, 0, 0, 3].into()];
+        let addresses = Arc::new(Addresses {
+            selection: AddressSelection::RoundRobin,
+            backends: vec![("Backend.internal".to_string(), ips.clone())],
+            dns_cache: None,
+            guards: Vec::new(),
+            next: AtomicUsize::new(0),
+        });
+        let mut resolver = TimedResolver(GaiResolver::new());
+        let mut firsts = Vec::new();
+        for _ in 0..4 {
+            let name: Name = "backend.internal".parse().unwrap();
+            let addrs: Vec<_> = ADDRESSES.scope(addresses.clone(), resolver.call(name)).await.unwrap().collect();
+            assert_eq!(addrs.len(), 3);
+            firsts.push(addrs[0].ip());
+        }
+        assert_eq!(firsts, [ips[0], ips[1], ips[2], ips[0]]);
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 1024];
+            let _ = tokio::io::AsyncReadExt::read(&mut stream, &mut buf).await;
+            let _ = tokio::io::AsyncWriteExt::write_all(&mut stream, b"HTTP/1.1 200 OK\r\ncontent-length: 2\r\n\r\nok").await;
+        });
+        let client = crate::Client::new()
+            .resolve_to("backend.internal", &[[127, 0, 0, 1].into()])
+            .address_selection(AddressSelection::Random);
+        let res = client.get(format!("http://backend.internal:{port}/")).send().await.unwrap();
+        assert_eq!(crate::ResponseExt::text(res).await.unwrap(), "ok");
+    }
+}
@@ -0,0 +1,144 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use crate::clock::{elapsed, Clock, SystemClock};
+
+/// Cache hostname lookups inside the client, so busy clients don't resolve the same hosts over and over.
+/// Set it with [`crate::Client::dns_cache`]. Clones share the cached addresses.
+///
+/// The system resolver doesn't report record TTLs, so every entry is kept for the same `ttl`.
+/// With [`DnsCache::stale_while_revalidate`], an expired entry keeps being used for a while longer
+/// while a single background lookup refreshes it.
+/// ```
+/// # use std::time::Duration;
+/// # use httpclient::{Client, DnsCache};
+/// let cache = DnsCache::new(Duration::from_secs(30)).stale_while_revalidate(Duration::from_secs(60));
+/// let client = Client::new().dns_cache(cache.clone());
+/// ```
+#[derive(Debug, Clone)]
+pub struct DnsCache {
+    ttl: Duration,
+    stale: Duration,
+    clock: Arc<dyn Clock>,
+    entries: Arc<Mutex<HashMap<String, Entry>>>,
+}
+
+#[derive(Debug)]
+struct Entry {
+    addrs: Vec<SocketAddr>,
+    resolved_at: SystemTime,
+    refreshing: bool,
+}
+
+/// What the resolver should do for a host.
+pub(crate) enum Lookup {
+    /// Use the cached addresses.
+    Hit(Vec<SocketAddr>),
+    /// Use the cached addresses, and refresh them in the background.
+    Refresh(Vec<SocketAddr>),
+    Miss,
+}
+
+impl DnsCache {
+    #[must_use]
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            stale: Duration::ZERO,
+            clock: Arc::new(SystemClock),
+            entries: Arc::default(),
+        }
+    }
+
+    /// Keep using an expired entry for up to `window` after its `ttl`, refreshing it in the background,
+    /// so requests never wait on a lookup for a host that was resolved recently.
+    #[must_use]
+    pub fn stale_while_revalidate(mut self, window: Duration) -> Self {
+        self.stale = window;
+        self
+    }
+
+    /// Measure entry age with this clock instead of the system time, e.g. a [`crate::clock::TestClock`].
+    #[must_use]
+    pub fn clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Forget every cached lookup, e.g. after a failover changed where a host points.
+    pub fn clear(&self) {
+        self.entries.lock().expect("DNS cache lock poisoned").clear();
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.entries.lock().expect("DNS cache lock poisoned").len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub(crate) fn lookup(&self, host: &str) -> Lookup {
+        let mut entries = self.entries.lock().expect("DNS cache lock poisoned");
+        let Some(entry) = entries.get_mut(&host.to_ascii_lowercase()) else {
+            return Lookup::Miss;
+        };
+        let age = elapsed(self.clock.as_ref(), entry.resolved_at);
+        if age < self.ttl || (age < self.ttl + self.stale && entry.refreshing) {
+            Lookup::Hit(entry.addrs.clone())
+        } else if age < self.ttl + self.stale {
+            entry.refreshing = true;
+            Lookup::Refresh(entry.addrs.clone())
+        } else {
+            Lookup::Miss
+        }
+    }
+
+    pub(crate) fn insert(&self, host: &str, addrs: Vec<SocketAddr>) {
+        let entry = Entry {
+            addrs,
+            resolved_at: self.clock.now(),
+            refreshing: false,
+        };
+        self.entries.lock().expect("DNS cache lock poisoned").insert(host.to_ascii_lowercase(), entry);
+    }
+
+    /// Let the next request retry a background refresh that failed.
+    pub(crate) fn refresh_failed(&self, host: &str) {
+        if let Some(entry) = self.entries.lock().expect("DNS cache lock poisoned").get_mut(&host.to_ascii_lowercase()) {
+            entry.refreshing = false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::clock::TestClock;
+
+    use super::*;
+
+    #[test]
+    fn test_dns_cache() {
+        let clock = TestClock::new();
+        let cache = DnsCache::new(Duration::from_secs(10)).stale_while_revalidate(Duration::from_secs(5)).clock(clock.clone());
+        let addrs = vec![SocketAddr::from(([10, 0, 0, 1], 0))];
+        assert!(matches!(cache.lookup("example.com"), Lookup::Miss));
+        cache.insert("Example.com", addrs.clone());
+        assert!(matches!(cache.lookup("example.com"), Lookup::Hit(a) if a == addrs));
+
+        clock.advance(Duration::from_secs(11));
+        assert!(matches!(cache.lookup("example.com"), Lookup::Refresh(_)));
+        assert!(matches!(cache.lookup("example.com"), Lookup::Hit(_)), "Only one caller refreshes");
+        cache.refresh_failed("example.com");
+        assert!(matches!(cache.lookup("example.com"), Lookup::Refresh(_)));
+
+        clock.advance(Duration::from_secs(5));
+        assert!(matches!(cache.lookup("example.com"), Lookup::Miss));
+        cache.clear();
+        assert!(cache.is_empty());
+    }
+}
@@ -0,0 +1,105 @@
+use std::io;
+
+use async_trait::async_trait;
+use futures::channel::oneshot;
+use futures::future::{select, Either};
+use js_sys::{Array, Promise, Uint8Array};
+use wasm_bindgen::prelude::wasm_bindgen;
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{AbortController, AbortSignal, Headers, RequestInit};
+
+use crate::error::{ProtocolError, ProtocolResult};
+use crate::{Body, InMemoryRequest, Response, Transport};
+
+#[wasm_bindgen]
+extern "C" {
+    /// The global `fetch`, which browsers, web workers, and Node.js all provide.
+    #[wasm_bindgen(js_name = fetch)]
+    fn fetch_with_request(request: &web_sys::Request) -> Promise;
+}
+
+/// Sends requests with the JavaScript host's `fetch` API. On wasm32 it's the default transport, in place of hyper,
+/// so clients, request builders, and middlewares work as they do elsewhere.
+///
+/// The host makes the connections, so the client's connection settings, like connect timeouts, TLS, and connection
+/// pool stats, don't apply. The host follows redirects itself, and may add headers of its own, like `Origin`.
+/// The response body is read in full before the response is returned.
+///
+/// Enable it with the `wasm` feature, without default features: the recorder needs a filesystem. `JwtAuth`,
+/// `FieldEncryption`, `ContentHash`, and presigned URLs use `ring`, and are only available on other targets.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Fetch;
+
+#[async_trait]
+impl Transport for Fetch {
+    async fn call(&self, request: InMemoryRequest) -> ProtocolResult<Response> {
+        // JavaScript values can't be sent between threads, so the fetch runs as a local task that only hands back
+        // its result. That keeps this future `Send`, like every other transport's.
+        let (mut tx, rx) = oneshot::channel();
+        wasm_bindgen_futures::spawn_local(async move {
+            let controller = AbortController::new().ok();
+            let fetch = fetch(request, controller.as_ref().map(AbortController::signal));
+            futures::pin_mut!(fetch);
+            let res = match select(fetch, tx.cancellation()).await {
+                Either::Left((res, _)) => Some(res),
+                Either::Right(_) => None,
+            };
+            match res {
+                Some(res) => {
+                    let _ = tx.send(res);
+                }
+                // The caller stopped waiting, e.g. after a timeout, so cancel the request.
+                None => {
+                    if let Some(controller) = controller {
+                        controller.abort();
+                    }
+                }
+            }
+        });
+        rx.await.map_err(|_| ProtocolError::IoError(io::Error::other("The fetch task ended without a response")))?
+    }
+}
+
+async fn fetch(request: InMemoryRequest, signal: Option<AbortSignal>) -> ProtocolResult<Response> {
+    let (parts, body) = request.into_parts();
+    let headers = Headers::new().map_err(js_error)?;
+    for (k, v) in &parts.headers {
+        let v = v
+            .to_str()
+            .map_err(|_| ProtocolError::invalid_input(format!("The {k} header isn't visible ASCII, which fetch requires")))?;
+        headers.append(k.as_str(), v).map_err(js_error)?;
+    }
+    let init = RequestInit::new();
+    init.set_method(parts.method.as_str());
+    init.set_headers(&headers);
+    init.set_signal(signal.as_ref());
+    let body = body.into_wire_bytes()?;
+    if !body.is_empty() {
+        init.set_body(&Uint8Array::from(body.as_ref()));
+    }
+    let request = web_sys::Request::new_with_str_and_init(&parts.uri.to_string(), &init).map_err(js_error)?;
+    let res: web_sys::Response = JsFuture::from(fetch_with_request(&request)).await.map_err(js_error)?.unchecked_into();
+
+    let mut b = Response::builder().status(res.status());
+    // Iterating `Headers` gives `[name, value]` pairs.
+    for entry in js_sys::try_iter(&res.headers()).map_err(js_error)?.into_iter().flatten() {
+        let entry: Array = entry.map_err(js_error)?.unchecked_into();
+        if let (Some(k), Some(v)) = (entry.get(0).as_string(), entry.get(1).as_string()) {
+            b = b.header(k, v);
+        }
+    }
+    let buffer = JsFuture::from(res.array_buffer().map_err(js_error)?).await.map_err(js_error)?;
+    let body = hyper::Body::from(Uint8Array::new(&buffer).to_vec());
+    b.body(Body::Hyper(body))
+        .map_err(|e| ProtocolError::invalid_input(format!("Invalid response from fetch: {e}")))
+}
+
+/// A JavaScript exception, e.g. fetch's `TypeError` when the network fails, as an I/O error.
+fn js_error(value: JsValue) -> ProtocolError {
+    let msg = match value.dyn_into::<js_sys::Error>() {
+        Ok(e) => String::from(e.message()),
+        Err(value) => value.as_string().unwrap_or_else(|| format!("{value:?}")),
+    };
+    ProtocolError::IoError(io::Error::other(msg))
+}
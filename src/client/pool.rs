@@ -0,0 +1,369 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use http::Uri;
+use hyper::client::connect::{Connected, Connection};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tower_service::Service;
+use tracing::info;
+
+use super::connector::time_resolution;
+use super::tls::{server_name, TlsConnection, TlsInfo};
+
+#[derive(Debug, Default)]
+struct HostCounters {
+    open: AtomicUsize,
+    connecting: AtomicUsize,
+    in_flight: AtomicUsize,
+    connects: AtomicU64,
+    connect_wait_micros: AtomicU64,
+    resolves: AtomicU64,
+    resolve_micros: AtomicU64,
+    ipv4_requests: AtomicU64,
+    ipv6_requests: AtomicU64,
+}
+
+/// A snapshot of the connections to a single host.
+///
+/// hyper doesn't expose its pool internals, so these are measured around it: `pending` counts
+/// connections being established, and `avg_acquire_wait` is the average time to establish one
+/// (including TLS). `idle` is the number of open connections without an in-flight request.
+/// `ipv4_requests` and `ipv6_requests` count responses by the address family of the connection that served them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostPoolStats {
+    /// `host:port`
+    pub host: String,
+    pub open: usize,
+    pub idle: usize,
+    pub in_flight: usize,
+    pub pending: usize,
+    pub avg_acquire_wait: Duration,
+    /// Average DNS lookup time, over the connections that needed one.
+    pub avg_resolve_time: Duration,
+    pub ipv4_requests: u64,
+    pub ipv6_requests: u64,
+}
+
+/// How the connection that served a response was established. Found in the response extensions:
+/// ```
+/// # use httpclient::{ConnectionInfo, Response};
+/// # fn f(res: Response) {
+/// let info = res.extensions().get::<ConnectionInfo>();
+/// # }
+/// ```
+/// Pooled connections are reused, so the timings are from when the connection was opened, not from this request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConnectionInfo {
+    pub remote_addr: SocketAddr,
+    pub local_addr: SocketAddr,
+    /// Time spent on the DNS lookup. `None` if there was no lookup (e.g. the host is an IP address),
+    /// or the connector was set with `Client::with_tls_connector`.
+    pub resolve_time: Option<Duration>,
+    /// Time to open the connection, including DNS lookup and TLS handshake.
+    pub connect_time: Duration,
+}
+
+impl ConnectionInfo {
+    #[must_use]
+    pub fn is_ipv6(&self) -> bool {
+        self.remote_addr.is_ipv6()
+    }
+}
+
+/// Connection timings, passed from the connector to the response extensions.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct ConnectTiming {
+    pub resolve_time: Option<Duration>,
+    pub connect_time: Duration,
+}
+
+/// Per-host connection counters, shared by a client and its clones.
+#[derive(Debug, Clone, Default)]
+pub struct PoolMetrics {
+    hosts: Arc<Mutex<HashMap<String, Arc<HostCounters>>>>,
+}
+
+fn micros(d: Duration) -> u64 {
+    u64::try_from(d.as_micros()).unwrap_or(u64::MAX)
+}
+
+fn host_key(scheme: Option<&str>, host: Option<&str>, port: Option<u16>) -> String {
+    let port = port.unwrap_or(if scheme == Some("https") { 443 } else { 80 });
+    format!("{}:{port}", host.unwrap_or_default())
+}
+
+impl PoolMetrics {
+    fn host(&self, key: String) -> Arc<HostCounters> {
+        let mut hosts = self.hosts.lock().expect("Pool metrics lock poisoned");
+        hosts.entry(key).or_default().clone()
+    }
+
+    /// Mark a request to `uri` as in-flight until the returned guard is dropped.
+    pub(crate) fn in_flight(&self, uri: &Uri) -> InFlightGuard {
+        let counters = self.host(host_key(uri.scheme_str(), uri.host(), uri.port_u16()));
+        counters.in_flight.fetch_add(1, Ordering::Relaxed);
+        InFlightGuard(counters)
+    }
+
+    #[must_use]
+    pub fn stats(&self) -> Vec<HostPoolStats> {
+        let hosts = self.hosts.lock().expect("Pool metrics lock poisoned");
+        let mut stats = hosts
+            .iter()
+            .map(|(host, c)| {
+                let open = c.open.load(Ordering::Relaxed);
+                let in_flight = c.in_flight.load(Ordering::Relaxed);
+                let connects = c.connects.load(Ordering::Relaxed);
+                let wait = c.connect_wait_micros.load(Ordering::Relaxed);
+                let resolves = c.resolves.load(Ordering::Relaxed);
+                let resolve = c.resolve_micros.load(Ordering::Relaxed);
+                HostPoolStats {
+                    host: host.clone(),
+                    open,
+                    idle: open.saturating_sub(in_flight),
+                    in_flight,
+                    pending: c.connecting.load(Ordering::Relaxed),
+                    avg_acquire_wait: Duration::from_micros(wait.checked_div(connects).unwrap_or(0)),
+                    avg_resolve_time: Duration::from_micros(resolve.checked_div(resolves).unwrap_or(0)),
+                    ipv4_requests: c.ipv4_requests.load(Ordering::Relaxed),
+                    ipv6_requests: c.ipv6_requests.load(Ordering::Relaxed),
+                }
+            })
+            .collect::<Vec<_>>();
+        stats.sort_by(|a, b| a.host.cmp(&b.host));
+        stats
+    }
+
+    /// Emit a `tracing` event per host every `interval`. Must be called from within a tokio runtime.
+    /// Abort the returned handle to stop reporting.
+    #[must_use]
+    pub fn spawn_reporter(&self, interval: Duration) -> tokio::task::JoinHandle<()> {
+        let metrics = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                for s in metrics.stats() {
+                    info!(
+                        host = s.host,
+                        open = s.open,
+                        idle = s.idle,
+                        in_flight = s.in_flight,
+                        pending = s.pending,
+                        avg_acquire_wait_ms = s.avg_acquire_wait.as_millis(),
+                        avg_resolve_time_ms = s.avg_resolve_time.as_millis(),
+                        ipv4_requests = s.ipv4_requests,
+                        ipv6_requests = s.ipv6_requests,
+                        "Connection pool stats"
+                    );
+                }
+            }
+        })
+    }
+}
+
+pub(crate) struct InFlightGuard(Arc<HostCounters>);
+
+impl InFlightGuard {
+    /// Count the address family of the connection that served the request.
+    pub(crate) fn record_remote_addr(&self, addr: SocketAddr) {
+        let counter = if addr.is_ipv6() { &self.0.ipv6_requests } else { &self.0.ipv4_requests };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.0.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Counts a connection as pending until dropped, so one that's cancelled partway, e.g. by a timeout, stops counting.
+struct ConnectingGuard(Arc<HostCounters>);
+
+impl ConnectingGuard {
+    fn new(counters: Arc<HostCounters>) -> Self {
+        counters.connecting.fetch_add(1, Ordering::Relaxed);
+        ConnectingGuard(counters)
+    }
+}
+
+impl Drop for ConnectingGuard {
+    fn drop(&mut self) {
+        self.0.connecting.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Wraps a connector to count connections per host.
+#[derive(Debug, Clone)]
+pub struct InstrumentedConnector<C> {
+    inner: C,
+    metrics: PoolMetrics,
+}
+
+impl<C> InstrumentedConnector<C> {
+    pub(crate) fn new(inner: C, metrics: PoolMetrics) -> Self {
+        InstrumentedConnector { inner, metrics }
+    }
+}
+
+// hyper 0.14 connects to an `http` 0.2 Uri.
+impl<C> Service<hyper::Uri> for InstrumentedConnector<C>
+where
+    C: Service<hyper::Uri>,
+    C::Future: Send + 'static,
+    C::Response: TlsConnection,
+{
+    type Response = TrackedStream<C::Response>;
+    type Error = C::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, uri: hyper::Uri) -> Self::Future {
+        let counters = self.metrics.host(host_key(uri.scheme_str(), uri.host(), uri.port_u16()));
+        let server_name = server_name(&uri);
+        let connecting = self.inner.call(uri);
+        let pending = ConnectingGuard::new(counters.clone());
+        Box::pin(async move {
+            let start = Instant::now();
+            let (result, resolve_time) = time_resolution(connecting).await;
+            drop(pending);
+            let stream = result?;
+            let timing = ConnectTiming {
+                resolve_time,
+                connect_time: start.elapsed(),
+            };
+            counters.connects.fetch_add(1, Ordering::Relaxed);
+            counters.connect_wait_micros.fetch_add(micros(timing.connect_time), Ordering::Relaxed);
+            if let Some(resolve_time) = resolve_time {
+                counters.resolves.fetch_add(1, Ordering::Relaxed);
+                counters.resolve_micros.fetch_add(micros(resolve_time), Ordering::Relaxed);
+            }
+            counters.open.fetch_add(1, Ordering::Relaxed);
+            let tls = stream.tls_info(server_name);
+            Ok(TrackedStream {
+                inner: stream,
+                counters,
+                timing,
+                tls,
+            })
+        })
+    }
+}
+
+/// A connection that is counted as open until dropped.
+#[derive(Debug)]
+pub struct TrackedStream<S> {
+    inner: S,
+    counters: Arc<HostCounters>,
+    timing: ConnectTiming,
+    tls: Option<TlsInfo>,
+}
+
+impl<S> Drop for TrackedStream<S> {
+    fn drop(&mut self) {
+        self.counters.open.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl<S: Connection> Connection for TrackedStream<S> {
+    fn connected(&self) -> Connected {
+        let connected = self.inner.connected().extra(self.timing);
+        match &self.tls {
+            Some(tls) => connected.extra(tls.clone()),
+            None => connected,
+        }
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for TrackedStream<S> {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for TrackedStream<S> {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+
+    fn poll_write_vectored(mut self: Pin<&mut Self>, cx: &mut Context<'_>, bufs: &[io::IoSlice<'_>]) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::FutureExt;
+    use hyper_rustls::MaybeHttpsStream;
+    use tokio::net::TcpStream;
+
+    use super::*;
+
+    #[derive(Debug, Clone)]
+    struct NeverConnects;
+
+    impl Service<hyper::Uri> for NeverConnects {
+        type Response = MaybeHttpsStream<TcpStream>;
+        type Error = io::Error;
+        type Future = futures::future::Pending<io::Result<Self::Response>>;
+
+        fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, _: hyper::Uri) -> Self::Future {
+            futures::future::pending()
+        }
+    }
+
+    #[test]
+    fn test_in_flight_stats() {
+        let metrics = PoolMetrics::default();
+        let uri = Uri::from_static("https://example.com/foo");
+        let guard = metrics.in_flight(&uri);
+        let stats = metrics.stats();
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].host, "example.com:443");
+        assert_eq!(stats[0].in_flight, 1);
+        assert_eq!(stats[0].idle, 0);
+        guard.record_remote_addr("[::1]:443".parse().unwrap());
+        drop(guard);
+        let stats = metrics.stats();
+        assert_eq!(stats[0].in_flight, 0);
+        assert_eq!((stats[0].ipv4_requests, stats[0].ipv6_requests), (0, 1));
+    }
+
+    #[tokio::test]
+    async fn test_dropped_connect_not_pending() {
+        let metrics = PoolMetrics::default();
+        let mut connector = InstrumentedConnector::new(NeverConnects, metrics.clone());
+        let mut connecting = connector.call(hyper::Uri::from_static("https://example.com/"));
+        assert!((&mut connecting).now_or_never().is_none());
+        assert_eq!(metrics.stats()[0].pending, 1);
+        drop(connecting);
+        assert_eq!(metrics.stats()[0].pending, 0);
+    }
+}
@@ -0,0 +1,275 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use http::{HeaderMap, HeaderName, HeaderValue, Uri};
+
+use crate::middleware::host_matches;
+use crate::{Client, InMemoryRequest, Middleware, Next, ProtocolResult, Response, Timeouts};
+
+/// Settings for requests to one API, so a single client (and its connection pool) can talk to several.
+/// Set it with [`Client::host_profile`]; it applies to requests whose URL matches the host when they're built.
+///
+/// - `timeouts` replace the client's, except `connect`, which is shared by the whole pool.
+/// - `default_headers` replace client default headers of the same name. Request headers still take precedence.
+/// - `middlewares` run after the client's, e.g. an [`crate::AdaptiveConcurrency`] limit. A retry policy such as
+///   [`crate::Retry`] replaces the client's instead.
+///
+/// A redirect to another host uses that host's profile, or none.
+/// ```
+/// # use std::time::Duration;
+/// # use httpclient::{Client, Profile, Retry, Timeouts};
+/// let client = Client::new().host_profile("api.stripe.com", Profile {
+///     timeouts: Some(Timeouts { total: Some(Duration::from_secs(80)), ..Timeouts::default() }),
+///     ..Profile::default()
+/// }.middleware(Retry::new()));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Profile {
+    pub timeouts: Option<Timeouts>,
+    pub default_headers: Vec<(String, String)>,
+    pub middlewares: Vec<Arc<dyn Middleware>>,
+}
+
+impl Profile {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn timeouts(mut self, timeouts: Timeouts) -> Self {
+        self.timeouts = Some(timeouts);
+        self
+    }
+
+    #[must_use]
+    pub fn default_header<S: AsRef<str>>(mut self, key: S, value: S) -> Self {
+        self.default_headers.push((key.as_ref().to_string(), value.as_ref().to_string()));
+        self
+    }
+
+    #[must_use]
+    pub fn middleware<T: Middleware + 'static>(mut self, middleware: T) -> Self {
+        self.middlewares.push(Arc::new(middleware));
+        self
+    }
+}
+
+impl Client {
+    /// Use `profile` for requests to `host`, e.g. `api.stripe.com` or `*.example.com`. See [`Profile`].
+    /// If several profiles match, the first one added wins.
+    #[must_use]
+    pub fn host_profile(mut self, host: &str, profile: Profile) -> Self {
+        self.profiles.push((host.to_ascii_lowercase(), Arc::new(profile)));
+        self
+    }
+
+    pub(crate) fn profile(&self, uri: &Uri) -> Option<&Arc<Profile>> {
+        let host = uri.host()?.to_ascii_lowercase();
+        self.profiles.iter().find(|(pattern, _)| host_matches(pattern, &host)).map(|(_, profile)| profile)
+    }
+
+    /// The timeouts for a request to `uri`: its host's profile's, or the client's.
+    pub(crate) fn timeouts_for(&self, uri: &Uri) -> Timeouts {
+        self.profile(uri).and_then(|p| p.timeouts).unwrap_or(self.timeouts)
+    }
+
+    /// The middlewares for a request to `uri`. A retry policy in its host's profile takes the place of the client's,
+    /// since it covers the whole request, redirects included. The profile's other middlewares are chosen again for
+    /// each redirect, so they run last, from [`HostProfile`].
+    pub(crate) fn middlewares_for(&self, uri: &Uri) -> Vec<Arc<dyn Middleware>> {
+        let mut middlewares = self.middlewares.clone();
+        if self.profiles.is_empty() {
+            return middlewares;
+        }
+        let retries = self
+            .profile(uri)
+            .map(|p| p.middlewares.iter().filter(|m| m.retries_requests()).cloned().collect::<Vec<_>>());
+        if let Some(retries) = retries.filter(|r| !r.is_empty()) {
+            let at = middlewares.iter().position(|m| m.retries_requests()).unwrap_or(middlewares.len());
+            middlewares.retain(|m| !m.retries_requests());
+            middlewares.splice(at..at, retries);
+        }
+        middlewares.push(Arc::new(HostProfile));
+        middlewares
+    }
+
+    /// The default headers for a request to `uri`: the client's, with its host's profile's replacing those of the same name.
+    pub(crate) fn default_headers_for(&self, uri: &Uri) -> HeaderMap {
+        let mut headers = header_map(&self.default_headers);
+        if let Some(profile) = self.profile(uri) {
+            let overrides = header_map(&profile.default_headers);
+            for name in overrides.keys() {
+                headers.remove(name);
+            }
+            headers.extend(overrides);
+        }
+        headers
+    }
+}
+
+fn header_map(headers: &[(String, String)]) -> HeaderMap {
+    let mut map = HeaderMap::new();
+    for (k, v) in headers {
+        let name = HeaderName::from_bytes(k.as_bytes()).expect("Invalid default header name");
+        map.append(name, HeaderValue::from_str(v).expect("Invalid default header value"));
+    }
+    map
+}
+
+/// The profile a request's default headers came from, so [`HostProfile`] can swap them when a redirect changes host.
+#[derive(Debug, Clone)]
+pub(crate) struct BuiltWith(pub Option<Arc<Profile>>);
+
+/// Runs the middlewares of each hop's host profile, after the client's, and swaps in that profile's default headers.
+#[derive(Debug)]
+struct HostProfile;
+
+#[async_trait]
+impl Middleware for HostProfile {
+    async fn handle(&self, mut request: InMemoryRequest, next: Next<'_>) -> ProtocolResult<Response> {
+        let profile = next.client.profile(request.uri()).cloned();
+        if let Some(BuiltWith(built)) = request.extensions_mut().remove::<BuiltWith>() {
+            if !built.as_ref().zip(profile.as_ref()).is_some_and(|(a, b)| Arc::ptr_eq(a, b)) {
+                swap_default_headers(next.client, request.headers_mut(), built.as_deref(), profile.as_deref());
+            }
+        }
+        let Some(profile) = profile.filter(|p| p.middlewares.iter().any(|m| !m.retries_requests())) else {
+            return next.run(request).await;
+        };
+        let middlewares = profile
+            .middlewares
+            .iter()
+            .filter(|m| !m.retries_requests())
+            .chain(next.middlewares)
+            .cloned()
+            .collect::<Vec<_>>();
+        Next {
+            client: next.client,
+            middlewares: &middlewares,
+        }
+        .run(request)
+        .await
+    }
+}
+
+/// Replace the default headers of the profile `from` with those of `to`. Headers whose value differs from the default
+/// were set on the request, so they're kept.
+fn swap_default_headers(client: &Client, headers: &mut HeaderMap, from: Option<&Profile>, to: Option<&Profile>) {
+    let defaults = header_map(&client.default_headers);
+    if let Some(from) = from {
+        let from = header_map(&from.default_headers);
+        for name in from.keys() {
+            if headers.get_all(name).iter().eq(from.get_all(name)) {
+                headers.remove(name);
+                for value in &defaults.get_all(name) {
+                    headers.append(name, value.clone());
+                }
+            }
+        }
+    }
+    if let Some(to) = to {
+        let to = header_map(&to.default_headers);
+        for name in to.keys() {
+            if headers.get_all(name).iter().eq(defaults.get_all(name)) {
+                headers.remove(name);
+                for value in &to.get_all(name) {
+                    headers.append(name, value.clone());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    use http::header::{LOCATION, USER_AGENT};
+    use http::StatusCode;
+
+    use crate::middleware::Retry;
+    use crate::{middleware_fn, Follow, InMemoryBody, ResponseExt, Transport};
+
+    use super::*;
+
+    #[test]
+    fn test_host_profile() {
+        let timeouts = Timeouts {
+            total: Some(Duration::from_secs(80)),
+            ..Timeouts::default()
+        };
+        let stripe_profile = Profile::new()
+            .timeouts(timeouts)
+            .default_header("User-Agent", "stripe-sdk")
+            .middleware(Retry::new().max_retries(5));
+        let client = Client::new()
+            .with_middleware(Retry::new())
+            .host_profile("api.stripe.com", stripe_profile)
+            .host_profile("*.example.com", Profile::new().default_header("X-Api-Key", "k"));
+
+        let stripe = "https://API.stripe.com/v1/charges".parse().unwrap();
+        assert_eq!(client.timeouts_for(&stripe), timeouts);
+        assert_eq!(client.timeouts_for(&"https://other.com/".parse().unwrap()), Timeouts::default());
+        // The profile's Retry replaces the client's rather than nesting inside it.
+        let builder = client.get("https://api.stripe.com/v1/charges");
+        let profile_retry = &client.profile(&stripe).unwrap().middlewares[0];
+        assert_eq!(builder.middlewares.len(), 2);
+        assert!(Arc::ptr_eq(&builder.middlewares[0], profile_retry));
+        assert!(!builder.middlewares[1].retries_requests());
+        let builder = client.get("https://other.com/");
+        assert!(Arc::ptr_eq(&builder.middlewares[0], &client.middlewares[0]));
+        let r = client.get("https://api.stripe.com/v1/charges").build();
+        assert_eq!(r.headers().get_all(USER_AGENT).iter().collect::<Vec<_>>(), vec!["stripe-sdk"]);
+
+        let r = client.get("https://eu.example.com/").build();
+        assert_eq!(r.headers()["x-api-key"], "k");
+        assert!(r.headers().contains_key(USER_AGENT));
+        assert!(!client.get("https://example.com/").build().headers().contains_key("x-api-key"));
+    }
+
+    #[derive(Debug)]
+    struct Redirecting;
+
+    /// Redirects `a.example.com` to `b.example.com`, which answers with the headers it received.
+    #[async_trait]
+    impl Transport for Redirecting {
+        async fn call(&self, request: InMemoryRequest) -> ProtocolResult<Response> {
+            if request.uri().host() == Some("a.example.com") {
+                let mut res = Response::new(InMemoryBody::Empty.into());
+                *res.status_mut() = StatusCode::FOUND;
+                res.headers_mut().insert(LOCATION, HeaderValue::from_static("https://b.example.com/"));
+                return Ok(res);
+            }
+            let key = request.headers().get("x-api-key").map_or("none", |v| v.to_str().unwrap());
+            let text = format!("{key} {}", request.headers()[USER_AGENT].to_str().unwrap());
+            Ok(Response::new(InMemoryBody::Text(text.into()).into()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_host_profile_redirect() {
+        let hops = Arc::new(AtomicUsize::new(0));
+        let counter = hops.clone();
+        let count = middleware_fn(move |request: InMemoryRequest, next| {
+            counter.fetch_add(1, Ordering::Relaxed);
+            async move { next.run(request).await }
+        });
+        let client = Client::new()
+            .with_middleware(Follow)
+            .no_default_headers()
+            .default_header("User-Agent", "client")
+            .host_profile(
+                "a.example.com",
+                Profile::new().default_header("X-Api-Key", "k").default_header("User-Agent", "a").middleware(count),
+            )
+            .transport(Redirecting);
+        let res = client.get("https://a.example.com/").send().await.unwrap();
+        assert_eq!(res.text().await.unwrap(), "none client");
+        assert_eq!(hops.load(Ordering::Relaxed), 1);
+
+        let res = client.get("https://a.example.com/").header("X-Api-Key", "mine").send().await.unwrap();
+        assert_eq!(res.text().await.unwrap(), "mine client");
+    }
+}
@@ -0,0 +1,178 @@
+use std::fmt::{Debug, Formatter};
+use std::task::{Context, Poll};
+
+use async_trait::async_trait;
+use futures::future::BoxFuture;
+use http_body_util::{BodyExt, Full};
+use hyper::body::Bytes;
+use tower_service::Service;
+
+use crate::client::Transport;
+use crate::error::{ProtocolError, ProtocolResult};
+use crate::{Body, Client, InMemoryRequest, Request, Response};
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+fn service_error(e: impl Into<BoxError>) -> ProtocolError {
+    ProtocolError::IoError(std::io::Error::other(e.into()))
+}
+
+/// Use the client as a `tower::Service`. Requests go through the client's middlewares like any other,
+/// with the `base_url` applied to relative URIs, default headers added where the request doesn't set them,
+/// and the [`crate::Profile`] of their host.
+/// Streamed bodies are buffered in memory first, because middlewares work on in-memory requests.
+impl Service<Request<Body>> for Client {
+    type Response = Response;
+    type Error = ProtocolError;
+    type Future = BoxFuture<'static, ProtocolResult<Response>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        let client = self.clone();
+        Box::pin(async move {
+            let (parts, body) = request.into_parts();
+            let body = body.into_memory().await?;
+            // Build it like any other request, so a host's profile applies its headers and middlewares too.
+            let mut builder = client.request(parts.method, parts.uri.to_string());
+            builder.version = parts.version;
+            builder.headers = parts.headers;
+            builder.extensions.extend(parts.extensions);
+            builder.body = Some(body);
+            builder.send().await
+        })
+    }
+}
+
+/// A [`Transport`] that hands requests to a `tower::Service` in the same process, e.g. an axum `Router`,
+/// instead of sending them over the network. See [`Client::for_service`].
+pub struct ServiceTransport<S> {
+    service: S,
+}
+
+impl<S> Debug for ServiceTransport<S> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ServiceTransport").finish_non_exhaustive()
+    }
+}
+
+impl<S> ServiceTransport<S> {
+    pub fn new(service: S) -> Self {
+        Self { service }
+    }
+}
+
+#[async_trait]
+impl<S, B> Transport for ServiceTransport<S>
+where
+    S: Service<http::Request<Full<Bytes>>, Response = http::Response<B>> + Clone + Send + Sync + 'static,
+    S::Future: Send,
+    S::Error: Into<BoxError>,
+    B: http_body::Body<Data = Bytes> + Send + 'static,
+    B::Error: Into<BoxError>,
+{
+    async fn call(&self, request: InMemoryRequest) -> ProtocolResult<Response> {
+        let (parts, body) = request.into_parts();
+        let request = http::Request::from_parts(parts, Full::new(body.into_wire_bytes()?));
+        // Services take `&mut self`, so call a clone, as a server would for each connection.
+        let mut service = self.service.clone();
+        std::future::poll_fn(|cx| service.poll_ready(cx)).await.map_err(service_error)?;
+        let (parts, body) = service.call(request).await.map_err(service_error)?.into_parts();
+        let body = body.collect().await.map_err(service_error)?.to_bytes();
+        Ok(Response::from_parts(parts, hyper::Body::from(body).into()))
+    }
+}
+
+impl Client {
+    /// A client that sends requests straight to `service`, e.g. an axum `Router`, without a listener or a network,
+    /// to test a service written in Rust. Its `base_url` is `http://localhost`, so requests can use paths:
+    /// ```ignore
+    /// let client = Client::for_service(app());
+    /// let res = client.get("/users/1").await?;
+    /// ```
+    /// Middlewares can be added as usual. To configure the client yourself, give it a [`ServiceTransport`]
+    /// with [`Client::transport`].
+    #[must_use]
+    pub fn for_service<S, B>(service: S) -> Self
+    where
+        S: Service<http::Request<Full<Bytes>>, Response = http::Response<B>> + Clone + Send + Sync + 'static,
+        S::Future: Send,
+        S::Error: Into<BoxError>,
+        B: http_body::Body<Data = Bytes> + Send + 'static,
+        B::Error: Into<BoxError>,
+    {
+        Client::new().base_url("http://localhost").transport(ServiceTransport::new(service))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::middleware::FakeTransport;
+    use crate::{InMemoryBody, Profile, ResponseExt, StatusCode};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_service() {
+        let transport = FakeTransport::new().respond_with(StatusCode::CREATED, InMemoryBody::Empty);
+        let mut client = Client::new()
+            .base_url("https://example.com/api")
+            .default_header("X-Tenant", "a")
+            .with_middleware(transport.clone());
+        let request = Request::builder()
+            .method("POST")
+            .uri("/items")
+            .header("x-tenant", "b")
+            .body(Body::from(InMemoryBody::Empty))
+            .unwrap();
+        let res = client.call(request).await.unwrap();
+        assert_eq!(res.status(), StatusCode::CREATED);
+        let sent = &transport.requests()[0];
+        assert_eq!(sent.uri(), "https://example.com/api/items");
+        assert_eq!(sent.headers().get_all("x-tenant").iter().collect::<Vec<_>>(), vec!["b"]);
+        assert!(sent.headers().contains_key("user-agent"));
+    }
+
+    #[tokio::test]
+    async fn test_service_host_profile() {
+        let transport = FakeTransport::new();
+        let profile = Profile::new().default_header("X-Api-Key", "secret").middleware(transport.clone());
+        let mut client = Client::new().host_profile("api.example.com", profile);
+        let request = Request::builder().uri("https://api.example.com/items").body(Body::from(InMemoryBody::Empty)).unwrap();
+        client.call(request).await.unwrap();
+        let sent = &transport.requests()[0];
+        assert_eq!(sent.headers()["x-api-key"], "secret");
+    }
+
+    #[derive(Debug, Clone)]
+    struct Greet;
+
+    impl Service<http::Request<Full<Bytes>>> for Greet {
+        type Response = http::Response<Full<Bytes>>;
+        type Error = std::convert::Infallible;
+        type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, request: http::Request<Full<Bytes>>) -> Self::Future {
+            Box::pin(async move {
+                let path = request.uri().path().to_string();
+                let name = request.into_body().collect().await?.to_bytes();
+                let body = format!("Hello {} from {path}", String::from_utf8_lossy(&name));
+                Ok(http::Response::builder().status(201).body(Full::new(Bytes::from(body))).unwrap())
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_for_service() {
+        let client = Client::for_service(Greet);
+        let res = client.post("/greetings").text("Ada".to_string()).send().await.unwrap();
+        assert_eq!(res.status(), StatusCode::CREATED);
+        assert_eq!(res.text().await.unwrap(), "Hello Ada from /greetings");
+    }
+}
@@ -0,0 +1,55 @@
+#[cfg(not(target_arch = "wasm32"))]
+use std::io;
+use std::time::Duration;
+
+#[cfg(not(target_arch = "wasm32"))]
+use futures::stream;
+#[cfg(not(target_arch = "wasm32"))]
+use hyper::body::HttpBody;
+
+/// Every timeout a client applies, in one place. Unset (`None`) timeouts don't limit anything.
+///
+/// Each one applies wherever it's enforced, independent of middleware order:
+/// - `connect`: establishing a TCP connection.
+/// - `first_byte`: each request sent over the wire, until its response headers arrive.
+/// - `per_try`: each attempt made by [`crate::Retry`], including the middlewares after it. Timed out attempts are retried.
+///   A `Retry` with its own `header_timeout` uses that instead.
+/// - `total`: the whole request, including every middleware, retry, and redirect, until the response headers arrive.
+/// - `idle_read`: the longest wait for the next chunk of a response body.
+///
+/// On wasm32, the host's `fetch` makes the connections and reads the whole body before the response headers are
+/// returned, so `connect` and `idle_read` don't apply there, and `first_byte` covers the body too.
+/// ```
+/// # use std::time::Duration;
+/// # use httpclient::{Client, Timeouts};
+/// let client = Client::new().timeouts(Timeouts {
+///     connect: Some(Duration::from_secs(5)),
+///     total: Some(Duration::from_secs(60)),
+///     ..Timeouts::default()
+/// });
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Timeouts {
+    pub connect: Option<Duration>,
+    pub first_byte: Option<Duration>,
+    pub per_try: Option<Duration>,
+    pub total: Option<Duration>,
+    pub idle_read: Option<Duration>,
+}
+
+/// Fail reading `body` with `io::ErrorKind::TimedOut` if no chunk arrives within `timeout`.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn idle_read_timeout(body: hyper::Body, timeout: Duration) -> hyper::Body {
+    let chunks = stream::unfold(Some(body), move |body| async move {
+        let mut body = body?;
+        match crate::clock::timeout(timeout, body.data()).await {
+            Ok(Some(chunk)) => Some((chunk.map_err(Into::into), Some(body))),
+            Ok(None) => None,
+            Err(_) => {
+                let err: Box<dyn std::error::Error + Send + Sync> = Box::new(io::Error::new(io::ErrorKind::TimedOut, "Timed out reading response body"));
+                Some((Err(err), None))
+            }
+        }
+    });
+    hyper::Body::wrap_stream(chunks)
+}
@@ -0,0 +1,74 @@
+use std::net::IpAddr;
+
+use hyper_rustls::MaybeHttpsStream;
+use tokio::net::TcpStream;
+
+/// The TLS session of the connection that served a response. Found in the response extensions of HTTPS responses:
+/// ```
+/// # use httpclient::{Response, TlsInfo};
+/// # use tracing::warn;
+/// # fn f(res: Response) {
+/// if let Some(tls) = res.extensions().get::<TlsInfo>() {
+///     if tls.protocol_version != "TLSv1_3" {
+///         warn!(cipher = tls.cipher_suite, "Weak TLS session");
+///     }
+/// }
+/// # }
+/// ```
+/// Pooled connections are reused, so this describes the handshake made when the connection was opened.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TlsInfo {
+    /// e.g. `TLSv1_3`.
+    pub protocol_version: String,
+    /// e.g. `TLS13_AES_256_GCM_SHA384`.
+    pub cipher_suite: String,
+    /// The certificate chain the server sent, DER encoded, starting with its own certificate.
+    pub peer_certificates: Vec<Vec<u8>>,
+    /// The host name sent with SNI. `None` when connecting to an IP address, which is sent without one.
+    pub server_name: Option<String>,
+    /// The protocol agreed with ALPN, e.g. `http/1.1`, if any.
+    pub alpn_protocol: Option<String>,
+}
+
+/// The host name the TLS handshake for `uri` sends with SNI.
+pub(crate) fn server_name(uri: &hyper::Uri) -> Option<String> {
+    let host = uri.host()?.trim_start_matches('[').trim_end_matches(']');
+    if host.parse::<IpAddr>().is_ok() {
+        None
+    } else {
+        Some(host.to_string())
+    }
+}
+
+/// A connection that may be over TLS.
+pub(crate) trait TlsConnection {
+    fn tls_info(&self, server_name: Option<String>) -> Option<TlsInfo>;
+}
+
+impl TlsConnection for MaybeHttpsStream<TcpStream> {
+    fn tls_info(&self, server_name: Option<String>) -> Option<TlsInfo> {
+        let MaybeHttpsStream::Https(tls) = self else {
+            return None;
+        };
+        let (_, session) = tls.get_ref();
+        Some(TlsInfo {
+            protocol_version: format!("{:?}", session.protocol_version()?),
+            cipher_suite: format!("{:?}", session.negotiated_cipher_suite()?.suite()),
+            peer_certificates: session.peer_certificates().unwrap_or_default().iter().map(|c| c.0.clone()).collect(),
+            server_name,
+            alpn_protocol: session.alpn_protocol().map(|p| String::from_utf8_lossy(p).into_owned()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_server_name() {
+        assert_eq!(server_name(&hyper::Uri::from_static("https://example.com:8443/")), Some("example.com".to_string()));
+        assert_eq!(server_name(&hyper::Uri::from_static("https://127.0.0.1/")), None);
+        assert_eq!(server_name(&hyper::Uri::from_static("https://[::1]/")), None);
+    }
+}
@@ -0,0 +1,78 @@
+use std::fmt::Debug;
+
+use async_trait::async_trait;
+
+use crate::error::ProtocolResult;
+use crate::middleware::copy_extensions;
+use crate::{InMemoryRequest, Response};
+
+/// The last step of the request pipeline, which sends a request after every middleware has run.
+/// By default the client sends requests over the network with hyper, or with `fetch` on wasm32; set a transport with [`crate::Client::transport`]
+/// to answer them in-process instead, e.g. with a router under test, a simulation, or another protocol.
+/// ```
+/// # use async_trait::async_trait;
+/// # use httpclient::{Client, InMemoryRequest, ProtocolResult, Response, Transport};
+/// #[derive(Debug)]
+/// struct Echo;
+///
+/// #[async_trait]
+/// impl Transport for Echo {
+///     async fn call(&self, request: InMemoryRequest) -> ProtocolResult<Response> {
+///         Ok(Response::new(request.into_body().into()))
+///     }
+/// }
+///
+/// let client = Client::new().transport(Echo);
+/// ```
+/// Streamed bodies, e.g. from [`crate::RequestBuilder::json_stream`], are read into memory before the transport is
+/// called. Like the default transport, the request's extensions are copied onto the response.
+#[async_trait]
+pub trait Transport: Send + Sync + Debug {
+    async fn call(&self, request: InMemoryRequest) -> ProtocolResult<Response>;
+}
+
+/// Send `request` with a custom transport, copying the request's extensions onto the response.
+pub(crate) async fn send_custom(transport: &dyn Transport, request: InMemoryRequest) -> ProtocolResult<Response> {
+    let extensions = request.extensions().clone();
+    let mut res = transport.call(request).await?;
+    copy_extensions(extensions, &mut res);
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use http::StatusCode;
+
+    use crate::{Client, InMemoryBody, ResponseExt};
+
+    use super::*;
+
+    #[derive(Debug)]
+    struct Echo;
+
+    #[async_trait]
+    impl Transport for Echo {
+        async fn call(&self, request: InMemoryRequest) -> ProtocolResult<Response> {
+            let (parts, body) = request.into_parts();
+            let text = format!("{} {} {}", parts.method, parts.uri, body.text().unwrap_or_default());
+            let mut res = Response::new(InMemoryBody::Text(text.into()).into());
+            *res.status_mut() = StatusCode::ACCEPTED;
+            Ok(res)
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Trace(u32);
+
+    #[tokio::test]
+    async fn test_transport() {
+        let client = Client::new().base_url("https://example.com").transport(Echo);
+        let res = client.post("/items").text("hi".to_string()).extension(Trace(7)).send().await.unwrap();
+        assert_eq!(res.status(), StatusCode::ACCEPTED);
+        assert_eq!(res.extensions().get::<Trace>(), Some(&Trace(7)));
+        assert_eq!(res.text().await.unwrap(), "POST https://example.com/items hi");
+
+        let res = client.post("/items").json_stream([1, 2]).send().await.unwrap();
+        assert_eq!(res.text().await.unwrap(), "POST https://example.com/items [1,2]");
+    }
+}
@@ -0,0 +1,128 @@
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use futures::future::BoxFuture;
+use futures::FutureExt;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) use std::time::Instant;
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) use tokio::time::{sleep, timeout};
+#[cfg(target_arch = "wasm32")]
+pub(crate) use web_time::Instant;
+
+/// The source of the current time for expiry logic, e.g. cache freshness and token lifetimes, and for waits like
+/// retry back-off. Swap in a [`TestClock`] to fast-forward time in tests.
+pub trait Clock: Send + Sync + Debug {
+    fn now(&self) -> SystemTime;
+
+    /// Wait for `duration` to pass.
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        sleep(duration).boxed()
+    }
+}
+
+/// The real system time. The default everywhere.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        // wasm32-unknown-unknown has no system clock, so `SystemTime::now` panics there. Ask the JavaScript host.
+        #[cfg(target_arch = "wasm32")]
+        return std::time::UNIX_EPOCH + Duration::from_secs_f64(js_sys::Date::now() / 1000.0);
+        #[cfg(not(target_arch = "wasm32"))]
+        SystemTime::now()
+    }
+}
+
+/// A clock that only moves when told to. Clones share the same time.
+/// Sleeping on it returns at once, after advancing it by the duration, so waits like retry back-off take no real time
+/// and show up in the clock instead.
+/// ```
+/// # use std::time::Duration;
+/// # use httpclient::clock::TestClock;
+/// # use httpclient::NegativeCache;
+/// let clock = TestClock::new();
+/// let cache = NegativeCache::new(Duration::from_secs(5)).clock(clock.clone());
+/// clock.advance(Duration::from_secs(6));
+/// ```
+#[derive(Debug, Clone)]
+pub struct TestClock(Arc<Mutex<SystemTime>>);
+
+impl Default for TestClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TestClock {
+    /// A clock stopped at the current system time.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::at(SystemTime::now())
+    }
+
+    #[must_use]
+    pub fn at(time: SystemTime) -> Self {
+        Self(Arc::new(Mutex::new(time)))
+    }
+
+    pub fn advance(&self, by: Duration) {
+        *self.0.lock().expect("Test clock lock poisoned") += by;
+    }
+
+    pub fn set(&self, time: SystemTime) {
+        *self.0.lock().expect("Test clock lock poisoned") = time;
+    }
+}
+
+impl Clock for TestClock {
+    fn now(&self) -> SystemTime {
+        *self.0.lock().expect("Test clock lock poisoned")
+    }
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        self.advance(duration);
+        futures::future::ready(()).boxed()
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_name = setTimeout)]
+    fn set_timeout(handler: &wasm_bindgen::JsValue, millis: i32) -> wasm_bindgen::JsValue;
+}
+
+/// Wait for `duration` with the JavaScript host's `setTimeout`, in place of tokio's timer on wasm32.
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn sleep(duration: Duration) -> impl std::future::Future<Output = ()> + Send {
+    let (tx, rx) = futures::channel::oneshot::channel();
+    let wake = wasm_bindgen::closure::Closure::once_into_js(move || {
+        let _ = tx.send(());
+    });
+    set_timeout(&wake, i32::try_from(duration.as_millis()).unwrap_or(i32::MAX));
+    rx.map(|_| ())
+}
+
+/// The error from [`timeout`] when the future didn't finish in time.
+#[cfg(target_arch = "wasm32")]
+#[derive(Debug)]
+pub(crate) struct Elapsed;
+
+/// Like `tokio::time::timeout`: wait for `future`, or fail once `duration` has passed.
+#[cfg(target_arch = "wasm32")]
+pub(crate) async fn timeout<F: std::future::Future>(duration: Duration, future: F) -> Result<F::Output, Elapsed> {
+    futures::pin_mut!(future);
+    match futures::future::select(future, sleep(duration)).await {
+        futures::future::Either::Left((output, _)) => Ok(output),
+        futures::future::Either::Right(_) => Err(Elapsed),
+    }
+}
+
+/// Time elapsed since `earlier` according to `clock`, or zero if `earlier` is in the future.
+pub(crate) fn elapsed(clock: &dyn Clock, earlier: SystemTime) -> Duration {
+    clock.now().duration_since(earlier).unwrap_or_default()
+}
@@ -0,0 +1,237 @@
+use std::time::Duration;
+
+use http::header::{
+    ACCESS_CONTROL_ALLOW_CREDENTIALS, ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_METHODS, ACCESS_CONTROL_ALLOW_ORIGIN, ACCESS_CONTROL_MAX_AGE,
+    ACCESS_CONTROL_REQUEST_HEADERS, ACCESS_CONTROL_REQUEST_METHOD, ORIGIN,
+};
+use http::{HeaderMap, HeaderValue, Method, StatusCode};
+
+use crate::error::ProtocolResult;
+use crate::{Client, Request, RequestBuilder, Response};
+
+/// Headers a browser sets itself, which never appear in `Access-Control-Request-Headers`.
+const FORBIDDEN_HEADERS: [&str; 9] = ["connection", "content-length", "cookie", "date", "host", "keep-alive", "origin", "referer", "user-agent"];
+
+/// Why a preflight would make the browser block the request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CorsProblem {
+    /// The preflight response wasn't 2xx.
+    Status(StatusCode),
+    /// `Access-Control-Allow-Origin` is missing or doesn't match the origin.
+    OriginNotAllowed(Option<String>),
+    /// Credentials were requested, but `Access-Control-Allow-Credentials` isn't `true`, or the origin is `*`.
+    CredentialsNotAllowed,
+    MethodNotAllowed(Method),
+    HeaderNotAllowed(String),
+}
+
+/// The outcome of a preflight, judged the way a browser would.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CorsVerdict {
+    pub problems: Vec<CorsProblem>,
+    /// How long the browser may cache the preflight, from `Access-Control-Max-Age`.
+    pub max_age: Option<Duration>,
+}
+
+impl CorsVerdict {
+    #[must_use]
+    pub fn is_allowed(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// Simulate the CORS preflight a browser on `origin` would send before a request, e.g. to check that a
+/// gateway exposes the right CORS headers.
+/// ```no_run
+/// # use httpclient::cors::Preflight;
+/// # use httpclient::Client;
+/// # async fn f(client: Client, token: &str) -> httpclient::ProtocolResult<()> {
+/// let req = client.put("/items/1").bearer_auth(token).build();
+/// let verdict = Preflight::new("https://app.example.com").credentials(true).check(&client, &req).await?;
+/// assert!(verdict.is_allowed(), "{:?}", verdict.problems);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Preflight {
+    origin: String,
+    credentials: bool,
+}
+
+fn list(headers: &HeaderMap, name: &http::HeaderName) -> Vec<String> {
+    headers
+        .get_all(name)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(ToString::to_string)
+        .collect()
+}
+
+fn is_safelisted(name: &str, value: &HeaderValue) -> bool {
+    match name {
+        "accept" | "accept-language" | "content-language" => true,
+        "content-type" => {
+            let mime = value.to_str().unwrap_or_default().split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+            matches!(mime.as_str(), "application/x-www-form-urlencoded" | "multipart/form-data" | "text/plain")
+        }
+        _ => false,
+    }
+}
+
+impl Preflight {
+    #[must_use]
+    pub fn new(origin: &str) -> Self {
+        Self {
+            origin: origin.to_string(),
+            credentials: false,
+        }
+    }
+
+    /// Simulate a request sent with credentials (cookies or `Authorization`), e.g. `fetch(url, { credentials: "include" })`.
+    #[must_use]
+    pub fn credentials(mut self, credentials: bool) -> Self {
+        self.credentials = credentials;
+        self
+    }
+
+    /// The header names a browser would list in `Access-Control-Request-Headers`: lowercase, sorted, and without
+    /// CORS-safelisted or browser-controlled headers.
+    #[must_use]
+    pub fn request_headers<B>(request: &Request<B>) -> Vec<String> {
+        let mut names: Vec<String> = request
+            .headers()
+            .iter()
+            .filter(|(k, v)| !is_safelisted(k.as_str(), v) && !FORBIDDEN_HEADERS.contains(&k.as_str()))
+            .map(|(k, _)| k.as_str().to_string())
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// Build the `OPTIONS` preflight for `request`.
+    #[must_use]
+    pub fn request<'a, B>(&self, client: &'a Client, request: &Request<B>) -> RequestBuilder<'a> {
+        let mut builder = RequestBuilder::new(client, Method::OPTIONS, request.uri().clone())
+            .set_middlewares(client.middlewares.clone())
+            .header(ORIGIN, &self.origin)
+            .header(ACCESS_CONTROL_REQUEST_METHOD, request.method().as_str());
+        let headers = Self::request_headers(request);
+        if !headers.is_empty() {
+            builder = builder.header(ACCESS_CONTROL_REQUEST_HEADERS, &headers.join(","));
+        }
+        builder
+    }
+
+    /// Judge a preflight response for `request`.
+    #[must_use]
+    pub fn verdict<B, R>(&self, request: &Request<B>, response: &http::Response<R>) -> CorsVerdict {
+        let headers = response.headers();
+        let mut problems = Vec::new();
+        if !response.status().is_success() {
+            problems.push(CorsProblem::Status(response.status()));
+        }
+
+        let allow_origin = headers.get(ACCESS_CONTROL_ALLOW_ORIGIN).and_then(|v| v.to_str().ok()).map(str::trim);
+        let wildcard_origin = allow_origin == Some("*");
+        if !(allow_origin == Some(self.origin.as_str()) || wildcard_origin && !self.credentials) {
+            problems.push(CorsProblem::OriginNotAllowed(allow_origin.map(ToString::to_string)));
+        }
+        if self.credentials && (wildcard_origin || headers.get(ACCESS_CONTROL_ALLOW_CREDENTIALS).is_none_or(|v| v != "true")) {
+            problems.push(CorsProblem::CredentialsNotAllowed);
+        }
+
+        // `*` is only a wildcard for requests without credentials.
+        let wildcard = |list: &[String]| !self.credentials && list.iter().any(|s| s == "*");
+        let methods = list(headers, &ACCESS_CONTROL_ALLOW_METHODS);
+        let method = request.method();
+        let simple = matches!(*method, Method::GET | Method::HEAD | Method::POST);
+        if !simple && !wildcard(&methods) && !methods.iter().any(|m| m == method.as_str()) {
+            problems.push(CorsProblem::MethodNotAllowed(method.clone()));
+        }
+        let allowed_headers = list(headers, &ACCESS_CONTROL_ALLOW_HEADERS);
+        for name in Self::request_headers(request) {
+            // `Authorization` is never covered by the wildcard.
+            let covered = wildcard(&allowed_headers) && name != "authorization";
+            if !covered && !allowed_headers.iter().any(|h| h.eq_ignore_ascii_case(&name)) {
+                problems.push(CorsProblem::HeaderNotAllowed(name));
+            }
+        }
+
+        let max_age = headers
+            .get(ACCESS_CONTROL_MAX_AGE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.trim().parse().ok())
+            .map(Duration::from_secs);
+        CorsVerdict { problems, max_age }
+    }
+
+    /// Send the preflight for `request` and judge the response.
+    pub async fn check<B>(&self, client: &Client, request: &Request<B>) -> ProtocolResult<CorsVerdict> {
+        let res: Response = self.request(client, request).send().await?;
+        Ok(self.verdict(request, &res))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::InMemoryBody;
+
+    use super::*;
+
+    #[test]
+    fn test_preflight() {
+        let client = Client::new().no_default_headers();
+        let req = client
+            .put("https://api.example.com/items/1")
+            .bearer_auth("token")
+            .header("x-trace-id", "1")
+            .content_type("text/plain")
+            .build();
+        let preflight = Preflight::new("https://app.example.com").credentials(true);
+        let options = preflight.request(&client, &req).build();
+        assert_eq!(options.method(), Method::OPTIONS);
+        assert_eq!(options.headers()[ACCESS_CONTROL_REQUEST_METHOD], "PUT");
+        assert_eq!(options.headers()[ACCESS_CONTROL_REQUEST_HEADERS], "authorization,x-trace-id");
+
+        let res = |headers: &[(&str, &str)]| {
+            let mut b = http::Response::builder().status(204);
+            for (k, v) in headers {
+                b = b.header(*k, *v);
+            }
+            b.body(InMemoryBody::Empty).unwrap()
+        };
+        let ok = res(&[
+            ("access-control-allow-origin", "https://app.example.com"),
+            ("access-control-allow-credentials", "true"),
+            ("access-control-allow-methods", "GET, PUT"),
+            ("access-control-allow-headers", "Authorization, X-Trace-Id"),
+            ("access-control-max-age", "600"),
+        ]);
+        let verdict = preflight.verdict(&req, &ok);
+        assert!(verdict.is_allowed(), "{:?}", verdict.problems);
+        assert_eq!(verdict.max_age, Some(Duration::from_secs(600)));
+
+        let wildcard = res(&[
+            ("access-control-allow-origin", "*"),
+            ("access-control-allow-methods", "*"),
+            ("access-control-allow-headers", "*"),
+        ]);
+        let verdict = preflight.verdict(&req, &wildcard);
+        assert_eq!(
+            verdict.problems,
+            vec![
+                CorsProblem::OriginNotAllowed(Some("*".to_string())),
+                CorsProblem::CredentialsNotAllowed,
+                CorsProblem::MethodNotAllowed(Method::PUT),
+                CorsProblem::HeaderNotAllowed("authorization".to_string()),
+                CorsProblem::HeaderNotAllowed("x-trace-id".to_string()),
+            ]
+        );
+        let verdict = Preflight::new("https://app.example.com").verdict(&req, &wildcard);
+        assert_eq!(verdict.problems, vec![CorsProblem::HeaderNotAllowed("authorization".to_string())]);
+    }
+}
@@ -0,0 +1,239 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use http::header::{ACCEPT, AUTHORIZATION, CONTENT_TYPE, COOKIE, REFERER, USER_AGENT};
+use http::{HeaderName, HeaderValue, Method, Uri};
+
+use crate::error::{ProtocolError, ProtocolResult};
+use crate::{Client, InMemoryBody, InMemoryRequest, RequestBuilder};
+
+/// Flags that don't affect the request itself, so they are accepted and ignored.
+const IGNORED_FLAGS: [&str; 14] = [
+    "-s",
+    "--silent",
+    "-S",
+    "--show-error",
+    "-L",
+    "--location",
+    "-k",
+    "--insecure",
+    "-v",
+    "--verbose",
+    "-i",
+    "--include",
+    "--compressed",
+    "--fail",
+];
+
+/// Split a shell command into words, handling single quotes, double quotes, backslash escapes, and line continuations.
+fn split_words(cmd: &str) -> ProtocolResult<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = cmd.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                let w = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => w.push(c),
+                        None => return Err(ProtocolError::invalid_input("Unterminated single quote".to_string())),
+                    }
+                }
+            }
+            '"' => {
+                let w = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\' | '$' | '`')) => w.push(c),
+                            Some('\n') => {}
+                            Some(c) => {
+                                w.push('\\');
+                                w.push(c);
+                            }
+                            None => return Err(ProtocolError::invalid_input("Unterminated double quote".to_string())),
+                        },
+                        Some(c) => w.push(c),
+                        None => return Err(ProtocolError::invalid_input("Unterminated double quote".to_string())),
+                    }
+                }
+            }
+            '\\' => match chars.next() {
+                Some('\n' | '\r') | None => {}
+                Some(c) => word.get_or_insert_with(String::new).push(c),
+            },
+            c if c.is_whitespace() => words.extend(word.take()),
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+fn header_value(value: &str) -> ProtocolResult<HeaderValue> {
+    HeaderValue::from_str(value.trim()).map_err(|_| ProtocolError::invalid_input(format!("Invalid header value: {value}")))
+}
+
+fn parse_header(header: &str) -> ProtocolResult<(HeaderName, HeaderValue)> {
+    let (name, value) = header.split_once(':').ok_or_else(|| ProtocolError::invalid_input(format!("Invalid header: {header}")))?;
+    let name = HeaderName::from_bytes(name.trim().as_bytes()).map_err(|_| ProtocolError::invalid_input(format!("Invalid header name: {name}")))?;
+    Ok((name, header_value(value)?))
+}
+
+/// Parse a `curl` command, e.g. copied from API docs or a browser's dev tools, into a request.
+///
+/// Supports the method (`-X`, `-I`, `-G`), headers (`-H`, `-A`, `-e`, `-b`), basic auth (`-u`),
+/// and bodies (`-d` and its `--data-*` variants, `--json`). Reading from files (`@file`) and multipart forms (`-F`) aren't supported.
+pub fn parse(cmd: &str) -> ProtocolResult<InMemoryRequest> {
+    let words = split_words(cmd)?;
+    let mut args = words.into_iter();
+    if args.next().as_deref() != Some("curl") {
+        return Err(ProtocolError::invalid_input("Command doesn't start with curl".to_string()));
+    }
+    let mut method = None;
+    let mut url = None;
+    let mut headers = Vec::new();
+    let mut data: Vec<String> = Vec::new();
+    let mut json = false;
+    let mut get = false;
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or_else(|| ProtocolError::invalid_input(format!("Missing value for {name}")));
+        match arg.as_str() {
+            "-X" | "--request" => method = Some(value(&arg)?),
+            "-H" | "--header" => headers.push(parse_header(&value(&arg)?)?),
+            "-A" | "--user-agent" => headers.push((USER_AGENT, header_value(&value(&arg)?)?)),
+            "-e" | "--referer" => headers.push((REFERER, header_value(&value(&arg)?)?)),
+            "-b" | "--cookie" => headers.push((COOKIE, header_value(&value(&arg)?)?)),
+            "-u" | "--user" => {
+                let credentials = STANDARD.encode(value(&arg)?);
+                headers.push((AUTHORIZATION, header_value(&format!("Basic {credentials}"))?));
+            }
+            "-d" | "--data" | "--data-raw" | "--data-binary" | "--data-ascii" | "--json" => {
+                let d = value(&arg)?;
+                if d.starts_with('@') && arg != "--data-raw" {
+                    return Err(ProtocolError::invalid_input(format!("Reading {arg} from a file isn't supported")));
+                }
+                json |= arg == "--json";
+                data.push(d);
+            }
+            "--data-urlencode" => {
+                let d = value(&arg)?;
+                // Like curl, the first `=` or `@` splits off the name. `=content` has no name and sends only the content.
+                data.push(match d.find(['=', '@']).map(|i| d.split_at(i)) {
+                    Some((_, file)) if file.starts_with('@') => {
+                        return Err(ProtocolError::invalid_input(format!("Reading {arg} from a file isn't supported")));
+                    }
+                    Some(("", content)) => urlencoding::encode(&content[1..]).into_owned(),
+                    Some((name, content)) => format!("{name}={}", urlencoding::encode(&content[1..])),
+                    None => urlencoding::encode(&d).into_owned(),
+                });
+            }
+            "-I" | "--head" => method = Some("HEAD".to_string()),
+            "-G" | "--get" => get = true,
+            "--url" => url = Some(value(&arg)?),
+            a if IGNORED_FLAGS.contains(&a) => {}
+            // clusters of ignored short flags, e.g. `-sSL`
+            a if a.len() > 2 && a.starts_with('-') && !a.starts_with("--") && a[1..].chars().all(|c| IGNORED_FLAGS.contains(&format!("-{c}").as_str())) => {}
+            a if a.starts_with('-') => return Err(ProtocolError::invalid_input(format!("Unsupported curl option: {a}"))),
+            _ => url = Some(arg),
+        }
+    }
+    let mut url = url.ok_or_else(|| ProtocolError::invalid_input("No URL in curl command".to_string()))?;
+    let data = (!data.is_empty()).then(|| data.join(if json { "" } else { "&" }));
+    let body = match data {
+        Some(data) if get => {
+            url.push(if url.contains('?') { '&' } else { '?' });
+            url.push_str(&data);
+            None
+        }
+        data => data,
+    };
+    let method = match method {
+        Some(m) => Method::from_bytes(m.as_bytes()).map_err(|_| ProtocolError::invalid_input(format!("Invalid method: {m}")))?,
+        None if body.is_some() => Method::POST,
+        None => Method::GET,
+    };
+    let uri: Uri = url.parse().map_err(|_| ProtocolError::InvalidUrl(url.clone()))?;
+
+    let mut b = http::Request::builder().method(method).uri(uri);
+    if body.is_some() && !headers.iter().any(|(k, _)| k == CONTENT_TYPE) {
+        let content_type = if json { "application/json" } else { "application/x-www-form-urlencoded" };
+        b = b.header(CONTENT_TYPE, content_type);
+    }
+    if json && !headers.iter().any(|(k, _)| k == ACCEPT) {
+        b = b.header(ACCEPT, "application/json");
+    }
+    for (k, v) in headers {
+        b = b.header(k, v);
+    }
+    let is_json = b
+        .headers_ref()
+        .and_then(|h| h.get(CONTENT_TYPE))
+        .is_some_and(|v| v.as_bytes().starts_with(b"application/json"));
+    let body = match body {
+        Some(body) if is_json => serde_json::from_str(&body).map_or_else(|_| InMemoryBody::Text(body.into()), InMemoryBody::Json),
+        Some(body) => InMemoryBody::Text(body.into()),
+        None => InMemoryBody::Empty,
+    };
+    b.body(body).map_err(|e| ProtocolError::invalid_input(e.to_string()))
+}
+
+impl Client {
+    /// Build a request from a `curl` command, using this client's default headers and middlewares.
+    /// Headers in the command replace the defaults. See [`parse`] for the supported options.
+    pub fn from_curl(&self, cmd: &str) -> ProtocolResult<RequestBuilder<'_>> {
+        let (parts, body) = parse(cmd)?.into_parts();
+        let mut builder = self.request(parts.method, parts.uri.to_string());
+        builder.headers = parts.headers;
+        Ok(builder.body(body))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let req = parse(
+            r#"curl -sSL -X PUT 'https://api.example.com/v1/items/1' \
+  -H "Authorization: Bearer abc" \
+  -H 'Content-Type: application/json' \
+  --data-raw '{"name": "it'"'"'s"}'"#,
+        )
+        .unwrap();
+        assert_eq!(req.method(), Method::PUT);
+        assert_eq!(req.uri(), "https://api.example.com/v1/items/1");
+        assert_eq!(req.headers()[AUTHORIZATION], "Bearer abc");
+        assert!(matches!(req.body(), InMemoryBody::Json(v) if *v == json!({"name": "it's"})));
+
+        let req = parse("curl https://example.com/search -G --data-urlencode 'q=a b' -d page=2 -u user:pass").unwrap();
+        assert_eq!(req.method(), Method::GET);
+        assert_eq!(req.uri(), "https://example.com/search?q=a%20b&page=2");
+        assert_eq!(req.headers()[AUTHORIZATION], "Basic dXNlcjpwYXNz");
+
+        let req = parse("curl -d a=1 -d b=2 https://example.com/form").unwrap();
+        assert_eq!(req.method(), Method::POST);
+        assert_eq!(req.headers()[CONTENT_TYPE], "application/x-www-form-urlencoded");
+        assert!(matches!(req.body(), InMemoryBody::Text(t) if t == "a=1&b=2"));
+
+        let req = parse("curl --data-urlencode '=a b&c' --data-urlencode 'q=x=y' https://example.com/form").unwrap();
+        assert!(matches!(req.body(), InMemoryBody::Text(t) if t == "a%20b%26c&q=x%3Dy"));
+        assert!(parse("curl --data-urlencode name@a.txt https://example.com/").is_err());
+        assert!(parse("curl --data-urlencode @a.txt https://example.com/").is_err());
+
+        assert!(parse("curl -F file=@a.txt https://example.com/").is_err());
+        assert!(parse("wget https://example.com/").is_err());
+
+        let client = Client::new().default_header("x-api-key", "k");
+        let req = client.from_curl("curl -A 'custom/1.0' https://example.com/").unwrap().build();
+        assert_eq!(req.headers().get_all(USER_AGENT).iter().collect::<Vec<_>>(), vec!["custom/1.0"]);
+        assert_eq!(req.headers()["x-api-key"], "k");
+
+        assert!(matches!(parse("curl -X"), Err(ProtocolError::InvalidInput(_))));
+    }
+}
@@ -0,0 +1,263 @@
+//! Build RFC 2822 email messages, e.g. for the Gmail API, which takes them base64url encoded in a `raw` field.
+//! ```no_run
+//! # use httpclient::email::Message;
+//! # use httpclient::Client;
+//! # use serde_json::json;
+//! # async fn f(client: Client, pdf_bytes: Vec<u8>) -> httpclient::InMemoryResult<()> {
+//! let message = Message::new()
+//!     .from("Ada <ada@example.com>")
+//!     .to("grace@example.com")
+//!     .subject("Quarterly report")
+//!     .text("See attached.".to_string())
+//!     .html("<p>See attached.</p>".to_string())
+//!     .attach("report.pdf", "application/pdf", pdf_bytes);
+//! let res = client.post("/gmail/v1/users/me/messages/send").json(json!({"raw": message.to_raw()})).await?;
+//! # Ok(())
+//! # }
+//! ```
+use base64::engine::general_purpose::{STANDARD, URL_SAFE};
+use base64::Engine;
+use http::header::{CONTENT_DISPOSITION, CONTENT_TYPE};
+use http::{HeaderMap, HeaderName, HeaderValue};
+use hyper::body::Bytes;
+
+use crate::header_ext::{BCC, CC, CONTENT_TRANSFER_ENCODING, FROM, MIME_VERSION, SUBJECT, TO};
+use crate::multipart::{Form, Part};
+use crate::InMemoryBody;
+
+/// Base64 bodies are wrapped to this line length, the most RFC 2045 allows.
+const LINE_LENGTH: usize = 76;
+
+/// A file attached to a [`Message`].
+#[derive(Debug, Clone)]
+pub struct Attachment {
+    pub filename: String,
+    pub content_type: String,
+    pub data: Bytes,
+}
+
+/// An email message with a text body, an HTML body, or both as `multipart/alternative`, and any attachments.
+#[derive(Debug, Clone, Default)]
+pub struct Message {
+    headers: HeaderMap,
+    text: Option<String>,
+    html: Option<String>,
+    attachments: Vec<Attachment>,
+}
+
+/// Encode non-ASCII text as an RFC 2047 encoded word, so it can go in a header.
+fn encode_word(text: &str) -> String {
+    if text.is_ascii() {
+        text.to_string()
+    } else {
+        format!("=?UTF-8?B?{}?=", STANDARD.encode(text))
+    }
+}
+
+/// Encode the display name of an address like `Name <user@example.com>`. The address itself must be ASCII.
+fn encode_address(address: &str) -> String {
+    match address.rsplit_once('<') {
+        Some((name, rest)) if !name.is_ascii() => format!("{} <{rest}", encode_word(name.trim().trim_matches('"'))),
+        _ => address.to_string(),
+    }
+}
+
+fn base64_lines(data: &[u8]) -> String {
+    let encoded = STANDARD.encode(data);
+    let lines: Vec<&str> = encoded
+        .as_bytes()
+        .chunks(LINE_LENGTH)
+        .map(|line| std::str::from_utf8(line).expect("Base64 is ASCII"))
+        .collect();
+    lines.join("\r\n")
+}
+
+fn text_part(content_type: &'static str, text: &str) -> Part<InMemoryBody> {
+    let mut headers = HeaderMap::new();
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+    if text.is_ascii() {
+        headers.insert(CONTENT_TRANSFER_ENCODING, HeaderValue::from_static("7bit"));
+        Part::new(headers, InMemoryBody::Text(text.into()))
+    } else {
+        headers.insert(CONTENT_TRANSFER_ENCODING, HeaderValue::from_static("base64"));
+        Part::new(headers, InMemoryBody::Text(base64_lines(text.as_bytes()).into()))
+    }
+}
+
+/// The `Content-Disposition` of an attachment. A non-ASCII filename is sent RFC 2231 encoded, after an ASCII
+/// fallback for clients that don't read it.
+fn disposition(filename: &str) -> String {
+    let fallback: String = filename.chars().filter(|c| *c != '"' && *c != '\\').map(|c| if c.is_ascii() { c } else { '_' }).collect();
+    if filename.is_ascii() {
+        format!("attachment; filename=\"{fallback}\"")
+    } else {
+        format!("attachment; filename=\"{fallback}\"; filename*=UTF-8''{}", urlencoding::encode(filename))
+    }
+}
+
+impl Attachment {
+    fn part(&self) -> Part<InMemoryBody> {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_str(&self.content_type).expect("Invalid attachment content type"));
+        headers.insert(
+            CONTENT_DISPOSITION,
+            HeaderValue::from_str(&disposition(&self.filename)).expect("Invalid attachment filename"),
+        );
+        headers.insert(CONTENT_TRANSFER_ENCODING, HeaderValue::from_static("base64"));
+        Part::new(headers, InMemoryBody::Text(base64_lines(&self.data).into()))
+    }
+}
+
+impl Message {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set a header. Non-ASCII values are encoded.
+    ///
+    /// # Panics
+    /// Panics if the value has control characters, like a newline.
+    #[must_use]
+    pub fn header(mut self, name: HeaderName, value: &str) -> Self {
+        self.headers.insert(name, HeaderValue::from_str(&encode_word(value)).expect("Invalid header value"));
+        self
+    }
+
+    /// Add `address` to the `name` field. RFC 5322 allows each field once, so the addresses are comma-separated.
+    fn address(mut self, name: HeaderName, address: &str) -> Self {
+        let address = encode_address(address);
+        let value = match self.headers.get(&name).and_then(|v| v.to_str().ok()) {
+            Some(addresses) => format!("{addresses}, {address}"),
+            None => address,
+        };
+        self.headers.insert(name, HeaderValue::from_str(&value).expect("Invalid address"));
+        self
+    }
+
+    #[must_use]
+    pub fn from(mut self, address: &str) -> Self {
+        self.headers.remove(FROM);
+        self.address(FROM, address)
+    }
+
+    /// Add a recipient. Call again for more.
+    #[must_use]
+    pub fn to(self, address: &str) -> Self {
+        self.address(TO, address)
+    }
+
+    #[must_use]
+    pub fn cc(self, address: &str) -> Self {
+        self.address(CC, address)
+    }
+
+    #[must_use]
+    pub fn bcc(self, address: &str) -> Self {
+        self.address(BCC, address)
+    }
+
+    #[must_use]
+    pub fn subject(self, subject: &str) -> Self {
+        self.header(SUBJECT, subject)
+    }
+
+    #[must_use]
+    pub fn text(mut self, text: String) -> Self {
+        self.text = Some(text);
+        self
+    }
+
+    #[must_use]
+    pub fn html(mut self, html: String) -> Self {
+        self.html = Some(html);
+        self
+    }
+
+    /// Attach a file. It's sent base64 encoded.
+    #[must_use]
+    pub fn attach(mut self, filename: &str, content_type: &str, data: impl Into<Bytes>) -> Self {
+        self.attachments.push(Attachment {
+            filename: filename.to_string(),
+            content_type: content_type.to_string(),
+            data: data.into(),
+        });
+        self
+    }
+
+    /// The message as a part: its headers, then the body, nested in `multipart/mixed` if it has attachments.
+    #[must_use]
+    pub fn to_part(&self) -> Part<InMemoryBody> {
+        let text = self.text.as_deref().map(|text| text_part("text/plain; charset=utf-8", text));
+        let html = self.html.as_deref().map(|html| text_part("text/html; charset=utf-8", html));
+        let body = match (text, html) {
+            (Some(text), Some(html)) => Part::form(Form::alternative().part(text).part(html)),
+            (text, html) => text.or(html).unwrap_or_else(|| text_part("text/plain; charset=utf-8", "")),
+        };
+        let mut part = if self.attachments.is_empty() {
+            body
+        } else {
+            let form = self.attachments.iter().fold(Form::mixed().part(body), |form, a| form.part(a.part()));
+            Part::form(form)
+        };
+        let mut headers = self.headers.clone();
+        headers.insert(MIME_VERSION, HeaderValue::from_static("1.0"));
+        headers.extend(part.headers);
+        part.headers = headers;
+        part
+    }
+
+    /// The message in RFC 2822 format.
+    #[must_use]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.to_part().into()
+    }
+
+    /// The message base64url encoded, e.g. for the `raw` field of the Gmail API.
+    #[must_use]
+    pub fn to_raw(&self) -> String {
+        URL_SAFE.encode(self.to_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message() {
+        let message = Message::new()
+            .from("Zoë <zoe@example.com>")
+            .to("a@example.com")
+            .to("b@example.com")
+            .subject("Café")
+            .text("Hi".to_string())
+            .html("<p>Hi</p>".to_string())
+            .attach("a.bin", "application/octet-stream", vec![0u8, 255]);
+        let bytes = URL_SAFE.decode(message.to_raw()).unwrap();
+        let text = String::from_utf8(bytes).unwrap();
+        let (head, body) = text.split_once("\r\n\r\n").unwrap();
+        assert!(head.contains("from: =?UTF-8?B?Wm/Dqw==?= <zoe@example.com>\r\n"));
+        assert!(head.contains("to: a@example.com, b@example.com\r\n"));
+        assert_eq!(head.matches("to: ").count(), 1);
+        assert!(head.contains("subject: =?UTF-8?B?Q2Fmw6k=?=\r\n"));
+        assert!(head.contains("mime-version: 1.0\r\n"));
+
+        let content_type = head.lines().find_map(|l| l.strip_prefix("content-type: ")).unwrap();
+        let mixed = Form::parse(content_type, body.as_bytes()).unwrap();
+        assert_eq!(mixed.content_type, "multipart/mixed");
+        let [alternative, attachment] = &mixed.parts[..] else { panic!("Expected two parts") };
+        assert_eq!(attachment.headers[CONTENT_DISPOSITION], "attachment; filename=\"a.bin\"");
+        assert!(matches!(&attachment.body, InMemoryBody::Bytes(b) if b == "AP8="));
+        let InMemoryBody::Text(inner) = &alternative.body else { panic!("Expected a text part") };
+        let alternative = Form::parse(alternative.header_str(CONTENT_TYPE).unwrap(), inner.as_bytes()).unwrap();
+        assert_eq!(alternative.content_type, "multipart/alternative");
+        assert_eq!(alternative.parts[1].headers[CONTENT_TYPE], "text/html; charset=utf-8");
+    }
+
+    #[test]
+    fn test_attachment_filename() {
+        assert_eq!(disposition("a \"b\".pdf"), "attachment; filename=\"a b.pdf\"");
+        assert_eq!(disposition("résumé.pdf"), "attachment; filename=\"r_sum_.pdf\"; filename*=UTF-8''r%C3%A9sum%C3%A9.pdf");
+    }
+}
@@ -14,12 +14,26 @@ pub enum ProtocolError {
     Utf8Error(FromUtf8Error),
     JsonError(serde_json::Error),
     IoError(std::io::Error),
+    InvalidUrl(String),
+    /// A URL policy, e.g. `SsrfGuard`, refused the request.
+    Blocked(String),
+    /// A single-use body would have to be sent more than once, e.g. by a middleware that retries.
+    NotReplayable(String),
+    /// Input the client couldn't use, e.g. a malformed curl command, key, or response body.
+    InvalidInput(String),
+    Timeout,
     TooManyRedirects,
     TooManyRetries,
 }
 
 impl std::error::Error for ProtocolError {}
 
+impl ProtocolError {
+    pub(crate) fn invalid_input(msg: impl Into<String>) -> Self {
+        Self::InvalidInput(msg.into())
+    }
+}
+
 impl Display for ProtocolError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -27,6 +41,11 @@ impl Display for ProtocolError {
             ProtocolError::Utf8Error(e) => write!(f, "Utf8Error: {e}"),
             ProtocolError::JsonError(e) => write!(f, "JsonError: {e}"),
             ProtocolError::IoError(e) => write!(f, "IoError: {e}"),
+            ProtocolError::InvalidUrl(msg) => write!(f, "InvalidUrl: {msg}"),
+            ProtocolError::Blocked(msg) => write!(f, "Blocked: {msg}"),
+            ProtocolError::NotReplayable(msg) => write!(f, "NotReplayable: {msg}"),
+            ProtocolError::InvalidInput(msg) => write!(f, "InvalidInput: {msg}"),
+            ProtocolError::Timeout => write!(f, "Timeout"),
             ProtocolError::TooManyRedirects => write!(f, "TooManyRedirects"),
             ProtocolError::TooManyRetries => write!(f, "TooManyRetries"),
         }
@@ -162,7 +181,13 @@ impl<T> From<ProtocolError> for Error<T> {
 
 impl From<hyper::Error> for ProtocolError {
     fn from(value: hyper::Error) -> Self {
-        Self::ConnectionError(value)
+        #[cfg(target_arch = "wasm32")]
+        return Self::ConnectionError(value);
+        #[cfg(not(target_arch = "wasm32"))]
+        match crate::client::blocked_reason(&value) {
+            Some(reason) => Self::Blocked(reason),
+            None => Self::ConnectionError(value),
+        }
     }
 }
 
@@ -0,0 +1,229 @@
+use std::fmt::{Display, Formatter};
+use std::future::IntoFuture;
+use std::marker::PhantomData;
+
+use futures::future::BoxFuture;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::{Client, Error, InMemoryError, InMemoryResponseExt};
+
+/// Where in the query an error occurred.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GraphqlLocation {
+    pub line: u32,
+    pub column: u32,
+}
+
+/// An entry of a GraphQL response's `errors`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GraphqlErrorObject {
+    pub message: String,
+    #[serde(default)]
+    pub locations: Vec<GraphqlLocation>,
+    /// The field the error is about, as field names and list indices.
+    #[serde(default)]
+    pub path: Vec<Value>,
+    #[serde(default)]
+    pub extensions: Option<Value>,
+}
+
+#[derive(Debug)]
+pub enum GraphqlError {
+    /// The request failed, or the server answered with an HTTP error that isn't a GraphQL response.
+    Http(InMemoryError),
+    /// The server returned `errors`. `data` is any partial data returned with them.
+    Graphql { errors: Vec<GraphqlErrorObject>, data: Option<Value> },
+    /// The response isn't a GraphQL response, or its `data` doesn't match the requested type.
+    Decode(serde_json::Error),
+}
+
+impl std::error::Error for GraphqlError {}
+
+impl Display for GraphqlError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GraphqlError::Http(e) => write!(f, "GraphQL request failed: {e}"),
+            GraphqlError::Graphql { errors, .. } => {
+                let messages: Vec<&str> = errors.iter().map(|e| e.message.as_str()).collect();
+                write!(f, "GraphQL errors: {}", messages.join("; "))
+            }
+            GraphqlError::Decode(e) => write!(f, "Invalid GraphQL response: {e}"),
+        }
+    }
+}
+
+impl From<InMemoryError> for GraphqlError {
+    fn from(e: InMemoryError) -> Self {
+        GraphqlError::Http(e)
+    }
+}
+
+impl From<serde_json::Error> for GraphqlError {
+    fn from(e: serde_json::Error) -> Self {
+        GraphqlError::Decode(e)
+    }
+}
+
+#[derive(Deserialize)]
+struct Envelope {
+    #[serde(default)]
+    data: Option<Value>,
+    #[serde(default)]
+    errors: Option<Vec<GraphqlErrorObject>>,
+}
+
+/// Decode a GraphQL response body into its `data`, or the errors it reports.
+#[allow(clippy::result_large_err)]
+fn decode<T: DeserializeOwned>(envelope: Envelope) -> Result<T, GraphqlError> {
+    match envelope.errors {
+        Some(errors) if !errors.is_empty() => Err(GraphqlError::Graphql { errors, data: envelope.data }),
+        _ => Ok(serde_json::from_value(envelope.data.unwrap_or(Value::Null))?),
+    }
+}
+
+/// A GraphQL operation, sent as a `POST` with the standard JSON envelope. Made by [`Client::graphql`].
+///
+/// Awaiting it decodes `data` into `T`. Responses with `errors` fail with [`GraphqlError::Graphql`],
+/// including error statuses whose body is a GraphQL response.
+/// ```no_run
+/// # use httpclient::Client;
+/// # #[derive(serde::Deserialize)]
+/// # struct Viewer {}
+/// # async fn f(client: Client) -> Result<(), httpclient::GraphqlError> {
+/// let data: Viewer = client.graphql("https://api.github.com/graphql")
+///     .query("query { viewer { login } }")
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct GraphqlRequest<'a, T = Value> {
+    client: &'a Client,
+    url: String,
+    query: String,
+    variables: Option<Value>,
+    operation_name: Option<String>,
+    data: PhantomData<fn() -> T>,
+}
+
+impl<T> GraphqlRequest<'_, T> {
+    #[must_use]
+    pub fn query(mut self, query: &str) -> Self {
+        self.query = query.to_string();
+        self
+    }
+
+    /// # Panics
+    /// Panics if `variables` fails to serialize, or doesn't serialize to a JSON object.
+    #[must_use]
+    pub fn variables<S: Serialize>(mut self, variables: S) -> Self {
+        let variables = serde_json::to_value(variables).expect("Failed to serialize GraphQL variables");
+        assert!(variables.is_object(), "GraphQL variables must be an object");
+        self.variables = Some(variables);
+        self
+    }
+
+    /// Which operation to run, when the query document defines several.
+    #[must_use]
+    pub fn operation_name(mut self, name: &str) -> Self {
+        self.operation_name = Some(name.to_string());
+        self
+    }
+
+    fn envelope(&self) -> Value {
+        let mut envelope = Map::new();
+        envelope.insert("query".to_string(), Value::String(self.query.clone()));
+        if let Some(variables) = &self.variables {
+            envelope.insert("variables".to_string(), variables.clone());
+        }
+        if let Some(name) = &self.operation_name {
+            envelope.insert("operationName".to_string(), Value::String(name.clone()));
+        }
+        Value::Object(envelope)
+    }
+}
+
+impl<'a, T: DeserializeOwned + Send + 'a> IntoFuture for GraphqlRequest<'a, T> {
+    type Output = Result<T, GraphqlError>;
+    type IntoFuture = BoxFuture<'a, Self::Output>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        Box::pin(async move {
+            let envelope = self.envelope();
+            match self.client.post(&self.url).json(envelope).await {
+                Ok(res) => decode(res.json()?),
+                Err(Error::HttpError(res)) => match res.body().json_borrowed::<Envelope>() {
+                    Ok(envelope) if envelope.errors.as_ref().is_some_and(|e| !e.is_empty()) => decode(envelope),
+                    _ => Err(GraphqlError::Http(Error::HttpError(res))),
+                },
+                Err(e) => Err(e.into()),
+            }
+        })
+    }
+}
+
+impl Client {
+    /// Start a GraphQL operation against the endpoint at `url`, decoding its `data` into `T`. See [`GraphqlRequest`].
+    #[must_use]
+    pub fn graphql<T: DeserializeOwned>(&self, url: impl AsRef<str>) -> GraphqlRequest<'_, T> {
+        GraphqlRequest {
+            client: self,
+            url: url.as_ref().to_string(),
+            query: String::new(),
+            variables: None,
+            operation_name: None,
+            data: PhantomData,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::StatusCode;
+    use serde_json::json;
+
+    use crate::middleware::FakeTransport;
+    use crate::InMemoryBody;
+
+    use super::*;
+
+    #[derive(Debug, Deserialize)]
+    struct Viewer {
+        login: String,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Data {
+        viewer: Viewer,
+    }
+
+    #[tokio::test]
+    async fn test_graphql() {
+        let transport = FakeTransport::new()
+            .respond_with(StatusCode::OK, InMemoryBody::Json(json!({"data": {"viewer": {"login": "kurt"}}})))
+            .respond_with(
+                StatusCode::OK,
+                InMemoryBody::Json(json!({"data": null, "errors": [{"message": "Field 'x' doesn't exist", "locations": [{"line": 1, "column": 9}]}]})),
+            )
+            .respond_with(StatusCode::BAD_REQUEST, InMemoryBody::Json(json!({"errors": [{"message": "Syntax error"}]})))
+            .respond_with(StatusCode::BAD_GATEWAY, InMemoryBody::Text("upstream down".into()));
+        let client = Client::new().with_middleware(transport.clone());
+        let url = "https://api.example.com/graphql";
+
+        let data: Data = client.graphql(url).query("query($n: Int) { viewer { login } }").variables(json!({"n": 1})).await.unwrap();
+        assert_eq!(data.viewer.login, "kurt");
+        assert!(matches!(transport.requests()[0].body(), InMemoryBody::Json(v) if *v == json!({
+            "query": "query($n: Int) { viewer { login } }",
+            "variables": {"n": 1},
+        })));
+
+        let err = client.graphql::<Value>(url).query("{ x }").await.unwrap_err();
+        assert!(matches!(&err, GraphqlError::Graphql { errors, data: None } if errors[0].locations == [GraphqlLocation { line: 1, column: 9 }]));
+        let err = client.graphql::<Value>(url).query("{").await.unwrap_err();
+        assert_eq!(err.to_string(), "GraphQL errors: Syntax error");
+        let err = client.graphql::<Value>(url).query("{ y }").await.unwrap_err();
+        assert!(matches!(err, GraphqlError::Http(e) if e.status() == Some(StatusCode::BAD_GATEWAY)));
+    }
+}
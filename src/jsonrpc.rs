@@ -0,0 +1,354 @@
+//! A JSON-RPC 2.0 client, layered over [`crate::Client`].
+//! ```no_run
+//! # use httpclient::jsonrpc::{JsonRpc, JsonRpcError};
+//! # use httpclient::Client;
+//! # use serde_json::json;
+//! # async fn f() -> Result<(), JsonRpcError> {
+//! let rpc = JsonRpc::new(Client::new(), "https://rpc.example.com");
+//! let height: u64 = rpc.call("getblockcount", ()).await?;
+//!
+//! let mut batch = rpc.batch();
+//! let balance = batch.call("getbalance", json!(["addr1"]));
+//! let fee = batch.call("estimatefee", json!([6]));
+//! let results = batch.send().await?;
+//! let balance: f64 = results.get(&balance)?;
+//! # Ok(())
+//! # }
+//! ```
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{Client, Error, InMemoryError, InMemoryResponseExt};
+
+const VERSION: &str = "2.0";
+
+/// A request or response id. This client only makes numeric ids, but servers may echo either kind.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Id {
+    Number(u64),
+    String(String),
+}
+
+impl Display for Id {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Id::Number(n) => write!(f, "{n}"),
+            Id::String(s) => write!(f, "{s:?}"),
+        }
+    }
+}
+
+/// A request envelope. Without an `id` it's a notification, which gets no response.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RpcRequest<P = Value> {
+    pub jsonrpc: String,
+    pub method: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub params: Option<P>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<Id>,
+}
+
+impl RpcRequest {
+    /// # Panics
+    /// Panics if `params` fails to serialize.
+    pub fn new<P: Serialize>(method: &str, params: P, id: Option<Id>) -> Self {
+        let params = serde_json::to_value(params).expect("Failed to serialize JSON-RPC params");
+        Self {
+            jsonrpc: VERSION.to_string(),
+            method: method.to_string(),
+            // `()` and `None` mean no params; the spec only allows arrays and objects.
+            params: (!params.is_null()).then_some(params),
+            id,
+        }
+    }
+}
+
+/// The error object of a failed call.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ErrorObject {
+    pub code: i64,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+impl ErrorObject {
+    pub const PARSE_ERROR: i64 = -32700;
+    pub const INVALID_REQUEST: i64 = -32600;
+    pub const METHOD_NOT_FOUND: i64 = -32601;
+    pub const INVALID_PARAMS: i64 = -32602;
+    pub const INTERNAL_ERROR: i64 = -32603;
+
+    /// Whether the code is in the range the spec reserves for server errors, -32099 to -32000.
+    #[must_use]
+    pub fn is_server_error(&self) -> bool {
+        (-32099..=-32000).contains(&self.code)
+    }
+}
+
+/// A response envelope: `result` on success, `error` on failure.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RpcResponse {
+    pub jsonrpc: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<ErrorObject>,
+    /// `None` when the server couldn't read the request's id, e.g. for a parse error.
+    pub id: Option<Id>,
+}
+
+impl RpcResponse {
+    /// Decode the result, or return the error object.
+    #[allow(clippy::result_large_err)]
+    pub fn into_result<R: DeserializeOwned>(self) -> Result<R, JsonRpcError> {
+        match self.error {
+            Some(error) => Err(JsonRpcError::Rpc(error)),
+            // `"result": null` deserializes to `None`, so a missing result is decoded as `null`.
+            None => Ok(serde_json::from_value(self.result.unwrap_or(Value::Null))?),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum JsonRpcError {
+    /// The request failed, or the server answered with an HTTP error that isn't a JSON-RPC response.
+    Http(InMemoryError),
+    /// The server returned an error object.
+    Rpc(ErrorObject),
+    /// The response isn't a JSON-RPC response, or its result doesn't match the requested type.
+    Decode(serde_json::Error),
+    /// A batch response has no response for this call.
+    MissingResponse(Id),
+}
+
+impl std::error::Error for JsonRpcError {}
+
+impl Display for JsonRpcError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JsonRpcError::Http(e) => write!(f, "JSON-RPC request failed: {e}"),
+            JsonRpcError::Rpc(e) => write!(f, "JSON-RPC error {}: {}", e.code, e.message),
+            JsonRpcError::Decode(e) => write!(f, "Invalid JSON-RPC response: {e}"),
+            JsonRpcError::MissingResponse(id) => write!(f, "No JSON-RPC response for id {id}"),
+        }
+    }
+}
+
+impl From<InMemoryError> for JsonRpcError {
+    fn from(e: InMemoryError) -> Self {
+        JsonRpcError::Http(e)
+    }
+}
+
+impl From<serde_json::Error> for JsonRpcError {
+    fn from(e: serde_json::Error) -> Self {
+        JsonRpcError::Decode(e)
+    }
+}
+
+/// A JSON-RPC endpoint. Ids count up from 1, and clones share the counter.
+#[derive(Debug, Clone)]
+pub struct JsonRpc {
+    client: Client,
+    url: String,
+    next_id: Arc<AtomicU64>,
+}
+
+impl JsonRpc {
+    /// Call the endpoint at `url`, a path relative to the client's base URL or an absolute URL.
+    #[must_use]
+    pub fn new(client: Client, url: &str) -> Self {
+        Self {
+            client,
+            url: url.to_string(),
+            next_id: Arc::new(AtomicU64::new(1)),
+        }
+    }
+
+    fn next_id(&self) -> Id {
+        Id::Number(self.next_id.fetch_add(1, Ordering::Relaxed))
+    }
+
+    /// Post `body`, returning the response body, or `None` if it's empty. Error statuses whose body is
+    /// JSON-RPC are returned as success, so the error object can be reported.
+    async fn post<B: Serialize>(&self, body: B) -> Result<Option<Value>, JsonRpcError> {
+        let res = match self.client.post(&self.url).json(body).await {
+            Ok(res) => res,
+            Err(Error::HttpError(res)) => match res.body().json_borrowed() {
+                Ok(value @ (Value::Object(_) | Value::Array(_))) => return Ok(Some(value)),
+                _ => return Err(JsonRpcError::Http(Error::HttpError(res))),
+            },
+            Err(e) => return Err(e.into()),
+        };
+        if res.body().is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(res.json()?))
+    }
+
+    /// Call `method` and decode its result. `params` should serialize to an array or object, or `()` for none.
+    pub async fn call<P: Serialize, R: DeserializeOwned>(&self, method: &str, params: P) -> Result<R, JsonRpcError> {
+        let id = self.next_id();
+        let body = self.post(RpcRequest::new(method, params, Some(id.clone()))).await?;
+        let response: RpcResponse = serde_json::from_value(body.ok_or(JsonRpcError::MissingResponse(id))?)?;
+        response.into_result()
+    }
+
+    /// Send a notification: a call without an id, which the server doesn't answer.
+    pub async fn notify<P: Serialize>(&self, method: &str, params: P) -> Result<(), JsonRpcError> {
+        self.post(RpcRequest::new(method, params, None)).await?;
+        Ok(())
+    }
+
+    /// Start a batch of calls, sent together in one request.
+    #[must_use]
+    pub fn batch(&self) -> Batch<'_> {
+        Batch { rpc: self, requests: Vec::new() }
+    }
+}
+
+/// A handle for reading one call's result from a [`BatchResponse`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchCall(Id);
+
+/// Calls and notifications sent as one JSON-RPC batch. Made by [`JsonRpc::batch`].
+#[derive(Debug)]
+pub struct Batch<'a> {
+    rpc: &'a JsonRpc,
+    requests: Vec<RpcRequest>,
+}
+
+impl Batch<'_> {
+    /// Add a call, returning the handle to read its result with.
+    pub fn call<P: Serialize>(&mut self, method: &str, params: P) -> BatchCall {
+        let id = self.rpc.next_id();
+        self.requests.push(RpcRequest::new(method, params, Some(id.clone())));
+        BatchCall(id)
+    }
+
+    pub fn notify<P: Serialize>(&mut self, method: &str, params: P) {
+        self.requests.push(RpcRequest::new(method, params, None));
+    }
+
+    /// Send the batch. Fails if the server rejects the batch as a whole, e.g. because it isn't valid JSON-RPC;
+    /// errors of individual calls are returned by [`BatchResponse::get`].
+    pub async fn send(self) -> Result<BatchResponse, JsonRpcError> {
+        if self.requests.is_empty() {
+            return Ok(BatchResponse { responses: HashMap::new() });
+        }
+        let responses = match self.rpc.post(&self.requests).await? {
+            Some(Value::Array(responses)) => responses,
+            Some(value) => {
+                // A single response to a batch means the server rejected the batch as a whole.
+                let response: RpcResponse = serde_json::from_value(value)?;
+                return Err(match response.error {
+                    Some(error) => JsonRpcError::Rpc(error),
+                    None => JsonRpcError::Decode(serde::de::Error::custom("Expected an array of responses to a batch")),
+                });
+            }
+            None => Vec::new(),
+        };
+        let mut by_id = HashMap::new();
+        for response in responses {
+            let response: RpcResponse = serde_json::from_value(response)?;
+            if let Some(id) = response.id.clone() {
+                by_id.insert(id, response);
+            }
+        }
+        Ok(BatchResponse { responses: by_id })
+    }
+}
+
+/// The responses to a [`Batch`], matched to its calls by id.
+#[derive(Debug, Clone)]
+pub struct BatchResponse {
+    responses: HashMap<Id, RpcResponse>,
+}
+
+impl BatchResponse {
+    /// Decode the result of `call`, or return its error object.
+    #[allow(clippy::result_large_err)]
+    pub fn get<R: DeserializeOwned>(&self, call: &BatchCall) -> Result<R, JsonRpcError> {
+        let response = self.responses.get(&call.0).ok_or_else(|| JsonRpcError::MissingResponse(call.0.clone()))?;
+        response.clone().into_result()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::StatusCode;
+    use serde_json::json;
+
+    use crate::middleware::FakeTransport;
+    use crate::InMemoryBody;
+
+    use super::*;
+
+    fn sent(transport: &FakeTransport, i: usize) -> Value {
+        match transport.requests()[i].body() {
+            InMemoryBody::Json(v) => v.clone(),
+            body => panic!("Unexpected body {body:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_jsonrpc() {
+        let transport = FakeTransport::new()
+            .respond_with(StatusCode::OK, InMemoryBody::Json(json!({"jsonrpc": "2.0", "result": 19, "id": 1})))
+            .respond_with(
+                StatusCode::OK,
+                InMemoryBody::Json(json!({"jsonrpc": "2.0", "error": {"code": -32601, "message": "Method not found"}, "id": 2})),
+            )
+            .respond_with(StatusCode::NO_CONTENT, InMemoryBody::Empty)
+            .respond_with(
+                StatusCode::OK,
+                InMemoryBody::Json(json!([
+                    {"jsonrpc": "2.0", "error": {"code": -32602, "message": "Invalid params"}, "id": 4},
+                    {"jsonrpc": "2.0", "result": "0x1", "id": 3},
+                ])),
+            )
+            .respond_with(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                InMemoryBody::Json(json!({"jsonrpc": "2.0", "error": {"code": -32000, "message": "Node syncing"}, "id": 5})),
+            );
+        let rpc = JsonRpc::new(Client::new().with_middleware(transport.clone()), "https://rpc.example.com");
+
+        let n: u64 = rpc.call("subtract", [42, 23]).await.unwrap();
+        assert_eq!(n, 19);
+        assert_eq!(sent(&transport, 0), json!({"jsonrpc": "2.0", "method": "subtract", "params": [42, 23], "id": 1}));
+
+        let err = rpc.call::<_, Value>("foobar", ()).await.unwrap_err();
+        assert!(matches!(
+            err,
+            JsonRpcError::Rpc(ErrorObject {
+                code: ErrorObject::METHOD_NOT_FOUND,
+                ..
+            })
+        ));
+        assert_eq!(sent(&transport, 1), json!({"jsonrpc": "2.0", "method": "foobar", "id": 2}));
+
+        rpc.notify("update", json!({"x": 1})).await.unwrap();
+        assert_eq!(sent(&transport, 2), json!({"jsonrpc": "2.0", "method": "update", "params": {"x": 1}}));
+
+        let mut batch = rpc.batch();
+        let chain = batch.call("eth_chainId", ());
+        let balance = batch.call("eth_getBalance", ["0xabc"]);
+        batch.notify("ping", ());
+        let results = batch.send().await.unwrap();
+        assert_eq!(results.get::<String>(&chain).unwrap(), "0x1");
+        assert!(matches!(results.get::<String>(&balance), Err(JsonRpcError::Rpc(e)) if e.code == ErrorObject::INVALID_PARAMS));
+        assert_eq!(sent(&transport, 3).as_array().unwrap().len(), 3);
+
+        let err = rpc.call::<_, Value>("eth_blockNumber", ()).await.unwrap_err();
+        assert!(matches!(err, JsonRpcError::Rpc(e) if e.is_server_error()));
+    }
+}
@@ -0,0 +1,84 @@
+use http::header::{ACCEPT_LANGUAGE, CONTENT_LANGUAGE};
+use http::HeaderMap;
+
+use crate::{Client, RequestBuilder};
+
+/// Format a quality value with at most three decimals, as RFC 9110 requires.
+fn qvalue(q: f32) -> String {
+    let q = format!("{:.3}", q.clamp(0.0, 1.0));
+    q.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+/// Build an `Accept-Language` value from language tags and their weights, e.g. `[("en-US", 1.0), ("de", 0.8)]`
+/// becomes `en-US, de;q=0.8`. Weights are clamped to `0.0..=1.0`; a weight of 1 is implied, so it is omitted.
+#[must_use]
+pub fn accept_language(languages: &[(&str, f32)]) -> String {
+    weighted(languages)
+}
+
+/// Join values with their quality values, leaving out weights of 1.
+pub(crate) fn weighted(values: &[(&str, f32)]) -> String {
+    values
+        .iter()
+        .map(|(value, q)| if *q >= 1.0 { (*value).to_string() } else { format!("{value};q={}", qvalue(*q)) })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// The language tags of all `Content-Language` headers, in order.
+#[must_use]
+pub fn content_language(headers: &HeaderMap) -> Vec<String> {
+    headers
+        .get_all(CONTENT_LANGUAGE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+        .map(ToString::to_string)
+        .collect()
+}
+
+impl<C, B> RequestBuilder<'_, C, B> {
+    /// Set the `Accept-Language` header from weighted language tags, e.g. `&[("en-US", 1.0), ("de", 0.8)]`.
+    /// Replaces the client default, if any.
+    #[must_use]
+    pub fn accept_language(mut self, languages: &[(&str, f32)]) -> Self {
+        let value = accept_language(languages).parse().expect("Invalid language tag");
+        self.headers.insert(ACCEPT_LANGUAGE, value);
+        self
+    }
+}
+
+impl Client {
+    /// Send an `Accept-Language` header built from weighted language tags with every request.
+    /// See [`RequestBuilder::accept_language`] to override it per request.
+    #[must_use]
+    pub fn accept_language(self, languages: &[(&str, f32)]) -> Self {
+        self.default_header(ACCEPT_LANGUAGE.as_str(), &accept_language(languages))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accept_and_content_language() {
+        assert_eq!(
+            accept_language(&[("en-US", 1.0), ("de", 0.8), ("fr", 0.125), ("*", 0.0)]),
+            "en-US, de;q=0.8, fr;q=0.125, *;q=0"
+        );
+
+        let client = Client::new().accept_language(&[("en", 1.0), ("de", 0.5)]);
+        let req = client.get("https://example.com/");
+        assert_eq!(req.clone().build().headers().get(ACCEPT_LANGUAGE).unwrap(), "en, de;q=0.5");
+        let req = req.accept_language(&[("fr", 1.0)]).build();
+        assert_eq!(req.headers().get_all(ACCEPT_LANGUAGE).iter().collect::<Vec<_>>(), vec!["fr"]);
+
+        let mut headers = HeaderMap::new();
+        headers.append(CONTENT_LANGUAGE, "de-DE, en-CA".parse().unwrap());
+        headers.append(CONTENT_LANGUAGE, "fr".parse().unwrap());
+        assert_eq!(content_language(&headers), vec!["de-DE", "en-CA", "fr"]);
+    }
+}
@@ -1,14 +1,42 @@
 #![deny(clippy::all, clippy::pedantic, clippy::unwrap_used)]
 #![allow(clippy::module_name_repetitions, clippy::missing_errors_doc, clippy::missing_panics_doc)]
 
-pub use body::{Body, InMemoryBody};
-pub use client::Client;
+#[cfg(all(target_arch = "wasm32", not(feature = "wasm")))]
+compile_error!("Enable the `wasm` feature to build for wasm32, where requests are sent with the JavaScript host's `fetch`.");
+#[cfg(all(target_arch = "wasm32", feature = "recorder"))]
+compile_error!("The `recorder` feature reads and writes files, which wasm32 doesn't have. Turn off default features to build for wasm32.");
+
+pub use body::{Body, InMemoryBody, ReplayableBody, TryClone, Utf8Bytes};
+#[cfg(target_arch = "wasm32")]
+pub use client::Fetch;
+#[cfg(all(feature = "tower", not(target_arch = "wasm32")))]
+pub use client::ServiceTransport;
+#[cfg(not(target_arch = "wasm32"))]
+pub use client::{AddressSelection, ClientIdentity, ConnectionInfo, DnsCache, HostPoolStats, PoolMetrics, TlsInfo};
+pub use client::{Client, Profile, Timeouts, Transport};
+pub use cookie::Cookie;
 pub use error::{Error, InMemoryError, InMemoryResult, ProtocolError, ProtocolResult, Result};
+#[cfg(feature = "graphql")]
+pub use graphql::{GraphqlError, GraphqlErrorObject, GraphqlLocation, GraphqlRequest};
 pub use http::{header, header::HeaderName, Method, StatusCode, Uri};
-pub use middleware::{Follow, Logger, Middleware, Next, Recorder, Retry};
-pub use request::{InMemoryRequest, Request, RequestBuilder, RequestBuilderExt, RequestExt};
+pub use longpoll::LongPoll;
+#[cfg(feature = "recorder")]
+pub use middleware::Recorder;
+pub use middleware::{
+    middleware_fn, AdaptiveConcurrency, Attempts, AudienceAuth, Failover, Follow, Hsts, IdempotencyKey, Logger, MapRequest, MapResponse, Middleware, NegativeCache, Next,
+    NoFollow, NoRetry, OpenApiValidator, RateLimit, RateLimitAware, Redirect, RedirectHistory, Retry, RetryBudget, SsrfGuard, TotalTimeout,
+};
+#[cfg(not(target_arch = "wasm32"))]
+pub use middleware::{ContentHash, JwtAuth};
+pub use pagination::{PageStrategy, Pagination, Paginator};
+pub use problem::ProblemDetails;
+pub use request::{request_from_http, InMemoryRequest, QueryFormat, Request, RequestBuilder, RequestBuilderExt, RequestExt};
+#[cfg(feature = "stream")]
+pub use response::TextStream;
 pub use response::{InMemoryResponse, InMemoryResponseExt, ResponseExt};
-use std::sync::OnceLock;
+pub use session::{CookieJar, FormLogin, Login, Session};
+use std::fmt::Write;
+use std::sync::RwLock;
 
 pub mod header_ext {
     use http::HeaderName;
@@ -16,29 +44,160 @@ pub mod header_ext {
     pub const SUBJECT: HeaderName = HeaderName::from_static("subject");
     pub const FROM: HeaderName = HeaderName::from_static("from");
     pub const TO: HeaderName = HeaderName::from_static("to");
+    pub const CC: HeaderName = HeaderName::from_static("cc");
+    pub const BCC: HeaderName = HeaderName::from_static("bcc");
+    pub const MIME_VERSION: HeaderName = HeaderName::from_static("mime-version");
     pub const CONTENT_TRANSFER_ENCODING: HeaderName = HeaderName::from_static("content-transfer-encoding");
 }
+
+/// Named status codes missing from `http::StatusCode`, including common unofficial ones.
+pub mod status_ext {
+    use http::StatusCode;
+
+    const fn status(code: u16) -> StatusCode {
+        match StatusCode::from_u16(code) {
+            Ok(s) => s,
+            Err(_) => panic!("Invalid status code"),
+        }
+    }
+
+    pub const TOO_EARLY: StatusCode = StatusCode::TOO_EARLY;
+    /// Twitter
+    pub const ENHANCE_YOUR_CALM: StatusCode = status(420);
+    /// nginx
+    pub const NO_RESPONSE: StatusCode = status(444);
+    /// nginx
+    pub const CLIENT_CLOSED_REQUEST: StatusCode = status(499);
+    /// Cloudflare
+    pub const WEB_SERVER_UNKNOWN_ERROR: StatusCode = status(520);
+    /// Cloudflare
+    pub const WEB_SERVER_IS_DOWN: StatusCode = status(521);
+    /// Cloudflare
+    pub const CONNECTION_TIMED_OUT: StatusCode = status(522);
+    /// Cloudflare
+    pub const ORIGIN_IS_UNREACHABLE: StatusCode = status(523);
+    /// Cloudflare
+    pub const A_TIMEOUT_OCCURRED: StatusCode = status(524);
+    /// Cloudflare
+    pub const SITE_IS_OVERLOADED: StatusCode = status(529);
+
+    /// Like `StatusCode::canonical_reason`, but also knows the unofficial codes in this module.
+    #[must_use]
+    pub fn canonical_reason(status: StatusCode) -> Option<&'static str> {
+        status.canonical_reason().or(match status.as_u16() {
+            420 => Some("Enhance Your Calm"),
+            444 => Some("No Response"),
+            499 => Some("Client Closed Request"),
+            520 => Some("Web Server Returned an Unknown Error"),
+            521 => Some("Web Server Is Down"),
+            522 => Some("Connection Timed Out"),
+            523 => Some("Origin Is Unreachable"),
+            524 => Some("A Timeout Occurred"),
+            529 => Some("Site Is Overloaded"),
+            _ => None,
+        })
+    }
+}
+
 pub type Response<T = Body> = http::Response<T>;
 
 mod body;
 mod client;
+pub mod clock;
+pub mod cors;
+pub mod curl;
+#[cfg(feature = "multipart")]
+pub mod email;
 mod error;
+#[cfg(feature = "graphql")]
+mod graphql;
+pub mod jsonrpc;
+pub mod language;
+pub mod link;
+mod longpoll;
+pub mod media;
 pub mod middleware;
+#[cfg(feature = "multipart")]
 pub mod multipart;
+pub mod pagination;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod presign;
+mod problem;
+#[cfg(feature = "recorder")]
 pub mod recorder;
 mod request;
 mod response;
-mod sanitize;
+pub mod sanitize;
+mod session;
+#[cfg(feature = "soap")]
+pub mod soap;
 
-static SHARED_CLIENT: OnceLock<Client> = OnceLock::new();
+/// Clients are leaked so `client()` can hand out `'static` references that outlive a reset.
+static SHARED_CLIENT: RwLock<Option<&'static Client>> = RwLock::new(None);
 
 /// Use this to customize the shared client.
-/// Must be called before any requests are made, otherwise it will have no effect.
+/// Must be called before any requests are made, otherwise it will have no effect. See [`try_init_shared_client`].
 pub fn init_shared_client(client: Client) {
-    let _ = SHARED_CLIENT.set(client);
+    init_shared_client_with(|| client);
+}
+
+/// Install `client` as the shared client, returning whether it took effect,
+/// i.e. `false` if the shared client was already initialized or used.
+#[must_use]
+pub fn try_init_shared_client(client: Client) -> bool {
+    init_shared_client_with(|| client)
+}
+
+/// Install the client built by `f` as the shared client, returning whether it took effect.
+/// `f` is only called if the shared client hasn't been initialized or used yet.
+pub fn init_shared_client_with<F: FnOnce() -> Client>(f: F) -> bool {
+    let mut shared = SHARED_CLIENT.write().expect("Shared client lock poisoned");
+    if shared.is_some() {
+        return false;
+    }
+    *shared = Some(Box::leak(Box::new(f())));
+    true
+}
+
+/// Forget the shared client, so the next `init_shared_client` takes effect, e.g. to install a
+/// [`Recorder`]-backed client in each integration test. References to the previous client stay valid.
+#[cfg(any(test, feature = "mock"))]
+pub fn reset_shared_client() {
+    *SHARED_CLIENT.write().expect("Shared client lock poisoned") = None;
 }
 
 /// Use the shared, global client
 pub fn client() -> &'static Client {
-    SHARED_CLIENT.get_or_init(Client::new)
+    if let Some(client) = *SHARED_CLIENT.read().expect("Shared client lock poisoned") {
+        return client;
+    }
+    init_shared_client_with(Client::new);
+    SHARED_CLIENT.read().expect("Shared client lock poisoned").expect("Shared client was just initialized")
+}
+
+/// Lowercase hex, e.g. for digests and signatures.
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::with_capacity(bytes.len() * 2), |mut s, b| {
+        let _ = write!(s, "{b:02x}");
+        s
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shared_client() {
+        reset_shared_client();
+        assert!(init_shared_client_with(|| Client::new().base_url("https://a.example.com")));
+        assert!(!try_init_shared_client(Client::new().base_url("https://b.example.com")));
+        let first = client();
+        assert_eq!(first.get("/x").build().uri(), "https://a.example.com/x");
+
+        reset_shared_client();
+        assert!(try_init_shared_client(Client::new().base_url("https://b.example.com")));
+        assert_eq!(client().get("/x").build().uri(), "https://b.example.com/x");
+        assert_eq!(first.get("/x").build().uri(), "https://a.example.com/x");
+    }
 }
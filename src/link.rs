@@ -0,0 +1,127 @@
+use std::collections::HashMap;
+
+use http::HeaderMap;
+
+/// A single link from an RFC 8288 `Link` header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Link {
+    /// The link target, as written in the header. It may be relative to the request url.
+    pub uri: String,
+    /// Link parameters, e.g. `rel`, `title`, `type`. Names are lowercased; quotes are removed from values.
+    pub params: Vec<(String, String)>,
+}
+
+impl Link {
+    #[must_use]
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params.iter().find(|(k, _)| k == name).map(|(_, v)| v.as_str())
+    }
+
+    /// The relation types of this link. `rel` can hold several space-separated types.
+    pub fn rels(&self) -> impl Iterator<Item = &str> {
+        self.param("rel").unwrap_or_default().split_whitespace()
+    }
+}
+
+/// Split `s` at the first `delim` that is not inside a quoted string.
+fn split_unquoted(s: &str, delim: char) -> (&str, Option<&str>) {
+    let mut quoted = false;
+    let mut escaped = false;
+    for (i, c) in s.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            c if c == delim && !quoted => return (&s[..i], Some(&s[i + c.len_utf8()..])),
+            _ => {}
+        }
+    }
+    (s, None)
+}
+
+fn unquote(s: &str) -> String {
+    match s.strip_prefix('"').and_then(|s| s.strip_suffix('"')) {
+        Some(inner) => inner.replace("\\\"", "\"").replace("\\\\", "\\"),
+        None => s.to_string(),
+    }
+}
+
+/// Parse the value of a `Link` header. Malformed entries are skipped.
+#[must_use]
+pub fn parse_link_header(header: &str) -> Vec<Link> {
+    let mut links = Vec::new();
+    let mut rest = header;
+    loop {
+        rest = rest.trim_start_matches(|c: char| c == ',' || c.is_whitespace());
+        let Some(after_open) = rest.strip_prefix('<') else {
+            break;
+        };
+        let Some((uri, after_uri)) = after_open.split_once('>') else {
+            break;
+        };
+        let (params, next) = split_unquoted(after_uri, ',');
+        let mut parsed = Vec::new();
+        let mut remaining = Some(params);
+        while let Some(p) = remaining {
+            let (param, next_param) = split_unquoted(p, ';');
+            remaining = next_param;
+            let param = param.trim();
+            if param.is_empty() {
+                continue;
+            }
+            let (name, value) = param.split_once('=').unwrap_or((param, ""));
+            parsed.push((name.trim().to_ascii_lowercase(), unquote(value.trim())));
+        }
+        links.push(Link {
+            uri: uri.trim().to_string(),
+            params: parsed,
+        });
+        match next {
+            Some(next) => rest = next,
+            None => break,
+        }
+    }
+    links
+}
+
+/// Collect the links of all `Link` headers, keyed by (lowercased) relation type, e.g. `next`, `prev`, `last`.
+/// If several links share a relation type, the first one wins.
+#[must_use]
+pub fn links(headers: &HeaderMap) -> HashMap<String, String> {
+    let mut map = HashMap::new();
+    for value in headers.get_all(http::header::LINK) {
+        let Ok(value) = value.to_str() else {
+            continue;
+        };
+        for link in parse_link_header(value) {
+            for rel in link.rels() {
+                map.entry(rel.to_ascii_lowercase()).or_insert_with(|| link.uri.clone());
+            }
+        }
+    }
+    map
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_link_header() {
+        let header = r#"<https://api.github.com/repositories/1/issues?page=2>; rel="next", <https://api.github.com/repositories/1/issues?page=5>; rel="last", </a,b>; rel="prev first"; title="x, \"y\"; z""#;
+        let links = parse_link_header(header);
+        assert_eq!(links.len(), 3);
+        assert_eq!(links[0].uri, "https://api.github.com/repositories/1/issues?page=2");
+        assert_eq!(links[0].param("rel"), Some("next"));
+        assert_eq!(links[2].uri, "/a,b");
+        assert_eq!(links[2].rels().collect::<Vec<_>>(), vec!["prev", "first"]);
+        assert_eq!(links[2].param("title"), Some(r#"x, "y"; z"#));
+
+        let mut headers = HeaderMap::new();
+        headers.insert(http::header::LINK, header.parse().unwrap());
+        let map = super::links(&headers);
+        assert_eq!(map["next"], "https://api.github.com/repositories/1/issues?page=2");
+        assert_eq!(map["last"], "https://api.github.com/repositories/1/issues?page=5");
+        assert_eq!(map["first"], "/a,b");
+    }
+}
@@ -0,0 +1,254 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::stream::{self, Stream};
+use http::StatusCode;
+
+use crate::clock::{self, Clock, SystemClock};
+use crate::{Client, InMemoryResponse, InMemoryResult};
+
+type IsEmptyFn = dyn Fn(&InMemoryResponse) -> bool + Send + Sync;
+type CursorFn = dyn Fn(&InMemoryResponse) -> Option<String> + Send + Sync;
+
+/// Repeatedly issue a GET against a long-poll endpoint, re-issuing it whenever the server
+/// returns an empty (or timed out) response, and yielding every other response on a `Stream`.
+///
+/// If a cursor is configured, the value extracted from each response is sent back as the
+/// `cursor_param` query parameter on the next request.
+///
+/// Empty polls are re-issued no faster than [`LongPoll::empty_backoff`] allows, so a server that answers them
+/// at once isn't polled in a hot loop.
+///
+/// ```
+/// # use httpclient::{Client, InMemoryResponseExt};
+/// # fn f(client: Client) {
+/// let mut updates = client.long_poll("/updates")
+///     .cursor_param("since")
+///     .cursor(|res| res.header("x-next-since").map(ToString::to_string))
+///     .stream();
+/// # }
+/// ```
+pub struct LongPoll<'a> {
+    client: &'a Client,
+    path: String,
+    cursor_param: Option<String>,
+    cursor_value: Option<String>,
+    extract_cursor: Option<Box<CursorFn>>,
+    is_empty: Box<IsEmptyFn>,
+    min_delay: Duration,
+    max_delay: Duration,
+    clock: Arc<dyn Clock>,
+}
+
+impl std::fmt::Debug for LongPoll<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LongPoll")
+            .field("path", &self.path)
+            .field("cursor_param", &self.cursor_param)
+            .field("cursor_value", &self.cursor_value)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Default detection of a response that carries no data: `204 No Content` or an empty body.
+#[must_use]
+pub fn is_empty_response(res: &InMemoryResponse) -> bool {
+    res.status() == StatusCode::NO_CONTENT || res.body().is_empty()
+}
+
+/// Servers commonly end a long-poll that saw no events with one of these statuses.
+fn is_timeout_status(status: StatusCode) -> bool {
+    matches!(status, StatusCode::REQUEST_TIMEOUT | StatusCode::GATEWAY_TIMEOUT)
+}
+
+impl<'a> LongPoll<'a> {
+    pub fn new(client: &'a Client, path: impl Into<String>) -> Self {
+        LongPoll {
+            client,
+            path: path.into(),
+            cursor_param: None,
+            cursor_value: None,
+            extract_cursor: None,
+            is_empty: Box::new(is_empty_response),
+            min_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(10),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Name of the query parameter used to send the cursor back to the server.
+    #[must_use]
+    pub fn cursor_param(mut self, param: &str) -> Self {
+        self.cursor_param = Some(param.to_string());
+        self
+    }
+
+    /// Initial cursor value, sent on the first request.
+    #[must_use]
+    pub fn initial_cursor(mut self, value: &str) -> Self {
+        self.cursor_value = Some(value.to_string());
+        self
+    }
+
+    /// Extract the next cursor from a non-empty response. Returning `None` keeps the current cursor.
+    #[must_use]
+    pub fn cursor<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&InMemoryResponse) -> Option<String> + Send + Sync + 'static,
+    {
+        self.extract_cursor = Some(Box::new(f));
+        self
+    }
+
+    /// Override how an empty response is detected. Empty responses are re-issued instead of yielded.
+    #[must_use]
+    pub fn empty_when<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&InMemoryResponse) -> bool + Send + Sync + 'static,
+    {
+        self.is_empty = Box::new(f);
+        self
+    }
+
+    /// After an empty or timed-out poll, start the next one at least `min` after it started, doubling the gap with
+    /// each empty poll in a row up to `max`. A poll the server held open for longer is re-issued at once.
+    /// Defaults to 100ms, up to 10s.
+    #[must_use]
+    pub fn empty_backoff(mut self, min: Duration, max: Duration) -> Self {
+        self.min_delay = min;
+        self.max_delay = max.max(min);
+        self
+    }
+
+    /// Wait between polls with this clock, e.g. a [`crate::clock::TestClock`].
+    #[must_use]
+    pub fn clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    async fn poll_once(&self) -> InMemoryResult<InMemoryResponse> {
+        let mut req = self.client.get(&self.path);
+        if let (Some(param), Some(value)) = (&self.cursor_param, &self.cursor_value) {
+            req = req.query(param, value);
+        }
+        req.await
+    }
+
+    /// Wait for the next non-empty response, updating the cursor.
+    pub async fn next(&mut self) -> InMemoryResult<InMemoryResponse> {
+        let mut delay = self.min_delay;
+        loop {
+            let start = self.clock.now();
+            match self.poll_once().await {
+                Ok(res) if !(self.is_empty)(&res) => {
+                    if let Some(cursor) = self.extract_cursor.as_ref().and_then(|f| f(&res)) {
+                        self.cursor_value = Some(cursor);
+                    }
+                    return Ok(res);
+                }
+                Err(e) if !e.status().is_some_and(is_timeout_status) => return Err(e),
+                // empty or timed out: re-issue the request.
+                _ => {}
+            }
+            let wait = delay.saturating_sub(clock::elapsed(self.clock.as_ref(), start));
+            if !wait.is_zero() {
+                self.clock.sleep(wait).await;
+            }
+            delay = delay.saturating_mul(2).min(self.max_delay);
+        }
+    }
+
+    /// Turn the long-poll into a `Stream` of non-empty responses. The stream ends after the first error.
+    pub fn stream(self) -> impl Stream<Item = InMemoryResult<InMemoryResponse>> + 'a {
+        stream::unfold(Some(self), |state| async move {
+            let mut poll = state?;
+            match poll.next().await {
+                Ok(res) => Some((Ok(res), Some(poll))),
+                Err(e) => Some((Err(e), None)),
+            }
+        })
+    }
+}
+
+impl Client {
+    /// Long-poll the given url or path. See [`LongPoll`].
+    #[must_use]
+    pub fn long_poll(&self, url_or_path: impl Into<String>) -> LongPoll<'_> {
+        LongPoll::new(self, url_or_path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::StreamExt;
+
+    use crate::clock::TestClock;
+    use crate::middleware::FakeTransport;
+    use crate::{InMemoryBody, InMemoryResponseExt};
+
+    use super::*;
+
+    fn response(status: StatusCode, body: InMemoryBody) -> InMemoryResponse {
+        http::Response::builder().status(status).body(body).unwrap()
+    }
+
+    #[test]
+    fn test_is_empty_response() {
+        assert!(is_empty_response(&response(StatusCode::OK, InMemoryBody::Empty)));
+        assert!(is_empty_response(&response(StatusCode::NO_CONTENT, InMemoryBody::Text("x".into()))));
+        assert!(!is_empty_response(&response(StatusCode::OK, InMemoryBody::Text("event".into()))));
+        assert!(is_timeout_status(StatusCode::GATEWAY_TIMEOUT));
+        assert!(!is_timeout_status(StatusCode::OK));
+    }
+
+    fn event(text: &str, next: &str) -> InMemoryResponse {
+        http::Response::builder().header("x-next-since", next).body(InMemoryBody::Text(text.into())).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_next() {
+        let transport = FakeTransport::new()
+            .respond_with(StatusCode::OK, InMemoryBody::Empty)
+            .respond_with(StatusCode::GATEWAY_TIMEOUT, InMemoryBody::Empty)
+            .respond_with(StatusCode::REQUEST_TIMEOUT, InMemoryBody::Empty)
+            .respond(event("a", "1"))
+            .respond_with(StatusCode::INTERNAL_SERVER_ERROR, InMemoryBody::Empty);
+        let client = Client::new().base_url("https://example.com").with_middleware(transport.clone());
+        let clock = TestClock::new();
+        let start = clock.now();
+        let mut poll = client
+            .long_poll("/updates")
+            .cursor_param("since")
+            .initial_cursor("0")
+            .cursor(|res| res.header("x-next-since").map(ToString::to_string))
+            .clock(clock.clone());
+        assert_eq!(poll.next().await.unwrap().text().unwrap(), "a");
+        // Three empty polls in a row: 100ms, then 200ms, then 400ms apart.
+        assert_eq!(clock.now().duration_since(start).unwrap(), Duration::from_millis(700));
+        let uris: Vec<String> = transport.requests().iter().map(|r| r.uri().to_string()).collect();
+        assert_eq!(uris, vec!["https://example.com/updates?since=0"; 4]);
+
+        poll.next().await.unwrap_err();
+        assert_eq!(transport.requests()[4].uri(), "https://example.com/updates?since=1");
+    }
+
+    #[tokio::test]
+    async fn test_stream() {
+        let transport = FakeTransport::new()
+            .respond(event("a", "1"))
+            .respond(event("b", "2"))
+            .respond_with(StatusCode::INTERNAL_SERVER_ERROR, InMemoryBody::Empty);
+        let client = Client::new().base_url("https://example.com").with_middleware(transport.clone());
+        let poll = client
+            .long_poll("/updates")
+            .cursor_param("since")
+            .cursor(|res| res.header("x-next-since").map(ToString::to_string));
+        let mut results: Vec<_> = poll.stream().collect().await;
+        assert_eq!(results.len(), 3);
+        assert_eq!(results.pop().unwrap().unwrap_err().status(), Some(StatusCode::INTERNAL_SERVER_ERROR));
+        let texts: Vec<String> = results.into_iter().map(|r| r.unwrap().text().unwrap()).collect();
+        assert_eq!(texts, ["a", "b"]);
+        assert_eq!(transport.requests()[2].uri(), "https://example.com/updates?since=2");
+    }
+}
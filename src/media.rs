@@ -0,0 +1,148 @@
+use std::fmt::{Display, Formatter};
+
+use http::header::{ACCEPT, CONTENT_TYPE};
+use http::HeaderMap;
+
+use crate::language::weighted;
+use crate::RequestBuilder;
+
+/// A parsed media type, e.g. from a `Content-Type` header. The type, subtype and parameter names are lowercased.
+/// ```no_run
+/// # use httpclient::{InMemoryResponse, InMemoryResponseExt};
+/// # use serde_json::Value;
+/// # fn parse_csv(_: &str) -> Value { Value::Null }
+/// # fn f(res: InMemoryResponse) -> Result<Value, Box<dyn std::error::Error>> {
+/// # Ok(
+/// match res.content_type() {
+///     Some(t) if t.is_json() => res.json::<Value>()?,
+///     Some(t) if t.essence() == "text/csv" => parse_csv(&res.text()?),
+///     _ => return Err("Unexpected response".into()),
+/// }
+/// # )
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MediaType {
+    /// e.g. `application`.
+    pub kind: String,
+    /// e.g. `vnd.api+json`.
+    pub subtype: String,
+    pub params: Vec<(String, String)>,
+}
+
+impl MediaType {
+    /// Parse a media type like `text/html; charset=utf-8`. Quoted parameter values are unquoted.
+    #[must_use]
+    pub fn parse(value: &str) -> Option<Self> {
+        let mut parts = value.split(';');
+        let (kind, subtype) = parts.next()?.trim().split_once('/')?;
+        if kind.is_empty() || subtype.is_empty() {
+            return None;
+        }
+        let params = parts
+            .filter_map(|p| p.split_once('='))
+            .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().trim_matches('"').to_string()))
+            .collect();
+        Some(Self {
+            kind: kind.to_ascii_lowercase(),
+            subtype: subtype.to_ascii_lowercase(),
+            params,
+        })
+    }
+
+    /// The type and subtype without parameters, e.g. `text/html`.
+    #[must_use]
+    pub fn essence(&self) -> String {
+        format!("{}/{}", self.kind, self.subtype)
+    }
+
+    #[must_use]
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)).map(|(_, v)| v.as_str())
+    }
+
+    #[must_use]
+    pub fn charset(&self) -> Option<&str> {
+        self.param("charset")
+    }
+
+    /// The structured syntax suffix, e.g. `json` for `application/vnd.api+json`.
+    #[must_use]
+    pub fn suffix(&self) -> Option<&str> {
+        self.subtype.rsplit_once('+').map(|(_, suffix)| suffix)
+    }
+
+    /// Whether this is `application/json` or a `+json` type.
+    #[must_use]
+    pub fn is_json(&self) -> bool {
+        (self.kind == "application" && self.subtype == "json") || self.suffix() == Some("json")
+    }
+}
+
+impl Display for MediaType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.kind, self.subtype)?;
+        for (name, value) in &self.params {
+            if value.is_empty() || value.contains(|c: char| c.is_ascii_whitespace() || "()<>@,;:\\\"/[]?=".contains(c)) {
+                write!(f, "; {name}=\"{}\"", value.replace('"', "\\\""))?;
+            } else {
+                write!(f, "; {name}={value}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Build an `Accept` value from media ranges and their weights, e.g. `[("application/json", 1.0), ("text/csv", 0.5)]`
+/// becomes `application/json, text/csv;q=0.5`. Weights are formatted like [`crate::language::accept_language`].
+#[must_use]
+pub fn accept(media_ranges: &[(&str, f32)]) -> String {
+    weighted(media_ranges)
+}
+
+/// The parsed `Content-Type` header, if there is a valid one.
+#[must_use]
+pub fn content_type(headers: &HeaderMap) -> Option<MediaType> {
+    MediaType::parse(headers.get(CONTENT_TYPE)?.to_str().ok()?)
+}
+
+impl<C, B> RequestBuilder<'_, C, B> {
+    /// Set the `Accept` header from weighted media ranges, e.g. `&[("application/json", 1.0), ("text/csv", 0.5)]`.
+    /// Replaces the client default, if any.
+    #[must_use]
+    pub fn accept(mut self, media_ranges: &[(&str, f32)]) -> Self {
+        let value = accept(media_ranges).parse().expect("Invalid media range");
+        self.headers.insert(ACCEPT, value);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Client, InMemoryBody, InMemoryResponse, InMemoryResponseExt};
+
+    use super::*;
+
+    #[test]
+    fn test_accept_and_content_type() {
+        let client = Client::new().accept("text/plain");
+        let req = client
+            .get("https://example.com/")
+            .accept(&[("application/json", 1.0), ("text/csv", 0.5), ("*/*", 0.1)])
+            .build();
+        assert_eq!(
+            req.headers().get_all(ACCEPT).iter().collect::<Vec<_>>(),
+            vec!["application/json, text/csv;q=0.5, */*;q=0.1"]
+        );
+
+        let mut res = InMemoryResponse::new(InMemoryBody::Empty);
+        res.headers_mut()
+            .insert(CONTENT_TYPE, "Application/VND.api+JSON; Charset=\"UTF-8\"; profile=\"a b\"".parse().unwrap());
+        let media = res.content_type().unwrap();
+        assert_eq!(media.essence(), "application/vnd.api+json");
+        assert_eq!(media.charset(), Some("UTF-8"));
+        assert!(media.is_json());
+        assert_eq!(media.to_string(), "application/vnd.api+json; charset=UTF-8; profile=\"a b\"");
+        assert_eq!(MediaType::parse("text"), None);
+    }
+}
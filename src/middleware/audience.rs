@@ -0,0 +1,198 @@
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use async_trait::async_trait;
+use http::header::AUTHORIZATION;
+use http::{HeaderValue, Uri};
+
+use crate::clock::{Clock, SystemClock};
+use crate::error::{ProtocolError, ProtocolResult};
+use crate::middleware::ssrf::host_matches;
+use crate::middleware::Next;
+use crate::{InMemoryRequest, Middleware, Response};
+
+/// The longest a token is cached, however long it says it's valid.
+const MAX_LIFETIME: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// A token minted for one audience, and how long it's valid.
+#[derive(Debug, Clone)]
+pub struct AudienceToken {
+    pub token: String,
+    pub expires_in: Duration,
+}
+
+/// Mints tokens for an audience, e.g. by calling an internal security token service.
+#[async_trait]
+pub trait TokenSource: Send + Sync {
+    async fn fetch(&self, audience: &str) -> ProtocolResult<AudienceToken>;
+}
+
+/// Attach audience-scoped tokens as `Authorization: Bearer <token>`, caching one token per audience.
+///
+/// Each request's audience is looked up by host, using the first matching `.audience()` mapping
+/// (`*.example.com` matches subdomains), and defaults to the request's origin, e.g. `https://api.example.com`.
+/// Tokens are renewed once less than `renew_before` (30s by default) of their lifetime remains, and are cached
+/// for at most a day.
+/// ```
+/// # use httpclient::middleware::TokenSource;
+/// # use httpclient::{AudienceAuth, Client};
+/// # fn f(sts: impl TokenSource + 'static) {
+/// let auth = AudienceAuth::new(sts).audience("*.internal.example.com", "internal-apis");
+/// let client = Client::new().with_middleware(auth);
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct AudienceAuth {
+    source: Arc<dyn TokenSource>,
+    audiences: Vec<(String, String)>,
+    renew_before: Duration,
+    tokens: Arc<Mutex<HashMap<String, (String, SystemTime)>>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl Debug for AudienceAuth {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AudienceAuth")
+            .field("audiences", &self.audiences)
+            .field("renew_before", &self.renew_before)
+            .finish_non_exhaustive()
+    }
+}
+
+impl AudienceAuth {
+    pub fn new<S: TokenSource + 'static>(source: S) -> Self {
+        Self {
+            source: Arc::new(source),
+            audiences: Vec::new(),
+            renew_before: Duration::from_secs(30),
+            tokens: Arc::default(),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Request tokens for `audience` when calling hosts matching `host`, e.g. `api.example.com` or `*.example.com`.
+    #[must_use]
+    pub fn audience(mut self, host: &str, audience: &str) -> Self {
+        self.audiences.push((host.to_ascii_lowercase(), audience.to_string()));
+        self
+    }
+
+    /// Renew a cached token once less than this much of its lifetime remains. Defaults to 30 seconds.
+    #[must_use]
+    pub fn renew_before(mut self, renew_before: Duration) -> Self {
+        self.renew_before = renew_before;
+        self
+    }
+
+    /// Expire tokens with this clock instead of the system time, e.g. a [`crate::clock::TestClock`].
+    #[must_use]
+    pub fn clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// The audience for requests to `uri`.
+    #[must_use]
+    pub fn audience_for(&self, uri: &Uri) -> String {
+        let host = uri.host().unwrap_or_default().to_ascii_lowercase();
+        match self.audiences.iter().find(|(pattern, _)| host_matches(pattern, &host)) {
+            Some((_, audience)) => audience.clone(),
+            None => format!("{}://{}", uri.scheme_str().unwrap_or("https"), uri.authority().map_or(host.as_str(), |a| a.as_str())),
+        }
+    }
+
+    /// The cached token for `audience`, fetching a new one if there is none or it's about to expire.
+    pub async fn token(&self, audience: &str) -> ProtocolResult<String> {
+        let now = self.clock.now();
+        if let Some((token, expires)) = self.tokens.lock().expect("Audience token lock poisoned").get(audience) {
+            // A `renew_before` too long to add to now renews every time, like one longer than the lifetime.
+            if now.checked_add(self.renew_before).is_some_and(|renew_at| renew_at < *expires) {
+                return Ok(token.clone());
+            }
+        }
+        let AudienceToken { token, expires_in } = self.source.fetch(audience).await?;
+        let expires = now.checked_add(expires_in.min(MAX_LIFETIME)).unwrap_or(now);
+        self.tokens
+            .lock()
+            .expect("Audience token lock poisoned")
+            .insert(audience.to_string(), (token.clone(), expires));
+        Ok(token)
+    }
+}
+
+#[async_trait]
+impl Middleware for AudienceAuth {
+    async fn handle(&self, mut request: InMemoryRequest, next: Next<'_>) -> ProtocolResult<Response> {
+        let token = self.token(&self.audience_for(request.uri())).await?;
+        let value = HeaderValue::from_str(&format!("Bearer {token}")).map_err(|_| ProtocolError::invalid_input("Token is not a valid header value"))?;
+        request.headers_mut().insert(AUTHORIZATION, value);
+        next.run(request).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use crate::clock::TestClock;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct CountingSource(AtomicUsize);
+
+    #[async_trait]
+    impl TokenSource for Arc<CountingSource> {
+        async fn fetch(&self, audience: &str) -> ProtocolResult<AudienceToken> {
+            let n = self.0.fetch_add(1, Ordering::SeqCst);
+            Ok(AudienceToken {
+                token: format!("{audience}#{n}"),
+                expires_in: Duration::from_secs(60),
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_audience_tokens() {
+        let source = Arc::new(CountingSource::default());
+        let clock = TestClock::new();
+        let auth = AudienceAuth::new(source.clone()).audience("*.internal.example.com", "internal").clock(clock.clone());
+        let uri = |s: &str| s.parse::<Uri>().unwrap();
+        assert_eq!(auth.audience_for(&uri("https://billing.internal.example.com/x")), "internal");
+        assert_eq!(auth.audience_for(&uri("https://api.example.com:8443/x")), "https://api.example.com:8443");
+
+        assert_eq!(auth.token("internal").await.unwrap(), "internal#0");
+        assert_eq!(auth.token("internal").await.unwrap(), "internal#0");
+        assert_eq!(auth.token("other").await.unwrap(), "other#1");
+        clock.advance(Duration::from_secs(31));
+        assert_eq!(auth.token("internal").await.unwrap(), "internal#2");
+        assert_eq!(source.0.load(Ordering::SeqCst), 3);
+    }
+
+    struct ForeverSource;
+
+    #[async_trait]
+    impl TokenSource for ForeverSource {
+        async fn fetch(&self, _audience: &str) -> ProtocolResult<AudienceToken> {
+            Ok(AudienceToken {
+                token: "forever".to_string(),
+                expires_in: Duration::MAX,
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_huge_durations() {
+        let clock = TestClock::new();
+        let auth = AudienceAuth::new(ForeverSource).clock(clock.clone());
+        assert_eq!(auth.token("a").await.unwrap(), "forever");
+        let expires = auth.tokens.lock().unwrap()["a"].1;
+        assert_eq!(expires, clock.now() + MAX_LIFETIME);
+
+        let auth = AudienceAuth::new(ForeverSource).renew_before(Duration::MAX);
+        assert_eq!(auth.token("a").await.unwrap(), "forever");
+        assert_eq!(auth.token("a").await.unwrap(), "forever");
+    }
+}
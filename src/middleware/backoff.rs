@@ -0,0 +1,153 @@
+use std::fmt::Debug;
+use std::time::Duration;
+
+use rand::Rng;
+
+/// Decide how long `Retry` waits before the next attempt, when the server doesn't send `Retry-After`.
+/// Implement this to match a custom retry policy.
+pub trait BackoffStrategy: Send + Sync + Debug {
+    /// The delay before retry number `attempt` (starting at 1). `prev` is the previous delay, or zero before the first retry.
+    fn delay(&self, attempt: u32, prev: Duration) -> Duration;
+}
+
+/// Pick a random duration in `[low, high]`.
+fn random_between(low: Duration, high: Duration) -> Duration {
+    if high <= low {
+        return low;
+    }
+    rand::thread_rng().gen_range(low..=high)
+}
+
+/// Wait the same delay between every attempt.
+#[derive(Debug, Clone)]
+pub struct Constant(pub Duration);
+
+impl BackoffStrategy for Constant {
+    fn delay(&self, _attempt: u32, _prev: Duration) -> Duration {
+        self.0
+    }
+}
+
+/// Double the delay after every attempt: `base`, `2 * base`, `4 * base`, ... up to `max`.
+/// With `jitter`, a random delay between zero and that value is used instead ("full jitter").
+#[derive(Debug, Clone)]
+pub struct Exponential {
+    pub base: Duration,
+    pub max: Duration,
+    pub jitter: bool,
+}
+
+impl Exponential {
+    #[must_use]
+    pub fn new(base: Duration) -> Self {
+        Self {
+            base,
+            max: Duration::MAX,
+            jitter: false,
+        }
+    }
+
+    #[must_use]
+    pub fn max(mut self, max: Duration) -> Self {
+        self.max = max;
+        self
+    }
+
+    #[must_use]
+    pub fn jitter(mut self) -> Self {
+        self.jitter = true;
+        self
+    }
+}
+
+impl BackoffStrategy for Exponential {
+    fn delay(&self, attempt: u32, _prev: Duration) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        let delay = self.base.saturating_mul(factor).min(self.max);
+        if self.jitter {
+            random_between(Duration::ZERO, delay)
+        } else {
+            delay
+        }
+    }
+}
+
+/// Grow the delay along the Fibonacci sequence: `base`, `base`, `2 * base`, `3 * base`, `5 * base`, ... up to `max`.
+#[derive(Debug, Clone)]
+pub struct Fibonacci {
+    pub base: Duration,
+    pub max: Duration,
+}
+
+impl Fibonacci {
+    #[must_use]
+    pub fn new(base: Duration) -> Self {
+        Self { base, max: Duration::MAX }
+    }
+
+    #[must_use]
+    pub fn max(mut self, max: Duration) -> Self {
+        self.max = max;
+        self
+    }
+}
+
+impl BackoffStrategy for Fibonacci {
+    fn delay(&self, attempt: u32, _prev: Duration) -> Duration {
+        let (mut a, mut b) = (1u32, 1u32);
+        for _ in 1..attempt {
+            (a, b) = (b, a.saturating_add(b));
+        }
+        self.base.saturating_mul(a).min(self.max)
+    }
+}
+
+/// Pick a random delay between `base` and three times the previous delay, up to `max`.
+/// Spreads out clients that fail at the same time better than exponential backoff with jitter.
+#[derive(Debug, Clone)]
+pub struct DecorrelatedJitter {
+    pub base: Duration,
+    pub max: Duration,
+}
+
+impl DecorrelatedJitter {
+    #[must_use]
+    pub fn new(base: Duration) -> Self {
+        Self { base, max: Duration::MAX }
+    }
+
+    #[must_use]
+    pub fn max(mut self, max: Duration) -> Self {
+        self.max = max;
+        self
+    }
+}
+
+impl BackoffStrategy for DecorrelatedJitter {
+    fn delay(&self, _attempt: u32, prev: Duration) -> Duration {
+        let high = prev.max(self.base).saturating_mul(3);
+        random_between(self.base, high).min(self.max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strategies() {
+        let ms = Duration::from_millis;
+        let exp = Exponential::new(ms(100)).max(ms(500));
+        assert_eq!((1..=4).map(|i| exp.delay(i, ms(0))).collect::<Vec<_>>(), vec![ms(100), ms(200), ms(400), ms(500)]);
+        let fib = Fibonacci::new(ms(100));
+        assert_eq!((1..=5).map(|i| fib.delay(i, ms(0))).collect::<Vec<_>>(), vec![ms(100), ms(100), ms(200), ms(300), ms(500)]);
+        assert_eq!(Constant(ms(50)).delay(7, ms(0)), ms(50));
+
+        let jittered = Exponential::new(ms(100)).jitter();
+        assert!(jittered.delay(3, ms(0)) <= ms(400));
+        let decorrelated = DecorrelatedJitter::new(ms(100)).max(ms(1000));
+        let d = decorrelated.delay(2, ms(200));
+        assert!(d >= ms(100) && d <= ms(600));
+        assert!(decorrelated.delay(9, ms(900)) <= ms(1000));
+    }
+}
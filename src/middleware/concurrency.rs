@@ -0,0 +1,323 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use tokio::sync::Notify;
+
+use crate::clock::Instant;
+use crate::error::ProtocolResult;
+use crate::middleware::Next;
+use crate::{InMemoryRequest, Middleware, Response};
+
+/// How [`AdaptiveConcurrency`] adjusts its limit after each response.
+#[derive(Debug, Clone, Copy)]
+pub enum LimitAlgorithm {
+    /// Additive increase, multiplicative decrease: add 1 after each success, and multiply by `backoff`
+    /// after a failure (a 5xx, 429, connection error, or a response slower than `timeout`).
+    Aimd { backoff: f64, timeout: Duration },
+    /// Scale the limit by how much latency has grown over its long-term average, plus a small queue allowance,
+    /// like Netflix's Gradient2. `tolerance` is how much latency growth is accepted before shrinking, e.g. 1.5;
+    /// `smoothing` is how quickly the limit moves towards the new estimate.
+    Gradient { tolerance: f64, smoothing: f64 },
+}
+
+#[derive(Debug)]
+struct State {
+    limit: f64,
+    in_flight: u32,
+    /// Exponential moving average of latency, for `Gradient`.
+    long_rtt: Option<f64>,
+}
+
+/// A snapshot of an [`AdaptiveConcurrency`] limiter, taken when a request gets its slot.
+/// The limiter adds it to the request's extensions, so later middlewares can derive headers from it
+/// with `request.extensions().get::<ConcurrencyState>()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConcurrencyState {
+    pub limit: u32,
+    /// Requests in flight, including this one.
+    pub in_flight: u32,
+}
+
+/// Limit the number of in-flight requests, adapting the limit to observed latency and errors: it grows while
+/// the upstream is healthy and shrinks quickly when it degrades. Requests over the limit wait for a free slot.
+///
+/// The limit is shared by clones of the middleware, so keep a clone to read [`AdaptiveConcurrency::limit`].
+/// ```
+/// # use httpclient::{AdaptiveConcurrency, Client};
+/// let limiter = AdaptiveConcurrency::gradient().max_limit(200);
+/// let client = Client::new().with_middleware(limiter.clone());
+/// ```
+#[derive(Debug, Clone)]
+pub struct AdaptiveConcurrency {
+    algorithm: LimitAlgorithm,
+    min_limit: u32,
+    max_limit: u32,
+    state: Arc<Mutex<State>>,
+    notify: Arc<Notify>,
+}
+
+impl Default for AdaptiveConcurrency {
+    fn default() -> Self {
+        Self::aimd()
+    }
+}
+
+/// A slot for one request, released on drop so cancelled requests free it too.
+struct Permit<'a>(&'a AdaptiveConcurrency);
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        self.0.lock().in_flight -= 1;
+        self.0.notify.notify_waiters();
+    }
+}
+
+impl AdaptiveConcurrency {
+    #[must_use]
+    pub fn new(algorithm: LimitAlgorithm) -> Self {
+        Self {
+            algorithm,
+            min_limit: 1,
+            max_limit: 1000,
+            state: Arc::new(Mutex::new(State {
+                limit: 20.0,
+                in_flight: 0,
+                long_rtt: None,
+            })),
+            notify: Arc::new(Notify::new()),
+        }
+    }
+
+    /// AIMD that shrinks the limit by 10% on each failure, treating responses slower than 5s as failures.
+    #[must_use]
+    pub fn aimd() -> Self {
+        Self::new(LimitAlgorithm::Aimd {
+            backoff: 0.9,
+            timeout: Duration::from_secs(5),
+        })
+    }
+
+    /// Gradient with a tolerance of 1.5x the long-term latency.
+    #[must_use]
+    pub fn gradient() -> Self {
+        Self::new(LimitAlgorithm::Gradient { tolerance: 1.5, smoothing: 0.2 })
+    }
+
+    /// The limit to start at. Defaults to 20. It's kept within `min_limit..=max_limit`.
+    #[must_use]
+    pub fn initial_limit(self, limit: u32) -> Self {
+        self.lock().limit = f64::from(limit);
+        self.clamp_limit()
+    }
+
+    /// Never go below this many concurrent requests. Defaults to 1.
+    #[must_use]
+    pub fn min_limit(mut self, limit: u32) -> Self {
+        self.min_limit = limit.max(1);
+        self.clamp_limit()
+    }
+
+    /// Never go above this many concurrent requests. Defaults to 1000. A `min_limit` above it wins.
+    #[must_use]
+    pub fn max_limit(mut self, limit: u32) -> Self {
+        self.max_limit = limit;
+        self.clamp_limit()
+    }
+
+    /// The range the limit stays in. It's never below 1, or requests would wait forever.
+    fn bounds(&self) -> (f64, f64) {
+        let min = self.min_limit.max(1);
+        (f64::from(min), f64::from(self.max_limit.max(min)))
+    }
+
+    fn clamp_limit(self) -> Self {
+        let (min, max) = self.bounds();
+        let mut state = self.lock();
+        state.limit = state.limit.clamp(min, max);
+        drop(state);
+        self
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().expect("Concurrency limit lock poisoned")
+    }
+
+    /// The current limit.
+    #[must_use]
+    pub fn limit(&self) -> u32 {
+        self.state().limit
+    }
+
+    /// How many requests are in flight.
+    #[must_use]
+    pub fn in_flight(&self) -> u32 {
+        self.lock().in_flight
+    }
+
+    /// The current limit and number of requests in flight.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)] // clamped to `min_limit..=max_limit`
+    pub fn state(&self) -> ConcurrencyState {
+        let state = self.lock();
+        ConcurrencyState {
+            limit: state.limit as u32,
+            in_flight: state.in_flight,
+        }
+    }
+
+    async fn acquire(&self) -> Permit<'_> {
+        loop {
+            // Register for wakeups before checking, so a release in between isn't missed.
+            let notified = self.notify.notified();
+            {
+                let mut state = self.lock();
+                if f64::from(state.in_flight) < state.limit.floor() {
+                    state.in_flight += 1;
+                    return Permit(self);
+                }
+            }
+            notified.await;
+        }
+    }
+
+    /// Update the limit with the outcome of a request that took `rtt`. Called while the request still holds its permit.
+    fn record(&self, rtt: Duration, failed: bool) {
+        let mut state = self.lock();
+        let in_flight = f64::from(state.in_flight);
+        let limit = state.limit;
+        // Only grow when the limit is actually being used, so idle periods don't inflate it.
+        let saturated = in_flight * 2.0 >= limit;
+        let new_limit = match self.algorithm {
+            LimitAlgorithm::Aimd { backoff, timeout } => {
+                if failed || rtt > timeout {
+                    limit * backoff
+                } else if saturated {
+                    limit + 1.0
+                } else {
+                    limit
+                }
+            }
+            LimitAlgorithm::Gradient { tolerance, smoothing } => {
+                let rtt = rtt.as_secs_f64();
+                let long_rtt = state.long_rtt.map_or(rtt, |long| long * 0.95 + rtt * 0.05);
+                state.long_rtt = Some(long_rtt);
+                if failed {
+                    limit * 0.9
+                } else if !saturated && rtt <= long_rtt * tolerance {
+                    limit
+                } else {
+                    let gradient = (long_rtt * tolerance / rtt).clamp(0.5, 1.0);
+                    let estimate = limit * gradient + limit.sqrt();
+                    limit * (1.0 - smoothing) + estimate * smoothing
+                }
+            }
+        };
+        let (min, max) = self.bounds();
+        state.limit = new_limit.clamp(min, max);
+        drop(state);
+        // A higher limit may let waiting requests through.
+        self.notify.notify_waiters();
+    }
+}
+
+#[async_trait]
+impl Middleware for AdaptiveConcurrency {
+    async fn handle(&self, mut request: InMemoryRequest, next: Next<'_>) -> ProtocolResult<Response> {
+        let _permit = self.acquire().await;
+        request.extensions_mut().insert(self.state());
+        let start = Instant::now();
+        let res = next.run(request).await;
+        let failed = match &res {
+            Ok(res) => res.status().is_server_error() || res.status() == http::StatusCode::TOO_MANY_REQUESTS,
+            Err(_) => true,
+        };
+        self.record(start.elapsed(), failed);
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Body, Client, InMemoryBody};
+
+    use super::*;
+
+    /// Responds with the limiter state it sees, like a middleware deriving a budget header would.
+    #[derive(Debug)]
+    struct Budget;
+
+    #[async_trait]
+    impl Middleware for Budget {
+        async fn handle(&self, request: InMemoryRequest, _next: Next<'_>) -> ProtocolResult<Response> {
+            let state = request.extensions().get::<ConcurrencyState>().unwrap();
+            let budget = format!("{}/{}", state.in_flight, state.limit);
+            Ok(http::Response::builder().header("x-client-cost-budget", budget).body(Body::default()).unwrap())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_state_extension() {
+        let client = Client::new();
+        let middlewares: [Arc<dyn Middleware>; 2] = [Arc::new(AdaptiveConcurrency::aimd().initial_limit(8)), Arc::new(Budget)];
+        let next = Next {
+            client: &client,
+            middlewares: &middlewares,
+        };
+        let request = http::Request::builder().uri("https://example.com/").body(InMemoryBody::Empty).unwrap();
+        let res = next.run(request).await.unwrap();
+        assert_eq!(res.headers()["x-client-cost-budget"], "1/8");
+    }
+
+    #[tokio::test]
+    async fn test_limits() {
+        let aimd = AdaptiveConcurrency::aimd().initial_limit(4).max_limit(5);
+        let permits = [aimd.acquire().await, aimd.acquire().await];
+        aimd.record(Duration::from_millis(10), false);
+        aimd.record(Duration::from_millis(10), false);
+        assert_eq!(aimd.limit(), 5);
+        aimd.record(Duration::from_millis(10), true);
+        assert_eq!(aimd.limit(), 4);
+        aimd.record(Duration::from_secs(6), false);
+        assert!(aimd.lock().limit < 4.5);
+        drop(permits);
+        assert_eq!(aimd.in_flight(), 0);
+
+        let gradient = AdaptiveConcurrency::gradient();
+        let mut permits = Vec::new();
+        for _ in 0..10 {
+            permits.push(gradient.acquire().await);
+        }
+        for _ in 0..10 {
+            gradient.record(Duration::from_millis(10), false);
+        }
+        let healthy = gradient.lock().limit;
+        assert!(healthy > 20.0);
+        for _ in 0..10 {
+            gradient.record(Duration::from_millis(100), false);
+        }
+        assert!(gradient.lock().limit < healthy);
+    }
+
+    #[test]
+    fn test_initial_limit_clamped() {
+        assert_eq!(AdaptiveConcurrency::aimd().initial_limit(0).limit(), 1);
+        assert_eq!(AdaptiveConcurrency::aimd().initial_limit(50).max_limit(10).limit(), 10);
+        assert_eq!(AdaptiveConcurrency::aimd().min_limit(30).initial_limit(5).limit(), 30);
+        assert_eq!(AdaptiveConcurrency::aimd().min_limit(30).max_limit(10).limit(), 30);
+    }
+
+    #[tokio::test]
+    async fn test_waits_for_slot() {
+        let limiter = AdaptiveConcurrency::aimd().initial_limit(1);
+        let permit = limiter.acquire().await;
+        let waiting = limiter.clone();
+        let task = tokio::spawn(async move {
+            let _permit = waiting.acquire().await;
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!task.is_finished());
+        drop(permit);
+        task.await.unwrap();
+    }
+}
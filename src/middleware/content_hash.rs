@@ -0,0 +1,128 @@
+use async_trait::async_trait;
+use http::header::IF_MATCH;
+use http::{HeaderName, HeaderValue, Method};
+
+use crate::error::ProtocolResult;
+use crate::middleware::Next;
+use crate::{hex, InMemoryRequest, Middleware, Response};
+
+/// Attach the SHA-256 of the request body as a strong validator, for upload APIs that use content hashes
+/// for optimistic concurrency. By default it's sent as a quoted `ETag` in `If-Match` on PUT and PATCH requests.
+///
+/// The hash covers the body as this middleware sees it, serialized the way it's sent, so place it after
+/// middlewares that rewrite the body, like `FieldEncryption`. A header the request already has, e.g. an `ETag`
+/// from an earlier `GET`, is sent as is.
+#[derive(Debug, Clone)]
+pub struct ContentHash {
+    header: HeaderName,
+    methods: Vec<Method>,
+}
+
+impl Default for ContentHash {
+    fn default() -> Self {
+        Self {
+            header: IF_MATCH,
+            methods: vec![Method::PUT, Method::PATCH],
+        }
+    }
+}
+
+impl ContentHash {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Send the hash in a different header, e.g. `X-Content-Sha256`. Only `If-Match` quotes it as an `ETag`.
+    #[must_use]
+    pub fn header(mut self, header: HeaderName) -> Self {
+        self.header = header;
+        self
+    }
+
+    /// Set which methods get a hash.
+    #[must_use]
+    pub fn methods(mut self, methods: Vec<Method>) -> Self {
+        self.methods = methods;
+        self
+    }
+
+    fn apply(&self, request: &mut InMemoryRequest) -> ProtocolResult<()> {
+        if !self.methods.contains(request.method()) || request.headers().contains_key(&self.header) {
+            return Ok(());
+        }
+        let hex = hex(&request.body().content_sha256()?);
+        let value = if self.header == IF_MATCH { format!("\"{hex}\"") } else { hex };
+        request
+            .headers_mut()
+            .insert(&self.header, HeaderValue::from_str(&value).expect("Hex digest is a valid header value"));
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Middleware for ContentHash {
+    async fn handle(&self, mut request: InMemoryRequest, next: Next<'_>) -> ProtocolResult<Response> {
+        self.apply(&mut request)?;
+        next.run(request).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::{InMemoryBody, Request};
+
+    use super::*;
+
+    const HELLO_SHA256: &str = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+
+    fn request(method: Method, body: InMemoryBody) -> InMemoryRequest {
+        Request::builder().method(method).uri("/files/a").body(body).unwrap()
+    }
+
+    #[test]
+    fn test_if_match() {
+        for method in [Method::PUT, Method::PATCH] {
+            let mut req = request(method, InMemoryBody::Text("hello".into()));
+            ContentHash::new().apply(&mut req).unwrap();
+            assert_eq!(req.headers()[IF_MATCH], format!("\"{HELLO_SHA256}\""));
+        }
+    }
+
+    #[test]
+    fn test_header() {
+        let mut req = request(Method::PUT, InMemoryBody::Text("hello".into()));
+        ContentHash::new().header(HeaderName::from_static("x-content-sha256")).apply(&mut req).unwrap();
+        assert_eq!(req.headers()["x-content-sha256"], HELLO_SHA256);
+        assert!(req.headers().get(IF_MATCH).is_none());
+    }
+
+    #[test]
+    fn test_hashes_sent_bytes() {
+        let body = json!({"name": "a", "size": 1});
+        let mut req = request(Method::PUT, InMemoryBody::Json(body.clone()));
+        ContentHash::new().apply(&mut req).unwrap();
+        let digest = ring::digest::digest(&ring::digest::SHA256, &serde_json::to_vec(&body).unwrap());
+        assert_eq!(req.headers()[IF_MATCH], format!("\"{}\"", hex(digest.as_ref())));
+    }
+
+    #[test]
+    fn test_keeps_existing_header() {
+        let mut req = request(Method::PUT, InMemoryBody::Text("hello".into()));
+        req.headers_mut().insert(IF_MATCH, HeaderValue::from_static("\"v1\""));
+        ContentHash::new().apply(&mut req).unwrap();
+        assert_eq!(req.headers()[IF_MATCH], "\"v1\"");
+    }
+
+    #[test]
+    fn test_methods() {
+        let mut post = request(Method::POST, InMemoryBody::Text("hello".into()));
+        ContentHash::new().apply(&mut post).unwrap();
+        assert!(post.headers().get(IF_MATCH).is_none());
+
+        ContentHash::new().methods(vec![Method::POST]).apply(&mut post).unwrap();
+        assert_eq!(post.headers()[IF_MATCH], format!("\"{HELLO_SHA256}\""));
+    }
+}
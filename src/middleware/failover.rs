@@ -0,0 +1,174 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use http::Uri;
+use tracing::warn;
+
+use crate::error::{ProtocolError, ProtocolResult};
+use crate::middleware::{Next, NoRetry};
+use crate::{InMemoryRequest, Middleware, Response};
+
+/// Send requests to the first of several equivalent endpoints that works, e.g. a primary and a secondary region.
+///
+/// Requests to any of the endpoints' origins are tried against each endpoint in order, moving on when connecting
+/// fails or the response is a 5xx. The last endpoint's result is returned if all of them fail.
+/// A request sent with [`crate::RequestBuilder::no_retry`] only fails over when connecting fails, since it was never sent.
+/// Only the scheme and host are replaced, so the path and query stay as they are. Requests to other origins pass through.
+///
+/// A sticky failover keeps using whichever endpoint last succeeded, instead of starting from the first every time.
+/// Clones share that choice.
+/// ```
+/// # use httpclient::{Client, Failover};
+/// let client = Client::new()
+///     .base_url("https://api.example.com")
+///     .with_middleware(Failover::new(&["https://api.example.com", "https://api-backup.example.com"]).sticky(true));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Failover {
+    endpoints: Vec<Uri>,
+    sticky: bool,
+    current: Arc<AtomicUsize>,
+}
+
+fn same_origin(a: &Uri, b: &Uri) -> bool {
+    a.scheme() == b.scheme() && a.authority() == b.authority()
+}
+
+fn with_origin(uri: &Uri, origin: &Uri) -> Uri {
+    let mut parts = uri.clone().into_parts();
+    parts.scheme = origin.scheme().cloned();
+    parts.authority = origin.authority().cloned();
+    Uri::from_parts(parts).expect("Rewritten URI is valid")
+}
+
+impl Failover {
+    /// Fail over between `endpoints`, in order of preference.
+    ///
+    /// # Panics
+    /// Panics if there are no endpoints, or one isn't an absolute URL with a scheme and host.
+    #[must_use]
+    pub fn new(endpoints: &[&str]) -> Self {
+        assert!(!endpoints.is_empty(), "Failover needs at least one endpoint");
+        let endpoints = endpoints
+            .iter()
+            .map(|e| match e.parse::<Uri>() {
+                Ok(uri) if uri.scheme().is_some() && uri.authority().is_some() => uri,
+                _ => panic!("Failover endpoint `{e}` must be an absolute URL with a scheme and host"),
+            })
+            .collect();
+        Self {
+            endpoints,
+            sticky: false,
+            current: Arc::default(),
+        }
+    }
+
+    /// Start from the endpoint that last succeeded, rather than the first.
+    #[must_use]
+    pub fn sticky(mut self, sticky: bool) -> Self {
+        self.sticky = sticky;
+        self
+    }
+}
+
+#[async_trait]
+impl Middleware for Failover {
+    async fn handle(&self, request: InMemoryRequest, next: Next<'_>) -> ProtocolResult<Response> {
+        if !self.endpoints.iter().any(|e| same_origin(e, request.uri())) {
+            return next.run(request).await;
+        }
+        let no_retry = request.extensions().get::<NoRetry>().is_some();
+        let start = if self.sticky { self.current.load(Ordering::Relaxed) } else { 0 };
+        let mut result = None;
+        for k in 0..self.endpoints.len() {
+            let i = (start + k) % self.endpoints.len();
+            let mut attempt = request.clone();
+            *attempt.uri_mut() = with_origin(request.uri(), &self.endpoints[i]);
+            let res = next.run(attempt).await;
+            let failed = match &res {
+                Ok(res) => !no_retry && res.status().is_server_error(),
+                Err(ProtocolError::ConnectionError(e)) => e.is_connect(),
+                Err(_) => false,
+            };
+            if !failed {
+                if self.sticky && res.is_ok() {
+                    self.current.store(i, Ordering::Relaxed);
+                }
+                return res;
+            }
+            warn!(endpoint = %self.endpoints[i], "Endpoint failed, failing over");
+            result = Some(res);
+        }
+        result.expect("Failover has at least one endpoint")
+    }
+    fn replays_requests(&self, request: &InMemoryRequest) -> bool {
+        request.extensions().get::<NoRetry>().is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::StatusCode;
+
+    use crate::middleware::{FakeTransport, MiddlewareTester};
+    use crate::{Client, InMemoryBody, ResponseExt};
+
+    use super::*;
+
+    fn get(url: &str) -> InMemoryRequest {
+        http::Request::builder().uri(url).body(InMemoryBody::Empty).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_failover() {
+        let transport = FakeTransport::new()
+            .respond_with(StatusCode::BAD_GATEWAY, InMemoryBody::Empty)
+            .respond_with(StatusCode::OK, InMemoryBody::Empty)
+            .respond_with(StatusCode::OK, InMemoryBody::Empty);
+        let failover = Failover::new(&["https://a.example.com", "https://b.example.com"]).sticky(true);
+        let tester = MiddlewareTester::new(failover).with_transport(transport.clone());
+        assert_eq!(tester.run(get("https://a.example.com/x?y=1")).await.unwrap().status(), StatusCode::OK);
+        // Sticks with the endpoint that worked.
+        tester.run(get("https://a.example.com/z")).await.unwrap();
+        tester.run(get("https://other.com/")).await.unwrap();
+        let uris: Vec<String> = transport.requests().iter().map(|r| r.uri().to_string()).collect();
+        assert_eq!(
+            uris,
+            [
+                "https://a.example.com/x?y=1",
+                "https://b.example.com/x?y=1",
+                "https://b.example.com/z",
+                "https://other.com/"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_no_retry() {
+        let transport = FakeTransport::new().respond_with(StatusCode::BAD_GATEWAY, InMemoryBody::Empty);
+        let failover = Failover::new(&["https://a.example.com", "https://b.example.com"]);
+        let mut request = get("https://a.example.com/hook");
+        request.extensions_mut().insert(NoRetry);
+        assert!(!failover.replays_requests(&request));
+        let tester = MiddlewareTester::new(failover).with_transport(transport.clone());
+        assert_eq!(tester.run(request).await.unwrap().status(), StatusCode::BAD_GATEWAY);
+        assert_eq!(transport.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_failover_on_connect_error() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 1024];
+            let _ = tokio::io::AsyncReadExt::read(&mut stream, &mut buf).await;
+            let _ = tokio::io::AsyncWriteExt::write_all(&mut stream, b"HTTP/1.1 200 OK\r\ncontent-length: 2\r\n\r\nok").await;
+        });
+        let backup = format!("http://127.0.0.1:{port}");
+        let client = Client::new().with_middleware(Failover::new(&["http://127.0.0.1:1", &backup]));
+        let res = client.get("http://127.0.0.1:1/health").no_retry().send().await.unwrap();
+        assert_eq!(res.text().await.unwrap(), "ok");
+    }
+}
@@ -0,0 +1,163 @@
+use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+use serde_json::Value;
+
+use crate::error::{ProtocolError, ProtocolResult};
+use crate::middleware::Next;
+use crate::{Body, InMemoryBody, InMemoryRequest, InMemoryResponseExt, Middleware, Response, ResponseExt};
+
+/// Encrypt selected fields of JSON request bodies, and decrypt selected fields of JSON response bodies,
+/// with AES-256-GCM. Fields are selected by JSON pointer, e.g. `/customer/ssn`.
+///
+/// An encrypted field is replaced by a base64 string of the nonce followed by the ciphertext of the field's JSON.
+/// Any JSON value can be encrypted, and decrypting restores the original type.
+/// Missing fields and non-JSON bodies are left as they are.
+/// ```
+/// # use httpclient::middleware::FieldEncryption;
+/// # use httpclient::Client;
+/// # fn f(key: [u8; 32]) {
+/// let crypto = FieldEncryption::new(&key).field("/customer/ssn");
+/// let client = Client::new().with_middleware(crypto);
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct FieldEncryption {
+    key: Arc<LessSafeKey>,
+    request_fields: Vec<String>,
+    response_fields: Vec<String>,
+}
+
+impl Debug for FieldEncryption {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FieldEncryption")
+            .field("request_fields", &self.request_fields)
+            .field("response_fields", &self.response_fields)
+            .finish_non_exhaustive()
+    }
+}
+
+impl FieldEncryption {
+    #[must_use]
+    pub fn new(key: &[u8; 32]) -> Self {
+        let key = UnboundKey::new(&AES_256_GCM, key).expect("AES-256 key is 32 bytes");
+        Self {
+            key: Arc::new(LessSafeKey::new(key)),
+            request_fields: Vec::new(),
+            response_fields: Vec::new(),
+        }
+    }
+
+    /// Encrypt this field in requests, and decrypt it in responses.
+    #[must_use]
+    pub fn field(self, pointer: &str) -> Self {
+        self.request_field(pointer).response_field(pointer)
+    }
+
+    /// Encrypt this field in requests.
+    #[must_use]
+    pub fn request_field(mut self, pointer: &str) -> Self {
+        self.request_fields.push(pointer.to_string());
+        self
+    }
+
+    /// Decrypt this field in responses.
+    #[must_use]
+    pub fn response_field(mut self, pointer: &str) -> Self {
+        self.response_fields.push(pointer.to_string());
+        self
+    }
+
+    pub fn encrypt(&self, value: &Value) -> ProtocolResult<String> {
+        let mut nonce = [0u8; NONCE_LEN];
+        SystemRandom::new().fill(&mut nonce).map_err(|_| ProtocolError::invalid_input("Failed to generate nonce"))?;
+        let mut data = serde_json::to_vec(value)?;
+        self.key
+            .seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::empty(), &mut data)
+            .map_err(|_| ProtocolError::invalid_input("Failed to encrypt field"))?;
+        let mut out = nonce.to_vec();
+        out.append(&mut data);
+        Ok(STANDARD.encode(out))
+    }
+
+    pub fn decrypt(&self, encrypted: &str) -> ProtocolResult<Value> {
+        let mut data = STANDARD.decode(encrypted).map_err(|_| ProtocolError::invalid_input("Encrypted field is not base64"))?;
+        if data.len() < NONCE_LEN {
+            return Err(ProtocolError::invalid_input("Encrypted field is too short"));
+        }
+        let mut ciphertext = data.split_off(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(&data).map_err(|_| ProtocolError::invalid_input("Invalid nonce"))?;
+        let plaintext = self
+            .key
+            .open_in_place(nonce, Aad::empty(), &mut ciphertext)
+            .map_err(|_| ProtocolError::invalid_input("Failed to decrypt field"))?;
+        serde_json::from_slice(plaintext).map_err(Into::into)
+    }
+
+    fn encrypt_fields(&self, body: &mut Value) -> ProtocolResult<()> {
+        for pointer in &self.request_fields {
+            if let Some(field) = body.pointer_mut(pointer) {
+                *field = Value::String(self.encrypt(field)?);
+            }
+        }
+        Ok(())
+    }
+
+    fn decrypt_fields(&self, body: &mut Value) -> ProtocolResult<()> {
+        for pointer in &self.response_fields {
+            if let Some(field) = body.pointer_mut(pointer) {
+                let Value::String(encrypted) = field else {
+                    return Err(ProtocolError::invalid_input("Encrypted field is not a string"));
+                };
+                *field = self.decrypt(encrypted)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Middleware for FieldEncryption {
+    async fn handle(&self, mut request: InMemoryRequest, next: Next<'_>) -> ProtocolResult<Response> {
+        if let InMemoryBody::Json(body) = request.body_mut() {
+            self.encrypt_fields(body)?;
+        }
+        let res = next.run(request).await?;
+        if self.response_fields.is_empty() {
+            return Ok(res);
+        }
+        let mut res = res.into_memory().await?;
+        if let Some(json) = res.json_value_mut()? {
+            self.decrypt_fields(json)?;
+        }
+        Ok(res.map(Body::InMemory))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let crypto = FieldEncryption::new(&[7; 32]).field("/customer/ssn").field("/card");
+        let mut body = json!({"customer": {"name": "Ada", "ssn": "123-45-6789"}, "card": {"number": 4242}});
+        crypto.encrypt_fields(&mut body).unwrap();
+        assert_eq!(body["customer"]["name"], "Ada");
+        assert!(body["customer"]["ssn"].as_str().unwrap() != "123-45-6789");
+        assert!(body["card"].is_string());
+
+        crypto.decrypt_fields(&mut body).unwrap();
+        assert_eq!(body, json!({"customer": {"name": "Ada", "ssn": "123-45-6789"}, "card": {"number": 4242}}));
+
+        let encrypted = crypto.encrypt(&json!("secret")).unwrap();
+        assert!(FieldEncryption::new(&[8; 32]).decrypt(&encrypted).is_err());
+    }
+}
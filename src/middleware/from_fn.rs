@@ -0,0 +1,119 @@
+use std::fmt::{Debug, Formatter};
+use std::future::Future;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+use crate::client::Client;
+use crate::error::ProtocolResult;
+use crate::middleware::Next;
+use crate::{InMemoryRequest, Middleware, Response};
+
+/// The rest of the middleware chain, owned so an `async move` block can hold it. Given to [`middleware_fn`] closures.
+#[derive(Debug, Clone)]
+pub struct OwnedNext {
+    client: Client,
+    middlewares: Vec<Arc<dyn Middleware>>,
+}
+
+impl OwnedNext {
+    #[must_use]
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// Send `request` through the remaining middlewares, like [`Next::run`].
+    pub async fn run(self, request: InMemoryRequest) -> ProtocolResult<Response> {
+        let next = Next {
+            client: &self.client,
+            middlewares: &self.middlewares,
+        };
+        next.run(request).await
+    }
+}
+
+/// A middleware made from an async closure by [`middleware_fn`].
+pub struct FnMiddleware<F>(F);
+
+impl<F> Debug for FnMiddleware<F> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("FnMiddleware")
+    }
+}
+
+/// Make a middleware from an async closure, for small behaviors that don't need their own type.
+///
+/// The closure gets the request and the rest of the chain as an [`OwnedNext`], which costs a clone of the client
+/// per request; implement [`Middleware`] instead for hot paths. Capture shared state in an `Arc`:
+/// ```
+/// # use std::sync::atomic::{AtomicUsize, Ordering};
+/// # use std::sync::Arc;
+/// # use httpclient::{middleware_fn, Client};
+/// let sent = Arc::new(AtomicUsize::new(0));
+/// let counter = sent.clone();
+/// let client = Client::new().with_middleware(middleware_fn(move |request, next| {
+///     let counter = counter.clone();
+///     async move {
+///         counter.fetch_add(1, Ordering::Relaxed);
+///         next.run(request).await
+///     }
+/// }));
+/// ```
+pub fn middleware_fn<F, Fut>(f: F) -> FnMiddleware<F>
+where
+    F: Fn(InMemoryRequest, OwnedNext) -> Fut + Send + Sync,
+    Fut: Future<Output = ProtocolResult<Response>> + Send,
+{
+    FnMiddleware(f)
+}
+
+#[async_trait]
+impl<F, Fut> Middleware for FnMiddleware<F>
+where
+    F: Fn(InMemoryRequest, OwnedNext) -> Fut + Send + Sync,
+    Fut: Future<Output = ProtocolResult<Response>> + Send,
+{
+    async fn handle(&self, request: InMemoryRequest, next: Next<'_>) -> ProtocolResult<Response> {
+        let next = OwnedNext {
+            client: next.client.clone(),
+            middlewares: next.middlewares.to_vec(),
+        };
+        (self.0)(request, next).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use http::StatusCode;
+
+    use crate::middleware::FakeTransport;
+    use crate::InMemoryBody;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_middleware_fn() {
+        let sent = Arc::new(AtomicUsize::new(0));
+        let counter = sent.clone();
+        let transport = FakeTransport::new().respond_with(StatusCode::CREATED, InMemoryBody::Empty);
+        let client = Client::new()
+            .with_middleware(middleware_fn(move |mut request, next| {
+                let counter = counter.clone();
+                async move {
+                    counter.fetch_add(1, Ordering::Relaxed);
+                    request.headers_mut().insert("x-count", "1".parse().unwrap());
+                    let mut res = next.run(request).await?;
+                    res.headers_mut().insert("x-seen", "1".parse().unwrap());
+                    Ok(res)
+                }
+            }))
+            .with_middleware(transport.clone());
+        let res = client.get("https://example.com/").send().await.unwrap();
+        assert_eq!(res.status(), StatusCode::CREATED);
+        assert_eq!(res.headers()["x-seen"], "1");
+        assert_eq!(transport.requests()[0].headers()["x-count"], "1");
+        assert_eq!(sent.load(Ordering::Relaxed), 1);
+    }
+}
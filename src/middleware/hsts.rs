@@ -0,0 +1,270 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use async_trait::async_trait;
+use http::header::STRICT_TRANSPORT_SECURITY;
+use http::uri::Scheme;
+use http::Uri;
+
+use crate::clock::{Clock, SystemClock};
+use crate::error::{ProtocolError, ProtocolResult};
+use crate::middleware::{Next, RedirectHistory};
+use crate::{Client, InMemoryRequest, Middleware, Response};
+
+/// The longest a policy is kept, however long a host asks for. Browsers cap it similarly.
+const MAX_POLICY_AGE: Duration = Duration::from_secs(2 * 365 * 24 * 60 * 60);
+
+#[derive(Debug, Clone, Copy)]
+struct Policy {
+    expires: SystemTime,
+    include_subdomains: bool,
+}
+
+/// Parse a `Strict-Transport-Security` value into its max age and whether it covers subdomains.
+fn parse_sts(value: &str) -> Option<(Duration, bool)> {
+    let mut max_age = None;
+    let mut include_subdomains = false;
+    for directive in value.split(';').map(str::trim) {
+        let (name, value) = directive.split_once('=').map_or((directive, None), |(n, v)| (n.trim(), Some(v.trim().trim_matches('"'))));
+        if name.eq_ignore_ascii_case("max-age") {
+            max_age = Some(Duration::from_secs(value?.parse().ok()?));
+        } else if name.eq_ignore_ascii_case("includeSubDomains") {
+            include_subdomains = true;
+        }
+    }
+    Some((max_age?, include_subdomains))
+}
+
+/// Upgrade `http://` requests to `https://` for hosts that sent a `Strict-Transport-Security` header over HTTPS,
+/// and optionally refuse plaintext requests altogether.
+///
+/// Known hosts are kept in memory and shared by clones of the middleware. Redirects pass through middlewares
+/// after `Follow`, so add this one after it to cover redirect hops too:
+/// ```
+/// # use httpclient::{Client, Follow};
+/// let client = Client::new().with_middleware(Follow).https_only(true);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Hsts {
+    https_only: bool,
+    hosts: Arc<Mutex<HashMap<String, Policy>>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl Default for Hsts {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Hsts {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            https_only: false,
+            hosts: Arc::default(),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Fail plaintext `http://` requests that can't be upgraded with `ProtocolError::Blocked`, instead of sending them.
+    #[must_use]
+    pub fn https_only(mut self, https_only: bool) -> Self {
+        self.https_only = https_only;
+        self
+    }
+
+    /// Expire policies with this clock instead of the system time, e.g. a [`crate::clock::TestClock`].
+    #[must_use]
+    pub fn clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Whether requests to `host` are upgraded to HTTPS.
+    #[must_use]
+    pub fn is_known(&self, host: &str) -> bool {
+        let host = host.to_ascii_lowercase();
+        let now = self.clock.now();
+        let hosts = self.hosts.lock().expect("HSTS lock poisoned");
+        let active = |h: &str, subdomain: bool| hosts.get(h).is_some_and(|p| p.expires > now && (!subdomain || p.include_subdomains));
+        if active(&host, false) {
+            return true;
+        }
+        host.match_indices('.').any(|(i, _)| active(&host[i + 1..], true))
+    }
+
+    /// Record the `Strict-Transport-Security` header of a response from `host`. A max age of 0 forgets the host,
+    /// and one longer than two years is shortened to two years.
+    fn record(&self, host: &str, value: &str) {
+        if host.parse::<IpAddr>().is_ok() {
+            return;
+        }
+        let Some((max_age, include_subdomains)) = parse_sts(value) else {
+            return;
+        };
+        let mut hosts = self.hosts.lock().expect("HSTS lock poisoned");
+        if max_age.is_zero() {
+            hosts.remove(&host.to_ascii_lowercase());
+        } else {
+            let Some(expires) = self.clock.now().checked_add(max_age.min(MAX_POLICY_AGE)) else {
+                return;
+            };
+            hosts.insert(host.to_ascii_lowercase(), Policy { expires, include_subdomains });
+        }
+    }
+}
+
+fn upgrade(uri: &Uri) -> Uri {
+    let mut parts = uri.clone().into_parts();
+    parts.scheme = Some(Scheme::HTTPS);
+    if let Some(authority) = &parts.authority {
+        // An explicit port 80 belongs to the plaintext scheme.
+        if authority.port_u16() == Some(80) {
+            parts.authority = authority.host().parse().ok();
+        }
+    }
+    Uri::from_parts(parts).expect("Upgraded URI is valid")
+}
+
+#[async_trait]
+impl Middleware for Hsts {
+    async fn handle(&self, mut request: InMemoryRequest, next: Next<'_>) -> ProtocolResult<Response> {
+        let host = request.uri().host().unwrap_or_default().to_string();
+        if request.uri().scheme() == Some(&Scheme::HTTP) {
+            if self.is_known(&host) {
+                *request.uri_mut() = upgrade(request.uri());
+            } else if self.https_only {
+                return Err(ProtocolError::Blocked(format!("{} is not https", request.uri())));
+            }
+        }
+        let uri = request.uri().clone();
+        let res = next.run(request).await?;
+        // After a `Follow` later in the stack, the response is from the last redirect's host, not this request's.
+        let answered = res.extensions().get::<RedirectHistory>().map_or(&uri, |history| &history.final_url);
+        if answered.scheme() == Some(&Scheme::HTTPS) {
+            if let Some(value) = res.headers().get(STRICT_TRANSPORT_SECURITY).and_then(|v| v.to_str().ok()) {
+                self.record(answered.host().unwrap_or_default(), value);
+            }
+        }
+        Ok(res)
+    }
+
+    fn checks_redirect_hops(&self) -> bool {
+        true
+    }
+
+    fn hsts_policy(&self) -> Option<&Hsts> {
+        Some(self)
+    }
+}
+
+impl Client {
+    /// Refuse plaintext `http://` requests, and upgrade hosts that sent `Strict-Transport-Security`. See [`Hsts`].
+    /// It replaces an `Hsts` added before, keeping the hosts it learned, and runs right after [`crate::Follow`],
+    /// so every redirect hop is checked too.
+    #[must_use]
+    pub fn https_only(mut self, https_only: bool) -> Self {
+        let hsts = self.middlewares.iter().find_map(|m| m.hsts_policy().cloned()).unwrap_or_default();
+        self.middlewares.retain(|m| m.hsts_policy().is_none());
+        let index = self.middlewares.iter().rposition(|m| m.follows_redirects()).map_or(0, |i| i + 1);
+        self.insert_middleware(index, Arc::new(hsts.https_only(https_only)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::StatusCode;
+
+    use crate::clock::TestClock;
+    use crate::middleware::{FakeTransport, Follow};
+    use crate::InMemoryBody;
+
+    use super::*;
+
+    fn redirect(location: &str) -> crate::InMemoryResponse {
+        http::Response::builder()
+            .status(StatusCode::FOUND)
+            .header("location", location)
+            .body(InMemoryBody::Empty)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_hsts() {
+        assert_eq!(parse_sts("max-age=31536000; includeSubDomains"), Some((Duration::from_secs(31_536_000), true)));
+        assert_eq!(parse_sts(r#"max-age="60""#), Some((Duration::from_secs(60), false)));
+        assert_eq!(parse_sts("includeSubDomains"), None);
+
+        let clock = TestClock::new();
+        let hsts = Hsts::new().clock(clock.clone());
+        hsts.record("Example.com", "max-age=60; includeSubDomains");
+        hsts.record("other.org", "max-age=60");
+        hsts.record("127.0.0.1", "max-age=60");
+        assert!(hsts.is_known("example.com"));
+        assert!(hsts.is_known("api.example.com"));
+        assert!(hsts.is_known("other.org"));
+        assert!(!hsts.is_known("api.other.org"));
+        assert!(!hsts.is_known("127.0.0.1"));
+
+        clock.advance(Duration::from_secs(61));
+        assert!(!hsts.is_known("example.com"));
+        hsts.record("other.org", "max-age=0");
+        assert!(hsts.hosts.lock().unwrap().get("other.org").is_none());
+
+        hsts.record("huge.org", &format!("max-age={}", u64::MAX));
+        assert!(hsts.is_known("huge.org"));
+        clock.advance(MAX_POLICY_AGE);
+        assert!(!hsts.is_known("huge.org"));
+
+        assert_eq!(upgrade(&"http://example.com:80/a?b".parse().unwrap()), "https://example.com/a?b");
+        assert_eq!(upgrade(&"http://example.com:8080/".parse().unwrap()), "https://example.com:8080/");
+    }
+
+    #[tokio::test]
+    async fn test_https_only() {
+        let client = Client::new().https_only(true);
+        let err = client.get("http://example.com/").send().await.unwrap_err();
+        assert!(matches!(err, ProtocolError::Blocked(_)));
+    }
+
+    #[tokio::test]
+    async fn test_https_only_checks_redirects() {
+        for client in [
+            Client::new().https_only(true).with_middleware(Follow),
+            Client::new().with_middleware(Follow).https_only(true),
+        ] {
+            let transport = FakeTransport::new().respond(redirect("http://other.com/"));
+            let client = client.with_middleware(transport.clone());
+            let err = client.get("https://example.com/").send().await.unwrap_err();
+            assert!(matches!(err, ProtocolError::Blocked(_)), "{err:?}");
+            assert_eq!(transport.requests().len(), 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_https_only_replaces() {
+        let transport = FakeTransport::new().respond_with(StatusCode::OK, InMemoryBody::Empty);
+        let client = Client::new().https_only(true).https_only(false).with_middleware(transport.clone());
+        assert_eq!(client.middlewares.iter().filter(|m| m.hsts_policy().is_some()).count(), 1);
+        client.get("http://example.com/").send().await.unwrap();
+        assert_eq!(transport.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_records_redirect_target() {
+        let hsts = Hsts::new();
+        let target = http::Response::builder().header(STRICT_TRANSPORT_SECURITY, "max-age=60").body(InMemoryBody::Empty).unwrap();
+        let transport = FakeTransport::new().respond(redirect("https://b.example.org/")).respond(target);
+        // Before `Follow`, so the response it sees is from the redirect's target.
+        let client = Client::new()
+            .with_middleware(Follow)
+            .with_middleware(transport)
+            .insert_middleware(0, Arc::new(hsts.clone()));
+        client.get("https://a.example.org/").send().await.unwrap();
+        assert!(hsts.is_known("b.example.org"));
+        assert!(!hsts.is_known("a.example.org"));
+    }
+}
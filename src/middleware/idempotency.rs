@@ -0,0 +1,100 @@
+use async_trait::async_trait;
+use http::{HeaderName, HeaderValue, Method};
+use rand::Rng;
+
+use crate::error::ProtocolResult;
+use crate::middleware::Next;
+use crate::{hex, InMemoryRequest, Middleware, Response};
+
+pub const IDEMPOTENCY_KEY: HeaderName = HeaderName::from_static("idempotency-key");
+
+/// Attach a generated `Idempotency-Key` header to non-idempotent requests (POST and PATCH by default),
+/// so servers that support it (e.g. Stripe) can de-duplicate retried sends.
+///
+/// Place this middleware *before* `Retry`, so every retry attempt of the same logical request carries
+/// the same key. Requests that already have the header are left untouched.
+#[derive(Debug, Clone)]
+pub struct IdempotencyKey {
+    header: HeaderName,
+    methods: Vec<Method>,
+}
+
+impl Default for IdempotencyKey {
+    fn default() -> Self {
+        Self {
+            header: IDEMPOTENCY_KEY,
+            methods: vec![Method::POST, Method::PATCH],
+        }
+    }
+}
+
+/// Generate a random (version 4) UUID.
+fn gen_key() -> String {
+    let mut bytes: [u8; 16] = rand::thread_rng().gen();
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex = hex(&bytes);
+    format!("{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..])
+}
+
+impl IdempotencyKey {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use a different header, e.g. `X-Request-Id`.
+    #[must_use]
+    pub fn header(mut self, header: HeaderName) -> Self {
+        self.header = header;
+        self
+    }
+
+    /// Set which methods get a key.
+    #[must_use]
+    pub fn methods(mut self, methods: Vec<Method>) -> Self {
+        self.methods = methods;
+        self
+    }
+
+    fn apply(&self, request: &mut InMemoryRequest) {
+        if !self.methods.contains(request.method()) {
+            return;
+        }
+        request
+            .headers_mut()
+            .entry(&self.header)
+            .or_insert_with(|| HeaderValue::from_str(&gen_key()).expect("UUID is a valid header value"));
+    }
+}
+
+#[async_trait]
+impl Middleware for IdempotencyKey {
+    async fn handle(&self, mut request: InMemoryRequest, next: Next<'_>) -> ProtocolResult<Response> {
+        self.apply(&mut request);
+        next.run(request).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{InMemoryBody, Request};
+
+    use super::*;
+
+    #[test]
+    fn test_apply() {
+        let m = IdempotencyKey::new();
+        let mut post: InMemoryRequest = Request::builder().method(Method::POST).uri("/charges").body(InMemoryBody::Empty).unwrap();
+        m.apply(&mut post);
+        let key = post.headers().get(IDEMPOTENCY_KEY).unwrap().clone();
+        assert_eq!(key.len(), 36);
+        // an existing key is kept, e.g. when the request is re-sent
+        m.apply(&mut post);
+        assert_eq!(post.headers().get(IDEMPOTENCY_KEY).unwrap(), &key);
+
+        let mut get: InMemoryRequest = Request::builder().method(Method::GET).uri("/charges").body(InMemoryBody::Empty).unwrap();
+        m.apply(&mut get);
+        assert!(get.headers().get(IDEMPOTENCY_KEY).is_none());
+    }
+}
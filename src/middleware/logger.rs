@@ -0,0 +1,319 @@
+use std::fmt::Write;
+
+use async_trait::async_trait;
+use futures::stream;
+use http::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use http::{HeaderMap, HeaderValue};
+use hyper::body::HttpBody;
+use tracing::Level;
+
+use crate::body::is_json;
+use crate::clock::Instant;
+use crate::error::ProtocolResult;
+use crate::middleware::{Attempts, Next};
+use crate::sanitize::sanitize_headers;
+use crate::{Body, InMemoryBody, InMemoryRequest, Middleware, Response};
+
+/// Emit a `tracing` event at a level chosen at runtime.
+macro_rules! event_at {
+    ($level:expr, $($args:tt)+) => {
+        match $level {
+            Level::ERROR => tracing::error!($($args)+),
+            Level::WARN => tracing::warn!($($args)+),
+            Level::INFO => tracing::info!($($args)+),
+            Level::DEBUG => tracing::debug!($($args)+),
+            Level::TRACE => tracing::trace!($($args)+),
+        }
+    };
+}
+
+/// Log requests and responses as `tracing` events, at `INFO` by default.
+///
+/// Sensitive headers and JSON fields (see [`crate::sanitize`]) are masked, and bodies are cut off after
+/// `max_body` bytes (4 KiB by default). Logging a response reads its whole body into memory,
+/// unless it's larger than the `stream_over` threshold. Responses from a [`crate::Retry`] added after the logger
+/// also log its `attempts` and total `retry_delay_ms`.
+/// ```
+/// # use httpclient::{Client, Logger};
+/// # use tracing::Level;
+/// let client = Client::new().with_middleware(Logger::new().level(Level::DEBUG).responses(false));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Logger {
+    level: Level,
+    max_body: usize,
+    requests: bool,
+    responses: bool,
+    sanitize: bool,
+    stream_over: Option<u64>,
+}
+
+impl Default for Logger {
+    fn default() -> Self {
+        Self {
+            level: Level::INFO,
+            max_body: 4096,
+            requests: true,
+            responses: true,
+            sanitize: true,
+            stream_over: None,
+        }
+    }
+}
+
+impl Logger {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn level(mut self, level: Level) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Cut off logged bodies after this many bytes. 0 leaves bodies out.
+    #[must_use]
+    pub fn max_body(mut self, max_body: usize) -> Self {
+        self.max_body = max_body;
+        self
+    }
+
+    /// Whether to log requests. Defaults to true.
+    #[must_use]
+    pub fn requests(mut self, requests: bool) -> Self {
+        self.requests = requests;
+        self
+    }
+
+    /// Whether to log responses. Defaults to true.
+    #[must_use]
+    pub fn responses(mut self, responses: bool) -> Self {
+        self.responses = responses;
+        self
+    }
+
+    /// Whether to mask sensitive headers and JSON fields. Defaults to true.
+    #[must_use]
+    pub fn sanitize(mut self, sanitize: bool) -> Self {
+        self.sanitize = sanitize;
+        self
+    }
+
+    /// Leave response bodies larger than this many bytes, or of unknown length, streaming for the caller.
+    /// Their headers are logged right away, and the first `max_body` bytes once they've been read, sanitized like
+    /// other bodies. A JSON body longer than `max_body` can't be, so when sanitizing, only its length is logged.
+    #[must_use]
+    pub fn stream_over(mut self, threshold: u64) -> Self {
+        self.stream_over = Some(threshold);
+        self
+    }
+
+    fn headers(&self, headers: &HeaderMap) -> String {
+        let mut headers = headers.clone();
+        if self.sanitize {
+            sanitize_headers(&mut headers);
+        }
+        headers
+            .iter()
+            .map(|(k, v)| format!("{k}: {}", v.to_str().unwrap_or("<binary>")))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    fn body(&self, body: &InMemoryBody) -> String {
+        let text = match body {
+            InMemoryBody::Empty => return String::new(),
+            InMemoryBody::Text(s) => s.to_string(),
+            InMemoryBody::Json(value) => self.json(value.clone()),
+            // JSON responses stay bytes until parsed.
+            InMemoryBody::Bytes(b) => match serde_json::from_slice(b) {
+                Ok(value) => self.json(value),
+                Err(_) => return format!("<{} bytes>", b.len()),
+            },
+        };
+        truncate(text, self.max_body)
+    }
+
+    /// Like [`Logger::body`], for the first bytes of a streamed body of `len` bytes. A JSON body that was cut off
+    /// can't have its sensitive fields masked, so when sanitizing, only its length is logged.
+    fn streamed_body(&self, prefix: &[u8], len: usize, content_type: Option<&HeaderValue>) -> String {
+        if len == 0 {
+            return String::new();
+        }
+        let complete = prefix.len() == len;
+        if complete {
+            if let Ok(value) = serde_json::from_slice(prefix) {
+                return truncate(self.json(value), self.max_body);
+            }
+        }
+        if self.sanitize && is_json(content_type) {
+            return format!("<{len} bytes>");
+        }
+        let mut text = match std::str::from_utf8(prefix) {
+            Ok(text) => text.to_string(),
+            // Cut off in the middle of a character.
+            Err(e) if !complete && e.error_len().is_none() => String::from_utf8_lossy(&prefix[..e.valid_up_to()]).into_owned(),
+            Err(_) => return format!("<{len} bytes>"),
+        };
+        if !complete {
+            let _ = write!(text, "... ({len} bytes)");
+        }
+        text
+    }
+
+    fn json(&self, mut value: serde_json::Value) -> String {
+        if self.sanitize {
+            crate::sanitize::sanitize_value(&mut value);
+        }
+        value.to_string()
+    }
+}
+
+pub(crate) fn content_length(headers: &HeaderMap) -> Option<u64> {
+    headers.get(CONTENT_LENGTH)?.to_str().ok()?.parse().ok()
+}
+
+/// Pass `body` through untouched, calling `log` with its first `max` bytes and how many bytes have been read
+/// once that many have arrived, or the body ends.
+fn log_prefix<F: FnOnce(&[u8], usize) + Send + 'static>(body: hyper::Body, max: usize, log: F) -> hyper::Body {
+    let chunks = stream::unfold((body, Vec::new(), 0, Some(log)), move |(mut body, mut prefix, mut len, mut log)| async move {
+        let chunk = body.data().await;
+        match &chunk {
+            Some(Ok(bytes)) => {
+                len += bytes.len();
+                prefix.extend_from_slice(&bytes[..bytes.len().min(max - prefix.len())]);
+                if prefix.len() >= max {
+                    if let Some(log) = log.take() {
+                        log(&prefix, len);
+                    }
+                }
+            }
+            Some(Err(_)) => {}
+            None => {
+                if let Some(log) = log.take() {
+                    log(&prefix, len);
+                }
+            }
+        }
+        chunk.map(|chunk| (chunk, (body, prefix, len, log)))
+    });
+    hyper::Body::wrap_stream(chunks)
+}
+
+/// Cut `text` off after at most `max` bytes, at a character boundary, noting the full length.
+fn truncate(mut text: String, max: usize) -> String {
+    if text.len() <= max {
+        return text;
+    }
+    let len = text.len();
+    let end = (0..=max).rev().find(|&i| text.is_char_boundary(i)).unwrap_or_default();
+    text.truncate(end);
+    let _ = write!(text, "... ({len} bytes)");
+    text
+}
+
+#[async_trait]
+impl Middleware for Logger {
+    async fn handle(&self, request: InMemoryRequest, next: Next<'_>) -> ProtocolResult<Response> {
+        let url = request.uri().to_string();
+        let method = request.method().to_string();
+        if self.requests {
+            let headers = self.headers(request.headers());
+            let body = self.body(request.body());
+            event_at!(self.level, method, url, version = ?request.version(), headers, body, "Request");
+        }
+        let start = Instant::now();
+        let res = next.run(request).await;
+        if !self.responses {
+            return res;
+        }
+        let elapsed_ms = start.elapsed().as_millis();
+        match res {
+            Err(e) => {
+                event_at!(self.level, method, url, elapsed_ms, error = %e, "Request failed");
+                Err(e)
+            }
+            Ok(res) => {
+                let (parts, body) = res.into_parts();
+                // Set when a `Retry` runs after this logger.
+                let attempts = parts.extensions.get::<Attempts>().copied();
+                let retry_delay_ms = attempts.map(|a| a.total_delay.as_millis());
+                let attempts = attempts.map(|a| a.count);
+                let body = match (self.stream_over, body) {
+                    (Some(threshold), Body::Hyper(body)) if content_length(&parts.headers).is_none_or(|len| len > threshold) => {
+                        let headers = self.headers(&parts.headers);
+                        event_at!(self.level, method, url, status = parts.status.as_u16(), version = ?parts.version, elapsed_ms, attempts, retry_delay_ms, headers, "Response");
+                        let logger = self.clone();
+                        let content_type = parts.headers.get(CONTENT_TYPE).cloned();
+                        let body = log_prefix(body, self.max_body, move |prefix, len| {
+                            let body = logger.streamed_body(prefix, len, content_type.as_ref());
+                            event_at!(logger.level, url, body, len, "Response body");
+                        });
+                        return Ok(Response::from_parts(parts, body.into()));
+                    }
+                    (_, body) => body,
+                };
+                let body = body.into_content_type(parts.headers.get(CONTENT_TYPE)).await?;
+                let headers = self.headers(&parts.headers);
+                let logged_body = self.body(&body);
+                event_at!(self.level, method, url, status = parts.status.as_u16(), version = ?parts.version, elapsed_ms, attempts, retry_delay_ms, headers, body = logged_body, "Response");
+                Ok(Response::from_parts(parts, body.into()))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use http::header::AUTHORIZATION;
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_logged_values() {
+        let logger = Logger::new().max_body(8);
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, "Bearer secret".parse().unwrap());
+        headers.insert(CONTENT_TYPE, "text/plain".parse().unwrap());
+        assert_eq!(logger.headers(&headers), "authorization: **********, content-type: text/plain");
+        assert_eq!(logger.body(&InMemoryBody::Text("héllo world".into())), "héllo w... (12 bytes)");
+        assert_eq!(logger.body(&InMemoryBody::Bytes(vec![0; 100].into())), "<100 bytes>");
+        let logger = logger.max_body(100);
+        assert_eq!(logger.body(&InMemoryBody::Json(json!({"password": "hunter2"}))), r#"{"password":"**********"}"#);
+        assert!(Logger::new().sanitize(false).headers(&headers).contains("Bearer secret"));
+    }
+
+    #[test]
+    fn test_streamed_body() {
+        let json = HeaderValue::from_static("application/json");
+        let logger = Logger::new().max_body(100);
+        let body = br#"{"password": "hunter2"}"#;
+        assert_eq!(logger.streamed_body(body, body.len(), Some(&json)), r#"{"password":"**********"}"#);
+        assert_eq!(logger.streamed_body(&body[..10], 500, Some(&json)), "<500 bytes>");
+        assert_eq!(logger.clone().sanitize(false).streamed_body(&body[..10], 500, Some(&json)), r#"{"password... (500 bytes)"#);
+        assert_eq!(logger.streamed_body("héllo".as_bytes(), 6, None), "héllo");
+        assert_eq!(logger.streamed_body(&"héllo".as_bytes()[..2], 6, None), "h... (6 bytes)");
+        assert_eq!(logger.streamed_body(&[0xff; 10], 100, None), "<100 bytes>");
+    }
+    #[tokio::test]
+    async fn test_log_prefix() {
+        let logged = Arc::new(Mutex::new(None));
+        let chunks: Vec<Result<&'static [u8], std::io::Error>> = vec![Ok(b"hello "), Ok(b"world"), Ok(b"!")];
+        let body = hyper::Body::wrap_stream(stream::iter(chunks));
+        let l = logged.clone();
+        let body = log_prefix(body, 8, move |prefix, len| *l.lock().unwrap() = Some((prefix.to_vec(), len)));
+        assert!(logged.lock().unwrap().is_none());
+        assert_eq!(hyper::body::to_bytes(body).await.unwrap().as_ref(), b"hello world!");
+        assert_eq!(logged.lock().unwrap().take(), Some((b"hello wo".to_vec(), 11)));
+
+        let mut headers = HeaderMap::new();
+        assert_eq!(content_length(&headers), None);
+        headers.insert(CONTENT_LENGTH, "42".parse().unwrap());
+        assert_eq!(content_length(&headers), Some(42));
+    }
+}
@@ -0,0 +1,237 @@
+use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use http::header::CONTENT_TYPE;
+use http::uri::Authority;
+use http::{HeaderName, Uri};
+use serde_json::Value;
+
+use crate::error::ProtocolResult;
+use crate::middleware::Next;
+use crate::{Body, InMemoryBody, InMemoryRequest, InMemoryResponseExt, Middleware, Response, ResponseExt};
+
+type RequestFn = dyn Fn(InMemoryRequest) -> InMemoryRequest + Send + Sync;
+
+/// Change every request with a function, for one-line transformations that don't need a whole [`Middleware`].
+/// ```
+/// # use httpclient::{Client, MapRequest};
+/// # use serde_json::json;
+/// let client = Client::new()
+///     .with_middleware(MapRequest::rewrite_host("api.staging.example.com", "api.example.com"))
+///     .with_middleware(MapRequest::json(|body| body["source"] = json!("sdk")));
+/// ```
+#[derive(Clone)]
+pub struct MapRequest(Arc<RequestFn>);
+
+impl Debug for MapRequest {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("MapRequest")
+    }
+}
+
+fn with_path(uri: &Uri, path_and_query: &str) -> Uri {
+    let mut parts = uri.clone().into_parts();
+    parts.path_and_query = Some(path_and_query.parse().expect("Rewritten path is valid"));
+    Uri::from_parts(parts).expect("Rewritten URI is valid")
+}
+
+impl MapRequest {
+    pub fn new<F: Fn(InMemoryRequest) -> InMemoryRequest + Send + Sync + 'static>(f: F) -> Self {
+        Self(Arc::new(f))
+    }
+
+    /// Put `prefix` before every request's path, e.g. `/v2` to turn `/users` into `/v2/users`.
+    /// Paths that already start with it are left alone, so a redirect to `/v2/users` isn't sent to `/v2/v2/users`.
+    #[must_use]
+    pub fn path_prefix(prefix: &str) -> Self {
+        let prefix = format!("/{}", prefix.trim_matches('/'));
+        Self::new(move |mut request| {
+            let path = request.uri().path();
+            if path == prefix || path.strip_prefix(prefix.as_str()).is_some_and(|rest| rest.starts_with('/')) {
+                return request;
+            }
+            let path_and_query = request.uri().path_and_query().map_or("/", |p| p.as_str());
+            let path_and_query = if path_and_query == "/" { prefix.clone() } else { format!("{prefix}{path_and_query}") };
+            *request.uri_mut() = with_path(request.uri(), &path_and_query);
+            request
+        })
+    }
+
+    /// Send requests for host `from` to host `to` instead, keeping the scheme, port, path and query.
+    ///
+    /// # Panics
+    /// Panics if `to` isn't a valid host, e.g. if it has a port.
+    #[must_use]
+    pub fn rewrite_host(from: &str, to: &str) -> Self {
+        let from = from.to_ascii_lowercase();
+        let authority = to.parse::<Authority>().unwrap_or_else(|_| panic!("Invalid host `{to}`"));
+        assert!(authority.port().is_none() && !to.contains('@'), "Invalid host `{to}`");
+        let to = to.to_string();
+        Self::new(move |mut request| {
+            if request.uri().host().is_some_and(|h| h.eq_ignore_ascii_case(&from)) {
+                let mut parts = request.uri().clone().into_parts();
+                let authority = match request.uri().port() {
+                    Some(port) => format!("{to}:{port}"),
+                    None => to.clone(),
+                };
+                parts.authority = Some(authority.parse().expect("Rewritten host is valid"));
+                *request.uri_mut() = Uri::from_parts(parts).expect("Rewritten URI is valid");
+            }
+            request
+        })
+    }
+
+    /// Move the values of header `from` to header `to`, e.g. for a proxy that expects a different name.
+    ///
+    /// # Panics
+    /// Panics if either name isn't a valid header name.
+    #[must_use]
+    pub fn rename_header(from: &str, to: &str) -> Self {
+        let from = HeaderName::from_bytes(from.as_bytes()).expect("Invalid header name");
+        let to = HeaderName::from_bytes(to.as_bytes()).expect("Invalid header name");
+        Self::new(move |mut request| {
+            let values: Vec<_> = request.headers().get_all(&from).iter().cloned().collect();
+            request.headers_mut().remove(&from);
+            for v in values {
+                request.headers_mut().append(&to, v);
+            }
+            request
+        })
+    }
+
+    /// Edit JSON request bodies in place. Other bodies are left alone.
+    pub fn json<F: Fn(&mut Value) + Send + Sync + 'static>(f: F) -> Self {
+        Self::new(move |mut request| {
+            if let InMemoryBody::Json(body) = request.body_mut() {
+                f(body);
+            }
+            request
+        })
+    }
+}
+
+#[async_trait]
+impl Middleware for MapRequest {
+    async fn handle(&self, request: InMemoryRequest, next: Next<'_>) -> ProtocolResult<Response> {
+        next.run((self.0)(request)).await
+    }
+}
+
+#[derive(Clone)]
+enum ResponseFn {
+    Response(Arc<dyn Fn(Response) -> Response + Send + Sync>),
+    Json(Arc<dyn Fn(&mut Value) + Send + Sync>),
+}
+
+/// Change every response with a function. See [`MapRequest`] for requests.
+/// ```
+/// # use httpclient::{Client, MapResponse};
+/// let client = Client::new().with_middleware(MapResponse::json(|body| {
+///     // unwrap the API's `{"data": ...}` envelope
+///     *body = body["data"].take();
+/// }));
+/// ```
+#[derive(Clone)]
+pub struct MapResponse(ResponseFn);
+
+impl Debug for MapResponse {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("MapResponse")
+    }
+}
+
+impl MapResponse {
+    /// Map responses as they arrive. Their bodies may still be streaming.
+    pub fn new<F: Fn(Response) -> Response + Send + Sync + 'static>(f: F) -> Self {
+        Self(ResponseFn::Response(Arc::new(f)))
+    }
+
+    /// Edit JSON response bodies in place. Other responses are passed on still streaming.
+    pub fn json<F: Fn(&mut Value) + Send + Sync + 'static>(f: F) -> Self {
+        Self(ResponseFn::Json(Arc::new(f)))
+    }
+}
+
+#[async_trait]
+impl Middleware for MapResponse {
+    async fn handle(&self, request: InMemoryRequest, next: Next<'_>) -> ProtocolResult<Response> {
+        let res = next.run(request).await?;
+        match &self.0 {
+            ResponseFn::Response(f) => Ok(f(res)),
+            ResponseFn::Json(f) => {
+                if !crate::body::is_json(res.headers().get(CONTENT_TYPE)) {
+                    return Ok(res);
+                }
+                let mut res = res.into_memory().await?;
+                if let Some(json) = res.json_value_mut()? {
+                    f(json);
+                }
+                Ok(res.map(Body::InMemory))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::middleware::FakeTransport;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_map() {
+        let transport = FakeTransport::new().respond(
+            http::Response::builder()
+                .header("content-type", "application/json")
+                .body(InMemoryBody::Text(r#"{"data": {"id": 1}}"#.into()))
+                .unwrap(),
+        );
+        let client = crate::Client::new()
+            .with_middleware(MapRequest::path_prefix("/v2/"))
+            .with_middleware(MapRequest::rewrite_host("staging.example.com", "api.example.com"))
+            .with_middleware(MapRequest::rename_header("X-Key", "Authorization"))
+            .with_middleware(MapRequest::json(|body| body["source"] = json!("sdk")))
+            .with_middleware(MapResponse::json(|body| *body = body["data"].take()))
+            .with_middleware(MapResponse::new(|mut res| {
+                res.headers_mut().insert("x-mapped", "1".parse().unwrap());
+                res
+            }))
+            .with_middleware(transport.clone());
+        let res = client
+            .post("https://staging.example.com:8443/users?a=1")
+            .header("x-key", "k")
+            .json(json!({}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.headers()["x-mapped"], "1");
+        assert_eq!(res.json::<Value>().await.unwrap(), json!({"id": 1}));
+
+        let sent = &transport.requests()[0];
+        assert_eq!(sent.uri(), "https://api.example.com:8443/v2/users?a=1");
+        assert_eq!(sent.headers()["authorization"], "k");
+        assert!(!sent.headers().contains_key("x-key"));
+        assert!(matches!(sent.body(), InMemoryBody::Json(v) if *v == json!({"source": "sdk"})));
+    }
+
+    #[test]
+    fn test_path_prefix() {
+        let prefix = MapRequest::path_prefix("v2");
+        let map = |uri: &str| (prefix.0)(http::Request::builder().uri(uri).body(InMemoryBody::Empty).unwrap()).uri().to_string();
+        assert_eq!(map("https://example.com/"), "https://example.com/v2");
+        assert_eq!(map("https://example.com/users?a=1"), "https://example.com/v2/users?a=1");
+        // e.g. a redirect that already has the prefix
+        assert_eq!(map("https://example.com/v2/users"), "https://example.com/v2/users");
+        assert_eq!(map("https://example.com/v2"), "https://example.com/v2");
+        assert_eq!(map("https://example.com/v2beta"), "https://example.com/v2/v2beta");
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid host")]
+    fn test_rewrite_host_invalid() {
+        let _ = MapRequest::rewrite_host("staging.example.com", "api.example.com:8443");
+    }
+}
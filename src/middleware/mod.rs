@@ -1,21 +1,81 @@
 use std::fmt::Debug;
 use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 
 use async_trait::async_trait;
 use cookie::time;
 use cookie::time::format_description::well_known::Rfc2822;
-use http::header::{CONTENT_LENGTH, LOCATION};
-use hyper::body::Bytes;
-use tokio::time::Duration;
+use http::header::LOCATION;
+#[cfg(not(target_arch = "wasm32"))]
+use http::header::{CONTENT_LENGTH, TRANSFER_ENCODING};
+use http::{Extensions, HeaderMap, StatusCode};
+#[cfg(not(target_arch = "wasm32"))]
+use http::{HeaderValue, Method};
+#[cfg(not(target_arch = "wasm32"))]
+use hyper::client::connect::HttpInfo;
+use tracing::debug;
 
+pub use audience::*;
+pub use backoff::*;
+pub use concurrency::*;
+#[cfg(not(target_arch = "wasm32"))]
+pub use content_hash::*;
+pub use failover::*;
+#[cfg(not(target_arch = "wasm32"))]
+pub use field_encryption::*;
+pub use from_fn::*;
+pub use hsts::*;
+pub use idempotency::*;
+#[cfg(not(target_arch = "wasm32"))]
+pub use jwt::*;
+pub use logger::*;
+pub use map::*;
+pub use negative_cache::*;
+pub use openapi::*;
+pub use rate_limit::*;
+#[cfg(feature = "recorder")]
 pub use recorder::*;
+pub use retry_budget::*;
+pub use ssrf::*;
+pub use testing::*;
+pub use timeout::*;
 
-use crate::client::Client;
+pub(crate) use logger::content_length;
+
+#[cfg(target_arch = "wasm32")]
+use crate::client::Fetch;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::client::{idle_read_timeout, ConnectTiming, ConnectionInfo, LocalAddress, TlsInfo};
+use crate::client::{send_custom, Client};
+use crate::clock::{self, Clock, SystemClock};
 use crate::error::{ProtocolError, ProtocolResult};
-use crate::{Body, InMemoryBody, InMemoryRequest, Response, Uri};
+use crate::{status_ext, Body, InMemoryRequest, ReplayableBody, Response, Uri};
 
+mod audience;
+mod backoff;
+mod concurrency;
+#[cfg(not(target_arch = "wasm32"))]
+mod content_hash;
+mod failover;
+#[cfg(not(target_arch = "wasm32"))]
+mod field_encryption;
+mod from_fn;
+mod hsts;
+mod idempotency;
+#[cfg(not(target_arch = "wasm32"))]
+mod jwt;
+mod logger;
+mod map;
+mod negative_cache;
+mod openapi;
+mod rate_limit;
+#[cfg(feature = "recorder")]
 mod recorder;
+mod retry_budget;
+mod ssrf;
+mod testing;
+mod timeout;
 
 pub type MiddlewareStack = Vec<Arc<dyn Middleware>>;
 
@@ -35,61 +95,213 @@ impl Next<'_> {
             middleware.handle(request, next).await
         } else {
             let (mut parts, body) = request.into_parts();
-            let body = match body {
-                InMemoryBody::Empty => Bytes::new(),
-                InMemoryBody::Bytes(b) => Bytes::from(b),
-                InMemoryBody::Text(s) => Bytes::from(s),
-                InMemoryBody::Json(val) => {
-                    let content = serde_json::to_string(&val)?;
-                    Bytes::from(content)
-                },
-            };
-            let len = body.len();
-            parts.headers.entry(CONTENT_LENGTH).or_insert(len.into());
-            let mut b = hyper::Request::builder().method(parts.method.as_str()).uri(parts.uri.to_string());
-            for (k, v) in parts.headers.iter() {
-                b = b.header(k.as_str(), v.to_str().unwrap());
+            if let Some(replayable) = parts.extensions.remove::<ReplayableBody>() {
+                let (body, len) = replayable.body()?;
+                return send_wire(self.client, parts, body, len).await;
             }
-            let request = b.body(hyper::Body::from(body)).expect("Failed to build request");
-            let res = self.client.inner.request(request).await?;
-            let (parts, body) = res.into_parts();
-            let body: Body = body.into();
-            let mut b = Response::builder().status(parts.status.as_u16());
-            for (k, v) in parts.headers.iter() {
-                b = b.header(k.as_str(), v.to_str().unwrap());
+            if let Some(transport) = &self.client.transport {
+                return send_custom(transport.as_ref(), InMemoryRequest::from_parts(parts, body)).await;
             }
-            let res = b.body(body).expect("Failed to build response");
-            Ok(res)
+            let body = body.into_wire_bytes()?;
+            // Middlewares may have changed the body after setting Content-Length, so always recompute it.
+            let len = body.len() as u64;
+            send_wire(self.client, parts, hyper::Body::from(body), Some(len)).await
+        }
+    }
+}
+
+/// Send a request over the wire, skipping middlewares. `len` is the body length, or `None` to send it chunked.
+/// With a custom transport, the body is read into memory and the request goes to the transport instead.
+pub(crate) async fn send_wire(client: &Client, parts: http::request::Parts, body: hyper::Body, len: Option<u64>) -> ProtocolResult<Response> {
+    if let Some(transport) = &client.transport {
+        let body = Body::Hyper(body).into_memory().await?;
+        return send_custom(transport.as_ref(), InMemoryRequest::from_parts(parts, body)).await;
+    }
+    send_network(client, parts, body, len).await
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+async fn send_network(client: &Client, mut parts: http::request::Parts, body: hyper::Body, len: Option<u64>) -> ProtocolResult<Response> {
+    set_framing(&parts.method, &mut parts.headers, len);
+    let inner = client.hyper_client(parts.extensions.get::<LocalAddress>().copied())?;
+    let request_extensions = std::mem::take(&mut parts.extensions);
+    let mut b = hyper::Request::builder().method(parts.method.as_str()).uri(parts.uri.to_string());
+    for (k, v) in parts.headers.iter() {
+        b = b.header(k.as_str(), v.to_str().unwrap());
+    }
+    let request = b.body(body).expect("Failed to build request");
+    let in_flight = client.pool_metrics().in_flight(&parts.uri);
+    let timeouts = client.timeouts_for(&parts.uri);
+    let res = match timeouts.first_byte {
+        Some(timeout) => tokio::time::timeout(timeout, inner.request(request)).await.map_err(|_| ProtocolError::Timeout)??,
+        None => inner.request(request).await?,
+    };
+    let (parts, body) = res.into_parts();
+    let body: Body = match timeouts.idle_read {
+        Some(timeout) => idle_read_timeout(body, timeout).into(),
+        None => body.into(),
+    };
+    let mut b = Response::builder().status(parts.status.as_u16());
+    for (k, v) in parts.headers.iter() {
+        b = b.header(k.as_str(), v.to_str().unwrap());
+    }
+    if let Some(http) = parts.extensions.get::<HttpInfo>() {
+        let timing = parts.extensions.get::<ConnectTiming>().copied().unwrap_or_default();
+        in_flight.record_remote_addr(http.remote_addr());
+        b = b.extension(ConnectionInfo {
+            remote_addr: http.remote_addr(),
+            local_addr: http.local_addr(),
+            resolve_time: timing.resolve_time,
+            connect_time: timing.connect_time,
+        });
+    }
+    if let Some(tls) = parts.extensions.get::<TlsInfo>() {
+        b = b.extension(tls.clone());
+    }
+    let mut res = b.body(body).expect("Failed to build response");
+    copy_extensions(request_extensions, &mut res);
+    Ok(res)
+}
+
+/// Send a request with the JavaScript host's `fetch`, which sets the framing headers itself.
+#[cfg(target_arch = "wasm32")]
+async fn send_network(client: &Client, parts: http::request::Parts, body: hyper::Body, _len: Option<u64>) -> ProtocolResult<Response> {
+    let timeout = client.timeouts_for(&parts.uri).first_byte;
+    let body = Body::Hyper(body).into_memory().await?;
+    let send = send_custom(&Fetch, InMemoryRequest::from_parts(parts, body));
+    match timeout {
+        Some(timeout) => clock::timeout(timeout, send).await.map_err(|_| ProtocolError::Timeout)?,
+        None => send.await,
+    }
+}
+
+/// Give `res` the request's extensions, keeping any of its own values of the same type.
+pub(crate) fn copy_extensions<B>(request: Extensions, res: &mut http::Response<B>) {
+    let own = std::mem::replace(res.extensions_mut(), request);
+    res.extensions_mut().extend(own);
+}
+
+/// Set the message framing headers for a body of `len` bytes, or of unknown length if `None`.
+/// Known lengths get `Content-Length`, unknown lengths get `Transfer-Encoding: chunked`, never both.
+/// Bodyless requests whose method doesn't expect a body (e.g. GET) get neither.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn set_framing(method: &Method, headers: &mut HeaderMap, len: Option<u64>) {
+    match len {
+        Some(0) if matches!(*method, Method::GET | Method::HEAD | Method::DELETE | Method::OPTIONS | Method::TRACE | Method::CONNECT) => {
+            headers.remove(CONTENT_LENGTH);
+            headers.remove(TRANSFER_ENCODING);
+        }
+        Some(len) => {
+            headers.remove(TRANSFER_ENCODING);
+            headers.insert(CONTENT_LENGTH, len.into());
+        }
+        None => {
+            headers.remove(CONTENT_LENGTH);
+            headers.insert(TRANSFER_ENCODING, HeaderValue::from_static("chunked"));
         }
     }
 }
 
+/// A step in the request pipeline, run in the order middlewares were added to the client.
+///
+/// # Extensions
+/// Middlewares can pass typed values to each other, and to the caller, through extensions
+/// ([`crate::RequestExt::ext_mut`], [`crate::ResponseExt::ext`]):
+/// - A value inserted into the request is seen by every later middleware, including on retries and redirects,
+///   which resend a clone of the request.
+/// - The transport copies the request's extensions onto the response, so the caller can read them back. Values
+///   the transport sets itself, like [`crate::ConnectionInfo`], take precedence.
+/// - A value inserted into the response is seen by every earlier middleware and by the caller, and is kept when the
+///   body is read into memory and when a 4xx/5xx becomes [`crate::Error::HttpError`].
+/// - Responses served by [`Recorder`] or [`NegativeCache`] without reaching the transport carry only what was
+///   stored with them: nothing for recordings, the original response's extensions for cached 404s.
+///
+/// Values must be `Clone + Send + Sync + 'static`; use a crate-private type as the key to avoid collisions.
+///
+/// # Streamed bodies
+/// A request sent with [`crate::RequestBuilder::replayable_body`] reaches middlewares with an empty body; the
+/// transport makes the stream when the request goes out. A middleware that may call `next.run` more than once
+/// must say so with [`Middleware::replays_requests`], so single-use streams are refused before anything is sent.
 #[async_trait]
 pub trait Middleware: Send + Sync + Debug {
     async fn handle(&self, request: InMemoryRequest, next: Next<'_>) -> ProtocolResult<Response> {
         next.run(request).await
     }
+
+    /// Whether this middleware may send `request` more than once, e.g. to retry it. Defaults to `false`.
+    fn replays_requests(&self, _request: &InMemoryRequest) -> bool {
+        false
+    }
+
+    /// Whether this middleware follows redirects, sending each hop through the middlewares after it, like [`Follow`].
+    /// Defaults to `false`.
+    fn follows_redirects(&self) -> bool {
+        false
+    }
+
+    /// Whether this middleware retries failed requests, like [`Retry`]. A host's [`crate::Profile`] with one
+    /// replaces the client's instead of nesting inside it. Defaults to `false`.
+    fn retries_requests(&self) -> bool {
+        false
+    }
+
+    /// Whether this middleware checks each request it's given, so it belongs after [`Follow`] to check every
+    /// redirect hop too, like [`SsrfGuard`] and [`Hsts`]. A middleware that follows redirects, added to a client
+    /// later, goes before it. Defaults to `false`.
+    fn checks_redirect_hops(&self) -> bool {
+        false
+    }
+
+    /// The HTTPS policy this middleware enforces, like [`Hsts`]. [`crate::Client::https_only`] replaces it, keeping
+    /// the hosts it has learned. Defaults to `None`.
+    fn hsts_policy(&self) -> Option<&Hsts> {
+        None
+    }
+
+    /// A guard the client's connector also applies to the addresses it dials, like [`SsrfGuard`]'s.
+    /// Defaults to `None`.
+    fn guards_connections(&self) -> Option<SsrfGuard> {
+        None
+    }
 }
 
 #[derive(Debug)]
 /// Retry a request up to N times, with a default of 3.
-/// The default back-off delay is 2 seconds.
+/// Unless the server sends `Retry-After`, the delay doubles after every attempt, starting at 200ms.
 pub struct Retry {
     max_retries: usize,
-    backoff_delay: Duration,
+    backoff: Arc<dyn BackoffStrategy>,
     // empty vec will retry the default set
     retry_codes: Vec<u16>,
+    // per-attempt limit on waiting for response headers
+    header_timeout: Option<Duration>,
+    budget: Option<RetryBudget>,
+    clock: Arc<dyn Clock>,
+}
+
+/// Request extension that makes [`Retry`] and [`Failover`] send the request only once. Set it with `RequestBuilder::no_retry`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoRetry;
+
+/// How many attempts [`Retry`] made, and how long it waited between them in total.
+/// Recorded in the extensions of the response it returns: `res.ext().get::<Attempts>()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Attempts {
+    pub count: usize,
+    pub total_delay: Duration,
 }
 
-fn calc_delay(res: &Response) -> Option<Duration> {
-    let v = res.headers().get(http::header::RETRY_AFTER)?;
-    let retry_after = v.to_str().unwrap();
+const DEFAULT_RETRY_CODES: [u16; 3] = [StatusCode::TOO_MANY_REQUESTS.as_u16(), StatusCode::REQUEST_TIMEOUT.as_u16(), status_ext::TOO_EARLY.as_u16()];
+
+/// The wait a `Retry-After` header asks for, given in seconds or as an HTTP date. Dates in the past are no wait.
+pub(crate) fn retry_after(headers: &HeaderMap, now: std::time::SystemTime) -> Option<Duration> {
+    let retry_after = headers.get(http::header::RETRY_AFTER)?.to_str().ok()?.trim();
 
     if let Ok(retry_after) = retry_after.parse() {
         Some(Duration::from_secs(retry_after))
     } else if let Ok(dt) = time::OffsetDateTime::parse(retry_after, &Rfc2822) {
-        let dur = dt - time::OffsetDateTime::now_utc();
-        Some(dur.try_into().unwrap())
+        let dur = dt - time::OffsetDateTime::from(now);
+        Some(dur.try_into().unwrap_or_default())
     } else {
         None
     }
@@ -98,9 +310,12 @@ fn calc_delay(res: &Response) -> Option<Duration> {
 impl Default for Retry {
     fn default() -> Self {
         Self {
-            backoff_delay: Duration::from_secs(2),
+            backoff: Arc::new(Exponential::new(Duration::from_millis(200))),
             max_retries: 3,
             retry_codes: Vec::new(),
+            header_timeout: None,
+            budget: None,
+            clock: Arc::new(SystemClock),
         }
     }
 }
@@ -110,9 +325,23 @@ impl Retry {
         Self::default()
     }
 
-    /// Set the back-off delay between retries, if the server doesn't specify a delay.
-    pub fn backoff_delay(mut self, delay: Duration) -> Self {
-        self.backoff_delay = delay;
+    /// Set the initial back-off delay between retries, if the server doesn't specify a delay.
+    /// The delay doubles after every attempt.
+    #[must_use]
+    pub fn backoff_delay(self, delay: Duration) -> Self {
+        self.backoff(Exponential::new(delay))
+    }
+
+    /// Set how the delay between retries grows, if the server doesn't specify a delay.
+    /// ```
+    /// # use std::time::Duration;
+    /// # use httpclient::middleware::Exponential;
+    /// # use httpclient::Retry;
+    /// let retry = Retry::new().backoff(Exponential::new(Duration::from_millis(100)).max(Duration::from_secs(5)).jitter());
+    /// ```
+    #[must_use]
+    pub fn backoff<S: BackoffStrategy + 'static>(mut self, strategy: S) -> Self {
+        self.backoff = Arc::new(strategy);
         self
     }
 
@@ -126,110 +355,131 @@ impl Retry {
         self.retry_codes = codes;
         self
     }
+
+    /// Abandon and retry an attempt if its response headers haven't arrived within `timeout`.
+    /// Only the wait for headers is limited, so an established stream (e.g. SSE) is never interrupted.
+    #[must_use]
+    pub fn header_timeout(mut self, timeout: Duration) -> Self {
+        self.header_timeout = Some(timeout);
+        self
+    }
+
+    /// Only retry while `budget` allows it. Share one budget across the client's `Retry` middlewares. See [`RetryBudget`].
+    #[must_use]
+    pub fn budget(mut self, budget: RetryBudget) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+
+    /// Wait between attempts, and resolve `Retry-After` dates, with this clock instead of the system time,
+    /// e.g. a [`crate::clock::TestClock`], which skips the waits.
+    #[must_use]
+    pub fn clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Whether the budget refuses another attempt after attempt `i` failed.
+    fn budget_exhausted(&self, i: usize) -> bool {
+        i < self.max_retries && self.budget.as_ref().is_some_and(|budget| !budget.withdraw())
+    }
 }
 
 #[async_trait]
 impl Middleware for Retry {
     async fn handle(&self, request: InMemoryRequest, next: Next<'_>) -> ProtocolResult<Response> {
+        if request.extensions().get::<NoRetry>().is_some() {
+            return next.run(request).await;
+        }
         let mut i = 0usize;
-        let mut delay = Duration::from_millis(100); // Initial delay
+        let mut delay = Duration::ZERO;
+        let mut total_delay = Duration::ZERO;
+        if let Some(budget) = &self.budget {
+            budget.deposit();
+        }
 
         loop {
             i += 1;
             if i > self.max_retries {
                 return Err(ProtocolError::TooManyRetries);
             }
-            match next.run(request.clone()).await {
-                Ok(res) => {
+            let attempt = next.run(request.clone());
+            let result = match self.header_timeout.or(next.client.timeouts_for(request.uri()).per_try) {
+                Some(timeout) => clock::timeout(timeout, attempt).await.ok(),
+                None => Some(attempt.await),
+            };
+            match result {
+                None | Some(Err(ProtocolError::Timeout)) if self.budget_exhausted(i) => return Err(ProtocolError::Timeout),
+                None | Some(Err(ProtocolError::Timeout)) => {
+                    debug!(attempt = i, "Timed out waiting for response headers");
+                    delay = self.backoff.delay(u32::try_from(i).unwrap_or(u32::MAX), delay);
+                    total_delay += delay;
+                    self.clock.sleep(delay).await;
+                }
+                Some(Ok(mut res)) => {
+                    res.extensions_mut().insert(Attempts { count: i, total_delay });
                     let status = res.status();
                     let status_as_u16 = status.as_u16();
 
-                    // Can't use StatusCode here, as it doesn't implement 425/TOO_EARLY
                     let mut retry_codes = self.retry_codes.as_slice();
                     if retry_codes.is_empty() {
-                        retry_codes = &[429, 408, 425];
+                        retry_codes = &DEFAULT_RETRY_CODES;
                     }
                     if !(retry_codes.contains(&status_as_u16) || status.is_server_error()) {
                         return Ok(res);
                     }
+                    if self.budget_exhausted(i) {
+                        return Ok(res);
+                    }
 
-                    if let Some(custom_delay) = calc_delay(&res) {
+                    if let Some(custom_delay) = retry_after(res.headers(), self.clock.now()) {
                         delay = custom_delay;
                     } else {
-                        delay *= 2; // Exponential back-off
+                        delay = self.backoff.delay(u32::try_from(i).unwrap_or(u32::MAX), delay);
                     }
 
-                    tokio::time::sleep(delay).await;
+                    total_delay += delay;
+                    self.clock.sleep(delay).await;
                 }
-                Err(err) => return Err(err),
+                Some(Err(err)) => return Err(err),
             }
         }
     }
-}
-
-#[derive(Debug)]
-pub struct Logger;
-
-fn headers_to_string(headers: &http::HeaderMap, dir: char) -> String {
-    headers.iter().map(|(k, v)| format!("{dir} {}: {}", k, v.to_str().unwrap())).collect::<Vec<_>>().join("\n")
-}
+    fn replays_requests(&self, request: &InMemoryRequest) -> bool {
+        request.extensions().get::<NoRetry>().is_none()
+    }
 
-#[async_trait]
-impl Middleware for Logger {
-    async fn handle(&self, request: InMemoryRequest, next: Next<'_>) -> ProtocolResult<Response> {
-        let url = request.uri().to_string();
-        let method = request.method().as_str().to_uppercase();
-        let version = request.version();
-        let headers = headers_to_string(request.headers(), '>');
-        let body = request.body();
-        println!(
-            ">>> Request:
-> {method} {url} {version:?}
-{headers}"
-        );
-        if !body.is_empty() {
-            match body {
-                InMemoryBody::Text(s) => println!("{s}"),
-                InMemoryBody::Json(o) => println!("{}", serde_json::to_string(&o).unwrap()),
-                _ => println!("{body:?}"),
-            }
-        }
-        let res = next.run(request).await;
-        match res {
-            Err(e) => {
-                println!("<<< Response to {url}:\n{e}");
-                Err(e)
-            }
-            Ok(res) => {
-                let version = res.version();
-                let status = res.status();
-                let headers = headers_to_string(res.headers(), '<');
-                println!(
-                    "<<< Response to {url}:
-< {version:?} {status}
-{headers}"
-                );
-                let (parts, body) = res.into_parts();
-                let content_type = parts.headers.get(http::header::CONTENT_TYPE);
-                let body = body.into_content_type(content_type).await?;
-                match &body {
-                    InMemoryBody::Text(text) => println!("{text}"),
-                    InMemoryBody::Json(o) => println!("{}", serde_json::to_string(&o).unwrap()),
-                    _ => println!("{body:?}"),
-                }
-                let res = Response::from_parts(parts, body.into());
-                Ok(res)
-            }
-        }
+    fn retries_requests(&self) -> bool {
+        true
     }
 }
 
 #[derive(Debug, Clone)]
-/// Follow redirects.
+/// Follow redirects. The response records where they led: see [`RedirectHistory`].
 pub struct Follow;
 
+/// Request extension that makes [`Follow`] return redirects as is. Set it with `RequestBuilder::no_follow`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoFollow;
+
+/// A redirect [`Follow`] followed: the URL that was requested, and the 3xx status it answered with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Redirect {
+    pub uri: Uri,
+    pub status: StatusCode,
+}
+
+/// The redirects [`Follow`] followed, in order, and the URL of the response they led to. Recorded in the extensions
+/// of the response it returns, even when there were no redirects. See [`crate::ResponseExt::final_url`] and
+/// [`crate::ResponseExt::redirect_history`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RedirectHistory {
+    pub redirects: Vec<Redirect>,
+    pub final_url: Uri,
+}
+
 /// Given an original Url, redirect to the new path.
-fn fix_url(original: &Uri, redirect_url: &str) -> Uri {
+pub(crate) fn fix_url(original: &Uri, redirect_url: &str) -> Uri {
     let url = Uri::from_str(redirect_url).unwrap();
     let mut parts = url.into_parts();
     if parts.authority.is_none() {
@@ -244,8 +494,13 @@ fn fix_url(original: &Uri, redirect_url: &str) -> Uri {
 #[async_trait]
 impl Middleware for Follow {
     async fn handle(&self, request: InMemoryRequest, next: Next<'_>) -> ProtocolResult<Response> {
+        if request.extensions().get::<NoFollow>().is_some() {
+            return next.run(request).await;
+        }
         let mut res = next.run(request.clone()).await?;
         let mut allowed_redirects = 10;
+        let mut redirects = Vec::new();
+        let mut final_url = request.uri().clone();
         while res.status().is_redirection() {
             if allowed_redirects == 0 {
                 return Err(ProtocolError::TooManyRedirects);
@@ -257,19 +512,186 @@ impl Middleware for Follow {
                 .to_str()
                 .unwrap();
             let url = fix_url(request.uri(), redirect);
+            redirects.push(Redirect {
+                uri: std::mem::replace(&mut final_url, url.clone()),
+                status: res.status(),
+            });
             let mut request: InMemoryRequest = request.clone();
             *request.uri_mut() = url;
             allowed_redirects -= 1;
             res = next.run(request).await?;
         }
+        res.extensions_mut().insert(RedirectHistory { redirects, final_url });
         Ok(res)
     }
+    fn replays_requests(&self, request: &InMemoryRequest) -> bool {
+        request.extensions().get::<NoFollow>().is_none()
+    }
+
+    fn follows_redirects(&self) -> bool {
+        true
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use crate::{InMemoryBody, InMemoryResponse, InMemoryResponseExt, RequestExt, ResponseExt};
+
     use super::*;
 
+    /// Hangs before sending headers on the first attempt, then responds.
+    #[derive(Debug, Default)]
+    struct HangsOnce(AtomicUsize);
+
+    #[async_trait]
+    impl Middleware for HangsOnce {
+        async fn handle(&self, _request: InMemoryRequest, _next: Next<'_>) -> ProtocolResult<Response> {
+            if self.0.fetch_add(1, Ordering::SeqCst) == 0 {
+                futures::future::pending::<()>().await;
+            }
+            Ok(Response::new(Body::default()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retry_header_timeout() {
+        let client = Client::new();
+        let hangs = Arc::new(HangsOnce::default());
+        let middlewares: Vec<Arc<dyn Middleware>> = vec![Arc::new(Retry::new().header_timeout(Duration::from_millis(10))), hangs.clone()];
+        let next = Next {
+            client: &client,
+            middlewares: &middlewares,
+        };
+        let request = http::Request::builder().uri("http://example.com/").body(InMemoryBody::Empty).unwrap();
+        let res = next.run(request).await.unwrap();
+        assert_eq!(res.status(), http::StatusCode::OK);
+        assert_eq!(hangs.0.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_retry_budget_exhausted() {
+        let transport = FakeTransport::new()
+            .respond_with(StatusCode::SERVICE_UNAVAILABLE, InMemoryBody::Empty)
+            .respond_with(StatusCode::SERVICE_UNAVAILABLE, InMemoryBody::Empty)
+            .respond_with(StatusCode::SERVICE_UNAVAILABLE, InMemoryBody::Empty);
+        let budget = RetryBudget::new(0.0).min_per_second(1);
+        let retry = Retry::new().backoff_delay(Duration::ZERO).budget(budget.clone());
+        let res = MiddlewareTester::new(retry)
+            .with_transport(transport.clone())
+            .run(InMemoryRequest::default())
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(transport.requests().len(), 2);
+        assert_eq!(res.ext().get::<Attempts>().map(|a| a.count), Some(2));
+        assert_eq!((budget.retries(), budget.rejected()), (1, 1));
+    }
+
+    #[tokio::test]
+    async fn test_retry_test_clock() {
+        use crate::clock::TestClock;
+
+        let start = std::time::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let clock = TestClock::at(start);
+        let mut retry_at = InMemoryResponse::new(InMemoryBody::Empty);
+        *retry_at.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+        let date = time::OffsetDateTime::from(start + Duration::from_secs(90)).format(&Rfc2822).unwrap();
+        retry_at.headers_mut().insert(http::header::RETRY_AFTER, HeaderValue::from_str(&date).unwrap());
+        let transport = FakeTransport::new().respond_with(StatusCode::SERVICE_UNAVAILABLE, InMemoryBody::Empty).respond(retry_at);
+        let retry = Retry::new().backoff_delay(Duration::from_secs(60)).clock(clock.clone());
+        let tester = MiddlewareTester::new(retry).with_transport(transport);
+        let res = tokio::time::timeout(Duration::from_secs(1), tester.run(InMemoryRequest::default()))
+            .await
+            .expect("The test clock skips waits")
+            .unwrap();
+        let attempts = res.ext().get::<Attempts>().copied().unwrap();
+        assert_eq!((attempts.count, attempts.total_delay), (3, Duration::from_secs(90)));
+        assert_eq!(clock.now(), start + Duration::from_secs(90));
+    }
+
+    #[tokio::test]
+    async fn test_redirect_history() {
+        let redirect = |status: StatusCode, location: &str| {
+            let mut res = InMemoryResponse::new(InMemoryBody::Empty);
+            *res.status_mut() = status;
+            res.headers_mut().insert(LOCATION, HeaderValue::from_str(location).unwrap());
+            res
+        };
+        let transport = FakeTransport::new()
+            .respond(redirect(StatusCode::FOUND, "/b"))
+            .respond(redirect(StatusCode::MOVED_PERMANENTLY, "https://other.com/c"));
+        let request = http::Request::builder().uri("https://example.com/a").body(InMemoryBody::Empty).unwrap();
+        let res = MiddlewareTester::new(Follow).with_transport(transport).run(request).await.unwrap();
+        assert_eq!(res.final_url(), Some(&Uri::from_static("https://other.com/c")));
+        let history: Vec<(String, u16)> = res.redirect_history().iter().map(|r| (r.uri.to_string(), r.status.as_u16())).collect();
+        assert_eq!(history, [("https://example.com/a".to_string(), 302), ("https://example.com/b".to_string(), 301)]);
+    }
+
+    #[tokio::test]
+    async fn test_no_retry_no_follow() {
+        let transport = FakeTransport::new().respond_with(StatusCode::SERVICE_UNAVAILABLE, InMemoryBody::Empty);
+        let request = http::Request::builder().extension(NoRetry).body(InMemoryBody::Empty).unwrap();
+        let res = MiddlewareTester::new(Retry::new()).with_transport(transport.clone()).run(request).await.unwrap();
+        assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(transport.requests().len(), 1);
+
+        let mut found = InMemoryResponse::new(InMemoryBody::Empty);
+        *found.status_mut() = StatusCode::FOUND;
+        found.headers_mut().insert(LOCATION, HeaderValue::from_static("/b"));
+        let transport = FakeTransport::new().respond(found);
+        let request = http::Request::builder().uri("https://example.com/a").extension(NoFollow).body(InMemoryBody::Empty).unwrap();
+        let res = MiddlewareTester::new(Follow).with_transport(transport.clone()).run(request).await.unwrap();
+        assert_eq!(res.status(), StatusCode::FOUND);
+        assert_eq!(transport.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_extensions() {
+        #[derive(Debug, Clone, PartialEq)]
+        struct Tenant(u32);
+        #[derive(Debug, Clone, PartialEq)]
+        struct Seen(u32);
+
+        let transport = FakeTransport::new().respond_with(StatusCode::SERVICE_UNAVAILABLE, InMemoryBody::Empty);
+        let client = Client::new()
+            .with_middleware(crate::middleware_fn(|mut request, next| async move {
+                request.ext_mut().insert(Tenant(7));
+                next.run(request).await
+            }))
+            .with_middleware(Retry::new().backoff_delay(Duration::ZERO))
+            .with_middleware(crate::middleware_fn(|request, next| async move {
+                let tenant = request.ext().get::<Tenant>().expect("Tenant set by an earlier middleware").0;
+                let mut res = next.run(request).await?;
+                res.ext_mut().insert(Seen(tenant));
+                Ok(res)
+            }))
+            .with_middleware(transport.clone());
+        let res = client.get("https://example.com/").await.unwrap();
+        assert_eq!(res.ext().get::<Tenant>(), Some(&Tenant(7)));
+        assert_eq!(res.ext().get::<Seen>(), Some(&Seen(7)));
+        assert_eq!(transport.requests().len(), 2);
+    }
+
+    #[test]
+    fn test_set_framing() {
+        let mut headers = HeaderMap::new();
+        headers.insert(TRANSFER_ENCODING, HeaderValue::from_static("chunked"));
+        set_framing(&Method::POST, &mut headers, Some(5));
+        assert_eq!(headers[CONTENT_LENGTH], "5");
+        assert!(!headers.contains_key(TRANSFER_ENCODING));
+
+        set_framing(&Method::POST, &mut headers, None);
+        assert_eq!(headers[TRANSFER_ENCODING], "chunked");
+        assert!(!headers.contains_key(CONTENT_LENGTH));
+
+        set_framing(&Method::GET, &mut headers, Some(0));
+        assert!(headers.is_empty());
+        set_framing(&Method::POST, &mut headers, Some(0));
+        assert_eq!(headers[CONTENT_LENGTH], "0");
+    }
+
     #[test]
     fn test_relative_route() {
         let original = Uri::from_str("https://www.google.com/").unwrap();
@@ -0,0 +1,175 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use async_trait::async_trait;
+use http::{Method, StatusCode};
+use tracing::debug;
+
+use crate::clock::{elapsed, Clock, SystemClock};
+use crate::error::ProtocolResult;
+use crate::middleware::Next;
+use crate::{Body, InMemoryRequest, InMemoryResponse, Middleware, Response, ResponseExt};
+
+/// Cache `404 Not Found` responses to `GET` and `HEAD` requests for a short time, for paths
+/// matching one of the configured patterns. Useful for discovery endpoints that 404 until a resource exists.
+///
+/// Patterns match the request path; `*` matches any run of characters, e.g. `/services/*/instances`.
+/// The cache is shared by clones of the middleware, so keep a clone to invalidate entries:
+/// ```
+/// # use std::time::Duration;
+/// # use httpclient::{Client, NegativeCache, Uri};
+/// # fn f(uri: Uri) {
+/// let cache = NegativeCache::new(Duration::from_secs(5)).path("/registry/*");
+/// let client = Client::new().with_middleware(cache.clone());
+/// // ...after registering the resource
+/// cache.invalidate(&uri);
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct NegativeCache {
+    ttl: Duration,
+    patterns: Vec<String>,
+    entries: Arc<Mutex<HashMap<String, (SystemTime, InMemoryResponse)>>>,
+    clock: Arc<dyn Clock>,
+}
+
+/// Match `path` against a pattern where `*` matches any run of characters.
+fn matches(pattern: &str, path: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = path.strip_prefix(first) else {
+        return false;
+    };
+    let parts = parts.collect::<Vec<_>>();
+    let Some((last, middle)) = parts.split_last() else {
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+impl NegativeCache {
+    #[must_use]
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            patterns: Vec::new(),
+            entries: Arc::default(),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Measure entry age with this clock instead of the system time, e.g. a [`crate::clock::TestClock`].
+    #[must_use]
+    pub fn clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Cache 404s for paths matching `pattern`.
+    #[must_use]
+    pub fn path(mut self, pattern: &str) -> Self {
+        self.patterns.push(pattern.to_string());
+        self
+    }
+
+    fn applies_to(&self, request: &InMemoryRequest) -> bool {
+        (request.method() == Method::GET || request.method() == Method::HEAD) && self.patterns.iter().any(|p| matches(p, request.uri().path()))
+    }
+
+    fn key(request: &InMemoryRequest) -> String {
+        format!("{} {}", request.method(), request.uri())
+    }
+
+    fn lookup(&self, key: &str) -> Option<InMemoryResponse> {
+        let mut entries = self.entries.lock().expect("Negative cache lock poisoned");
+        match entries.get(key) {
+            Some((cached_at, res)) if elapsed(self.clock.as_ref(), *cached_at) < self.ttl => Some(res.clone()),
+            Some(_) => {
+                entries.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Forget the cached 404s for `uri`.
+    pub fn invalidate(&self, uri: &http::Uri) {
+        let uri = uri.to_string();
+        let mut entries = self.entries.lock().expect("Negative cache lock poisoned");
+        entries.retain(|key, _| key.split_once(' ').map(|(_, u)| u) != Some(uri.as_str()));
+    }
+
+    /// Forget all cached 404s.
+    pub fn invalidate_all(&self) {
+        self.entries.lock().expect("Negative cache lock poisoned").clear();
+    }
+}
+
+#[async_trait]
+impl Middleware for NegativeCache {
+    async fn handle(&self, request: InMemoryRequest, next: Next<'_>) -> ProtocolResult<Response> {
+        if !self.applies_to(&request) {
+            return next.run(request).await;
+        }
+        let key = Self::key(&request);
+        if let Some(cached) = self.lookup(&key) {
+            debug!(url = request.uri().to_string(), "Using cached 404 response");
+            let (parts, body) = cached.into_parts();
+            return Ok(Response::from_parts(parts, Body::InMemory(body)));
+        }
+        let res = next.run(request).await?;
+        if res.status() != StatusCode::NOT_FOUND {
+            return Ok(res);
+        }
+        let res = res.into_memory().await?;
+        self.entries.lock().expect("Negative cache lock poisoned").insert(key, (self.clock.now(), res.clone()));
+        let (parts, body) = res.into_parts();
+        Ok(Response::from_parts(parts, Body::InMemory(body)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::HeaderMap;
+
+    use crate::clock::TestClock;
+    use crate::{InMemoryBody, InMemoryResponseExt, Request};
+
+    use super::*;
+
+    #[test]
+    fn test_matches() {
+        assert!(matches("/services/*/instances", "/services/foo/instances"));
+        assert!(matches("/registry/*", "/registry/a/b"));
+        assert!(matches("/health", "/health"));
+        assert!(!matches("/health", "/health/live"));
+        assert!(!matches("/services/*/instances", "/services/foo/bar"));
+    }
+
+    #[test]
+    fn test_lookup_and_invalidate() {
+        let clock = TestClock::new();
+        let cache = NegativeCache::new(Duration::from_secs(60)).path("/registry/*").clock(clock.clone());
+        let request: InMemoryRequest = Request::builder().uri("https://example.com/registry/a").body(InMemoryBody::Empty).unwrap();
+        assert!(cache.applies_to(&request));
+        let key = NegativeCache::key(&request);
+        let res = <InMemoryResponse as InMemoryResponseExt>::new(StatusCode::NOT_FOUND, HeaderMap::new(), InMemoryBody::Empty);
+        cache.entries.lock().unwrap().insert(key.clone(), (clock.now(), res.clone()));
+        assert!(cache.clone().lookup(&key).is_some());
+        cache.invalidate(request.uri());
+        assert!(cache.lookup(&key).is_none());
+
+        cache.entries.lock().unwrap().insert(key.clone(), (clock.now(), res));
+        clock.advance(Duration::from_secs(59));
+        assert!(cache.lookup(&key).is_some());
+        clock.advance(Duration::from_secs(1));
+        assert!(cache.lookup(&key).is_none());
+    }
+}
@@ -0,0 +1,328 @@
+use async_trait::async_trait;
+use http::header::CONTENT_TYPE;
+use http::{HeaderName, Method, Uri};
+use serde_json::Value;
+use tracing::warn;
+
+use crate::body::media_type;
+use crate::error::{ProtocolError, ProtocolResult};
+use crate::middleware::Next;
+use crate::{InMemoryRequest, Middleware, Response};
+
+const METHODS: [&str; 8] = ["get", "put", "post", "delete", "options", "head", "patch", "trace"];
+
+#[derive(Debug, Clone)]
+enum Segment {
+    Literal(String),
+    Param,
+}
+
+#[derive(Debug, Clone)]
+struct Operation {
+    method: Method,
+    path: String,
+    segments: Vec<Segment>,
+    required_query: Vec<String>,
+    required_headers: Vec<HeaderName>,
+    body_required: bool,
+    /// Media types the request body may have. Empty if the operation doesn't describe a body.
+    content_types: Vec<String>,
+}
+
+impl Operation {
+    fn matches_path(&self, segments: &[&str]) -> bool {
+        self.segments.len() == segments.len()
+            && self.segments.iter().zip(segments).all(|(s, p)| match s {
+                Segment::Literal(l) => l == p,
+                Segment::Param => !p.is_empty(),
+            })
+    }
+
+    /// What's wrong with `request` according to this operation.
+    fn problems(&self, request: &InMemoryRequest) -> Vec<String> {
+        let mut problems = Vec::new();
+        let query: Vec<&str> = request
+            .uri()
+            .query()
+            .unwrap_or_default()
+            .split('&')
+            .map(|p| p.split('=').next().unwrap_or_default())
+            .collect();
+        for name in &self.required_query {
+            if !query.contains(&name.as_str()) {
+                problems.push(format!("missing required query parameter `{name}`"));
+            }
+        }
+        for name in &self.required_headers {
+            if !request.headers().contains_key(name) {
+                problems.push(format!("missing required header `{name}`"));
+            }
+        }
+        let has_body = !request.body().is_empty();
+        if self.body_required && !has_body {
+            problems.push("missing required request body".to_string());
+        }
+        if has_body && !self.content_types.is_empty() {
+            let content_type = media_type(request.headers().get(CONTENT_TYPE)).unwrap_or_default().trim().to_ascii_lowercase();
+            if !self.content_types.iter().any(|t| media_type_matches(t, &content_type)) {
+                problems.push(format!("content type `{content_type}` isn't one of {}", self.content_types.join(", ")));
+            }
+        }
+        problems
+    }
+}
+
+/// Match a media type against a spec's media type range, e.g. `application/*`.
+fn media_type_matches(range: &str, media_type: &str) -> bool {
+    match range.split_once('/') {
+        Some(("*", "*")) => true,
+        Some((kind, "*")) => media_type.split_once('/').is_some_and(|(k, _)| k == kind),
+        _ => range == media_type,
+    }
+}
+
+/// Follow a local `$ref`, e.g. `#/components/parameters/Limit`.
+fn resolve<'a>(spec: &'a Value, value: &'a Value) -> &'a Value {
+    let mut value = value;
+    // Bounded, in case of a reference cycle.
+    for _ in 0..8 {
+        match value.get("$ref").and_then(Value::as_str).and_then(|r| r.strip_prefix('#')) {
+            Some(pointer) => value = spec.pointer(pointer).unwrap_or(&Value::Null),
+            None => break,
+        }
+    }
+    value
+}
+
+fn parameters<'a>(spec: &'a Value, item: &'a Value) -> impl Iterator<Item = &'a Value> {
+    item.get("parameters").and_then(Value::as_array).into_iter().flatten().map(move |p| resolve(spec, p))
+}
+
+fn parse_operations(spec: &Value) -> Vec<Operation> {
+    let mut operations = Vec::new();
+    let Some(paths) = spec.get("paths").and_then(Value::as_object) else {
+        return operations;
+    };
+    for (path, item) in paths {
+        let item = resolve(spec, item);
+        let segments = path
+            .trim_matches('/')
+            .split('/')
+            .map(|s| {
+                if s.starts_with('{') && s.ends_with('}') {
+                    Segment::Param
+                } else {
+                    Segment::Literal(s.to_string())
+                }
+            })
+            .collect::<Vec<_>>();
+        for method in METHODS {
+            let Some(op) = item.get(method) else { continue };
+            // Operation parameters override path item parameters with the same name and location.
+            let mut params: Vec<&Value> = parameters(spec, op).collect();
+            for p in parameters(spec, item) {
+                if !params.iter().any(|o| o.get("name") == p.get("name") && o.get("in") == p.get("in")) {
+                    params.push(p);
+                }
+            }
+            let required = |location: &str| {
+                params
+                    .iter()
+                    .filter(|p| p.get("in").and_then(Value::as_str) == Some(location) && p.get("required").and_then(Value::as_bool) == Some(true))
+                    .filter_map(|p| p.get("name").and_then(Value::as_str))
+                    .map(str::to_string)
+                    .collect::<Vec<_>>()
+            };
+            let body = op.get("requestBody").map(|b| resolve(spec, b));
+            operations.push(Operation {
+                method: Method::from_bytes(method.to_ascii_uppercase().as_bytes()).expect("Known method"),
+                path: path.clone(),
+                segments: segments.clone(),
+                required_query: required("query"),
+                required_headers: required("header").iter().filter_map(|h| HeaderName::from_bytes(h.as_bytes()).ok()).collect(),
+                body_required: body.and_then(|b| b.get("required")).and_then(Value::as_bool) == Some(true),
+                content_types: body
+                    .and_then(|b| b.get("content"))
+                    .and_then(Value::as_object)
+                    .map(|c| c.keys().map(|k| k.to_ascii_lowercase()).collect())
+                    .unwrap_or_default(),
+            });
+        }
+    }
+    operations
+}
+
+/// Check outgoing requests against an `OpenAPI` 3 spec, to catch a client and the server's spec drifting apart.
+///
+/// Requests are matched to an operation by method and path, after the path of the spec's first server URL.
+/// Then required query parameters, required headers, a required body, and the body's content type are checked.
+/// Mismatches are logged as warnings, or with `strict`, fail the request without sending it.
+/// Requests to hosts other than the spec's server pass through unchecked.
+/// ```
+/// # use httpclient::{Client, OpenApiValidator};
+/// # fn f(spec: &str) -> serde_json::Result<()> {
+/// let validator = OpenApiValidator::from_json(spec)?.strict(cfg!(debug_assertions));
+/// let client = Client::new().base_url("https://api.example.com/v1").with_middleware(validator);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct OpenApiValidator {
+    host: Option<String>,
+    base_path: String,
+    operations: Vec<Operation>,
+    strict: bool,
+}
+
+impl OpenApiValidator {
+    /// Validate against a parsed spec. Only local `$ref`s are followed.
+    #[must_use]
+    pub fn new(spec: &Value) -> Self {
+        let server: Option<Uri> = spec.pointer("/servers/0/url").and_then(Value::as_str).and_then(|u| u.parse().ok());
+        Self {
+            host: server.as_ref().and_then(Uri::host).map(str::to_ascii_lowercase),
+            base_path: server.as_ref().map(|s| s.path().trim_end_matches('/').to_string()).unwrap_or_default(),
+            operations: parse_operations(spec),
+            strict: false,
+        }
+    }
+
+    pub fn from_json(spec: &str) -> serde_json::Result<Self> {
+        Ok(Self::new(&serde_json::from_str(spec)?))
+    }
+
+    /// Fail mismatched requests instead of logging them.
+    #[must_use]
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// What's wrong with `request`, if anything. `None` if the spec doesn't cover its host.
+    fn validate(&self, request: &InMemoryRequest) -> Option<Vec<String>> {
+        if let Some(host) = &self.host {
+            if !request.uri().host().is_some_and(|h| h.eq_ignore_ascii_case(host)) {
+                return None;
+            }
+        }
+        let path = request.uri().path();
+        let Some(path) = path.strip_prefix(&self.base_path).filter(|p| p.is_empty() || p.starts_with('/')) else {
+            return Some(vec![format!("path `{path}` is outside the spec's base path `{}`", self.base_path)]);
+        };
+        let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+        let candidates: Vec<&Operation> = self.operations.iter().filter(|o| o.matches_path(&segments)).collect();
+        if candidates.is_empty() {
+            return Some(vec![format!("path `{path}` isn't in the spec")]);
+        }
+        // Prefer literal segments over parameters, e.g. `/users/me` over `/users/{id}`.
+        let literals = |o: &&Operation| o.segments.iter().filter(|s| matches!(s, Segment::Literal(_))).count();
+        let Some(operation) = candidates.iter().copied().filter(|o| o.method == request.method()).max_by_key(literals) else {
+            return Some(vec![format!("{} isn't allowed on `{}`", request.method(), candidates[0].path)]);
+        };
+        Some(operation.problems(request))
+    }
+}
+
+#[async_trait]
+impl Middleware for OpenApiValidator {
+    async fn handle(&self, request: InMemoryRequest, next: Next<'_>) -> ProtocolResult<Response> {
+        let problems = self.validate(&request).unwrap_or_default();
+        if !problems.is_empty() {
+            let message = format!("{} {} doesn't match the OpenAPI spec: {}", request.method(), request.uri(), problems.join("; "));
+            if self.strict {
+                return Err(ProtocolError::invalid_input(message));
+            }
+            warn!("{message}");
+        }
+        next.run(request).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::middleware::{FakeTransport, MiddlewareTester};
+    use crate::InMemoryBody;
+
+    use super::*;
+
+    fn spec() -> Value {
+        json!({
+            "openapi": "3.0.0",
+            "servers": [{"url": "https://api.example.com/v1"}],
+            "paths": {
+                "/users/{id}": {
+                    "parameters": [{"$ref": "#/components/parameters/Tenant"}],
+                    "get": {"parameters": [{"name": "fields", "in": "query", "required": true}]},
+                },
+                "/users/me": {"get": {}},
+                "/users": {
+                    "post": {"requestBody": {"required": true, "content": {"application/json": {}}}},
+                },
+            },
+            "components": {"parameters": {"Tenant": {"name": "X-Tenant", "in": "header", "required": true}}},
+        })
+    }
+
+    fn request(method: Method, url: &str, headers: &[(&str, &str)], body: InMemoryBody) -> InMemoryRequest {
+        let mut b = http::Request::builder().method(method).uri(url);
+        for (k, v) in headers {
+            b = b.header(*k, *v);
+        }
+        b.body(body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_openapi_validator() {
+        let validator = OpenApiValidator::new(&spec());
+        let problems = |r: InMemoryRequest| validator.validate(&r).unwrap();
+
+        assert!(problems(request(
+            Method::GET,
+            "https://api.example.com/v1/users/7?fields=name",
+            &[("x-tenant", "a")],
+            InMemoryBody::Empty
+        ))
+        .is_empty());
+        assert!(problems(request(Method::GET, "https://api.example.com/v1/users/me", &[], InMemoryBody::Empty)).is_empty());
+        assert_eq!(
+            problems(request(Method::GET, "https://api.example.com/v1/users/7", &[], InMemoryBody::Empty)),
+            ["missing required query parameter `fields`", "missing required header `x-tenant`"]
+        );
+        assert_eq!(
+            problems(request(
+                Method::POST,
+                "https://api.example.com/v1/users",
+                &[("content-type", "text/plain")],
+                InMemoryBody::Text("x".into())
+            )),
+            ["content type `text/plain` isn't one of application/json"]
+        );
+        assert_eq!(
+            problems(request(Method::POST, "https://api.example.com/v1/users", &[], InMemoryBody::Empty)),
+            ["missing required request body"]
+        );
+        assert_eq!(
+            problems(request(Method::DELETE, "https://api.example.com/v1/users", &[], InMemoryBody::Empty)),
+            ["DELETE isn't allowed on `/users`"]
+        );
+        assert_eq!(
+            problems(request(Method::GET, "https://api.example.com/v1/orders", &[], InMemoryBody::Empty)),
+            ["path `/orders` isn't in the spec"]
+        );
+        assert!(validator.validate(&request(Method::GET, "https://other.com/x", &[], InMemoryBody::Empty)).is_none());
+
+        let transport = FakeTransport::new();
+        let tester = MiddlewareTester::new(validator.strict(true)).with_transport(transport.clone());
+        assert!(tester
+            .run(request(Method::GET, "https://api.example.com/v1/orders", &[], InMemoryBody::Empty))
+            .await
+            .is_err());
+        tester
+            .run(request(Method::GET, "https://api.example.com/v1/users/me", &[], InMemoryBody::Empty))
+            .await
+            .unwrap();
+        assert_eq!(transport.requests().len(), 1);
+    }
+}
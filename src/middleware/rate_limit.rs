@@ -0,0 +1,265 @@
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use http::{HeaderMap, StatusCode};
+use tracing::debug;
+
+use crate::clock::{Clock, SystemClock};
+use crate::error::ProtocolResult;
+use crate::middleware::{retry_after, Next};
+use crate::{InMemoryRequest, Middleware, Response};
+
+/// Reset values above this are Unix timestamps rather than seconds from now.
+const EPOCH_THRESHOLD: u64 = 1_000_000_000;
+
+/// The furthest ahead a reset is kept, however far a host puts it.
+const MAX_RESET: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// What a host last said about its rate limit. Fields are `None` when it didn't say.
+/// [`RateLimitAware`] adds it to the extensions of requests to a host it has a budget for, after taking the
+/// request from the budget, so later middlewares can read it with `request.extensions().get::<RateLimit>()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RateLimit {
+    pub limit: Option<u64>,
+    /// Requests left until `reset`, less the ones sent since.
+    pub remaining: Option<u64>,
+    pub reset: Option<SystemTime>,
+}
+
+type LimitedFn = dyn Fn(&str, Duration) + Send + Sync;
+
+/// Read the rate limit headers that hosts send, and hold back requests to a host once its budget is used up,
+/// instead of sending them only to get a `429 Too Many Requests`.
+///
+/// Understands `X-RateLimit-Limit`, `X-RateLimit-Remaining` and `X-RateLimit-Reset`, the unprefixed
+/// `RateLimit-*` headers, and `Retry-After` on 429 and 503 responses. Resets may be in seconds or a Unix timestamp,
+/// and are kept at most a day ahead.
+/// Budgets are kept per host and shared by clones of the middleware, so keep a clone to read them:
+/// ```
+/// # use httpclient::{Client, RateLimitAware, Retry};
+/// # use tracing::warn;
+/// let limits = RateLimitAware::new().on_limited(|host, wait| warn!(host, ?wait, "Rate limited"));
+/// let client = Client::new().with_middleware(limits.clone()).with_middleware(Retry::new());
+/// // ...
+/// let budget = limits.budget("api.github.com");
+/// ```
+/// Put it before [`crate::Retry`], so retries wait for the budget too.
+#[derive(Clone)]
+pub struct RateLimitAware {
+    max_wait: Duration,
+    on_limited: Option<Arc<LimitedFn>>,
+    hosts: Arc<Mutex<HashMap<String, RateLimit>>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl Debug for RateLimitAware {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RateLimitAware")
+            .field("max_wait", &self.max_wait)
+            .field("hosts", &self.hosts)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for RateLimitAware {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A number from `X-RateLimit-{name}` or `RateLimit-{name}`, ignoring anything after it like `;w=60`.
+fn number(headers: &HeaderMap, name: &str) -> Option<u64> {
+    let value = headers.get(format!("x-ratelimit-{name}")).or_else(|| headers.get(format!("ratelimit-{name}")))?;
+    let value = value.to_str().ok()?.trim();
+    value.split(|c: char| !c.is_ascii_digit()).next()?.parse().ok()
+}
+
+impl RateLimitAware {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            max_wait: Duration::from_secs(60),
+            on_limited: None,
+            hosts: Arc::default(),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Wait at most this long for a budget to reset before sending anyway. Defaults to a minute.
+    #[must_use]
+    pub fn max_wait(mut self, max_wait: Duration) -> Self {
+        self.max_wait = max_wait;
+        self
+    }
+
+    /// Call `f` with the host and the time until its reset whenever a response uses up a host's budget,
+    /// including `429` and `503` responses with a `Retry-After`.
+    #[must_use]
+    pub fn on_limited<F: Fn(&str, Duration) + Send + Sync + 'static>(mut self, f: F) -> Self {
+        self.on_limited = Some(Arc::new(f));
+        self
+    }
+
+    /// Measure resets with this clock instead of the system time, e.g. a [`crate::clock::TestClock`].
+    #[must_use]
+    pub fn clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// The current budget for `host`, if it has sent rate limit headers and they haven't reset yet.
+    #[must_use]
+    pub fn budget(&self, host: &str) -> Option<RateLimit> {
+        let budget = *self.lock().get(&host.to_ascii_lowercase())?;
+        match budget.reset {
+            Some(reset) if reset <= self.clock.now() => None,
+            _ => Some(budget),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, RateLimit>> {
+        self.hosts.lock().expect("Rate limit lock poisoned")
+    }
+
+    /// Take a request from `host`'s budget, returning how long to wait first if it's used up.
+    fn reserve(&self, host: &str) -> Option<Duration> {
+        let now = self.clock.now();
+        let mut hosts = self.lock();
+        let budget = hosts.get_mut(host)?;
+        if budget.reset.is_some_and(|reset| reset <= now) {
+            hosts.remove(host);
+            return None;
+        }
+        match budget.remaining {
+            Some(0) => budget.reset.and_then(|reset| reset.duration_since(now).ok()).map(|wait| wait.min(self.max_wait)),
+            Some(n) => {
+                budget.remaining = Some(n - 1);
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn update(&self, host: &str, status: StatusCode, headers: &HeaderMap) {
+        let now = self.clock.now();
+        // Clamp resets to `MAX_RESET` from now, including ones too far off to represent.
+        let latest = now.checked_add(MAX_RESET).unwrap_or(now);
+        let clamp = |reset: Option<SystemTime>| reset.map_or(latest, |reset| reset.min(latest));
+        let mut budget = RateLimit {
+            limit: number(headers, "limit"),
+            remaining: number(headers, "remaining"),
+            reset: number(headers, "reset").map(|n| {
+                if n > EPOCH_THRESHOLD {
+                    clamp(UNIX_EPOCH.checked_add(Duration::from_secs(n)))
+                } else {
+                    clamp(now.checked_add(Duration::from_secs(n)))
+                }
+            }),
+        };
+        if status == StatusCode::TOO_MANY_REQUESTS || status == StatusCode::SERVICE_UNAVAILABLE {
+            if let Some(wait) = retry_after(headers, now) {
+                budget.remaining = Some(0);
+                budget.reset = Some(clamp(now.checked_add(wait)));
+            }
+        }
+        if budget == RateLimit::default() {
+            return;
+        }
+        self.lock().insert(host.to_string(), budget);
+        if let (Some(0), Some(reset)) = (budget.remaining, budget.reset) {
+            let wait = reset.duration_since(now).unwrap_or_default();
+            debug!(host, wait_ms = wait.as_millis(), "Rate limit reached");
+            if let Some(f) = &self.on_limited {
+                f(host, wait);
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Middleware for RateLimitAware {
+    async fn handle(&self, mut request: InMemoryRequest, next: Next<'_>) -> ProtocolResult<Response> {
+        let Some(host) = request.uri().host().map(str::to_ascii_lowercase) else {
+            return next.run(request).await;
+        };
+        if let Some(wait) = self.reserve(&host) {
+            debug!(host, wait_ms = wait.as_millis(), "Waiting for rate limit to reset");
+            self.clock.sleep(wait).await;
+        }
+        if let Some(budget) = self.budget(&host) {
+            request.extensions_mut().insert(budget);
+        }
+        let res = next.run(request).await?;
+        self.update(&host, res.status(), res.headers());
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::clock::TestClock;
+    use crate::middleware::{FakeTransport, MiddlewareTester};
+    use crate::{InMemoryBody, InMemoryResponse};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_rate_limit_aware() {
+        let start = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let clock = TestClock::at(start);
+        let limited = Arc::new(Mutex::new(Vec::new()));
+        let seen = limited.clone();
+        let limits = RateLimitAware::new()
+            .clock(clock.clone())
+            .on_limited(move |host, wait| seen.lock().unwrap().push((host.to_string(), wait)));
+
+        let mut ok = InMemoryResponse::new(InMemoryBody::Empty);
+        ok.headers_mut().insert("x-ratelimit-limit", "10".parse().unwrap());
+        ok.headers_mut().insert("x-ratelimit-remaining", "2".parse().unwrap());
+        ok.headers_mut().insert("x-ratelimit-reset", "1700000030".parse().unwrap());
+        let mut limited_res = InMemoryResponse::new(InMemoryBody::Empty);
+        *limited_res.status_mut() = StatusCode::TOO_MANY_REQUESTS;
+        limited_res.headers_mut().insert("retry-after", "5".parse().unwrap());
+        let transport = FakeTransport::new().respond(ok).respond(limited_res);
+        let tester = MiddlewareTester::new(limits.clone()).with_transport(transport);
+
+        let request = || http::Request::get("https://API.example.com/repos").body(InMemoryBody::Empty).unwrap();
+        let res = tester.run(request()).await.unwrap();
+        assert_eq!(res.extensions().get::<RateLimit>(), None);
+        let budget = limits.budget("api.example.com").unwrap();
+        assert_eq!(budget.limit, Some(10));
+        assert_eq!(budget.remaining, Some(2));
+        assert_eq!(budget.reset, Some(start + Duration::from_secs(30)));
+
+        let res = tester.run(request()).await.unwrap();
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+        // the request carried the budget left after it was taken
+        assert_eq!(res.extensions().get::<RateLimit>().unwrap().remaining, Some(1));
+        assert_eq!(limits.budget("api.example.com").unwrap().remaining, Some(0));
+        assert_eq!(*limited.lock().unwrap(), vec![("api.example.com".to_string(), Duration::from_secs(5))]);
+        assert_eq!(limits.reserve("api.example.com"), Some(Duration::from_secs(5)));
+
+        clock.advance(Duration::from_secs(6));
+        assert_eq!(limits.budget("api.example.com"), None);
+        assert_eq!(limits.reserve("api.example.com"), None);
+    }
+
+    #[test]
+    fn test_huge_reset() {
+        let start = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let limits = RateLimitAware::new().clock(TestClock::at(start));
+        let mut headers = HeaderMap::new();
+        headers.insert("x-ratelimit-remaining", "0".parse().unwrap());
+        headers.insert("x-ratelimit-reset", u64::MAX.to_string().parse().unwrap());
+        limits.update("a.example.com", StatusCode::OK, &headers);
+        assert_eq!(limits.budget("a.example.com").unwrap().reset, Some(start + MAX_RESET));
+
+        let mut headers = HeaderMap::new();
+        headers.insert("retry-after", u64::MAX.to_string().parse().unwrap());
+        limits.update("b.example.com", StatusCode::TOO_MANY_REQUESTS, &headers);
+        assert_eq!(limits.budget("b.example.com").unwrap().reset, Some(start + MAX_RESET));
+    }
+}
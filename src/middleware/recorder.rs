@@ -1,14 +1,14 @@
 use std::sync::OnceLock;
 
 use async_trait::async_trait;
-use http::header::CONTENT_TYPE;
 use tracing::info;
 
 use crate::error::ProtocolResult;
+use crate::middleware::logger::content_length;
 use crate::middleware::Next;
 use crate::middleware::ProtocolError;
-use crate::recorder::{HashableRequest, RequestRecorder};
-use crate::{Body, InMemoryRequest, InMemoryResponse, Middleware, Response};
+use crate::recorder::{not_modified, HashableRequest, RequestRecorder};
+use crate::{Body, InMemoryRequest, InMemoryResponseExt, Middleware, Response, ResponseExt};
 
 #[derive(PartialEq, Eq, Clone, Copy, Default, Debug)]
 pub enum RecorderMode {
@@ -19,13 +19,15 @@ pub enum RecorderMode {
     IgnoreRecordings,
     /// Always use recordings. Fail if no recording is found.
     ForceNoRequests,
+    /// Always make the request, and replace the recordings for its method and URL with the new response.
+    Rerecord,
 }
 
 impl RecorderMode {
     #[must_use]
     pub fn should_lookup(self) -> bool {
         match self {
-            RecorderMode::IgnoreRecordings => false,
+            RecorderMode::IgnoreRecordings | RecorderMode::Rerecord => false,
             RecorderMode::ForceNoRequests | RecorderMode::RecordOrRequest => true,
         }
     }
@@ -33,7 +35,7 @@ impl RecorderMode {
     #[must_use]
     pub fn should_request(self) -> bool {
         match self {
-            RecorderMode::IgnoreRecordings | RecorderMode::RecordOrRequest => true,
+            RecorderMode::IgnoreRecordings | RecorderMode::RecordOrRequest | RecorderMode::Rerecord => true,
             RecorderMode::ForceNoRequests => false,
         }
     }
@@ -41,22 +43,55 @@ impl RecorderMode {
 
 static SHARED_RECORDER: OnceLock<RequestRecorder> = OnceLock::new();
 
+/// The recorder the [`Recorder`] middleware uses. Unless one was installed with [`try_init_shared_recorder`], it's
+/// loaded from `data/vcr` on first use, skipping corrupt recordings.
 pub fn shared_recorder() -> &'static RequestRecorder {
     SHARED_RECORDER.get_or_init(RequestRecorder::new)
 }
 
+/// Install `recorder` as the shared recorder, returning whether it took effect,
+/// i.e. `false` if the shared recorder was already loaded. To fail on corrupt recordings:
+/// ```no_run
+/// # use httpclient::middleware::try_init_shared_recorder;
+/// # use httpclient::recorder::RequestRecorder;
+/// # fn f() -> httpclient::ProtocolResult<()> {
+/// assert!(try_init_shared_recorder(RequestRecorder::new_strict()?));
+/// # Ok(())
+/// # }
+/// ```
+#[must_use]
+pub fn try_init_shared_recorder(recorder: RequestRecorder) -> bool {
+    SHARED_RECORDER.set(recorder).is_ok()
+}
+
 #[derive(Default, Copy, Clone, Debug)]
 /// This middleware caches requests to the local filesystem. Subsequent requests will return results
 /// from the filesystem, and not touch the remote server.
 ///
 /// The recordings are sanitized to hide secrets.
 ///
+/// When replaying, a request whose `If-None-Match` matches the recorded `ETag` gets a `304 Not Modified`,
+/// so conditional request logic can be tested against recordings.
+///
 /// Use `.mode()` to configure the behavior:
 /// - `RecorderMode::RecordOrRequest` (default): Will check for recordings, but will make the request if no recording is found.
 /// - `RecorderMode::IgnoreRecordings`: Always make the request. (Use to force refresh recordings.)
 /// - `RecorderMode::ForceNoRequests`: Fail if no recording is found. (Use to run tests without hitting the remote server.)
+/// - `RecorderMode::Rerecord`: Always make the request, replacing older recordings for the same method and URL.
+///
+/// After a test run, `shared_recorder().unused()` lists recordings nothing played back, and `prune()` deletes them.
+/// Requests match recordings on method, URL and body; use `shared_recorder().match_headers(&[ACCEPT])` to match
+/// on headers too.
+///
+/// `shared_recorder().compression(Compression::Gzip)` writes `.json.gz` recordings (with the `recorder-gzip` or
+/// `recorder-zstd` feature), and `sidecar_bodies(64 * 1024)` moves large bodies into their own files. Both kinds of
+/// file are read back whatever the settings.
+///
+/// Use `.max_body()` to pass large downloads through without reading them into memory to record them.
 pub struct Recorder {
     pub mode: RecorderMode,
+    /// Responses with a `Content-Length` over this many bytes aren't recorded, and stream through to the caller.
+    pub max_body: Option<u64>,
 }
 
 impl Recorder {
@@ -71,6 +106,13 @@ impl Recorder {
         self
     }
 
+    /// Don't record responses with a `Content-Length` over this many bytes, and leave their bodies streaming.
+    #[must_use]
+    pub fn max_body(mut self, max_body: u64) -> Self {
+        self.max_body = Some(max_body);
+        self
+    }
+
     fn should_lookup(self) -> bool {
         self.mode.should_lookup()
     }
@@ -92,6 +134,7 @@ impl Middleware for Recorder {
 
             if let Some(recorded) = recorded {
                 info!(url = request.uri().to_string(), "Using recorded response");
+                let recorded = not_modified(&request, &recorded).unwrap_or(recorded);
 
                 let (parts, body) = recorded.into_parts();
                 return Ok(Response::from_parts(parts, Body::InMemory(body)));
@@ -103,12 +146,22 @@ impl Middleware for Recorder {
         }
 
         let response = next.run(request.clone()).await?;
-        let (parts, body) = response.into_parts();
-        let content_type = parts.headers.get(CONTENT_TYPE);
-        let body = body.into_content_type(content_type).await?;
-        let response = InMemoryResponse::from_parts(parts, body);
-
-        recorder.record_response(request.0, response.clone())?;
+        if let (Some(max), Some(len)) = (self.max_body, content_length(response.headers())) {
+            if len > max {
+                info!(url = request.uri().to_string(), len, "Not recording large response");
+                return Ok(response);
+            }
+        }
+        let response = response.into_memory().await?;
+
+        // Record JSON as JSON rather than an array of bytes, but pass the response on unparsed.
+        let mut recorded = response.clone();
+        recorded.json_value_mut().ok();
+        if self.mode == RecorderMode::Rerecord {
+            recorder.replace_response(request.0, recorded).await?;
+        } else {
+            recorder.record_response(request.0, recorded).await?;
+        }
 
         let (parts, body) = response.into_parts();
         Ok(Response::from_parts(parts, Body::InMemory(body)))
@@ -0,0 +1,151 @@
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use tracing::warn;
+
+use crate::clock::{elapsed, Clock, SystemClock};
+
+/// Most retries that unused credit can add up to, so a long healthy stretch can't bank enough to amplify a later outage.
+const MAX_BALANCE: f64 = 10.0;
+
+#[derive(Debug)]
+struct State {
+    balance: f64,
+    reserve: f64,
+    refilled: SystemTime,
+    retries: u64,
+    rejected: u64,
+}
+
+/// Limit retries to a share of all requests, so retries can't multiply the load on a struggling server.
+///
+/// Every request earns `ratio` of a retry and every retry spends one, plus `min_per_second` retries a second
+/// so low-traffic clients can still retry. When the budget is spent, [`crate::Retry`] returns the last
+/// response or timeout instead of retrying. Clones share the budget, so give the same one to every `Retry` of a client:
+/// ```
+/// # use httpclient::{Client, Retry, RetryBudget};
+/// let budget = RetryBudget::new(0.1);
+/// let client = Client::new().with_middleware(Retry::new().budget(budget.clone()));
+/// // later
+/// budget.rejected();
+/// ```
+#[derive(Debug, Clone)]
+pub struct RetryBudget {
+    ratio: f64,
+    min_per_second: u32,
+    state: Arc<Mutex<State>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl Default for RetryBudget {
+    /// Retries may add 20% to requests, plus 10 a second.
+    fn default() -> Self {
+        Self::new(0.2)
+    }
+}
+
+impl RetryBudget {
+    /// A budget allowing `ratio` retries per request, e.g. `0.1` for at most 10% extra requests, plus 10 retries a second.
+    #[must_use]
+    pub fn new(ratio: f64) -> Self {
+        let clock: Arc<dyn Clock> = Arc::new(SystemClock);
+        Self {
+            ratio,
+            min_per_second: 10,
+            state: Arc::new(Mutex::new(State {
+                balance: 0.0,
+                reserve: 10.0,
+                refilled: clock.now(),
+                retries: 0,
+                rejected: 0,
+            })),
+            clock,
+        }
+    }
+
+    /// Retries allowed every second regardless of traffic. Defaults to 10.
+    #[must_use]
+    pub fn min_per_second(mut self, min_per_second: u32) -> Self {
+        self.min_per_second = min_per_second;
+        self.state.lock().expect("Retry budget lock poisoned").reserve = f64::from(min_per_second);
+        self
+    }
+
+    /// Refill the per-second allowance with this clock instead of the system time, e.g. a [`crate::clock::TestClock`].
+    #[must_use]
+    pub fn clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.state.lock().expect("Retry budget lock poisoned").refilled = clock.now();
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Credit the budget for a request.
+    pub(crate) fn deposit(&self) {
+        let mut state = self.state.lock().expect("Retry budget lock poisoned");
+        state.balance = (state.balance + self.ratio).min(MAX_BALANCE);
+    }
+
+    /// Spend one retry, if the budget allows it.
+    pub(crate) fn withdraw(&self) -> bool {
+        let mut state = self.state.lock().expect("Retry budget lock poisoned");
+        let max_reserve = f64::from(self.min_per_second);
+        let now = self.clock.now();
+        state.reserve = (state.reserve + elapsed(self.clock.as_ref(), state.refilled).as_secs_f64() * max_reserve).min(max_reserve);
+        state.refilled = now;
+        if state.balance >= 1.0 {
+            state.balance -= 1.0;
+        } else if state.reserve >= 1.0 {
+            state.reserve -= 1.0;
+        } else {
+            state.rejected += 1;
+            warn!(rejected = state.rejected, "Retry budget exhausted, not retrying");
+            return false;
+        }
+        state.retries += 1;
+        true
+    }
+
+    /// How many retries the budget has allowed.
+    #[must_use]
+    pub fn retries(&self) -> u64 {
+        self.state.lock().expect("Retry budget lock poisoned").retries
+    }
+
+    /// How many retries the budget has refused.
+    #[must_use]
+    pub fn rejected(&self) -> u64 {
+        self.state.lock().expect("Retry budget lock poisoned").rejected
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::clock::TestClock;
+
+    use super::*;
+
+    #[test]
+    fn test_retry_budget() {
+        let clock = TestClock::new();
+        let budget = RetryBudget::new(0.5).min_per_second(1).clock(clock.clone());
+        // The per-second allowance.
+        assert!(budget.withdraw());
+        assert!(!budget.withdraw());
+        // Two requests earn a retry.
+        budget.deposit();
+        assert!(!budget.withdraw());
+        budget.deposit();
+        assert!(budget.withdraw());
+        assert!(!budget.withdraw());
+        clock.advance(Duration::from_secs(1));
+        assert!(budget.clone().withdraw());
+        assert_eq!((budget.retries(), budget.rejected()), (3, 3));
+
+        for _ in 0..100 {
+            budget.deposit();
+        }
+        assert_eq!((0..20).filter(|_| budget.withdraw()).count(), 10);
+    }
+}
@@ -0,0 +1,228 @@
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+use crate::error::{ProtocolError, ProtocolResult};
+use crate::middleware::Next;
+use crate::{Client, InMemoryRequest, Middleware, Response};
+
+/// Hostnames of cloud metadata services that don't look like IP addresses.
+const METADATA_HOSTS: [&str; 2] = ["metadata.google.internal", "metadata.goog"];
+
+/// Whether `ip` is somewhere user-supplied URLs shouldn't reach: loopback, private, link-local
+/// (including the `169.254.169.254` metadata endpoint), carrier-grade NAT, multicast, or unspecified.
+/// IPv6 addresses that embed an IPv4 address (mapped, compatible, or NAT64 `64:ff9b::/96`) are judged by it.
+#[must_use]
+pub fn is_internal_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_internal_v4(ip),
+        IpAddr::V6(ip) => {
+            // IPv4-mapped ::ffff:0:0/96 and IPv4-compatible ::/96, which also covers :: and ::1.
+            if let Some(v4) = ip.to_ipv4() {
+                return is_internal_v4(v4);
+            }
+            // NAT64 64:ff9b::/96 translates to the IPv4 address in the last 32 bits.
+            if ip.segments()[..6] == [0x64, 0xff9b, 0, 0, 0, 0] {
+                let [.., a, b, c, d] = ip.octets();
+                return is_internal_v4(Ipv4Addr::new(a, b, c, d));
+            }
+            let first = ip.segments()[0];
+            ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                // unique local fc00::/7 (including AWS's fd00:ec2::254) and link-local fe80::/10
+                || first & 0xfe00 == 0xfc00
+                || first & 0xffc0 == 0xfe80
+        }
+    }
+}
+
+fn is_internal_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        || a == 0
+        // carrier-grade NAT 100.64.0.0/10
+        || a == 100 && b & 0xc0 == 64
+}
+
+/// Match `host` against a pattern, where `*.example.com` matches any subdomain of `example.com`.
+pub(crate) fn host_matches(pattern: &str, host: &str) -> bool {
+    match pattern.strip_prefix("*.") {
+        Some(domain) => host.len() > domain.len() && host.ends_with(domain) && host.as_bytes()[host.len() - domain.len() - 1] == b'.',
+        None => pattern.eq_ignore_ascii_case(host),
+    }
+}
+
+/// Refuse requests to internal addresses, for clients that fetch user-supplied URLs.
+///
+/// Blocks private, loopback, and link-local IPs (including cloud metadata endpoints), both as literal hosts and
+/// as what a hostname resolves to. Adding it to a client also makes the client's connector skip blocked addresses
+/// when it dials, so a hostname whose DNS answer changes after the URL is checked still can't reach them.
+/// Optionally, only allowlisted hosts are reachable. Refused requests fail with `ProtocolError::Blocked`.
+///
+/// Every redirect hop passes through middlewares after `Follow`, so add this one after it:
+/// ```
+/// # use httpclient::{Client, Follow, SsrfGuard};
+/// let client = Client::new().with_middleware(Follow).with_middleware(SsrfGuard::new().allow_host("*.example.com"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct SsrfGuard {
+    allowed_hosts: Vec<String>,
+    allow_internal: bool,
+}
+
+impl Default for SsrfGuard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SsrfGuard {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            allowed_hosts: Vec::new(),
+            allow_internal: false,
+        }
+    }
+
+    /// Only allow these hosts, e.g. `api.example.com` or `*.example.com`. Without any, all public hosts are allowed.
+    #[must_use]
+    pub fn allow_host(mut self, pattern: &str) -> Self {
+        self.allowed_hosts.push(pattern.to_ascii_lowercase());
+        self
+    }
+
+    /// Allow internal addresses, e.g. to keep only the host allowlist in a trusted network.
+    #[must_use]
+    pub fn allow_internal(mut self, allow: bool) -> Self {
+        self.allow_internal = allow;
+        self
+    }
+
+    pub(crate) fn check_ip(&self, ip: IpAddr) -> ProtocolResult<()> {
+        if !self.allow_internal && is_internal_ip(ip) {
+            return Err(ProtocolError::Blocked(format!("{ip} is an internal address")));
+        }
+        Ok(())
+    }
+
+    /// Check the URL without resolving it.
+    fn check_url(&self, uri: &http::Uri) -> ProtocolResult<()> {
+        if !matches!(uri.scheme_str(), Some("http" | "https")) {
+            return Err(ProtocolError::Blocked(format!("{uri} is not an http(s) URL")));
+        }
+        let host = uri.host().unwrap_or_default().trim_start_matches('[').trim_end_matches(']').to_ascii_lowercase();
+        if !self.allowed_hosts.is_empty() && !self.allowed_hosts.iter().any(|p| host_matches(p, &host)) {
+            return Err(ProtocolError::Blocked(format!("{host} is not an allowed host")));
+        }
+        if let Ok(ip) = host.parse() {
+            return self.check_ip(ip);
+        }
+        if !self.allow_internal && (host == "localhost" || host.ends_with(".localhost") || METADATA_HOSTS.contains(&host.as_str())) {
+            return Err(ProtocolError::Blocked(format!("{host} is an internal host")));
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Middleware for SsrfGuard {
+    async fn handle(&self, request: InMemoryRequest, next: Next<'_>) -> ProtocolResult<Response> {
+        self.check_url(request.uri())?;
+        next.run(request).await
+    }
+
+    fn checks_redirect_hops(&self) -> bool {
+        true
+    }
+
+    fn guards_connections(&self) -> Option<SsrfGuard> {
+        Some(self.clone())
+    }
+}
+
+impl Client {
+    /// Only allow requests to these hosts, and never to internal addresses. See [`SsrfGuard`].
+    /// The guard runs right after [`crate::Follow`], if the client has it, so every redirect hop is checked too.
+    #[must_use]
+    pub fn allowed_hosts(self, hosts: &[&str]) -> Self {
+        let guard = hosts.iter().fold(SsrfGuard::new(), |guard, host| guard.allow_host(host));
+        let index = self.middlewares.iter().rposition(|m| m.follows_redirects()).map_or(0, |i| i + 1);
+        self.insert_middleware(index, Arc::new(guard))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::middleware::{FakeTransport, Follow};
+
+    use super::*;
+
+    #[test]
+    fn test_check_url() {
+        let guard = SsrfGuard::new();
+        let check = |guard: &SsrfGuard, url: &str| guard.check_url(&url.parse().unwrap()).is_ok();
+        assert!(check(&guard, "https://example.com/"));
+        assert!(check(&guard, "http://93.184.215.14/"));
+        assert!(!check(&guard, "http://169.254.169.254/latest/meta-data/"));
+        assert!(!check(&guard, "http://10.0.0.1/"));
+        assert!(!check(&guard, "http://100.64.1.1/"));
+        assert!(!check(&guard, "http://[::1]:8080/"));
+        assert!(!check(&guard, "http://[::ffff:127.0.0.1]/"));
+        assert!(!check(&guard, "http://[::127.0.0.1]/"));
+        assert!(!check(&guard, "http://[64:ff9b::a9fe:a9fe]/"));
+        assert!(check(&guard, "http://[64:ff9b::5db8:d70e]/"));
+        assert!(!check(&guard, "http://[fd00:ec2::254]/"));
+        assert!(!check(&guard, "http://metadata.google.internal/"));
+        assert!(!check(&guard, "http://localhost:3000/"));
+        assert!(!check(&guard, "ftp://example.com/"));
+
+        let guard = SsrfGuard::new().allow_host("*.example.com").allow_host("api.other.org");
+        assert!(check(&guard, "https://a.example.com/"));
+        assert!(check(&guard, "https://API.other.org/"));
+        assert!(!check(&guard, "https://example.com/"));
+        assert!(!check(&guard, "https://evilexample.com/"));
+        assert!(check(&guard.allow_internal(true).allow_host("10.0.0.1"), "http://10.0.0.1/"));
+    }
+
+    #[tokio::test]
+    async fn test_client_allowed_hosts() {
+        let client = Client::new().allowed_hosts(&["localhost", "*.localhost"]);
+        let err = client.get("http://localhost:1/").send().await.unwrap_err();
+        assert!(matches!(err, ProtocolError::Blocked(_)));
+    }
+
+    #[tokio::test]
+    async fn test_allowed_hosts_after_follow() {
+        let redirect = http::Response::builder()
+            .status(http::StatusCode::FOUND)
+            .header("location", "http://localhost/admin")
+            .body(crate::InMemoryBody::Empty)
+            .unwrap();
+        let transport = FakeTransport::new().respond(redirect);
+        let client = Client::new().with_middleware(Follow).with_middleware(transport.clone()).allowed_hosts(&["example.com"]);
+        assert!(client.middlewares[1].guards_connections().is_some());
+        let err = client.get("https://example.com/").send().await.unwrap_err();
+        assert!(matches!(err, ProtocolError::Blocked(_)), "{err:?}");
+        assert_eq!(transport.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_connector_checks_resolved_addresses() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        // The hostname passes the URL check, but resolves to loopback when it's dialed.
+        let client = Client::new().resolve_to("public.example.com", &[[127, 0, 0, 1].into()]).with_middleware(SsrfGuard::new());
+        let err = client.get(format!("http://public.example.com:{port}/")).send().await.unwrap_err();
+        assert!(matches!(err, ProtocolError::Blocked(_)), "{err:?}");
+        let accepted = tokio::time::timeout(std::time::Duration::from_millis(50), listener.accept()).await;
+        assert!(accepted.is_err(), "blocked address was dialed");
+    }
+}
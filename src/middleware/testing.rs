@@ -0,0 +1,134 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use http::StatusCode;
+
+use crate::client::Client;
+use crate::error::ProtocolResult;
+use crate::middleware::{copy_extensions, Next};
+use crate::{Body, InMemoryBody, InMemoryRequest, InMemoryResponse, Middleware, Response};
+
+/// A stand-in for the network that answers with scripted responses, in order, and records every request it receives.
+/// Once the script runs out, it answers `200 OK` with an empty body. Clones share the script and the recorded requests.
+#[derive(Debug, Clone, Default)]
+pub struct FakeTransport {
+    responses: Arc<Mutex<VecDeque<ProtocolResult<InMemoryResponse>>>>,
+    requests: Arc<Mutex<Vec<InMemoryRequest>>>,
+}
+
+impl FakeTransport {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answer the next unanswered request with `response`.
+    #[must_use]
+    pub fn respond(self, response: InMemoryResponse) -> Self {
+        self.responses.lock().expect("Fake transport lock poisoned").push_back(Ok(response));
+        self
+    }
+
+    /// Answer the next unanswered request with a response with this status and body.
+    #[must_use]
+    pub fn respond_with(self, status: StatusCode, body: InMemoryBody) -> Self {
+        let mut response = InMemoryResponse::new(body);
+        *response.status_mut() = status;
+        self.respond(response)
+    }
+
+    /// Fail the next unanswered request with `error`, e.g. to simulate a connection failure.
+    #[must_use]
+    pub fn fail(self, error: crate::ProtocolError) -> Self {
+        self.responses.lock().expect("Fake transport lock poisoned").push_back(Err(error));
+        self
+    }
+
+    /// The requests received so far, as they were after passing through the middlewares.
+    #[must_use]
+    pub fn requests(&self) -> Vec<InMemoryRequest> {
+        self.requests.lock().expect("Fake transport lock poisoned").clone()
+    }
+}
+
+#[async_trait]
+impl Middleware for FakeTransport {
+    async fn handle(&self, request: InMemoryRequest, _next: Next<'_>) -> ProtocolResult<Response> {
+        let extensions = request.extensions().clone();
+        self.requests.lock().expect("Fake transport lock poisoned").push(request);
+        let scripted = self.responses.lock().expect("Fake transport lock poisoned").pop_front();
+        let mut response = scripted.unwrap_or_else(|| Ok(InMemoryResponse::new(InMemoryBody::Empty)))?;
+        // Like the real transport, hand the request's extensions back on the response.
+        copy_extensions(extensions, &mut response);
+        let (parts, body) = response.into_parts();
+        Ok(Response::from_parts(parts, Body::InMemory(body)))
+    }
+}
+
+/// Run a single middleware against a [`FakeTransport`], to unit test it without a real client or the network.
+/// ```
+/// # use httpclient::middleware::{FakeTransport, MiddlewareTester};
+/// # use httpclient::{InMemoryBody, InMemoryRequest, Middleware, StatusCode};
+/// # async fn f(request: InMemoryRequest, my_middleware: impl Middleware + 'static) -> httpclient::ProtocolResult<()> {
+/// let transport = FakeTransport::new().respond_with(StatusCode::SERVICE_UNAVAILABLE, InMemoryBody::Empty);
+/// let tester = MiddlewareTester::new(my_middleware).with_transport(transport.clone());
+/// let res = tester.run(request).await?;
+/// assert_eq!(transport.requests()[0].headers()["x-added"], "1");
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct MiddlewareTester {
+    client: Client,
+    middlewares: [Arc<dyn Middleware>; 2],
+}
+
+impl MiddlewareTester {
+    pub fn new<M: Middleware + 'static>(middleware: M) -> Self {
+        Self {
+            client: Client::new(),
+            middlewares: [Arc::new(middleware), Arc::new(FakeTransport::new())],
+        }
+    }
+
+    /// Answer requests that the middleware passes on with this transport. Keep a clone to inspect the requests.
+    #[must_use]
+    pub fn with_transport(mut self, transport: FakeTransport) -> Self {
+        self.middlewares[1] = Arc::new(transport);
+        self
+    }
+
+    /// Send `request` through the middleware.
+    pub async fn run(&self, request: InMemoryRequest) -> ProtocolResult<Response> {
+        let next = Next {
+            client: &self.client,
+            middlewares: &self.middlewares,
+        };
+        next.run(request).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::header::STRICT_TRANSPORT_SECURITY;
+
+    use crate::middleware::Hsts;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_middleware_tester() {
+        let mut hsts_response = InMemoryResponse::new(InMemoryBody::Empty);
+        hsts_response.headers_mut().insert(STRICT_TRANSPORT_SECURITY, "max-age=60".parse().unwrap());
+        let transport = FakeTransport::new().respond(hsts_response).respond_with(StatusCode::NOT_FOUND, InMemoryBody::Empty);
+        let tester = MiddlewareTester::new(Hsts::new()).with_transport(transport.clone());
+        let request = |uri: &str| http::Request::builder().uri(uri).body(InMemoryBody::Empty).unwrap();
+
+        assert_eq!(tester.run(request("https://example.com/")).await.unwrap().status(), StatusCode::OK);
+        assert_eq!(tester.run(request("http://example.com/a")).await.unwrap().status(), StatusCode::NOT_FOUND);
+        assert_eq!(tester.run(request("http://example.com/b")).await.unwrap().status(), StatusCode::OK);
+        let uris: Vec<String> = transport.requests().iter().map(|r| r.uri().to_string()).collect();
+        assert_eq!(uris, ["https://example.com/", "https://example.com/a", "https://example.com/b"]);
+    }
+}
@@ -0,0 +1,28 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+
+use crate::error::{ProtocolError, ProtocolResult};
+use crate::middleware::Next;
+use crate::{InMemoryRequest, Middleware, Response};
+
+/// Fail with `ProtocolError::Timeout` if the response headers haven't arrived within the given time.
+///
+/// The limit covers every middleware after this one, so place it first to bound retries and redirects too.
+/// Reading the body afterwards isn't limited.
+#[derive(Debug, Clone, Copy)]
+pub struct TotalTimeout(pub Duration);
+
+impl TotalTimeout {
+    #[must_use]
+    pub fn new(timeout: Duration) -> Self {
+        Self(timeout)
+    }
+}
+
+#[async_trait]
+impl Middleware for TotalTimeout {
+    async fn handle(&self, request: InMemoryRequest, next: Next<'_>) -> ProtocolResult<Response> {
+        crate::clock::timeout(self.0, next.run(request)).await.map_err(|_| ProtocolError::Timeout)?
+    }
+}
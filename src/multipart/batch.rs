@@ -0,0 +1,125 @@
+use std::collections::HashMap;
+
+use crate::error::ProtocolError;
+use crate::multipart::{Form, Part};
+use crate::{Client, Error, InMemoryRequest, InMemoryResponse, InMemoryResult, RequestBuilder};
+
+/// Requests sent together as one `multipart/mixed` request, like the batch endpoints of Google APIs.
+/// Each request is a part with its own `Content-ID`, and the responses are matched back to them by it.
+/// ```no_run
+/// # use httpclient::Client;
+/// # async fn f(client: Client) -> httpclient::InMemoryResult<()> {
+/// let mut batch = client.batch("/batch/farm/v1");
+/// batch.add(client.get("/farm/v1/animals/pony"));
+/// batch.add(client.delete("/farm/v1/animals/sheep"));
+/// let [pony, sheep] = &batch.send().await?[..] else { unreachable!() };
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Batch<'a> {
+    client: &'a Client,
+    url: String,
+    requests: Vec<InMemoryRequest>,
+}
+
+/// The `Content-ID` of the `i`th request in a batch, without angle brackets.
+fn content_id(i: usize) -> String {
+    format!("item-{}", i + 1)
+}
+
+impl<'a> Batch<'a> {
+    /// Send the batch to `url`, a path relative to the client's base URL or an absolute URL.
+    #[must_use]
+    pub fn new(client: &'a Client, url: &str) -> Self {
+        Self {
+            client,
+            url: url.to_string(),
+            requests: Vec::new(),
+        }
+    }
+
+    /// Add a request. Its middlewares don't run; the batch request goes through the client's middlewares instead.
+    pub fn add<C>(&mut self, request: RequestBuilder<'_, C>) {
+        self.requests.push(request.build());
+    }
+
+    /// Send the batch, returning the response to each request in the order they were added,
+    /// or `None` for a request the server didn't answer.
+    ///
+    /// Fails if the batch request itself fails, or its response isn't multipart.
+    pub async fn send(self) -> InMemoryResult<Vec<Option<InMemoryResponse>>> {
+        let count = self.requests.len();
+        let mut form = Form::mixed();
+        for (i, request) in self.requests.into_iter().enumerate() {
+            form.push(Part::request(request).content_id(&format!("<{}>", content_id(i))));
+        }
+        let res = self.client.post(&self.url).multipart(form).await?;
+        let form = Form::from_response(res).ok_or_else(|| Error::Protocol(ProtocolError::invalid_input("Batch response is not multipart")))?;
+        let mut by_id: HashMap<String, InMemoryResponse> = HashMap::new();
+        for part in form.parts {
+            let Some(id) = part.header_str("content-id") else {
+                continue;
+            };
+            // Servers answer `<item-1>` with `<response-item-1>`.
+            let id = id.trim_matches(|c| c == '<' || c == '>');
+            let id = id.strip_prefix("response-").unwrap_or(id).to_string();
+            by_id.insert(id, part.body);
+        }
+        Ok((0..count).map(|i| by_id.remove(&content_id(i))).collect())
+    }
+}
+
+impl Client {
+    /// Start a batch of requests, sent together to the batch endpoint at `url`. See [`Batch`].
+    #[must_use]
+    pub fn batch(&self, url: &str) -> Batch<'_> {
+        Batch::new(self, url)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::header::CONTENT_TYPE;
+    use http::StatusCode;
+
+    use crate::middleware::FakeTransport;
+    use crate::InMemoryBody;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_batch() {
+        let body = "--batch\r\nContent-Type: application/http\r\nContent-ID: <response-item-2>\r\n\r\n\
+            HTTP/1.1 404 Not Found\r\nContent-Type: text/plain\r\n\r\nmissing\r\n\
+            --batch\r\nContent-Type: application/http\r\nContent-ID: <response-item-1>\r\n\r\n\
+            HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\n\r\npony\r\n\
+            --batch--\r\n";
+        let mut res = InMemoryResponse::new(InMemoryBody::Text(body.into()));
+        res.headers_mut().insert(CONTENT_TYPE, "multipart/mixed; boundary=batch".parse().unwrap());
+        let transport = FakeTransport::new().respond(res);
+        let client = Client::new().with_middleware(transport.clone());
+
+        let mut batch = client.batch("https://example.com/batch/farm/v1");
+        batch.add(client.get("https://example.com/farm/v1/animals/pony?fields=name"));
+        batch.add(client.delete("https://example.com/farm/v1/animals/sheep"));
+        batch.add(client.put("https://example.com/farm/v1/animals/goat").text("bleat".to_string()));
+        let responses = batch.send().await.unwrap();
+        let [Some(pony), Some(sheep), None] = &responses[..] else {
+            panic!("Unexpected responses {responses:?}")
+        };
+        assert_eq!(pony.status(), StatusCode::OK);
+        assert!(matches!(pony.body(), InMemoryBody::Text(t) if t == "pony"));
+        assert_eq!(sheep.status(), StatusCode::NOT_FOUND);
+
+        let sent = &transport.requests()[0];
+        assert!(sent.headers()[CONTENT_TYPE].to_str().unwrap().starts_with("multipart/mixed; boundary="));
+        let InMemoryBody::Text(sent) = sent.body() else { panic!("Expected a text body") };
+        assert!(sent.contains("content-id: <item-1>\r\n\r\nGET /farm/v1/animals/pony?fields=name"));
+        assert!(sent.contains("content-id: <item-2>\r\n\r\nDELETE /farm/v1/animals/sheep\r\n"));
+        assert!(sent.contains("PUT /farm/v1/animals/goat\r\ncontent-type: text/plain\r\n"));
+        assert!(sent.contains("user-agent: httpclient/"));
+        // The headers end with a blank line, then the body.
+        assert!(sent.contains("\r\n\r\nbleat\r\n--"));
+    }
+}
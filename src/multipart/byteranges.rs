@@ -0,0 +1,128 @@
+use std::fmt::Write;
+
+use http::header::{CONTENT_RANGE, CONTENT_TYPE, RANGE};
+use http::{HeaderMap, StatusCode};
+
+use crate::error::{ProtocolError, ProtocolResult};
+use crate::multipart::parse::{boundary, parse_parts};
+use crate::{InMemoryResponse, RequestBuilder};
+
+/// A parsed `Content-Range: bytes <start>-<end>/<total>` header. `end` is inclusive, like in the header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContentRange {
+    pub start: u64,
+    pub end: u64,
+    /// `None` if the server sent `*` because it doesn't know the total length.
+    pub total: Option<u64>,
+}
+
+impl ContentRange {
+    #[must_use]
+    pub fn parse(value: &str) -> Option<Self> {
+        let range = value.trim().strip_prefix("bytes ")?;
+        let (range, total) = range.split_once('/')?;
+        let (start, end) = range.split_once('-')?;
+        Some(ContentRange {
+            start: start.trim().parse().ok()?,
+            end: end.trim().parse().ok()?,
+            total: match total.trim() {
+                "*" => None,
+                total => Some(total.parse().ok()?),
+            },
+        })
+    }
+
+    fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        Self::parse(headers.get(CONTENT_RANGE)?.to_str().ok()?)
+    }
+}
+
+/// One range of a `206 Partial Content` response.
+#[derive(Debug, Clone)]
+pub struct ByteRange {
+    pub content_range: ContentRange,
+    pub content_type: Option<String>,
+    pub bytes: Vec<u8>,
+}
+
+/// Split a `206 Partial Content` response into its ranges. Handles both `multipart/byteranges`
+/// (multiple ranges) and a single range with a `Content-Range` header.
+pub fn byteranges(res: InMemoryResponse) -> ProtocolResult<Vec<ByteRange>> {
+    if res.status() != StatusCode::PARTIAL_CONTENT {
+        return Err(ProtocolError::invalid_input("Expected a 206 Partial Content response"));
+    }
+    let (parts, body) = res.into_parts();
+    let content_type = parts.headers.get(CONTENT_TYPE).and_then(|v| v.to_str().ok()).map(ToString::to_string);
+    let bytes: Vec<u8> = body.into();
+    match content_type {
+        Some(ct) if ct.trim_start().to_ascii_lowercase().starts_with("multipart/byteranges") => {
+            let boundary = boundary(&ct).ok_or_else(|| ProtocolError::invalid_input("multipart/byteranges response has no boundary"))?;
+            parse_parts(boundary, &bytes)?
+                .into_iter()
+                .map(|part| {
+                    Ok(ByteRange {
+                        content_range: ContentRange::from_headers(&part.headers).ok_or_else(|| ProtocolError::invalid_input("Part has no valid Content-Range"))?,
+                        content_type: part.header_str(CONTENT_TYPE).map(ToString::to_string),
+                        bytes: part.body.into(),
+                    })
+                })
+                .collect()
+        }
+        content_type => Ok(vec![ByteRange {
+            content_range: ContentRange::from_headers(&parts.headers).ok_or_else(|| ProtocolError::invalid_input("Response has no valid Content-Range"))?,
+            content_type,
+            bytes,
+        }]),
+    }
+}
+
+impl<C, B> RequestBuilder<'_, C, B> {
+    /// Request byte ranges, e.g. `&[(0, 99), (200, 299)]`. Both ends are inclusive, like in the `Range` header.
+    /// Use [`byteranges`] to split the response.
+    #[must_use]
+    pub fn ranges(mut self, ranges: &[(u64, u64)]) -> Self {
+        let mut value = "bytes=".to_string();
+        for (i, (start, end)) in ranges.iter().enumerate() {
+            let sep = if i == 0 { "" } else { "," };
+            let _ = write!(value, "{sep}{start}-{end}");
+        }
+        self.headers.insert(RANGE, value.parse().expect("Range header is valid"));
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Client, InMemoryBody, Method};
+
+    use super::*;
+
+    #[test]
+    fn test_byteranges() {
+        let client = Client::new();
+        let req = client.request(Method::GET, "https://example.com/file").ranges(&[(0, 3), (10, 12)]);
+        assert_eq!(req.headers.get(RANGE).unwrap(), "bytes=0-3,10-12");
+
+        let body = "--b\r\nContent-Type: text/plain\r\nContent-Range: bytes 0-3/20\r\n\r\nabcd\r\n--b\r\nContent-Range: bytes 10-12/20\r\n\r\nklm\r\n--b--\r\n";
+        let res: InMemoryResponse = http::Response::builder()
+            .status(StatusCode::PARTIAL_CONTENT)
+            .header(CONTENT_TYPE, "multipart/byteranges; boundary=b")
+            .body(InMemoryBody::Text(body.into()))
+            .unwrap();
+        let ranges = byteranges(res).unwrap();
+        assert_eq!(ranges.len(), 2);
+        assert_eq!(ranges[0].content_range, ContentRange::parse("bytes 0-3/20").unwrap());
+        assert_eq!((ranges[0].content_range.start, ranges[0].content_range.end), (0, 3));
+        assert_eq!(ranges[0].content_type.as_deref(), Some("text/plain"));
+        assert_eq!(ranges[1].bytes, b"klm");
+
+        let single: InMemoryResponse = http::Response::builder()
+            .status(StatusCode::PARTIAL_CONTENT)
+            .header(CONTENT_RANGE, "bytes 5-6/*")
+            .body(InMemoryBody::Bytes(vec![1, 2].into()))
+            .unwrap();
+        let ranges = byteranges(single).unwrap();
+        assert_eq!(ranges[0].content_range.total, None);
+        assert_eq!(ranges[0].bytes, vec![1, 2]);
+    }
+}
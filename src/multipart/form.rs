@@ -1,7 +1,11 @@
-use http::header::CONTENT_TYPE;
-use crate::{InMemoryResponse, InMemoryResponseExt, multipart};
+use crate::error::{ProtocolError, ProtocolResult};
+use crate::multipart::parse;
 use crate::multipart::part::Part;
 use crate::multipart::{write_boundary, write_headers, write_terminate, WriteBytes};
+use crate::{multipart, Body, InMemoryBody, InMemoryRequest, InMemoryResponse, InMemoryResponseExt};
+use futures::{stream, Stream, StreamExt};
+use http::header::CONTENT_TYPE;
+use hyper::body::Bytes;
 
 /// Form<B> does not have headers. This is an intentional design decision, because
 /// if you have a request body that's multipart, you have a Request<Form<B>>, and the request
@@ -38,6 +42,8 @@ impl Form<InMemoryResponse> {
             let (headers, mut part) = multipart::parse_headers(part)?;
             debug_assert!(part.starts_with("\r\n"));
             part = &part[2..];
+            // The line break before the next boundary belongs to the boundary.
+            part = part.strip_suffix("\r\n").unwrap_or(part);
             let body = multipart::parse_response(part)?;
             form.push(Part { headers, body });
         }
@@ -45,6 +51,25 @@ impl Form<InMemoryResponse> {
     }
 }
 
+impl Form<InMemoryBody> {
+    /// Parse a multipart body. Part bodies are kept as bytes unless they are valid UTF-8 text.
+    pub fn parse(content_type: &str, body: &[u8]) -> ProtocolResult<Self> {
+        let boundary = multipart::boundary(content_type).ok_or_else(|| ProtocolError::invalid_input("Multipart content type has no boundary"))?;
+        Ok(Form {
+            content_type: content_type.split(';').next().unwrap_or_default().trim().to_string(),
+            boundary: boundary.to_string(),
+            parts: parse::parse_parts(boundary, body)?,
+        })
+    }
+
+    /// Parse the multipart body of a request, e.g. an incoming `multipart/form-data` upload.
+    pub fn from_request(req: InMemoryRequest) -> ProtocolResult<Self> {
+        let content_type = req.headers().get(CONTENT_TYPE).and_then(|v| v.to_str().ok()).unwrap_or_default().to_string();
+        let body = req.into_body().into_wire_bytes()?;
+        Self::parse(&content_type, &body)
+    }
+}
+
 impl<B> Form<B> {
     #[must_use]
     pub fn full_content_type(&self) -> String {
@@ -115,3 +140,22 @@ impl<T: WriteBytes> From<Form<T>> for Vec<u8> {
         buf
     }
 }
+
+impl Form<Body> {
+    /// The encoded form as a stream, reading each part's body only as the stream reaches it.
+    /// Its length isn't known up front, so it's sent with `Transfer-Encoding: chunked`.
+    pub fn into_stream(self) -> impl Stream<Item = ProtocolResult<Bytes>> + Send {
+        let boundary = self.boundary;
+        let mut end = Vec::new();
+        write_terminate(&mut end, boundary.as_bytes());
+        let parts = stream::iter(self.parts).flat_map(move |part| {
+            let mut head = Vec::new();
+            write_boundary(&mut head, boundary.as_bytes());
+            write_headers(&mut head, &part.headers);
+            stream::once(async { Ok(Bytes::from(head)) })
+                .chain(part.body)
+                .chain(stream::once(async { Ok(Bytes::from_static(b"\r\n")) }))
+        });
+        parts.chain(stream::once(async { Ok(Bytes::from(end)) }))
+    }
+}
@@ -1,11 +1,17 @@
 use crate::{InMemoryBody, InMemoryRequest, InMemoryResponse};
+pub use batch::Batch;
+pub use byteranges::{byteranges, ByteRange, ContentRange};
 pub use form::Form;
 use http::{header, HeaderMap, StatusCode};
+pub use parse::{boundary, parse_stream, Parser};
 pub use part::Part;
 use rand::Rng;
 use std::str::FromStr;
 
+mod batch;
+mod byteranges;
 mod form;
+mod parse;
 mod part;
 
 fn gen_boundary() -> String {
@@ -81,7 +87,8 @@ fn parse_response(text: &str) -> Option<InMemoryResponse> {
     let _reason = split.next()?;
 
     let (headers, text) = parse_headers(text)?;
-    let body = InMemoryBody::Text(text.to_string());
+    let text = text.strip_prefix("\r\n").unwrap_or(text);
+    let body = InMemoryBody::Text(text.into());
     let mut res = http::Response::builder().status(status);
     *res.headers_mut().unwrap() = headers;
     res.body(body).ok()
@@ -118,15 +125,19 @@ pub trait WriteBytes {
 impl WriteBytes for InMemoryRequest {
     fn write(self, buf: &mut Vec<u8>) {
         let method = self.method().as_str();
-        let uri = self.uri().path();
+        let uri = self.uri().path_and_query().map_or("/", |p| p.as_str());
         buf.extend_from_slice(method.as_bytes());
         buf.extend(b" ");
         buf.extend_from_slice(uri.as_bytes());
-        let body = self.into_body();
-        if !body.is_empty() {
+        for (key, value) in self.headers() {
             buf.extend_from_slice(b"\r\n");
+            buf.extend_from_slice(key.as_str().as_bytes());
+            buf.extend_from_slice(b": ");
+            buf.extend_from_slice(value.as_bytes());
         }
-        body.write(buf);
+        // End the last header line, then a blank line ends the headers, even without a body.
+        buf.extend_from_slice(b"\r\n\r\n");
+        self.into_body().write(buf);
     }
 }
 
@@ -153,7 +164,8 @@ impl WriteBytes for InMemoryBody {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::Request;
+    use crate::{Client, ReplayableBody, Request};
+    use futures::stream;
     use serde_json::json;
 
     #[test]
@@ -169,10 +181,23 @@ mod tests {
 
         let bytes: Vec<u8> = form.into();
         let s = String::from_utf8(bytes).expect("Unable to convert bytes to string");
-        let right = format!("--{0}\r\ncontent-type: application/http\r\n\r\nGET /farm/v1/animals/pony\r\n--{0}--\r\n", &boundary);
+        let right = format!("--{0}\r\ncontent-type: application/http\r\n\r\nGET /farm/v1/animals/pony\r\n\r\n\r\n--{0}--\r\n", &boundary);
         assert_eq!(s, right);
     }
 
+    #[test]
+    fn test_bodyless_request_part() {
+        let request = Request::builder()
+            .method("DELETE")
+            .uri("/farm/v1/animals/sheep")
+            .header("if-match", "\"v1\"")
+            .body(InMemoryBody::Empty)
+            .unwrap();
+        let mut buf = Vec::new();
+        request.write(&mut buf);
+        assert_eq!(String::from_utf8(buf).unwrap(), "DELETE /farm/v1/animals/sheep\r\nif-match: \"v1\"\r\n\r\n");
+    }
+
     #[test]
     fn test_to_bytes2() {
         let boundary = "zzz".to_string();
@@ -199,4 +224,23 @@ mod tests {
         let right = "--zzz\r\ncontent-disposition: form-data; name=\"MetaData\"\r\n\r\n{\"Content\":\"message\",\"DisputeTypeCode\":\"BackupRequest\",\"DisputeTypeDescription\":\"Backup Request\",\"Documents\":[],\"TransactionId\":1}\r\n--zzz--\r\n";
         assert_eq!(s, right);
     }
+
+    #[tokio::test]
+    async fn test_multipart_stream() {
+        let chunks = stream::iter(vec![Ok::<_, std::io::Error>("ab"), Ok("cd")]);
+        let form = Form::form_data()
+            .boundary("zzz".to_string())
+            .part(Part::text("hello".to_string()).into())
+            .part(Part::stream(chunks).header(header::CONTENT_DISPOSITION, "form-data; name=\"file\"".parse().unwrap()));
+        let client = Client::new();
+        let req = client.post("/upload").multipart_stream(form).build();
+        assert_eq!(req.headers()[header::CONTENT_TYPE], "multipart/form-data; boundary=zzz");
+        let (body, len) = req.extensions().get::<ReplayableBody>().unwrap().body().unwrap();
+        assert_eq!(len, None);
+        let bytes = hyper::body::to_bytes(body).await.unwrap();
+        let right = "--zzz\r\ncontent-type: text/plain\r\n\r\nhello\r\n\
+            --zzz\r\ncontent-type: application/octet-stream\r\ncontent-disposition: form-data; name=\"file\"\r\n\r\nabcd\r\n\
+            --zzz--\r\n";
+        assert_eq!(String::from_utf8(bytes.to_vec()).unwrap(), right);
+    }
 }
@@ -0,0 +1,287 @@
+use std::collections::VecDeque;
+
+use futures::stream::{self, Stream, StreamExt};
+use http::header::{HeaderName, CONTENT_TYPE};
+use http::{HeaderMap, HeaderValue};
+use hyper::body::HttpBody;
+
+use crate::error::{ProtocolError, ProtocolResult};
+use crate::multipart::Part;
+use crate::{Body, InMemoryBody};
+
+fn invalid(msg: &str) -> ProtocolError {
+    ProtocolError::invalid_input(format!("Invalid multipart body: {msg}"))
+}
+
+fn find(haystack: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+    haystack.get(from..)?.windows(needle.len()).position(|w| w == needle).map(|i| i + from)
+}
+
+/// Read the `boundary` parameter of a multipart content type, e.g. `multipart/mixed; boundary="abc"`.
+#[must_use]
+pub fn boundary(content_type: &str) -> Option<&str> {
+    content_type.split(';').skip(1).find_map(|param| {
+        let (k, v) = param.split_once('=')?;
+        k.trim().eq_ignore_ascii_case("boundary").then(|| v.trim().trim_matches('"'))
+    })
+}
+
+fn parse_part_headers(block: &[u8]) -> ProtocolResult<HeaderMap> {
+    let mut headers = HeaderMap::new();
+    for line in block.split(|&b| b == b'\n') {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        if line.is_empty() {
+            continue;
+        }
+        let colon = line.iter().position(|&b| b == b':').ok_or_else(|| invalid("header line without a colon"))?;
+        let name = HeaderName::from_bytes(&line[..colon]).map_err(|_| invalid("bad header name"))?;
+        let value = HeaderValue::from_bytes(line[colon + 1..].trim_ascii()).map_err(|_| invalid("bad header value"))?;
+        headers.append(name, value);
+    }
+    Ok(headers)
+}
+
+/// Binary parts stay bytes; everything else that is valid UTF-8 becomes text.
+fn part_body(headers: &HeaderMap, bytes: Vec<u8>) -> InMemoryBody {
+    if bytes.is_empty() {
+        return InMemoryBody::Empty;
+    }
+    let content_type = headers.get(CONTENT_TYPE).and_then(|v| v.to_str().ok()).and_then(|t| t.split(';').next());
+    if content_type == Some("application/octet-stream") {
+        return InMemoryBody::Bytes(bytes.into());
+    }
+    match String::from_utf8(bytes) {
+        Ok(s) => InMemoryBody::Text(s.into()),
+        Err(e) => InMemoryBody::Bytes(e.into_bytes().into()),
+    }
+}
+
+#[derive(Debug)]
+enum State {
+    Preamble,
+    AfterDelimiter,
+    Headers,
+    Body(HeaderMap),
+    Done,
+}
+
+enum Step {
+    NeedInput,
+    Continue,
+    Part(Part<InMemoryBody>),
+}
+
+/// Incremental multipart parser. Feed it chunks as they arrive; each part is returned as soon as its
+/// closing delimiter has been read, so only one part at a time is held in memory.
+#[derive(Debug)]
+pub struct Parser {
+    /// `\r\n--boundary`
+    delimiter: Vec<u8>,
+    buf: Vec<u8>,
+    /// Where to resume searching for the delimiter.
+    scanned: usize,
+    state: State,
+}
+
+impl Parser {
+    #[must_use]
+    pub fn new(boundary: &str) -> Self {
+        Parser {
+            delimiter: format!("\r\n--{boundary}").into_bytes(),
+            // The first delimiter isn't preceded by a line break, so add one to find it like the others.
+            buf: b"\r\n".to_vec(),
+            scanned: 0,
+            state: State::Preamble,
+        }
+    }
+
+    /// Whether the closing delimiter has been read.
+    #[must_use]
+    pub fn is_done(&self) -> bool {
+        matches!(self.state, State::Done)
+    }
+
+    /// Add a chunk of the body, returning the parts it completed.
+    pub fn feed(&mut self, chunk: &[u8]) -> ProtocolResult<Vec<Part<InMemoryBody>>> {
+        if !self.is_done() {
+            self.buf.extend_from_slice(chunk);
+        }
+        let mut parts = Vec::new();
+        loop {
+            match self.step()? {
+                Step::NeedInput => break,
+                Step::Continue => {}
+                Step::Part(part) => parts.push(part),
+            }
+        }
+        Ok(parts)
+    }
+
+    /// Call once the body has ended. Fails if the closing delimiter is missing.
+    pub fn finish(&self) -> ProtocolResult<()> {
+        if self.is_done() {
+            Ok(())
+        } else {
+            Err(invalid("body ended before the closing delimiter"))
+        }
+    }
+
+    fn step(&mut self) -> ProtocolResult<Step> {
+        match std::mem::replace(&mut self.state, State::Done) {
+            State::Preamble => {
+                let Some(i) = find(&self.buf, &self.delimiter, self.scanned) else {
+                    self.keep_tail();
+                    self.state = State::Preamble;
+                    return Ok(Step::NeedInput);
+                };
+                self.consume(i + self.delimiter.len());
+                self.state = State::AfterDelimiter;
+                Ok(Step::Continue)
+            }
+            State::AfterDelimiter => {
+                let Some(eol) = find(&self.buf, b"\r\n", 0) else {
+                    self.state = State::AfterDelimiter;
+                    // `--` ends the body, even without a trailing line break.
+                    if self.buf.starts_with(b"--") {
+                        self.state = State::Done;
+                    }
+                    return Ok(Step::NeedInput);
+                };
+                if self.buf.starts_with(b"--") {
+                    self.state = State::Done;
+                    return Ok(Step::NeedInput);
+                }
+                // Ignore transport padding after the delimiter.
+                if !self.buf[..eol].iter().all(|b| *b == b' ' || *b == b'\t') {
+                    return Err(invalid("unexpected data after delimiter"));
+                }
+                self.consume(eol + 2);
+                self.state = State::Headers;
+                Ok(Step::Continue)
+            }
+            State::Headers => {
+                let end = if self.buf.starts_with(b"\r\n") {
+                    Some(0)
+                } else {
+                    find(&self.buf, b"\r\n\r\n", 0).map(|i| i + 2)
+                };
+                let Some(end) = end else {
+                    self.state = State::Headers;
+                    return Ok(Step::NeedInput);
+                };
+                let headers = parse_part_headers(&self.buf[..end])?;
+                self.consume(end + 2);
+                self.state = State::Body(headers);
+                Ok(Step::Continue)
+            }
+            State::Body(headers) => {
+                let Some(i) = find(&self.buf, &self.delimiter, self.scanned) else {
+                    self.scanned = self.buf.len().saturating_sub(self.delimiter.len() - 1);
+                    self.state = State::Body(headers);
+                    return Ok(Step::NeedInput);
+                };
+                let body = self.buf[..i].to_vec();
+                self.consume(i + self.delimiter.len());
+                self.state = State::AfterDelimiter;
+                let body = part_body(&headers, body);
+                Ok(Step::Part(Part { headers, body }))
+            }
+            State::Done => Ok(Step::NeedInput),
+        }
+    }
+
+    fn consume(&mut self, n: usize) {
+        self.buf.drain(..n);
+        self.scanned = 0;
+    }
+
+    /// Drop data that can't be part of a delimiter.
+    fn keep_tail(&mut self) {
+        let keep = self.delimiter.len() - 1;
+        if self.buf.len() > keep {
+            self.buf.drain(..self.buf.len() - keep);
+        }
+    }
+}
+
+/// Parse a complete multipart body.
+pub(crate) fn parse_parts(boundary: &str, body: &[u8]) -> ProtocolResult<Vec<Part<InMemoryBody>>> {
+    let mut parser = Parser::new(boundary);
+    let parts = parser.feed(body)?;
+    parser.finish()?;
+    Ok(parts)
+}
+
+/// Parse a multipart body as it streams in, yielding each part once it is complete.
+pub fn parse_stream(boundary: &str, body: Body) -> impl Stream<Item = ProtocolResult<Part<InMemoryBody>>> {
+    let chunks = match body {
+        Body::InMemory(m) => stream::iter(vec![m.into_wire_bytes().map_err(ProtocolError::from)]).boxed(),
+        Body::Hyper(b) => stream::unfold(b, |mut b| async move { b.data().await.map(|chunk| (chunk.map_err(ProtocolError::from), b)) }).boxed(),
+    };
+    let state = (chunks, Parser::new(boundary), VecDeque::new(), false);
+    stream::unfold(state, |(mut chunks, mut parser, mut pending, ended)| async move {
+        loop {
+            if let Some(part) = pending.pop_front() {
+                return Some((Ok(part), (chunks, parser, pending, ended)));
+            }
+            if ended {
+                return None;
+            }
+            match chunks.next().await {
+                Some(Ok(chunk)) => match parser.feed(&chunk) {
+                    Ok(parts) => pending.extend(parts),
+                    Err(e) => return Some((Err(e), (chunks, parser, pending, true))),
+                },
+                Some(Err(e)) => return Some((Err(e), (chunks, parser, pending, true))),
+                None => {
+                    let finished = parser.finish();
+                    return finished.err().map(|e| (Err(e), (chunks, parser, pending, true)));
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_binary_incrementally() {
+        let mut body = b"preamble\r\n--zzz\r\nContent-Type: application/octet-stream\r\n\r\n".to_vec();
+        body.extend_from_slice(&[0xff, 0x00, b'\r', b'\n', 0xfe]);
+        body.extend_from_slice(b"\r\n--zzz\r\ncontent-disposition: form-data; name=\"a\"\r\n\r\nhello\r\n--zzz\r\n\r\n\r\n--zzz--\r\n");
+
+        let parts = parse_parts("zzz", &body).unwrap();
+        assert_eq!(parts.len(), 3);
+        assert!(matches!(&parts[0].body, InMemoryBody::Bytes(b) if b == &[0xff, 0x00, b'\r', b'\n', 0xfe][..]));
+        assert_eq!(parts[1].header_str("content-disposition"), Some("form-data; name=\"a\""));
+        assert!(matches!(&parts[1].body, InMemoryBody::Text(t) if t == "hello"));
+        assert!(matches!(parts[2].body, InMemoryBody::Empty));
+
+        // the same result, one byte at a time
+        let mut parser = Parser::new("zzz");
+        let mut streamed = Vec::new();
+        for b in &body {
+            streamed.extend(parser.feed(&[*b]).unwrap());
+        }
+        parser.finish().unwrap();
+        assert_eq!(format!("{streamed:?}"), format!("{parts:?}"));
+
+        assert!(parse_parts("zzz", b"--zzz\r\n\r\ntruncated").is_err());
+        assert_eq!(boundary("multipart/mixed; Boundary=\"a b\""), Some("a b"));
+    }
+
+    #[tokio::test]
+    async fn test_parse_stream() {
+        let (mut tx, body) = hyper::Body::channel();
+        tokio::spawn(async move {
+            for chunk in ["--b\r\n\r\none\r\n--", "b\r\n\r\ntwo", "\r\n--b--"] {
+                tx.send_data(chunk.into()).await.unwrap();
+            }
+        });
+        let parts = parse_stream("b", Body::Hyper(body)).collect::<Vec<_>>().await;
+        assert_eq!(parts.len(), 2);
+        assert!(matches!(&parts[1], Ok(Part { body: InMemoryBody::Text(t), .. }) if t == "two"));
+    }
+}
@@ -1,8 +1,10 @@
-use http::{header, HeaderMap, HeaderValue};
-use http::header::{AsHeaderName, CONTENT_TYPE, IntoHeaderName};
-use crate::{InMemoryBody, InMemoryRequest, multipart};
-use crate::multipart::WriteBytes;
 use crate::multipart::form::Form;
+use crate::multipart::WriteBytes;
+use crate::{multipart, Body, InMemoryBody, InMemoryRequest};
+use futures::Stream;
+use http::header::{AsHeaderName, IntoHeaderName, CONTENT_TYPE};
+use http::{header, HeaderMap, HeaderValue};
+use hyper::body::Bytes;
 
 impl<T: WriteBytes> WriteBytes for Part<T> {
     fn write(self, buf: &mut Vec<u8>) {
@@ -36,7 +38,6 @@ impl<B> Part<B> {
         self.headers.insert(h, v);
         self
     }
-
 }
 
 impl Part<InMemoryRequest> {
@@ -51,13 +52,19 @@ impl Part<InMemoryBody> {
     pub fn text(body: String) -> Self {
         let mut headers = HeaderMap::new();
         headers.insert(header::CONTENT_TYPE, "text/plain".parse().expect("Unable to parse content type"));
-        Part { headers, body: InMemoryBody::Text(body) }
+        Part {
+            headers,
+            body: InMemoryBody::Text(body.into()),
+        }
     }
 
     pub fn html(body: String) -> Self {
         let mut headers = HeaderMap::new();
         headers.insert(header::CONTENT_TYPE, "text/html".parse().expect("Unable to parse content type"));
-        Part { headers, body: InMemoryBody::Text(body) }
+        Part {
+            headers,
+            body: InMemoryBody::Text(body.into()),
+        }
     }
 
     pub fn form(form: Form<InMemoryBody>) -> Self {
@@ -65,15 +72,41 @@ impl Part<InMemoryBody> {
         headers.insert(CONTENT_TYPE, form.full_content_type().parse().expect("Unable to parse content type"));
         let body: Vec<u8> = form.into();
         let body = match String::from_utf8(body) {
-            Ok(s) => InMemoryBody::Text(s),
-            Err(e) => {
-                InMemoryBody::Bytes(e.into_bytes())
-            }
+            Ok(s) => InMemoryBody::Text(s.into()),
+            Err(e) => InMemoryBody::Bytes(e.into_bytes().into()),
         };
         Part { headers, body }
     }
 }
 
+impl Part<Body> {
+    /// A part read from `stream` while the request is sent, e.g. a file or a generator, so it's never held in memory.
+    /// Send a form with streamed parts with `RequestBuilder::multipart_stream`.
+    pub fn stream<S, O, E>(stream: S) -> Self
+    where
+        S: Stream<Item = Result<O, E>> + Send + 'static,
+        O: Into<Bytes> + 'static,
+        E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/octet-stream"));
+        Part {
+            headers,
+            body: Body::Hyper(hyper::Body::wrap_stream(stream)),
+        }
+    }
+}
+
+/// Use an in-memory part in a form with streamed parts.
+impl From<Part<InMemoryBody>> for Part<Body> {
+    fn from(part: Part<InMemoryBody>) -> Self {
+        Part {
+            headers: part.headers,
+            body: Body::InMemory(part.body),
+        }
+    }
+}
+
 impl<T: Default> Default for Part<T> {
     fn default() -> Self {
         Part::new(HeaderMap::new(), T::default())
@@ -93,8 +126,8 @@ impl Into<InMemoryBody> for Part<InMemoryBody> {
         let mut buf = Vec::new();
         self.write(&mut buf);
         match String::from_utf8(buf) {
-            Ok(s) => InMemoryBody::Text(s),
-            Err(e) => InMemoryBody::Bytes(e.into_bytes())
+            Ok(s) => InMemoryBody::Text(s.into()),
+            Err(e) => InMemoryBody::Bytes(e.into_bytes().into()),
         }
     }
 }
@@ -0,0 +1,446 @@
+use std::fmt::{Debug, Write};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use futures::stream::{self, Stream, StreamExt};
+use http::uri::PathAndQuery;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::{InMemoryResponse, InMemoryResponseExt, InMemoryResult, RequestBuilder, Uri};
+
+/// A fetched page, handed to a [`PageStrategy`] to decide which page comes next.
+#[derive(Debug)]
+pub struct Page<'a> {
+    /// The url the page was fetched from.
+    pub uri: &'a Uri,
+    pub response: &'a InMemoryResponse,
+    /// The response body, if it was JSON.
+    pub json: Option<&'a Value>,
+    /// The number of items found on the page.
+    pub num_items: usize,
+}
+
+/// Decide the url of the next page. Implement this to support custom pagination schemes.
+pub trait PageStrategy: Send + Sync + Debug {
+    /// Return the url of the next page, or `None` if `page` is the last one.
+    fn next_page(&self, page: &Page<'_>) -> Option<Uri>;
+}
+
+/// Follow the `rel="next"` url of the `Link` header (RFC 8288).
+#[derive(Debug, Clone)]
+pub struct LinkHeader;
+
+/// Increment a page number query parameter, until a page has no items.
+#[derive(Debug, Clone)]
+pub struct PageNumber {
+    pub param: String,
+    pub first: u64,
+}
+
+/// Increment an offset query parameter by the number of items received, until a page has no items.
+#[derive(Debug, Clone)]
+pub struct Offset {
+    pub param: String,
+}
+
+/// Send back a cursor found in the response body, until the cursor is missing or empty.
+#[derive(Debug, Clone)]
+pub struct Cursor {
+    pub param: String,
+    /// JSON pointer to the next cursor in the response body, e.g. `/meta/next_cursor`
+    pub pointer: String,
+}
+
+/// Read a query parameter, as it appears in the url.
+fn query_value<'u>(uri: &'u Uri, key: &str) -> Option<&'u str> {
+    let key = urlencoding::encode(key);
+    uri.query()?.split('&').find_map(|pair| {
+        let (k, v) = pair.split_once('=')?;
+        (k == key).then_some(v)
+    })
+}
+
+/// Read an integer query parameter.
+fn query_param(uri: &Uri, key: &str) -> Option<u64> {
+    query_value(uri, key)?.parse().ok()
+}
+
+/// Set a query parameter, replacing any existing value.
+fn set_query_param(uri: &Uri, key: &str, value: &str) -> Option<Uri> {
+    let mut parts = uri.clone().into_parts();
+    let pq = parts.path_and_query.as_ref().map_or("/", PathAndQuery::path);
+    let key = urlencoding::encode(key);
+    let mut query = uri
+        .query()
+        .unwrap_or_default()
+        .split('&')
+        .filter(|pair| !pair.is_empty() && pair.split('=').next() != Some(key.as_ref()))
+        .collect::<Vec<_>>()
+        .join("&");
+    if !query.is_empty() {
+        query.push('&');
+    }
+    let _ = write!(query, "{}={}", key, urlencoding::encode(value));
+    parts.path_and_query = Some(PathAndQuery::from_str(&format!("{pq}?{query}")).ok()?);
+    Uri::from_parts(parts).ok()
+}
+
+/// Remove `.` and `..` segments from an absolute path (RFC 3986, section 5.2.4).
+fn remove_dot_segments(path: &str) -> String {
+    let segments = path.split('/').collect::<Vec<_>>();
+    let mut out: Vec<&str> = Vec::new();
+    for (i, segment) in segments.iter().enumerate() {
+        let last = i == segments.len() - 1;
+        match *segment {
+            "." | ".." => {
+                if *segment == ".." && out.len() > 1 {
+                    out.pop();
+                }
+                if last {
+                    out.push("");
+                }
+            }
+            segment => out.push(segment),
+        }
+    }
+    let path = out.join("/");
+    if path.starts_with('/') {
+        path
+    } else {
+        format!("/{path}")
+    }
+}
+
+/// Resolve a url reference, like the target of a `Link` header, against the url it was found on
+/// (RFC 3986, section 5.2). Returns `None` if the result isn't a valid url.
+fn resolve_reference(base: &Uri, reference: &str) -> Option<Uri> {
+    let reference = reference.split('#').next().unwrap_or_default();
+    let has_scheme = reference
+        .split_once(':')
+        .is_some_and(|(scheme, _)| scheme.starts_with(|c: char| c.is_ascii_alphabetic()) && scheme.chars().all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c)));
+    if has_scheme {
+        return Uri::from_str(reference).ok();
+    }
+    let scheme = base.scheme_str()?;
+    if let Some(rest) = reference.strip_prefix("//") {
+        return Uri::from_str(&format!("{scheme}://{rest}")).ok();
+    }
+    let authority = base.authority()?;
+    let (path, query) = match reference.split_once('?') {
+        Some((path, query)) => (path, Some(query)),
+        None => (reference, None),
+    };
+    let (path, query) = if path.is_empty() {
+        (base.path().to_string(), query.or(base.query()))
+    } else if path.starts_with('/') {
+        (remove_dot_segments(path), query)
+    } else {
+        let dir = base.path().rsplit_once('/').map_or("", |(dir, _)| dir);
+        (remove_dot_segments(&format!("{dir}/{path}")), query)
+    };
+    let query = query.map(|q| format!("?{q}")).unwrap_or_default();
+    Uri::from_str(&format!("{scheme}://{authority}{path}{query}")).ok()
+}
+
+impl PageStrategy for LinkHeader {
+    fn next_page(&self, page: &Page<'_>) -> Option<Uri> {
+        let links = page.response.links();
+        resolve_reference(page.uri, links.get("next")?)
+    }
+}
+
+impl PageStrategy for PageNumber {
+    fn next_page(&self, page: &Page<'_>) -> Option<Uri> {
+        if page.num_items == 0 {
+            return None;
+        }
+        let current = query_param(page.uri, &self.param).unwrap_or(self.first);
+        set_query_param(page.uri, &self.param, &(current + 1).to_string())
+    }
+}
+
+impl PageStrategy for Offset {
+    fn next_page(&self, page: &Page<'_>) -> Option<Uri> {
+        if page.num_items == 0 {
+            return None;
+        }
+        let current = query_param(page.uri, &self.param).unwrap_or(0);
+        set_query_param(page.uri, &self.param, &(current + page.num_items as u64).to_string())
+    }
+}
+
+impl PageStrategy for Cursor {
+    fn next_page(&self, page: &Page<'_>) -> Option<Uri> {
+        let cursor = match page.json?.pointer(&self.pointer)? {
+            Value::String(s) if !s.is_empty() => s.clone(),
+            Value::Number(n) => n.to_string(),
+            _ => return None,
+        };
+        if query_value(page.uri, &self.param) == Some(urlencoding::encode(&cursor).as_ref()) {
+            return None;
+        }
+        set_query_param(page.uri, &self.param, &cursor)
+    }
+}
+
+/// How to find the next page, and where the items are in each page.
+#[derive(Debug, Clone)]
+pub struct Pagination {
+    strategy: Arc<dyn PageStrategy>,
+    items_pointer: Option<String>,
+}
+
+impl Pagination {
+    pub fn new<S: PageStrategy + 'static>(strategy: S) -> Self {
+        Pagination {
+            strategy: Arc::new(strategy),
+            items_pointer: None,
+        }
+    }
+
+    /// Follow the `rel="next"` url of the `Link` header.
+    #[must_use]
+    pub fn link_header() -> Self {
+        Self::new(LinkHeader)
+    }
+
+    /// Increment the `param` query parameter, starting from 1.
+    #[must_use]
+    pub fn page_number(param: &str) -> Self {
+        Self::new(PageNumber {
+            param: param.to_string(),
+            first: 1,
+        })
+    }
+
+    /// Increment the `param` query parameter by the number of items received.
+    #[must_use]
+    pub fn offset(param: &str) -> Self {
+        Self::new(Offset { param: param.to_string() })
+    }
+
+    /// Send the cursor found at the JSON `pointer` of the response body as the `param` query parameter.
+    #[must_use]
+    pub fn cursor(param: &str, pointer: &str) -> Self {
+        Self::new(Cursor {
+            param: param.to_string(),
+            pointer: pointer.to_string(),
+        })
+    }
+
+    /// JSON pointer to the items array in the response body, e.g. `/data`.
+    /// By default, the body itself must be an array.
+    #[must_use]
+    pub fn items_at(mut self, pointer: &str) -> Self {
+        self.items_pointer = Some(pointer.to_string());
+        self
+    }
+
+    fn items(&self, json: Option<&Value>) -> Option<Vec<Value>> {
+        let json = json?;
+        let items = match &self.items_pointer {
+            Some(pointer) => json.pointer(pointer)?,
+            None => json,
+        };
+        items.as_array().cloned()
+    }
+}
+
+/// Fetch every page of a paginated endpoint. Create one with [`RequestBuilder::paginate`].
+#[derive(Debug)]
+pub struct Paginator<'a> {
+    request: RequestBuilder<'a>,
+    pagination: Pagination,
+}
+
+impl<'a> Paginator<'a> {
+    /// Fetch the next page, returning the response and its items, and update the request to point at the following page.
+    async fn fetch(request: RequestBuilder<'a>, pagination: &Pagination) -> (InMemoryResult<(InMemoryResponse, Vec<Value>)>, Option<RequestBuilder<'a>>) {
+        let mut next = request.clone();
+        let res = match request.await {
+            Ok(res) => res,
+            Err(e) => return (Err(e), None),
+        };
+        let json = res.body().clone().json::<Value>().ok();
+        let items = pagination.items(json.as_ref()).unwrap_or_default();
+        let page = Page {
+            uri: &next.uri,
+            response: &res,
+            json: json.as_ref(),
+            num_items: items.len(),
+        };
+        // A server that points back at the same page would otherwise be fetched forever.
+        let next = pagination.strategy.next_page(&page).filter(|uri| *uri != next.uri).map(|uri| {
+            next.uri = uri;
+            next
+        });
+        (Ok((res, items)), next)
+    }
+
+    fn raw_pages(self) -> impl Stream<Item = InMemoryResult<(InMemoryResponse, Vec<Value>)>> + 'a {
+        let Paginator { request, pagination } = self;
+        stream::unfold(Some(request), move |state| {
+            let pagination = pagination.clone();
+            async move {
+                let (page, next) = Self::fetch(state?, &pagination).await;
+                Some((page, next))
+            }
+        })
+    }
+
+    /// A stream of every page's response. The stream ends after the last page, or after the first error.
+    #[allow(clippy::result_large_err)]
+    pub fn pages(self) -> impl Stream<Item = InMemoryResult<InMemoryResponse>> + 'a {
+        self.raw_pages().map(|page| page.map(|(res, _)| res))
+    }
+
+    /// A stream of the deserialized items on every page.
+    #[allow(clippy::result_large_err)]
+    pub fn items<T: DeserializeOwned + 'a>(self) -> impl Stream<Item = InMemoryResult<T>> + 'a {
+        self.raw_pages().flat_map(|page| {
+            let items = match page {
+                Ok((_, items)) => items.into_iter().map(|v| serde_json::from_value(v).map_err(Into::into)).collect(),
+                Err(e) => vec![Err(e)],
+            };
+            stream::iter(items)
+        })
+    }
+}
+
+impl<'a> RequestBuilder<'a> {
+    /// Fetch every page of this request, following `pagination`.
+    /// ```
+    /// # use httpclient::{Client, Pagination};
+    /// # #[derive(serde::Deserialize)]
+    /// # struct User {}
+    /// # fn f(client: Client) {
+    /// let users = client.get("/users").paginate(Pagination::link_header()).items::<User>();
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn paginate(self, pagination: Pagination) -> Paginator<'a> {
+        Paginator { request: self, pagination }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::{HeaderMap, StatusCode};
+    use serde_json::json;
+
+    use crate::middleware::FakeTransport;
+    use crate::{Client, InMemoryBody};
+
+    use super::*;
+
+    fn page<'a>(uri: &'a Uri, res: &'a InMemoryResponse, json: Option<&'a Value>, num_items: usize) -> Page<'a> {
+        Page {
+            uri,
+            response: res,
+            json,
+            num_items,
+        }
+    }
+
+    #[test]
+    fn test_link_header() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "link",
+            r#"<https://api.example.com/items?page=1>; rel="prev", <https://api.example.com/items?page=3>; rel="next""#
+                .parse()
+                .unwrap(),
+        );
+        let res = <InMemoryResponse as InMemoryResponseExt>::new(StatusCode::OK, headers, InMemoryBody::Empty);
+        let uri = Uri::from_static("https://api.example.com/items?page=2");
+        let next = LinkHeader.next_page(&page(&uri, &res, None, 0)).unwrap();
+        assert_eq!(next.to_string(), "https://api.example.com/items?page=3");
+    }
+
+    #[test]
+    fn test_resolve_reference() {
+        let base = Uri::from_static("https://api.example.com/v1/items?page=1");
+        let resolve = |reference| resolve_reference(&base, reference).unwrap().to_string();
+        assert_eq!(resolve("page2"), "https://api.example.com/v1/page2");
+        assert_eq!(resolve("?page=2"), "https://api.example.com/v1/items?page=2");
+        assert_eq!(resolve("../v2/items?page=2#top"), "https://api.example.com/v2/items?page=2");
+        assert_eq!(resolve("/items/./all"), "https://api.example.com/items/all");
+        assert_eq!(resolve("//cdn.example.com/items"), "https://cdn.example.com/items");
+        assert_eq!(resolve("http://other.example.com/"), "http://other.example.com/");
+        assert_eq!(resolve(""), "https://api.example.com/v1/items?page=1");
+        assert!(resolve_reference(&base, "page 2").is_none());
+
+        let mut headers = HeaderMap::new();
+        headers.insert("link", r#"<page2>; rel="next""#.parse().unwrap());
+        let res = <InMemoryResponse as InMemoryResponseExt>::new(StatusCode::OK, headers, InMemoryBody::Empty);
+        let next = LinkHeader.next_page(&page(&base, &res, None, 0)).unwrap();
+        assert_eq!(next.to_string(), "https://api.example.com/v1/page2");
+    }
+
+    #[test]
+    fn test_set_query_param_encoded_key() {
+        let uri = Uri::from_static("https://api.example.com/items?ids%5B%5D=1");
+        let next = set_query_param(&uri, "ids[]", "2").unwrap();
+        assert_eq!(next.to_string(), "https://api.example.com/items?ids%5B%5D=2");
+        assert_eq!(query_param(&next, "ids[]"), Some(2));
+    }
+
+    #[tokio::test]
+    async fn test_stops_on_repeated_page() {
+        let link = |next: &str| {
+            http::Response::builder()
+                .header("link", format!("<{next}>; rel=\"next\""))
+                .body(InMemoryBody::Empty)
+                .unwrap()
+        };
+        let transport = FakeTransport::new().respond(link("/items?page=2")).respond(link("/items?page=2"));
+        let client = Client::new().base_url("https://api.example.com").with_middleware(transport.clone());
+        let pages: Vec<_> = client.get("/items").paginate(Pagination::link_header()).pages().collect().await;
+        assert_eq!(pages.len(), 2);
+        assert_eq!(transport.requests().len(), 2);
+
+        let body = |cursor: &str| InMemoryBody::Text(format!(r#"{{"data": [1], "next": "{cursor}"}}"#).into());
+        let transport = FakeTransport::new().respond_with(StatusCode::OK, body("abc")).respond_with(StatusCode::OK, body("abc"));
+        let client = Client::new().base_url("https://api.example.com").with_middleware(transport.clone());
+        let pages: Vec<_> = client
+            .get("/items")
+            .paginate(Pagination::cursor("cursor", "/next").items_at("/data"))
+            .pages()
+            .collect()
+            .await;
+        assert_eq!(pages.len(), 2);
+        assert_eq!(transport.requests()[1].uri(), "https://api.example.com/items?cursor=abc");
+    }
+
+    #[test]
+    fn test_page_number_and_offset() {
+        let res = <InMemoryResponse as InMemoryResponseExt>::new(StatusCode::OK, HeaderMap::new(), InMemoryBody::Empty);
+        let uri = Uri::from_static("https://api.example.com/items?q=a&page=2");
+        let strategy = PageNumber {
+            param: "page".to_string(),
+            first: 1,
+        };
+        let next = strategy.next_page(&page(&uri, &res, None, 10)).unwrap();
+        assert_eq!(next.to_string(), "https://api.example.com/items?q=a&page=3");
+        assert!(strategy.next_page(&page(&uri, &res, None, 0)).is_none());
+
+        let uri = Uri::from_static("https://api.example.com/items");
+        let next = Offset { param: "offset".to_string() }.next_page(&page(&uri, &res, None, 25)).unwrap();
+        assert_eq!(next.to_string(), "https://api.example.com/items?offset=25");
+    }
+
+    #[test]
+    fn test_cursor_and_items() {
+        let res = <InMemoryResponse as InMemoryResponseExt>::new(StatusCode::OK, HeaderMap::new(), InMemoryBody::Empty);
+        let uri = Uri::from_static("https://api.example.com/items");
+        let body = json!({"data": [1, 2], "meta": {"next": "abc"}});
+        let pagination = Pagination::cursor("cursor", "/meta/next").items_at("/data");
+        assert_eq!(pagination.items(Some(&body)).unwrap().len(), 2);
+        let next = pagination.strategy.next_page(&page(&uri, &res, Some(&body), 2)).unwrap();
+        assert_eq!(next.to_string(), "https://api.example.com/items?cursor=abc");
+
+        let last = json!({"data": [], "meta": {"next": null}});
+        assert!(pagination.strategy.next_page(&page(&uri, &res, Some(&last), 0)).is_none());
+    }
+}
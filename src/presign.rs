@@ -0,0 +1,181 @@
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use http::uri::PathAndQuery;
+use http::Method;
+use ring::hmac;
+
+use crate::{hex, RequestBuilder, Uri};
+
+/// Generate and verify presigned urls: time-limited links with an HMAC-SHA256 signature in the query string.
+///
+/// The signature covers the method, host, path and every query parameter (including the expiry),
+/// so none of them can be changed without invalidating the link.
+/// ```
+/// # use std::time::Duration;
+/// # use httpclient::presign::Presigner;
+/// # use httpclient::{Client, Method};
+/// # let client = Client::new().base_url("https://example.com");
+/// # let secret = b"secret";
+/// let presigner = Presigner::new(secret);
+/// let url = client.get("/files/report.pdf").presigned_url(&presigner, Duration::from_secs(300));
+/// // on the server
+/// assert!(presigner.verify(&Method::GET, &url));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Presigner {
+    key: hmac::Key,
+    expires_param: String,
+    signature_param: String,
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+fn unhex(s: &str) -> Option<Vec<u8>> {
+    if s.len() % 2 != 0 {
+        return None;
+    }
+    (0..s.len()).step_by(2).map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok()).collect()
+}
+
+/// Whether the query `pair` is for the parameter `name`.
+fn is_param(pair: &str, name: &str) -> bool {
+    let key = pair.split_once('=').map_or(pair, |(k, _)| k);
+    urlencoding::decode(key).is_ok_and(|k| k == name)
+}
+
+impl Presigner {
+    #[must_use]
+    pub fn new(secret: &[u8]) -> Self {
+        Self {
+            key: hmac::Key::new(hmac::HMAC_SHA256, secret),
+            expires_param: "expires".to_string(),
+            signature_param: "signature".to_string(),
+        }
+    }
+
+    /// Name of the query parameter holding the expiry, as a unix timestamp. Defaults to `expires`.
+    #[must_use]
+    pub fn expires_param(mut self, name: &str) -> Self {
+        self.expires_param = name.to_string();
+        self
+    }
+
+    /// Name of the query parameter holding the signature. Defaults to `signature`.
+    #[must_use]
+    pub fn signature_param(mut self, name: &str) -> Self {
+        self.signature_param = name.to_string();
+        self
+    }
+
+    /// The string that gets signed. Query parameters are sorted, so their order doesn't matter.
+    fn canonical(&self, method: &Method, uri: &Uri) -> String {
+        let mut query = uri
+            .query()
+            .unwrap_or_default()
+            .split('&')
+            .filter(|pair| !pair.is_empty() && !is_param(pair, &self.signature_param))
+            .collect::<Vec<_>>();
+        query.sort_unstable();
+        format!("{method}\n{}\n{}\n{}", uri.authority().map_or("", |a| a.as_str()), uri.path(), query.join("&"))
+    }
+
+    /// Sign `uri`, adding the expiry and signature query parameters. Any it already has, e.g. from signing it before,
+    /// are replaced.
+    #[must_use]
+    pub fn presign(&self, method: &Method, uri: &Uri, ttl: Duration) -> Uri {
+        self.presign_until(method, uri, now() + ttl.as_secs())
+    }
+
+    fn presign_until(&self, method: &Method, uri: &Uri, expires: u64) -> Uri {
+        let mut parts = uri.clone().into_parts();
+        let path = parts.path_and_query.as_ref().map_or("/", PathAndQuery::path);
+        let mut query: Vec<&str> = uri
+            .query()
+            .unwrap_or_default()
+            .split('&')
+            .filter(|pair| !pair.is_empty() && !is_param(pair, &self.expires_param) && !is_param(pair, &self.signature_param))
+            .collect();
+        let expires = format!("{}={expires}", urlencoding::encode(&self.expires_param));
+        query.push(&expires);
+        let pq = format!("{path}?{}", query.join("&"));
+        parts.path_and_query = Some(PathAndQuery::from_str(&pq).expect("Presigned path and query is valid"));
+        let unsigned = Uri::from_parts(parts).expect("Presigned url is valid");
+
+        let tag = hmac::sign(&self.key, self.canonical(method, &unsigned).as_bytes());
+        let mut parts = unsigned.into_parts();
+        let pq = format!("{pq}&{}={}", urlencoding::encode(&self.signature_param), hex(tag.as_ref()));
+        parts.path_and_query = Some(PathAndQuery::from_str(&pq).expect("Presigned path and query is valid"));
+        Uri::from_parts(parts).expect("Presigned url is valid")
+    }
+
+    /// Check that `uri` carries a valid signature for `method` and hasn't expired.
+    /// A url with the expiry or signature parameter more than once is rejected.
+    #[must_use]
+    pub fn verify(&self, method: &Method, uri: &Uri) -> bool {
+        let param = |name: &str| {
+            let mut values = uri.query()?.split('&').filter(|pair| is_param(pair, name));
+            match (values.next(), values.next()) {
+                (Some(pair), None) => pair.split_once('=').map(|(_, v)| v),
+                _ => None,
+            }
+        };
+        let Some(expires) = param(&self.expires_param).and_then(|v| v.parse::<u64>().ok()) else {
+            return false;
+        };
+        let Some(signature) = param(&self.signature_param).and_then(unhex) else {
+            return false;
+        };
+        expires >= now() && hmac::verify(&self.key, self.canonical(method, uri).as_bytes(), &signature).is_ok()
+    }
+}
+
+impl<C, B> RequestBuilder<'_, C, B> {
+    /// A presigned url for this request's method and url, valid for `ttl`.
+    /// Headers and body are not covered by the signature.
+    #[must_use]
+    pub fn presigned_url(&self, presigner: &Presigner, ttl: Duration) -> Uri {
+        presigner.presign(&self.method, &self.uri, ttl)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_presign_and_verify() {
+        let presigner = Presigner::new(b"secret");
+        let uri = Uri::from_static("https://example.com/files/report.pdf?download=1");
+        let signed = presigner.presign(&Method::GET, &uri, Duration::from_secs(60));
+        assert!(signed.query().unwrap().starts_with("download=1&expires="));
+        assert!(presigner.verify(&Method::GET, &signed));
+
+        assert!(!presigner.verify(&Method::PUT, &signed));
+        assert!(!Presigner::new(b"other").verify(&Method::GET, &signed));
+        let tampered = Uri::from_str(&signed.to_string().replace("download=1", "download=2")).unwrap();
+        assert!(!presigner.verify(&Method::GET, &tampered));
+        assert!(!presigner.verify(&Method::GET, &uri));
+
+        let expired = presigner.presign_until(&Method::GET, &uri, 1);
+        assert!(!presigner.verify(&Method::GET, &expired));
+    }
+
+    #[test]
+    fn test_resign() {
+        let presigner = Presigner::new(b"secret");
+        let uri = Uri::from_static("https://example.com/files/report.pdf?download=1");
+        let old = presigner.presign_until(&Method::GET, &uri, 1);
+        let resigned = presigner.presign(&Method::GET, &old, Duration::from_secs(60));
+        assert!(presigner.verify(&Method::GET, &resigned));
+        let query = resigned.query().unwrap();
+        assert_eq!(query.matches("expires=").count(), 1);
+        assert_eq!(query.matches("signature=").count(), 1);
+
+        // An old or attacker-chosen expiry placed first must not be read instead of the signed one.
+        let never = Uri::from_str(&format!("https://example.com/files/report.pdf?expires=99999999999&{query}")).unwrap();
+        assert!(!presigner.verify(&Method::GET, &never));
+    }
+}
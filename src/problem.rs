@@ -0,0 +1,116 @@
+use std::fmt::{Display, Formatter};
+
+use serde_json::{Map, Value};
+
+use crate::{InMemoryError, InMemoryResponse, InMemoryResponseExt};
+
+/// An RFC 9457 problem details object, the body of `application/problem+json` error responses.
+/// Read it from an error with [`InMemoryError::problem`], and branch on its type URI:
+/// ```no_run
+/// # use httpclient::Client;
+/// # async fn top_up() -> httpclient::InMemoryResult<httpclient::InMemoryResponse> { unimplemented!() }
+/// # async fn f(client: Client, transfer: serde_json::Value) -> httpclient::InMemoryResult<()> {
+/// match client.post("/transfers").json(&transfer).await {
+///     Err(e) if e.problem().is_some_and(|p| p.kind == "https://example.com/probs/out-of-credit") => top_up().await?,
+///     res => res?,
+/// };
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProblemDetails {
+    /// The `type` member, a URI identifying the problem type. `about:blank` if absent.
+    pub kind: String,
+    pub title: Option<String>,
+    pub status: Option<u16>,
+    pub detail: Option<String>,
+    pub instance: Option<String>,
+    /// Members other than the standard ones, e.g. `balance` or `invalid-params`.
+    pub extensions: Map<String, Value>,
+}
+
+impl ProblemDetails {
+    pub const CONTENT_TYPE: &'static str = "application/problem+json";
+
+    /// Read problem details from a response with an `application/problem+json` body.
+    /// Standard members with the wrong JSON type are ignored, as RFC 9457 asks.
+    #[must_use]
+    pub fn from_response(res: &InMemoryResponse) -> Option<Self> {
+        if res.content_type()?.essence() != Self::CONTENT_TYPE {
+            return None;
+        }
+        let mut members: Map<String, Value> = res.json_borrowed().ok()?;
+        let mut string = |name: &str| match members.remove(name) {
+            Some(Value::String(s)) => Some(s),
+            _ => None,
+        };
+        let kind = string("type").unwrap_or_else(|| "about:blank".to_string());
+        let title = string("title");
+        let detail = string("detail");
+        let instance = string("instance");
+        let status = members.remove("status").and_then(|s| s.as_u64()).and_then(|s| u16::try_from(s).ok());
+        Some(Self {
+            kind,
+            title,
+            status,
+            detail,
+            instance,
+            extensions: members,
+        })
+    }
+}
+
+impl Display for ProblemDetails {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.title.as_deref().unwrap_or(&self.kind))?;
+        if let Some(detail) = &self.detail {
+            write!(f, ": {detail}")?;
+        }
+        Ok(())
+    }
+}
+
+impl InMemoryError {
+    /// The problem details of an error response, if it was sent as `application/problem+json`.
+    #[must_use]
+    pub fn problem(&self) -> Option<ProblemDetails> {
+        match self {
+            InMemoryError::HttpError(res) => ProblemDetails::from_response(res),
+            InMemoryError::Protocol(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::header::CONTENT_TYPE;
+    use http::StatusCode;
+    use serde_json::json;
+
+    use crate::middleware::FakeTransport;
+    use crate::{Client, InMemoryBody};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_problem_details() {
+        let body = r#"{"type": "https://example.com/probs/out-of-credit", "title": "You do not have enough credit.",
+            "status": 403, "detail": "Your balance is 30, but that costs 50.", "instance": 7, "balance": 30}"#;
+        let mut res = InMemoryResponse::new(InMemoryBody::Text(body.into()));
+        *res.status_mut() = StatusCode::FORBIDDEN;
+        res.headers_mut().insert(CONTENT_TYPE, "application/problem+json".parse().unwrap());
+        let transport = FakeTransport::new().respond(res).respond_with(StatusCode::NOT_FOUND, InMemoryBody::Text("{}".into()));
+        let client = Client::new().with_middleware(transport);
+
+        let err = client.get("https://example.com/transfers").await.unwrap_err();
+        let problem = err.problem().unwrap();
+        assert_eq!(problem.kind, "https://example.com/probs/out-of-credit");
+        assert_eq!(problem.status, Some(403));
+        assert_eq!(problem.instance, None);
+        assert_eq!(problem.extensions, json!({"balance": 30}).as_object().unwrap().clone());
+        assert_eq!(problem.to_string(), "You do not have enough credit.: Your balance is 30, but that costs 50.");
+
+        let err = client.get("https://example.com/transfers").await.unwrap_err();
+        assert_eq!(err.problem(), None);
+    }
+}
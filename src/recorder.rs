@@ -1,18 +1,28 @@
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 use std::hash::{Hash, Hasher};
 use std::path::{Path, PathBuf};
-use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
 
+use http::header::{CACHE_CONTROL, CONTENT_LOCATION, DATE, ETAG, EXPIRES, IF_NONE_MATCH, VARY};
+use http::{HeaderMap, HeaderName, HeaderValue, StatusCode};
 use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 use walkdir::WalkDir;
 
-use crate::error::ProtocolResult;
+use crate::error::{ProtocolError, ProtocolResult};
 use crate::request::RequestExt;
-use crate::sanitize::{sanitize_request, sanitize_response};
+use crate::sanitize::{is_sanitize_disabled, sanitize_headers, sanitize_request, sanitize_response};
 use crate::{InMemoryBody, InMemoryRequest, InMemoryResponse};
 
+pub(crate) use storage::BodyFile;
+pub use storage::Compression;
+use storage::{body_file_contents, body_files, cassette_path, cassette_stem, read_body_file, Storage};
+
+mod storage;
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct RequestResponsePair {
     #[serde(with = "crate::request::serde_request")]
@@ -26,10 +36,30 @@ pub struct Recording {
     pub request: InMemoryRequest,
     pub response: InMemoryResponse,
     pub filename: String,
+    pub path: PathBuf,
 }
 
 pub struct HashableRequest(pub InMemoryRequest);
 
+/// The sanitized values of the headers set with [`RequestRecorder::match_headers`], kept in a request's extensions
+/// so they're part of its identity when looking up recordings.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct MatchedHeaders(Vec<(HeaderName, Vec<HeaderValue>)>);
+
+impl MatchedHeaders {
+    fn new(headers: &HeaderMap, names: &[HeaderName]) -> Self {
+        let mut selected = HeaderMap::new();
+        for name in names {
+            for value in headers.get_all(name) {
+                selected.append(name.clone(), value.clone());
+            }
+        }
+        // Recordings store sanitized headers, so sanitize the live request's too for them to compare equal.
+        sanitize_headers(&mut selected);
+        Self(names.iter().map(|name| (name.clone(), selected.get_all(name).iter().cloned().collect())).collect())
+    }
+}
+
 impl std::fmt::Debug for HashableRequest {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         self.0.fmt(f)
@@ -50,15 +80,8 @@ impl Hash for HashableRequest {
         self.method().hash(state);
         // url, contains query params.
         self.uri().hash(state);
-        // headers, sorted
-        // let mut sorted = self.headers().iter()
-        //     .map(|(k, v)| (k.as_str(), v.as_bytes()))
-        //     .collect::<Vec<(&str, &[u8])>>();
-        // sorted.sort();
-        // sorted.into_iter().for_each(|(k, v)| {
-        //     k.hash(state);
-        //     v.hash(state);
-        // });
+        // headers opted in with `RequestRecorder::match_headers`
+        self.extensions().get::<MatchedHeaders>().hash(state);
         // body
         self.body().hash(state);
     }
@@ -66,20 +89,20 @@ impl Hash for HashableRequest {
 
 impl PartialEq for HashableRequest {
     fn eq(&self, other: &Self) -> bool {
-        if !(self.method() == other.method() && self.uri() == other.uri()) {
+        if !(self.method() == other.method() && self.uri() == other.uri()) || self.extensions().get::<MatchedHeaders>() != other.extensions().get::<MatchedHeaders>() {
             return false;
         }
         let s: std::borrow::Cow<'_, [u8]> = match self.body() {
             InMemoryBody::Text(s) => s.as_bytes().into(),
             InMemoryBody::Empty => b"".into(),
-            InMemoryBody::Bytes(s) => s.into(),
+            InMemoryBody::Bytes(s) => s.as_ref().into(),
             InMemoryBody::Json(serde_json::Value::String(s)) => s.as_bytes().into(),
             InMemoryBody::Json(s) => serde_json::to_vec(s).unwrap().into(),
         };
         let o: std::borrow::Cow<'_, [u8]> = match other.body() {
             InMemoryBody::Text(s) => s.as_bytes().into(),
             InMemoryBody::Empty => b"".into(),
-            InMemoryBody::Bytes(s) => s.into(),
+            InMemoryBody::Bytes(s) => s.as_ref().into(),
             InMemoryBody::Json(serde_json::Value::String(s)) => s.as_bytes().into(),
             InMemoryBody::Json(s) => serde_json::to_vec(s).unwrap().into(),
         };
@@ -93,23 +116,112 @@ impl Eq for HashableRequest {}
 pub struct RequestRecorder {
     pub base_path: PathBuf,
     pub requests: Arc<RwLock<IndexMap<HashableRequest, InMemoryResponse>>>,
+    load_errors: Vec<LoadError>,
+    cassettes: Arc<Mutex<Cassettes>>,
+    match_headers: Arc<RwLock<Vec<HeaderName>>>,
+    storage: Arc<RwLock<Storage>>,
 }
 
-fn load_requests(path: &PathBuf) -> impl Iterator<Item = Recording> {
-    WalkDir::new(path)
+/// Where each recording lives on disk, and which files this run has used.
+#[derive(Debug, Default)]
+struct Cassettes {
+    /// The file holding each recorded request, by request hash. Requests recorded in several files use the last one loaded.
+    paths: HashMap<u64, PathBuf>,
+    /// Every recording file, and whether it's been played back or written since loading.
+    used: BTreeMap<PathBuf, bool>,
+}
+
+impl Cassettes {
+    /// The file to record the request with `hash` in: its existing file, or a new one next to `partial_path`.
+    /// An existing file in another format is replaced by one in `compression`, which is returned along with it.
+    fn path_for(&mut self, hash: u64, partial_path: &Path, idx: usize, compression: Compression) -> (PathBuf, Option<PathBuf>) {
+        if let Some(path) = self.paths.get(&hash).cloned() {
+            let stem = cassette_stem(&path).unwrap_or_default();
+            let wanted = path.with_file_name(format!("{stem}.json{}", compression.suffix()));
+            if wanted == path {
+                return (path, None);
+            }
+            self.used.remove(&path);
+            self.paths.insert(hash, wanted.clone());
+            return (wanted, Some(path));
+        }
+        let taken = |path: &Path| {
+            [Compression::None.suffix(), ".gz", ".zst"].iter().any(|suffix| {
+                let path = path.with_file_name(format!("{}.json{suffix}", cassette_stem(path).unwrap_or_default()));
+                self.used.contains_key(&path) || path.exists()
+            })
+        };
+        let mut n = idx;
+        let mut path = cassette_path(partial_path, n, compression);
+        while taken(&path) {
+            n += 1;
+            path = cassette_path(partial_path, n, compression);
+        }
+        self.paths.insert(hash, path.clone());
+        (path, None)
+    }
+}
+
+/// A recording file that couldn't be loaded, and why.
+#[derive(Debug, Clone)]
+pub struct LoadError {
+    pub path: PathBuf,
+    pub error: String,
+}
+
+impl std::fmt::Display for LoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.error)
+    }
+}
+
+fn load_recording(path: &Path) -> Result<Recording, String> {
+    let f = storage::read(path).map_err(|e| e.to_string())?;
+    let RequestResponsePair { mut request, mut response } = serde_json::from_slice(&f).map_err(|e| e.to_string())?;
+    if let Some(BodyFile(name)) = request.extensions_mut().remove() {
+        *request.body_mut() = read_body_file(path, &name)?;
+    }
+    if let Some(BodyFile(name)) = response.extensions_mut().remove() {
+        *response.body_mut() = read_body_file(path, &name)?;
+    }
+    Ok(Recording {
+        request,
+        response,
+        filename: path.file_name().unwrap_or_default().to_string_lossy().to_string(),
+        path: path.to_path_buf(),
+    })
+}
+
+/// Load every recording under `path`, compressed or not, collecting the files that fail to load rather than stopping at them.
+fn load_requests(path: &Path) -> (Vec<Recording>, Vec<LoadError>) {
+    let mut recordings = Vec::new();
+    let mut errors = Vec::new();
+    let files = WalkDir::new(path)
         .into_iter()
         .filter_map(Result::ok)
-        .filter(|e| e.file_type().is_file() && e.file_name().to_str().unwrap().ends_with(".json"))
-        .map(|filepath| {
-            debug!(file = filepath.path().display().to_string(), "Loading recording");
-            let f = fs::read_to_string(filepath.path()).unwrap();
-            let rr: RequestResponsePair = serde_json::from_str(&f).unwrap();
-            Recording {
-                request: rr.request,
-                response: rr.response,
-                filename: filepath.path().file_name().unwrap().to_str().unwrap().to_string(),
+        .filter(|e| e.file_type().is_file() && cassette_stem(e.path()).is_some());
+    for filepath in files {
+        debug!(file = filepath.path().display().to_string(), "Loading recording");
+        match load_recording(filepath.path()) {
+            Ok(recording) => recordings.push(recording),
+            Err(error) => {
+                warn!(file = filepath.path().display().to_string(), error, "Skipping corrupt recording");
+                errors.push(LoadError {
+                    path: filepath.path().to_path_buf(),
+                    error,
+                });
             }
-        })
+        }
+    }
+    (recordings, errors)
+}
+
+/// Distinguishes the temporary files of concurrent writes to the same recording.
+static NEXT_TMP: AtomicUsize = AtomicUsize::new(0);
+
+/// `data/vcr` under the current directory.
+fn default_path() -> PathBuf {
+    std::env::current_dir().expect("Failed to read the current directory").join("data").join("vcr")
 }
 
 fn calculate_hash<T: Hash>(t: &T) -> u64 {
@@ -120,21 +232,114 @@ fn calculate_hash<T: Hash>(t: &T) -> u64 {
 
 impl RequestRecorder {
     pub fn new() -> Self {
-        let path = std::env::current_dir().unwrap().join("data").join("vcr");
+        Self::load(default_path(), false).expect("Lenient loading doesn't fail")
+    }
+
+    /// Like [`RequestRecorder::new`], but fail if any recording is corrupt, instead of skipping it.
+    /// Install it for the [`crate::Recorder`] middleware with [`crate::middleware::try_init_shared_recorder`].
+    pub fn new_strict() -> ProtocolResult<Self> {
+        Self::load(default_path(), true)
+    }
+
+    /// Load the recordings under `path`. Corrupt files are skipped and listed in `load_errors`,
+    /// or with `strict`, fail the load with an error naming them.
+    pub fn load(path: PathBuf, strict: bool) -> ProtocolResult<Self> {
         debug!(dir = path.display().to_string(), "Request recorder created");
-        let mut requests = load_requests(&path).collect::<Vec<_>>();
+        let (mut requests, load_errors) = load_requests(&path);
+        if strict && !load_errors.is_empty() {
+            let msg = load_errors.iter().map(ToString::to_string).collect::<Vec<_>>().join("; ");
+            return Err(ProtocolError::invalid_input(format!("Corrupt recordings: {msg}")));
+        }
         requests.sort_by_key(|rr| rr.filename.clone());
+        let mut cassettes = Cassettes::default();
+        for recording in &requests {
+            cassettes.used.insert(recording.path.clone(), false);
+            cassettes.paths.insert(calculate_hash(&HashableRequest(recording.request.clone())), recording.path.clone());
+        }
         let requests: IndexMap<HashableRequest, InMemoryResponse> = requests.into_iter().map(|r| (HashableRequest(r.request), r.response)).collect::<_>();
-        info!(num_recordings = requests.len(), dir = path.display().to_string(), "Request recorder loaded");
+        info!(
+            num_recordings = requests.len(),
+            num_errors = load_errors.len(),
+            dir = path.display().to_string(),
+            "Request recorder loaded"
+        );
         let requests = Arc::new(RwLock::new(requests));
-        RequestRecorder { base_path: path, requests }
+        Ok(RequestRecorder {
+            base_path: path,
+            requests,
+            load_errors,
+            cassettes: Arc::new(Mutex::new(cassettes)),
+            match_headers: Arc::default(),
+            storage: Arc::default(),
+        })
+    }
+
+    /// The recording files that were skipped because they couldn't be read or parsed.
+    #[must_use]
+    pub fn load_errors(&self) -> &[LoadError] {
+        &self.load_errors
+    }
+
+    /// Make these headers part of a request's identity, so requests that differ only in them get different recordings,
+    /// e.g. `Accept` for endpoints that answer with JSON or CSV. Headers are otherwise ignored when matching.
+    ///
+    /// Values are compared after sanitizing, so sensitive headers like `Authorization` only match on whether they're set.
+    pub fn match_headers(&self, names: &[HeaderName]) {
+        names.clone_into(&mut self.match_headers.write().expect("Recorder lock poisoned"));
+        let mut requests = self.requests.write().expect("Recorder lock poisoned");
+        let mut cassettes = self.cassettes.lock().expect("Recorder lock poisoned");
+        let mut paths = HashMap::new();
+        let rekeyed = std::mem::take(&mut *requests)
+            .into_iter()
+            .map(|(request, response)| {
+                let old = calculate_hash(&request);
+                let request = self.key(request.0);
+                if let Some(path) = cassettes.paths.get(&old) {
+                    paths.insert(calculate_hash(&request), path.clone());
+                }
+                (request, response)
+            })
+            .collect();
+        *requests = rekeyed;
+        cassettes.paths = paths;
+    }
+
+    /// Compress recording files written from now on. Existing recordings are rewritten in this format when they're
+    /// next recorded, and load either way.
+    pub fn compression(&self, compression: Compression) {
+        self.storage.write().expect("Recorder lock poisoned").compression = compression;
+    }
+
+    /// Write request and response bodies of at least `min_len` bytes to their own files next to the recording, e.g.
+    /// `get.0001.response.json` for `get.0001.json`, so large bodies don't bury the rest of the recording in diffs.
+    /// Text and JSON bodies stay readable; binary bodies are written as is, instead of base64.
+    pub fn sidecar_bodies(&self, min_len: usize) {
+        self.storage.write().expect("Recorder lock poisoned").sidecar_min = Some(min_len);
+    }
+
+    /// Wrap `request` for lookup, noting the values of the headers it's matched on.
+    fn key(&self, mut request: InMemoryRequest) -> HashableRequest {
+        let names = self.match_headers.read().expect("Recorder lock poisoned");
+        if names.is_empty() {
+            request.extensions_mut().remove::<MatchedHeaders>();
+        } else {
+            let matched = MatchedHeaders::new(request.headers(), &names);
+            request.extensions_mut().insert(matched);
+        }
+        HashableRequest(request)
     }
 
     pub fn get_response(&self, request: &HashableRequest) -> Option<InMemoryResponse> {
+        let request = &self.key(request.0.clone());
         debug!(url = request.url().to_string(), hash = calculate_hash(request), "Checking for recorded response");
-        let map = self.requests.read().unwrap();
-        let res = map.get(request);
-        res.cloned()
+        let res = self.requests.read().expect("Recorder lock poisoned").get(request).cloned();
+        if res.is_some() {
+            let mut cassettes = self.cassettes.lock().expect("Recorder lock poisoned");
+            if let Some(path) = cassettes.paths.get(&calculate_hash(request)).cloned() {
+                cassettes.used.insert(path, true);
+            }
+        }
+        res
     }
 
     fn partial_filepath(&self, request: &InMemoryRequest) -> PathBuf {
@@ -149,26 +354,145 @@ impl RequestRecorder {
         self.requests.write().unwrap().clear();
     }
 
-    pub fn record_response(&self, mut request: InMemoryRequest, mut response: InMemoryResponse) -> ProtocolResult<()> {
+    /// Store the pair in memory and write it to disk. The file is written without blocking the runtime,
+    /// to a temporary file that's renamed into place, so concurrent recordings never leave a partial file.
+    pub async fn record_response(&self, request: InMemoryRequest, response: InMemoryResponse) -> ProtocolResult<()> {
+        self.record(request, response).await.map(|_hash| ())
+    }
+
+    /// Record the pair, returning the hash of the stored request.
+    async fn record(&self, mut request: InMemoryRequest, mut response: InMemoryResponse) -> ProtocolResult<u64> {
         let partial_path = self.partial_filepath(&request);
-        sanitize_request(&mut request);
-        sanitize_response(&mut response);
+        if !is_sanitize_disabled(&request) {
+            sanitize_request(&mut request);
+            sanitize_response(&mut response);
+        }
+
+        let storage = *self.storage.read().expect("Recorder lock poisoned");
+        let request = self.key(request);
+        let hash = calculate_hash(&request);
+        let idx = {
+            let requests = self.requests.read().expect("Recorder lock poisoned");
+            requests.get_index_of(&request).unwrap_or(requests.len())
+        };
+        let (path, replaced) = {
+            let mut cassettes = self.cassettes.lock().expect("Recorder lock poisoned");
+            let (path, replaced) = cassettes.path_for(hash, &partial_path, idx, storage.compression);
+            cassettes.used.insert(path.clone(), true);
+            (path, replaced)
+        };
+        let stem = cassette_stem(&path).unwrap_or_default().to_string();
 
+        let mut request = request.0;
+        let mut sidecars = Vec::new();
+        if let Some(min) = storage.sidecar_min {
+            let request_file = body_file_contents(request.body()).map_err(std::io::Error::from)?;
+            if let Some((ext, contents)) = request_file.filter(|(_, c)| c.len() >= min) {
+                let name = format!("{stem}.request.{ext}{}", storage.compression.suffix());
+                request.extensions_mut().insert(BodyFile(name.clone()));
+                sidecars.push((name, contents));
+            }
+            let response_file = body_file_contents(response.body()).map_err(std::io::Error::from)?;
+            if let Some((ext, contents)) = response_file.filter(|(_, c)| c.len() >= min) {
+                let name = format!("{stem}.response.{ext}{}", storage.compression.suffix());
+                response.extensions_mut().insert(BodyFile(name.clone()));
+                sidecars.push((name, contents));
+            }
+        }
         let rr = RequestResponsePair { request, response };
-        let stringified = serde_json::to_string_pretty(&rr).unwrap();
-        let RequestResponsePair { request, response } = rr;
-        let idx;
-        {
-            let mut write = self.requests.write().unwrap();
-            let (i, _old) = write.insert_full(HashableRequest(request), response);
-            idx = i;
+        let stringified = serde_json::to_vec_pretty(&rr).map_err(std::io::Error::from)?;
+        let RequestResponsePair { mut request, mut response } = rr;
+        request.extensions_mut().remove::<BodyFile>();
+        response.extensions_mut().remove::<BodyFile>();
+        self.requests.write().expect("Recorder lock poisoned").insert(HashableRequest(request), response);
+
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let mut written = Vec::new();
+        for (name, contents) in sidecars {
+            let sidecar = path.with_file_name(name);
+            write_atomic(&sidecar, storage.compression.compress(contents)?).await?;
+            written.push(sidecar);
+        }
+        write_atomic(&path, storage.compression.compress(stringified)?).await?;
+        for stale in body_files(&path).into_iter().filter(|p| !written.contains(p)) {
+            remove_recording(&stale).await?;
+        }
+        if let Some(replaced) = replaced {
+            remove_recording(&replaced).await?;
+        }
+        Ok(hash)
+    }
+
+    /// Record the pair like [`Self::record_response`], then delete every other recording for the same method and URL,
+    /// e.g. ones made with an older request body. The new file is in place before the old ones are removed.
+    pub async fn replace_response(&self, request: InMemoryRequest, response: InMemoryResponse) -> ProtocolResult<()> {
+        let method = request.method().clone();
+        let uri = request.uri().clone();
+        let keep = self.record(request, response).await?;
+        let mut stale = Vec::new();
+        self.requests.write().expect("Recorder lock poisoned").retain(|request, _| {
+            let hash = calculate_hash(request);
+            let is_stale = request.method() == method && *request.uri() == uri && hash != keep;
+            if is_stale {
+                stale.push(hash);
+            }
+            !is_stale
+        });
+        let stale: Vec<PathBuf> = {
+            let mut cassettes = self.cassettes.lock().expect("Recorder lock poisoned");
+            let paths: Vec<PathBuf> = stale.iter().filter_map(|hash| cassettes.paths.remove(hash)).collect();
+            for path in &paths {
+                cassettes.used.remove(path);
+            }
+            paths
+        };
+        for path in stale {
+            debug!(file = path.display().to_string(), "Removing replaced recording");
+            for body_file in body_files(&path) {
+                remove_recording(&body_file).await?;
+            }
+            remove_recording(&path).await?;
         }
-        let path = partial_path.with_extension(format!("{idx:04}.json"));
-        fs::create_dir_all(path.parent().unwrap()).unwrap();
-        fs::write(&path, stringified)?;
         Ok(())
     }
 
+    /// Recording files that haven't been played back or written since they were loaded, including ones shadowed by a
+    /// later file for the same request. Check after a test run to find stale recordings, then [`Self::prune`] them.
+    #[must_use]
+    pub fn unused(&self) -> Vec<PathBuf> {
+        let cassettes = self.cassettes.lock().expect("Recorder lock poisoned");
+        cassettes.used.iter().filter(|(_, used)| !**used).map(|(path, _)| path.clone()).collect()
+    }
+
+    /// Delete the [`Self::unused`] recordings, and forget their requests. Returns the deleted files.
+    /// Only call this after a run that exercised every recording you want to keep.
+    pub fn prune(&self) -> ProtocolResult<Vec<PathBuf>> {
+        let unused = self.unused();
+        let mut requests = self.requests.write().expect("Recorder lock poisoned");
+        let mut cassettes = self.cassettes.lock().expect("Recorder lock poisoned");
+        for path in &unused {
+            cassettes.used.remove(path);
+            let hashes: Vec<u64> = cassettes.paths.iter().filter(|(_, p)| *p == path).map(|(hash, _)| *hash).collect();
+            for hash in hashes {
+                cassettes.paths.remove(&hash);
+                requests.retain(|request, _| calculate_hash(request) != hash);
+            }
+            for body_file in body_files(path) {
+                match fs::remove_file(body_file) {
+                    Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                    _ => {}
+                }
+            }
+            match fs::remove_file(path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                _ => info!(file = path.display().to_string(), "Pruned unused recording"),
+            }
+        }
+        Ok(unused)
+    }
+
     pub fn load_from_path(_path: &Path) {
         unimplemented!()
     }
@@ -178,6 +502,50 @@ impl RequestRecorder {
     }
 }
 
+/// Write `contents` to a temporary file next to `path`, then rename it into place.
+async fn write_atomic(path: &Path, contents: Vec<u8>) -> std::io::Result<()> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let tmp = path.with_file_name(format!("{name}.{}.tmp", NEXT_TMP.fetch_add(1, Ordering::Relaxed)));
+    tokio::fs::write(&tmp, contents).await?;
+    tokio::fs::rename(&tmp, path).await
+}
+
+async fn remove_recording(path: &Path) -> std::io::Result<()> {
+    match tokio::fs::remove_file(path).await {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+/// Whether an `If-None-Match` header value matches `etag`, using the weak comparison (RFC 9110 13.1.2).
+fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    let strip_weak = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    let etag = strip_weak(etag);
+    if_none_match.trim() == "*" || if_none_match.split(',').any(|tag| strip_weak(tag) == etag)
+}
+
+/// If `request` is conditional and its `If-None-Match` matches the recorded response's `ETag`,
+/// build the `304 Not Modified` the server would have sent instead of the full response.
+pub fn not_modified(request: &InMemoryRequest, recorded: &InMemoryResponse) -> Option<InMemoryResponse> {
+    let if_none_match = request.headers().get(IF_NONE_MATCH)?.to_str().ok()?;
+    let etag = recorded.headers().get(ETAG)?.to_str().ok()?;
+    if !recorded.status().is_success() || !etag_matches(if_none_match, etag) {
+        return None;
+    }
+    let mut headers = HeaderMap::new();
+    // The headers a 304 must carry over from the full response.
+    for name in [CACHE_CONTROL, CONTENT_LOCATION, DATE, ETAG, EXPIRES, VARY] {
+        for value in recorded.headers().get_all(&name) {
+            headers.append(name.clone(), value.clone());
+        }
+    }
+    let mut res = InMemoryResponse::new(InMemoryBody::Empty);
+    *res.status_mut() = StatusCode::NOT_MODIFIED;
+    *res.version_mut() = recorded.version();
+    *res.headers_mut() = headers;
+    Some(res)
+}
+
 impl Default for RequestRecorder {
     fn default() -> Self {
         Self::new()
@@ -226,4 +594,144 @@ mod tests {
         };
         assert_eq!(h1, h2);
     }
+
+    #[test]
+    fn test_not_modified() {
+        let recorded: InMemoryResponse = http::Response::builder()
+            .header(ETAG, "\"v1\"")
+            .header(CACHE_CONTROL, "max-age=60")
+            .header("content-type", "application/json")
+            .body(InMemoryBody::Text("{}".into()))
+            .unwrap();
+        let request = |tag: &str| Request::builder().uri("https://example.com/").header(IF_NONE_MATCH, tag).body(InMemoryBody::Empty).unwrap();
+
+        let res = not_modified(&request("W/\"v0\", W/\"v1\""), &recorded).unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(res.headers().get(ETAG).unwrap(), "\"v1\"");
+        assert_eq!(res.headers().get(CACHE_CONTROL).unwrap(), "max-age=60");
+        assert!(res.headers().get("content-type").is_none());
+        assert!(not_modified(&request("*"), &recorded).is_some());
+        assert!(not_modified(&request("\"v2\""), &recorded).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_load_skips_corrupt() {
+        let dir = std::env::temp_dir().join(format!("httpclient-recorder-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let recorder = RequestRecorder::load(dir.clone(), true).unwrap();
+        let request = Request::builder().uri("https://example.com/a").body(InMemoryBody::Empty).unwrap();
+        recorder.record_response(request, InMemoryResponse::new(InMemoryBody::Text("ok".into()))).await.unwrap();
+        let concurrent = (0..8).map(|i| {
+            let request = Request::builder().uri(format!("https://example.com/b/{i}")).body(InMemoryBody::Empty).unwrap();
+            recorder.record_response(request, InMemoryResponse::new(InMemoryBody::Empty))
+        });
+        futures::future::try_join_all(concurrent).await.unwrap();
+        fs::write(dir.join("corrupt.json"), "{\"request\": ").unwrap();
+
+        let recorder = RequestRecorder::load(dir.clone(), false).unwrap();
+        assert_eq!(recorder.requests.read().unwrap().len(), 9);
+        assert_eq!(recorder.load_errors().len(), 1);
+        assert_eq!(recorder.load_errors()[0].path, dir.join("corrupt.json"));
+        assert!(RequestRecorder::load(dir.clone(), true).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_rerecord_and_prune() {
+        let dir = std::env::temp_dir().join(format!("httpclient-rerecord-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let files = || load_requests(&dir).0.len();
+        let request = |method: Method, path: &str, body: &str| {
+            Request::builder()
+                .method(method)
+                .uri(format!("https://example.com{path}"))
+                .body(InMemoryBody::Text(body.into()))
+                .unwrap()
+        };
+        let ok = || InMemoryResponse::new(InMemoryBody::Text("ok".into()));
+
+        let recorder = RequestRecorder::load(dir.clone(), true).unwrap();
+        recorder.record_response(request(Method::POST, "/a", "1"), ok()).await.unwrap();
+        recorder.record_response(request(Method::POST, "/a", "1"), ok()).await.unwrap();
+        assert_eq!(files(), 1, "Recording the same request again overwrites its file");
+        recorder.record_response(request(Method::POST, "/a", "2"), ok()).await.unwrap();
+        recorder.record_response(request(Method::GET, "/b", ""), ok()).await.unwrap();
+        assert_eq!(files(), 3);
+        recorder.replace_response(request(Method::POST, "/a", "3"), ok()).await.unwrap();
+        assert_eq!(files(), 2);
+        assert!(recorder.get_response(&HashableRequest(request(Method::POST, "/a", "1"))).is_none());
+
+        let recorder = RequestRecorder::load(dir.clone(), true).unwrap();
+        assert!(recorder.get_response(&HashableRequest(request(Method::POST, "/a", "3"))).is_some());
+        let unused = recorder.unused();
+        assert_eq!(unused.len(), 1);
+        assert!(unused[0].starts_with(dir.join("example.com/b")));
+        assert_eq!(recorder.prune().unwrap(), unused);
+        assert_eq!(files(), 1);
+        assert!(recorder.unused().is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_match_headers() {
+        let dir = std::env::temp_dir().join(format!("httpclient-match-headers-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let request = |accept: &str, token: &str| {
+            Request::builder()
+                .uri("https://example.com/report")
+                .header(http::header::ACCEPT, accept)
+                .header(http::header::AUTHORIZATION, token)
+                .body(InMemoryBody::Empty)
+                .unwrap()
+        };
+        let recorder = RequestRecorder::load(dir.clone(), true).unwrap();
+        let text = |body: &str| InMemoryResponse::new(InMemoryBody::Text(body.into()));
+        recorder.record_response(request("application/json", "Bearer a"), text("json")).await.unwrap();
+        recorder.record_response(request("text/csv", "Bearer a"), text("csv")).await.unwrap();
+        assert_eq!(recorder.requests.read().unwrap().len(), 1, "Headers are ignored by default");
+
+        let recorder = RequestRecorder::load(dir.clone(), true).unwrap();
+        recorder.match_headers(&[http::header::ACCEPT, http::header::AUTHORIZATION]);
+        recorder.record_response(request("application/json", "Bearer a"), text("json")).await.unwrap();
+        let lookup = |accept: &str| recorder.get_response(&HashableRequest(request(accept, "Bearer b"))).map(|r| r.into_body().text().unwrap());
+        assert_eq!(lookup("text/csv").as_deref(), Some("csv"));
+        assert_eq!(lookup("application/json").as_deref(), Some("json"));
+        assert_eq!(lookup("application/xml"), None);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_sidecar_bodies() {
+        let dir = std::env::temp_dir().join(format!("httpclient-sidecar-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let request = Request::builder().uri("https://example.com/big").body(InMemoryBody::Empty).unwrap();
+        let items = serde_json::json!({"items": vec!["x"; 100]});
+        let big = InMemoryBody::Json(items.clone());
+        let json = |res: InMemoryResponse| match res.into_body() {
+            InMemoryBody::Json(value) => value,
+            body => panic!("Expected a JSON body, got {body:?}"),
+        };
+        let recorder = RequestRecorder::load(dir.clone(), true).unwrap();
+        recorder.sidecar_bodies(256);
+        recorder.record_response(request.clone(), InMemoryResponse::new(big.clone())).await.unwrap();
+        let recording = fs::read_to_string(dir.join("example.com/big/get.0000.json")).unwrap();
+        assert!(recording.contains("\"body_file\": \"get.0000.response.json\""));
+        assert!(dir.join("example.com/big/get.0000.response.json").exists());
+
+        let recorder = RequestRecorder::load(dir.clone(), true).unwrap();
+        assert_eq!(json(recorder.get_response(&HashableRequest(request.clone())).unwrap()), items);
+
+        #[cfg(feature = "recorder-gzip")]
+        {
+            recorder.sidecar_bodies(256);
+            recorder.compression(Compression::Gzip);
+            recorder.record_response(request.clone(), InMemoryResponse::new(big.clone())).await.unwrap();
+            let mut files: Vec<_> = fs::read_dir(dir.join("example.com/big")).unwrap().map(|e| e.unwrap().file_name()).collect();
+            files.sort();
+            assert_eq!(files, ["get.0000.json.gz", "get.0000.response.json.gz"]);
+            let recorder = RequestRecorder::load(dir.clone(), true).unwrap();
+            assert_eq!(json(recorder.get_response(&HashableRequest(request)).unwrap()), items);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
@@ -0,0 +1,144 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::InMemoryBody;
+
+/// How recording files are compressed on disk. Compressed recordings are always loaded, whatever this is set to,
+/// as long as the crate is built with the matching feature.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Compression {
+    #[default]
+    None,
+    /// `.json.gz` files. Needs the `recorder-gzip` feature.
+    #[cfg(feature = "recorder-gzip")]
+    Gzip,
+    /// `.json.zst` files. Needs the `recorder-zstd` feature.
+    #[cfg(feature = "recorder-zstd")]
+    Zstd,
+}
+
+impl Compression {
+    /// The suffix after the file's own extension, e.g. `.gz`.
+    pub(crate) fn suffix(self) -> &'static str {
+        match self {
+            Compression::None => "",
+            #[cfg(feature = "recorder-gzip")]
+            Compression::Gzip => ".gz",
+            #[cfg(feature = "recorder-zstd")]
+            Compression::Zstd => ".zst",
+        }
+    }
+
+    #[allow(clippy::unnecessary_wraps)] // fallible with a compression feature enabled
+    pub(crate) fn compress(self, data: Vec<u8>) -> io::Result<Vec<u8>> {
+        match self {
+            Compression::None => Ok(data),
+            #[cfg(feature = "recorder-gzip")]
+            Compression::Gzip => {
+                use std::io::Write;
+                let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(&data)?;
+                encoder.finish()
+            }
+            #[cfg(feature = "recorder-zstd")]
+            Compression::Zstd => zstd::encode_all(&data[..], 0),
+        }
+    }
+}
+
+/// The settings for writing recordings.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Storage {
+    pub compression: Compression,
+    /// Bodies of at least this many bytes are written to their own file.
+    pub sidecar_min: Option<usize>,
+}
+
+/// The name of the file, next to the recording, holding a request or response body. Set in the extensions of a
+/// recorded request or response so it's serialized as a reference to the file, and read from them when loading.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct BodyFile(pub String);
+
+#[cfg(not(all(feature = "recorder-gzip", feature = "recorder-zstd")))]
+fn unsupported(feature: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::Unsupported, format!("Reading this file needs the `{feature}` feature"))
+}
+
+/// The file name without its compression suffix.
+fn strip_compression(name: &str) -> &str {
+    name.strip_suffix(".gz").or_else(|| name.strip_suffix(".zst")).unwrap_or(name)
+}
+
+/// Read a recording or body file, decompressing it according to its name.
+pub(crate) fn read(path: &Path) -> io::Result<Vec<u8>> {
+    let data = std::fs::read(path)?;
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    if name.ends_with(".gz") {
+        #[cfg(feature = "recorder-gzip")]
+        {
+            use std::io::Read;
+            let mut out = Vec::new();
+            flate2::read::GzDecoder::new(&data[..]).read_to_end(&mut out)?;
+            return Ok(out);
+        }
+        #[cfg(not(feature = "recorder-gzip"))]
+        return Err(unsupported("recorder-gzip"));
+    }
+    if name.ends_with(".zst") {
+        #[cfg(feature = "recorder-zstd")]
+        return zstd::decode_all(&data[..]);
+        #[cfg(not(feature = "recorder-zstd"))]
+        return Err(unsupported("recorder-zstd"));
+    }
+    Ok(data)
+}
+
+/// The name of a recording file without its extension, e.g. `get.0001` for `get.0001.json.gz`,
+/// or `None` if the file isn't a recording.
+pub(crate) fn cassette_stem(path: &Path) -> Option<&str> {
+    let name = path.file_name()?.to_str()?;
+    let stem = strip_compression(name).strip_suffix(".json")?;
+    // Body files are named after their recording, e.g. `get.0001.response.json`.
+    (!stem.ends_with(".request") && !stem.ends_with(".response")).then_some(stem)
+}
+
+/// The path of recording `n` for `partial_path`, e.g. `.../get.0001.json`.
+pub(crate) fn cassette_path(partial_path: &Path, n: usize, compression: Compression) -> PathBuf {
+    partial_path.with_extension(format!("{n:04}.json{}", compression.suffix()))
+}
+
+/// The body files of the recording at `path`.
+pub(crate) fn body_files(path: &Path) -> Vec<PathBuf> {
+    let (Some(dir), Some(stem)) = (path.parent(), cassette_stem(path)) else {
+        return Vec::new();
+    };
+    let prefixes = [format!("{stem}.request."), format!("{stem}.response.")];
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(Result::ok)
+        .map(|e| e.path())
+        .filter(|p| p.file_name().and_then(|n| n.to_str()).is_some_and(|n| prefixes.iter().any(|prefix| n.starts_with(prefix))))
+        .collect()
+}
+
+/// The contents of a body file for `body`, and its extension: `txt`, `json`, or `bin` for binary bodies.
+pub(crate) fn body_file_contents(body: &InMemoryBody) -> serde_json::Result<Option<(&'static str, Vec<u8>)>> {
+    Ok(match body {
+        InMemoryBody::Empty => None,
+        InMemoryBody::Text(text) => Some(("txt", text.as_bytes().to_vec())),
+        InMemoryBody::Bytes(bytes) => Some(("bin", bytes.to_vec())),
+        InMemoryBody::Json(value) => Some(("json", serde_json::to_vec_pretty(value)?)),
+    })
+}
+
+/// Read the body file `name` next to the recording at `path`.
+pub(crate) fn read_body_file(path: &Path, name: &str) -> Result<InMemoryBody, String> {
+    let data = read(&path.with_file_name(name)).map_err(|e| format!("{name}: {e}"))?;
+    match strip_compression(name).rsplit_once('.').map(|(_, ext)| ext) {
+        Some("txt") => String::from_utf8(data).map(|text| InMemoryBody::Text(text.into())).map_err(|e| format!("{name}: {e}")),
+        Some("json") => serde_json::from_slice(&data).map(InMemoryBody::Json).map_err(|e| format!("{name}: {e}")),
+        _ => Ok(InMemoryBody::Bytes(data.into())),
+    }
+}
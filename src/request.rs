@@ -1,12 +1,14 @@
-use http::{HeaderName, HeaderValue, Uri};
+use http::{Extensions, HeaderName, HeaderValue, Uri};
 
 pub use builder::RequestBuilder;
 pub use memory::*;
+pub use query::QueryFormat;
 
 use crate::Body;
 
 mod builder;
 mod memory;
+mod query;
 
 pub type Request<T = Body> = http::Request<T>;
 
@@ -16,6 +18,10 @@ pub trait RequestExt {
     fn url(&self) -> &Uri;
     fn header<H: TryInto<HeaderName>>(&self, h: H) -> Option<&HeaderValue>;
     fn header_str<H: TryInto<HeaderName>>(&self, h: H) -> Option<&str>;
+    /// Typed values carried with the request, e.g. `req.ext_mut().insert(Tenant(7))`.
+    /// See [`crate::Middleware`] for how far they travel.
+    fn ext(&self) -> &Extensions;
+    fn ext_mut(&mut self) -> &mut Extensions;
 }
 
 impl<B> RequestExt for Request<B> {
@@ -39,6 +45,14 @@ impl<B> RequestExt for Request<B> {
     fn header_str<H: TryInto<HeaderName>>(&self, h: H) -> Option<&str> {
         self.header(h).and_then(|v| v.to_str().ok())
     }
+
+    fn ext(&self) -> &Extensions {
+        self.extensions()
+    }
+
+    fn ext_mut(&mut self) -> &mut Extensions {
+        self.extensions_mut()
+    }
 }
 
 pub trait RequestBuilderExt {
@@ -1,18 +1,27 @@
 use std::future::IntoFuture;
+#[cfg(not(target_arch = "wasm32"))]
+use std::net::IpAddr;
 use std::str::FromStr;
 use std::sync::Arc;
 
 use futures::future::BoxFuture;
+use futures::stream;
 use http::header::{Entry, HeaderName, ACCEPT, AUTHORIZATION, CONTENT_TYPE, COOKIE};
 use http::uri::PathAndQuery;
-use http::{header, HeaderMap, HeaderValue, Method, Uri, Version};
+use http::{header, Extensions, HeaderMap, HeaderValue, Method, Uri, Version};
+use hyper::body::Bytes;
 use serde::Serialize;
 use serde_json::Value;
 
+#[cfg(not(target_arch = "wasm32"))]
+use crate::client::LocalAddress;
 use crate::error::ProtocolResult;
-use crate::middleware::Next;
+use crate::middleware::{NoFollow, NoRetry};
+#[cfg(feature = "multipart")]
 use crate::multipart::Form;
-use crate::{Client, Error, InMemoryBody, InMemoryResponse, Middleware, Request, Response};
+use crate::request::query::{to_query_string, QueryFormat};
+use crate::sanitize::NoSanitize;
+use crate::{Client, Error, InMemoryBody, InMemoryResponse, Middleware, ReplayableBody, Request, Response, Utf8Bytes};
 
 pub static ACCEPT_JSON: HeaderValue = HeaderValue::from_static("application/json");
 pub static CONTENT_JSON: HeaderValue = HeaderValue::from_static("application/json; charset=utf-8");
@@ -34,8 +43,30 @@ pub struct RequestBuilder<'a, C = Client, B = InMemoryBody> {
     pub method: Method,
     pub uri: Uri,
     pub headers: HeaderMap,
+    /// The client's default headers, added when the request is built for any header it doesn't set itself.
+    pub default_headers: HeaderMap,
     pub body: Option<B>,
+    pub extensions: Extensions,
     pub middlewares: Vec<Arc<dyn Middleware>>,
+    /// How arrays are written by [`RequestBuilder::set_query`] and [`RequestBuilder::form`].
+    pub query_format: QueryFormat,
+}
+
+impl<C, B: Clone> Clone for RequestBuilder<'_, C, B> {
+    fn clone(&self) -> Self {
+        RequestBuilder {
+            client: self.client,
+            version: self.version,
+            method: self.method.clone(),
+            uri: self.uri.clone(),
+            headers: self.headers.clone(),
+            default_headers: self.default_headers.clone(),
+            body: self.body.clone(),
+            extensions: self.extensions.clone(),
+            middlewares: self.middlewares.clone(),
+            query_format: self.query_format,
+        }
+    }
 }
 
 impl<'a> RequestBuilder<'a, ()> {
@@ -64,8 +95,11 @@ impl<'a, C> RequestBuilder<'a, C> {
             method,
             uri,
             headers: Default::default(),
+            default_headers: HeaderMap::default(),
             body: Default::default(),
+            extensions: Default::default(),
             middlewares: Default::default(),
+            query_format: QueryFormat::default(),
         }
     }
 
@@ -73,16 +107,15 @@ impl<'a, C> RequestBuilder<'a, C> {
     pub fn form<S: Serialize>(mut self, obj: S) -> Self {
         match self.body {
             None => {
-                let body = serde_qs::to_string(&obj).unwrap();
-                self.body = Some(InMemoryBody::Text(body));
+                let body = to_query_string(&obj, self.query_format).expect("Failed to serialize form in .form");
+                self.body = Some(InMemoryBody::Text(body.into()));
                 self.headers.entry(CONTENT_TYPE).or_insert(CONTENT_URL_ENCODED.clone());
                 self.headers.entry(ACCEPT).or_insert(HeaderValue::from_static("html/text"));
                 self
             }
             Some(InMemoryBody::Text(ref mut body)) => {
-                let new_body = serde_qs::to_string(&obj).unwrap();
-                body.push('&');
-                body.push_str(&new_body);
+                let new_body = to_query_string(&obj, self.query_format).expect("Failed to serialize form in .form");
+                *body = format!("{body}&{new_body}").into();
                 self
             }
             _ => {
@@ -119,9 +152,8 @@ impl<'a, C> RequestBuilder<'a, C> {
 
     /// Sets content-type to `application/octet-stream` and the body to the supplied bytes.
     #[must_use]
-    pub fn bytes(mut self, bytes: Vec<u8>) -> Self {
-        // self.headers.insert(CONTENT_LENGTH, HeaderValue::from(bytes.len()));
-        self.body = Some(InMemoryBody::Bytes(bytes));
+    pub fn bytes(mut self, bytes: impl Into<Bytes>) -> Self {
+        self.body = Some(InMemoryBody::Bytes(bytes.into()));
         self.headers.entry(CONTENT_TYPE).or_insert(HeaderValue::from_static("application/octet-stream"));
         self
     }
@@ -129,12 +161,23 @@ impl<'a, C> RequestBuilder<'a, C> {
     /// Sets content-type to `text/plain` and the body to the supplied text.
     #[must_use]
     pub fn text(mut self, text: String) -> Self {
-        // self.headers.insert(CONTENT_LENGTH, HeaderValue::from(text.len()));
-        self.body = Some(InMemoryBody::Text(text));
+        self.body = Some(InMemoryBody::Text(text.into()));
         self.headers.entry(CONTENT_TYPE).or_insert(HeaderValue::from_static("text/plain"));
         self
     }
 
+    /// Stream the body from `body`, which is made again for every attempt, so retries and redirects can resend it.
+    /// The request goes through middlewares, which see an empty body.
+    /// Sets content-type to `application/octet-stream` unless it's already set.
+    #[must_use]
+    pub fn replayable_body(mut self, body: ReplayableBody) -> Self {
+        self.body = Some(InMemoryBody::Empty);
+        self.extensions.insert(body);
+        self.headers.entry(CONTENT_TYPE).or_insert(HeaderValue::from_static("application/octet-stream"));
+        self
+    }
+
+    #[cfg(feature = "multipart")]
     #[must_use]
     pub fn multipart<B>(mut self, form: Form<B>) -> Self
     where
@@ -143,14 +186,48 @@ impl<'a, C> RequestBuilder<'a, C> {
         let content_type = form.full_content_type();
         self.headers.entry(CONTENT_TYPE).or_insert(content_type.parse().unwrap());
         let body: Vec<u8> = form.into();
-        // let len = body.len();
         match String::from_utf8(body) {
-            Ok(text) => self.body = Some(InMemoryBody::Text(text)),
-            Err(bytes) => self.body = Some(InMemoryBody::Bytes(bytes.into_bytes())),
+            Ok(text) => self.body = Some(InMemoryBody::Text(text.into())),
+            Err(bytes) => self.body = Some(InMemoryBody::Bytes(bytes.into_bytes().into())),
         }
-        // self.headers.insert(CONTENT_LENGTH, HeaderValue::from(len));
         self
     }
+
+    /// Send `items` as a JSON array in a chunked body, serializing one item at a time as the body is written,
+    /// so the whole array is never held in memory.
+    ///
+    /// The body is a [`ReplayableBody::once`]: the request goes through middlewares, which see an empty body,
+    /// but fails with [`crate::ProtocolError::NotReplayable`] if one of them may resend it, e.g. [`crate::Retry`] unless
+    /// the request is sent with [`RequestBuilder::no_retry`].
+    #[must_use]
+    pub fn json_stream<I>(mut self, items: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Serialize,
+        I::IntoIter: Send + 'static,
+    {
+        let items = items.into_iter().enumerate().map(|(i, item)| {
+            let mut chunk = if i == 0 { Vec::new() } else { vec![b','] };
+            serde_json::to_writer(&mut chunk, &item)?;
+            Ok::<_, serde_json::Error>(chunk)
+        });
+        let chunks = std::iter::once(Ok(vec![b'['])).chain(items).chain(std::iter::once(Ok(vec![b']'])));
+        self.headers.entry(CONTENT_TYPE).or_insert(CONTENT_JSON.clone());
+        self.headers.entry(ACCEPT).or_insert(ACCEPT_JSON.clone());
+        self.replayable_body(ReplayableBody::once(stream::iter(chunks)))
+    }
+
+    /// Send a multipart form whose parts may be streams, e.g. from [`crate::multipart::Part::stream`].
+    /// Each part is read only as the body is written, and the body is sent chunked.
+    ///
+    /// Like [`RequestBuilder::json_stream`], the body can only be sent once.
+    #[cfg(feature = "multipart")]
+    #[must_use]
+    pub fn multipart_stream(mut self, form: Form<crate::Body>) -> Self {
+        let content_type = form.full_content_type();
+        self.headers.entry(CONTENT_TYPE).or_insert(content_type.parse().expect("Invalid multipart content type"));
+        self.replayable_body(ReplayableBody::once(form.into_stream()))
+    }
 }
 
 impl<'a> RequestBuilder<'a> {
@@ -159,29 +236,61 @@ impl<'a> RequestBuilder<'a> {
     pub async fn send(self) -> ProtocolResult<Response> {
         let client = self.client;
         let (request, middlewares) = self.into_req_and_middleware();
-        let next = Next {
-            client,
-            middlewares: &middlewares,
-        };
-        next.run(request).await
+        client.run(request, &middlewares).await
+    }
+}
+
+/// Add the `defaults` for headers that `headers` doesn't set, so headers set on the request always take precedence.
+fn merge_defaults(mut headers: HeaderMap, defaults: HeaderMap) -> HeaderMap {
+    let mut name = None;
+    for (k, v) in defaults {
+        // `HeaderMap::into_iter` only yields the name for the first of several values.
+        if let Some(k) = k {
+            name = (!headers.contains_key(&k)).then_some(k);
+        }
+        if let Some(name) = &name {
+            headers.append(name, v);
+        }
     }
+    headers
 }
 
 impl<'a, C, B: Default> RequestBuilder<'a, C, B> {
     pub fn build(self) -> Request<B> {
         let mut b = Request::builder().method(self.method).uri(self.uri).version(self.version);
-        *b.headers_mut().unwrap() = self.headers;
+        *b.headers_mut().expect("Request builder is valid") = merge_defaults(self.headers, self.default_headers);
+        *b.extensions_mut().expect("Request builder is valid") = self.extensions;
         b.body(self.body.unwrap_or_default()).expect("Failed to build request in .build")
     }
 
     pub fn into_req_and_middleware(self) -> (Request<B>, Vec<Arc<dyn Middleware>>) {
         let mut request = http::Request::builder().method(self.method).uri(self.uri).version(self.version);
-        *request.headers_mut().unwrap() = self.headers;
+        *request.headers_mut().expect("Request builder is valid") = merge_defaults(self.headers, self.default_headers);
+        *request.extensions_mut().expect("Request builder is valid") = self.extensions;
         let request = request.body(self.body.unwrap_or_default().into()).unwrap();
         (request, self.middlewares)
     }
 }
 
+impl<'a, B> RequestBuilder<'a, Client, B> {
+    /// Start from the parts of an existing `http::Request`, e.g. while migrating code from another client.
+    /// The request is sent through the client's middlewares, but its default headers aren't added.
+    pub fn from_http_parts(client: &'a Client, parts: http::request::Parts, body: B) -> Self {
+        RequestBuilder {
+            client,
+            version: parts.version,
+            method: parts.method,
+            uri: parts.uri,
+            headers: parts.headers,
+            default_headers: HeaderMap::default(),
+            body: Some(body),
+            extensions: parts.extensions,
+            middlewares: client.middlewares.clone(),
+            query_format: QueryFormat::default(),
+        }
+    }
+}
+
 impl<'a, C, B> RequestBuilder<'a, C, B> {
     pub fn for_client(client: &'a C) -> RequestBuilder<'a, C> {
         RequestBuilder {
@@ -190,8 +299,11 @@ impl<'a, C, B> RequestBuilder<'a, C, B> {
             method: Default::default(),
             uri: Default::default(),
             headers: Default::default(),
+            default_headers: HeaderMap::default(),
             body: Default::default(),
+            extensions: Default::default(),
             middlewares: Default::default(),
+            query_format: QueryFormat::default(),
         }
     }
 
@@ -230,6 +342,18 @@ impl<'a, C, B> RequestBuilder<'a, C, B> {
         self
     }
 
+    /// Add a header value, keeping any values already set for `key`, e.g. for headers that may be sent more than once.
+    /// [`RequestBuilder::header`] replaces them instead.
+    #[must_use]
+    pub fn append_header<K: TryInto<HeaderName>>(mut self, key: K, value: &str) -> Self
+    where
+        <K as TryInto<HeaderName>>::Error: std::fmt::Debug,
+    {
+        let header = key.try_into().expect("Failed to convert key to HeaderName");
+        self.headers.append(header, HeaderValue::from_str(value).expect("Invalid header value"));
+        self
+    }
+
     #[must_use]
     pub fn cookie(mut self, key: &str, value: &str) -> Self {
         match self.headers.entry(COOKIE) {
@@ -263,10 +387,18 @@ impl<'a, C, B> RequestBuilder<'a, C, B> {
         self
     }
 
+    /// Choose how arrays are serialized by later calls to [`RequestBuilder::set_query`] and [`RequestBuilder::form`].
+    /// Defaults to [`QueryFormat::Indexed`] (`key[0]=a&key[1]=b`).
+    #[must_use]
+    pub fn query_format(mut self, format: QueryFormat) -> Self {
+        self.query_format = format;
+        self
+    }
+
     /// Overwrite the query with the provided value.
     #[must_use]
     pub fn set_query<S: Serialize>(mut self, obj: S) -> Self {
-        let qs = serde_qs::to_string(&obj).expect("Failed to serialize query in .set_query");
+        let qs = to_query_string(&obj, self.query_format).expect("Failed to serialize query in .set_query");
         let mut parts = std::mem::take(&mut self.uri).into_parts();
         let pq = parts.path_and_query.unwrap();
         let pq = PathAndQuery::from_str(&format!("{}?{}", pq.path(), qs)).unwrap();
@@ -301,12 +433,108 @@ impl<'a, C, B> RequestBuilder<'a, C, B> {
         self
     }
 
+    /// Add a url query parameter from any displayable value, e.g. a number or an enum, like [`RequestBuilder::query`].
+    #[must_use]
+    pub fn query_typed(self, k: &str, v: impl std::fmt::Display) -> Self {
+        self.query(k, &v.to_string())
+    }
+
+    /// Add a url query parameter if `v` is `Some`, e.g. for optional filters. `None` adds nothing.
+    /// ```
+    /// # use httpclient::Client;
+    /// # let client = Client::new();
+    /// let r = client.get("/users").query_opt("page", Some(2)).query_opt("cursor", None::<&str>);
+    /// assert_eq!(r.uri.to_string(), "/users?page=2");
+    /// ```
+    #[must_use]
+    pub fn query_opt(self, k: &str, v: Option<impl std::fmt::Display>) -> Self {
+        match v {
+            Some(v) => self.query_typed(k, v),
+            None => self,
+        }
+    }
+
+    /// Add each key and value as a url query parameter, in order, keeping existing parameters.
+    #[must_use]
+    pub fn query_pairs<K: AsRef<str>, V: std::fmt::Display>(self, pairs: impl IntoIterator<Item = (K, V)>) -> Self {
+        pairs.into_iter().fold(self, |r, (k, v)| r.query_typed(k.as_ref(), v))
+    }
+
+    /// Substitute `{name}` placeholders in the path with `value`, percent-encoded so it stays within one path segment.
+    /// # Panics
+    /// If the path has no `{name}` placeholder.
+    /// # Examples
+    /// ```
+    /// # use httpclient::Client;
+    /// # let client = Client::new();
+    /// let r = client.get("/users/{id}/repos").path_param("id", "a/b c");
+    /// assert_eq!(r.uri.path(), "/users/a%2Fb%20c/repos");
+    /// ```
+    #[must_use]
+    pub fn path_param(mut self, name: &str, value: impl std::fmt::Display) -> Self {
+        let value = value.to_string();
+        // `.` and `..` are unreserved, but would be resolved as relative path segments.
+        let encoded = if value == "." || value == ".." {
+            value.replace('.', "%2E")
+        } else {
+            urlencoding::encode(&value).into_owned()
+        };
+        let mut parts = std::mem::take(&mut self.uri).into_parts();
+        let pq = parts.path_and_query.expect("URI has no path");
+        let placeholder = format!("{{{name}}}");
+        assert!(pq.path().contains(&placeholder), "No {placeholder} placeholder in path {}", pq.path());
+        let path = pq.path().replace(&placeholder, &encoded);
+        let pq = match pq.query() {
+            Some(q) => format!("{path}?{q}"),
+            None => path,
+        };
+        parts.path_and_query = Some(PathAndQuery::from_str(&pq).expect("Percent-encoded path is valid"));
+        self.uri = Uri::from_parts(parts).expect("Percent-encoded URI is valid");
+        self
+    }
+
     #[must_use]
     pub fn content_type(mut self, content_type: &str) -> Self {
         self.headers.insert(header::CONTENT_TYPE, content_type.parse().unwrap());
         self
     }
 
+    /// Bind this request's connection to a local address, e.g. a tenant's egress IP, instead of the client's
+    /// [`Client::local_address`]. Connections for each local address are pooled separately. They use the client's
+    /// connector settings, so the request fails if the client uses a connector set with `Client::with_tls_connector`.
+    #[cfg(not(target_arch = "wasm32"))]
+    #[must_use]
+    pub fn local_address(mut self, addr: impl Into<IpAddr>) -> Self {
+        self.extensions.insert(LocalAddress(addr.into()));
+        self
+    }
+
+    /// Attach a typed value to the request, which middlewares can read with `request.extensions().get::<T>()`.
+    #[must_use]
+    pub fn extension<T: Clone + Send + Sync + 'static>(mut self, value: T) -> Self {
+        self.extensions.insert(value);
+        self
+    }
+
+    /// Record this request and its response byte-exact, without hiding secrets. See [`crate::sanitize::NoSanitize`].
+    #[must_use]
+    pub fn no_sanitize(self) -> Self {
+        self.extension(NoSanitize)
+    }
+
+    /// Return a redirect as is instead of following it, even if the client uses [`crate::Follow`].
+    #[must_use]
+    pub fn no_follow(self) -> Self {
+        self.extension(NoFollow)
+    }
+
+    /// Send this request once, even if the client uses [`crate::Retry`] or [`crate::Failover`], e.g. for a webhook or
+    /// a presigned upload that mustn't be repeated.
+    #[must_use]
+    pub fn no_retry(self) -> Self {
+        self.extension(NoRetry)
+    }
+
     /// Warning: Does not set content-type!
     #[must_use]
     pub fn body(mut self, body: B) -> Self {
@@ -344,9 +572,9 @@ impl<'a> IntoFuture for RequestBuilder<'a, Client> {
                 Err(e) => return Err(e.into()),
             };
             if let InMemoryBody::Bytes(bytes) = body {
-                body = match String::from_utf8(bytes) {
+                body = match Utf8Bytes::from_utf8(bytes.clone()) {
                     Ok(text) => InMemoryBody::Text(text),
-                    Err(e) => InMemoryBody::Bytes(e.into_bytes()),
+                    Err(_) => InMemoryBody::Bytes(bytes),
                 };
             }
             let status = &parts.status;
@@ -365,6 +593,7 @@ mod tests {
     use serde::{Deserialize, Serialize};
 
     use super::*;
+    use crate::{InMemoryRequest, ProtocolError};
 
     #[derive(Serialize, Deserialize)]
     pub struct TopLevel {
@@ -383,4 +612,87 @@ mod tests {
         let r = c.get("/api").set_query(qs).build();
         assert_eq!(r.uri().to_string(), "/api?inside[a]=1");
     }
+
+    #[test]
+    fn test_typed_query() {
+        let c = Client::new();
+        let r = c.get("/api?a=1").query_typed("limit", 10).query_opt("page", Some(2)).query_opt("cursor", None::<&str>);
+        let r = r.query_pairs([("tag", "a b"), ("tag", "c")]);
+        assert_eq!(r.uri.to_string(), "/api?a=1&limit=10&page=2&tag=a%20b&tag=c");
+    }
+
+    #[test]
+    fn test_path_param() {
+        let c = Client::new();
+        let r = c.get("https://example.com/orgs/{org}/users/{id}?page=2").path_param("org", "a/b c").path_param("id", 42);
+        assert_eq!(r.uri.to_string(), "https://example.com/orgs/a%2Fb%20c/users/42?page=2");
+        let r = c.get("/files/{name}").path_param("name", "..");
+        assert_eq!(r.uri.path(), "/files/%2E%2E");
+    }
+
+    #[test]
+    fn test_from_http_parts() {
+        let c = Client::new();
+        let (parts, ()) = http::Request::builder()
+            .method("PUT")
+            .uri("https://example.com/a")
+            .header("x-a", "1")
+            .body(())
+            .unwrap()
+            .into_parts();
+        let r = RequestBuilder::from_http_parts(&c, parts, InMemoryBody::Text("hi".into())).build();
+        assert_eq!(r.method(), Method::PUT);
+        assert_eq!(r.headers()["x-a"], "1");
+        assert!(matches!(r.body(), InMemoryBody::Text(t) if t == "hi"));
+    }
+
+    #[test]
+    fn test_default_header_precedence() {
+        let c = Client::new()
+            .default_header("Content-Type", "application/json")
+            .default_header("X-A", "1")
+            .default_header("X-A", "2");
+        let r = c.post("/api").text("hi".to_string()).build();
+        assert_eq!(r.headers().get_all(CONTENT_TYPE).iter().collect::<Vec<_>>(), vec!["text/plain"]);
+        assert_eq!(r.headers().get_all("x-a").iter().collect::<Vec<_>>(), vec!["1", "2"]);
+        let r = c.get("/api").headers([("x-a", "3")].into_iter()).build();
+        assert_eq!(r.headers().get_all("x-a").iter().collect::<Vec<_>>(), vec!["3"]);
+        assert_eq!(r.headers()[CONTENT_TYPE], "application/json");
+    }
+
+    #[test]
+    fn test_no_sanitize() {
+        let c = Client::new();
+        let r = c.get("/api").build();
+        assert!(!crate::sanitize::is_sanitize_disabled(&r));
+        let r = c.get("/api").no_sanitize().build();
+        assert!(crate::sanitize::is_sanitize_disabled(&r));
+    }
+
+    #[tokio::test]
+    async fn test_json_stream() {
+        let c = Client::new();
+        let rows = (0..3).map(|a| Nested { a });
+        let r = c.post("/api").json_stream(rows).build();
+        assert_eq!(r.headers()[CONTENT_TYPE], CONTENT_JSON);
+        let (body, _) = r.extensions().get::<ReplayableBody>().unwrap().body().unwrap();
+        assert_eq!(hyper::body::to_bytes(body).await.unwrap(), br#"[{"a":0},{"a":1},{"a":2}]"#.as_slice());
+
+        let r = c.post("/api").json_stream(Vec::<Nested>::new()).build();
+        let (body, _) = r.extensions().get::<ReplayableBody>().unwrap().body().unwrap();
+        assert_eq!(hyper::body::to_bytes(body).await.unwrap(), b"[]".as_slice());
+    }
+
+    #[tokio::test]
+    async fn test_json_stream_middlewares() {
+        let respond = crate::middleware_fn(|request: InMemoryRequest, _next| async move {
+            assert_eq!(request.headers()[CONTENT_TYPE], CONTENT_JSON);
+            Ok(Response::new(InMemoryBody::Empty.into()))
+        });
+        let c = Client::new().with_middleware(crate::Retry::new()).with_middleware(respond);
+        let err = c.post("/api").json_stream([1, 2]).send().await.unwrap_err();
+        assert!(matches!(err, ProtocolError::NotReplayable(_)));
+        let res = c.post("/api").json_stream([1, 2]).no_retry().send().await.unwrap();
+        assert_eq!(res.status(), http::StatusCode::OK);
+    }
 }
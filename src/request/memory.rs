@@ -1,7 +1,19 @@
+use http::header::CONTENT_TYPE;
+
+use crate::error::ProtocolResult;
 use crate::{InMemoryBody, Request};
 
 pub type InMemoryRequest = Request<InMemoryBody>;
 
+/// Convert a plain `http::Request`, e.g. one built for another client, keeping its version and extensions.
+/// The body is decoded by its `Content-Type` like a response body, so invalid JSON is an error.
+pub fn request_from_http(request: Request<Vec<u8>>) -> ProtocolResult<InMemoryRequest> {
+    let (parts, body) = request.into_parts();
+    let body = crate::body::decode(body.into(), parts.headers.get(CONTENT_TYPE))?;
+    Ok(Request::from_parts(parts, body))
+}
+
+#[cfg(feature = "recorder")]
 pub mod serde_request {
     use std::str::FromStr;
 
@@ -10,19 +22,28 @@ pub mod serde_request {
     use serde::ser::SerializeMap;
     use serde::{Deserializer, Serializer};
 
+    use crate::body::BASE64_ENCODING;
+    use crate::recorder::BodyFile;
     use crate::{InMemoryBody, InMemoryRequest};
 
     pub fn serialize<S>(req: &InMemoryRequest, serializer: S) -> crate::Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
-        let size = 3 + usize::from(!req.body().is_empty());
+        let body_file = req.extensions().get::<BodyFile>();
+        let base64 = req.body().recorded_base64().filter(|_| !req.body().is_empty() && body_file.is_none());
+        let size = 3 + usize::from(!req.body().is_empty()) + usize::from(base64.is_some());
         let mut map = serializer.serialize_map(Some(size))?;
         map.serialize_entry("method", &req.method().as_str())?;
         map.serialize_entry("url", &req.uri().to_string().as_str())?;
         let ordered: std::collections::BTreeMap<_, _> = req.headers().iter().map(|(k, v)| (k.as_str(), v.to_str().unwrap())).collect();
         map.serialize_entry("headers", &ordered)?;
-        if !req.body().is_empty() {
+        if let Some(BodyFile(name)) = body_file {
+            map.serialize_entry("body_file", name)?;
+        } else if let Some(base64) = base64 {
+            map.serialize_entry("body_encoding", BASE64_ENCODING)?;
+            map.serialize_entry("body", &base64)?;
+        } else if !req.body().is_empty() {
             map.serialize_entry("body", &req.body())?;
         }
         map.end()
@@ -48,6 +69,8 @@ pub mod serde_request {
             let mut url = None;
             let mut headers = None;
             let mut body = None;
+            let mut encoding = None;
+            let mut body_file = None;
             while let Some(key) = map.next_key::<Cow<str>>()? {
                 match key.as_ref() {
                     "method" => {
@@ -70,6 +93,8 @@ pub mod serde_request {
                         }
                         body = Some(map.next_value::<InMemoryBody>()?);
                     }
+                    "body_encoding" => encoding = Some(map.next_value::<String>()?),
+                    "body_file" => body_file = Some(map.next_value::<String>()?),
                     "headers" => {
                         if headers.is_some() {
                             return Err(<A::Error as Error>::duplicate_field("headers"));
@@ -89,9 +114,12 @@ pub mod serde_request {
                     .iter()
                     .map(|(k, v)| (HeaderName::from_bytes(k.as_bytes()).unwrap(), HeaderValue::from_str(v).unwrap())),
             );
-            let body = body.unwrap_or(InMemoryBody::Empty);
+            let body = body.unwrap_or(InMemoryBody::Empty).decode_recorded(encoding.as_deref())?;
             let mut b = Request::builder().method(method).uri(url);
             *b.headers_mut().unwrap() = headers;
+            if let Some(name) = body_file {
+                b = b.extension(BodyFile(name));
+            }
             b.body(body).map_err(|e| <A::Error as Error>::custom(format!("Invalid request: {}", e)))
         }
     }
@@ -106,16 +134,17 @@ pub mod serde_request {
 
 #[cfg(test)]
 mod tests {
-    use std::io::BufWriter;
-
-    use serde::{Deserialize, Serialize};
-
-    use crate::recorder::HashableRequest;
-
     use super::*;
 
+    #[cfg(feature = "recorder")]
     #[test]
     fn test_request_serialization_roundtrip() {
+        use std::io::BufWriter;
+
+        use serde::{Deserialize, Serialize};
+
+        use crate::recorder::HashableRequest;
+
         #[derive(Serialize, Deserialize, Debug)]
         struct Foobar {
             a: u32,
@@ -136,4 +165,21 @@ mod tests {
         let r2 = serde_request::deserialize(&mut deserializer).unwrap();
         assert_eq!(HashableRequest(r1), HashableRequest(r2));
     }
+    #[test]
+    fn test_request_from_http() {
+        let request = Request::builder()
+            .method("POST")
+            .uri("https://example.com/")
+            .header(CONTENT_TYPE, "application/json; charset=utf-8")
+            .extension(7u8)
+            .body(br#"{"a":1}"#.to_vec())
+            .unwrap();
+        let request = request_from_http(request).unwrap();
+        assert!(matches!(request.body(), InMemoryBody::Json(v) if v["a"] == 1));
+        assert_eq!(request.extensions().get::<u8>(), Some(&7));
+        let text = request_from_http(Request::new(b"hi".to_vec())).unwrap();
+        assert!(matches!(text.body(), InMemoryBody::Text(t) if t == "hi"));
+        let invalid = Request::builder().header(CONTENT_TYPE, "application/json").body(b"{".to_vec()).unwrap();
+        assert!(request_from_http(invalid).is_err());
+    }
 }
@@ -0,0 +1,74 @@
+use serde::Serialize;
+
+/// How arrays are written in query strings and url-encoded forms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum QueryFormat {
+    /// `key[0]=a&key[1]=b`
+    #[default]
+    Indexed,
+    /// `key[]=a&key[]=b`
+    Brackets,
+    /// `key=a&key=b`
+    Repeated,
+    /// `key=a,b`
+    Comma,
+}
+
+/// Split a key like `tags[0]` into `tags`, if it ends with an array index.
+fn strip_index(key: &str) -> Option<&str> {
+    let rest = key.strip_suffix(']')?;
+    let (base, index) = rest.rsplit_once('[')?;
+    (!index.is_empty() && index.bytes().all(|b| b.is_ascii_digit())).then_some(base)
+}
+
+/// Serialize `obj` to a query string, writing arrays as `format` says.
+pub(crate) fn to_query_string<S: Serialize>(obj: &S, format: QueryFormat) -> Result<String, serde_qs::Error> {
+    let qs = serde_qs::to_string(obj)?;
+    if format == QueryFormat::Indexed {
+        return Ok(qs);
+    }
+    let mut pairs: Vec<(String, String)> = Vec::new();
+    for pair in qs.split('&').filter(|p| !p.is_empty()) {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        let Some(base) = strip_index(key) else {
+            pairs.push((key.to_string(), value.to_string()));
+            continue;
+        };
+        match format {
+            QueryFormat::Brackets => pairs.push((format!("{base}[]"), value.to_string())),
+            QueryFormat::Comma => match pairs.last_mut() {
+                Some((k, v)) if k == base => {
+                    v.push(',');
+                    v.push_str(value);
+                }
+                _ => pairs.push((base.to_string(), value.to_string())),
+            },
+            QueryFormat::Repeated | QueryFormat::Indexed => pairs.push((base.to_string(), value.to_string())),
+        }
+    }
+    Ok(pairs.into_iter().map(|(k, v)| format!("{k}={v}")).collect::<Vec<_>>().join("&"))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::RequestBuilder;
+
+    use super::*;
+
+    #[test]
+    fn test_query_formats() {
+        let obj = json!({"q": "a b", "tags": ["x", "y"], "filter": {"ids": [1, 2]}});
+        let qs = |format| to_query_string(&obj, format).unwrap();
+        assert_eq!(qs(QueryFormat::Indexed), "filter[ids][0]=1&filter[ids][1]=2&q=a+b&tags[0]=x&tags[1]=y");
+        assert_eq!(qs(QueryFormat::Brackets), "filter[ids][]=1&filter[ids][]=2&q=a+b&tags[]=x&tags[]=y");
+        assert_eq!(qs(QueryFormat::Repeated), "filter[ids]=1&filter[ids]=2&q=a+b&tags=x&tags=y");
+        assert_eq!(qs(QueryFormat::Comma), "filter[ids]=1,2&q=a+b&tags=x,y");
+
+        let req = RequestBuilder::get("https://example.com/search")
+            .query_format(QueryFormat::Repeated)
+            .set_query(json!({"tags": ["x", "y"]}));
+        assert_eq!(req.uri.to_string(), "https://example.com/search?tags=x&tags=y");
+    }
+}
@@ -1,14 +1,26 @@
+use std::collections::HashMap;
+
 use async_trait::async_trait;
-use http::Response;
-use hyper::body::Bytes;
+use cookie::Cookie;
+use http::header::SET_COOKIE;
+use http::{Extensions, HeaderMap, HeaderName, HeaderValue, Response, Uri};
+use hyper::body::{Bytes, HttpBody};
 use serde::de::DeserializeOwned;
 
 pub use memory::*;
+#[cfg(feature = "stream")]
+pub use text_stream::TextStream;
 
-use crate::body::Body;
+use crate::body::{Body, TryClone};
+use crate::error::ProtocolResult;
+use crate::media::MediaType;
+use crate::middleware::{Redirect, RedirectHistory};
+use crate::sanitize::sanitize_response;
 use crate::{InMemoryResult, Result};
 
 mod memory;
+#[cfg(feature = "stream")]
+mod text_stream;
 
 #[async_trait]
 pub trait ResponseExt
@@ -20,7 +32,50 @@ where
     async fn json<U: DeserializeOwned>(self) -> InMemoryResult<U>;
     /// Get body as bytes.
     async fn bytes(self) -> InMemoryResult<Bytes>;
+    /// The value of the cookie `name` as sent, from whichever `Set-Cookie` header sets it.
+    /// [`ResponseExt::cookies`] has percent-decoded values.
     fn get_cookie(&self, name: &str) -> Option<&str>;
+    /// Every cookie the response sets, one per `Set-Cookie` header, with attributes like `Path` and `Max-Age`.
+    fn cookies(&self) -> Vec<Cookie<'_>>;
+    /// Every value of the header `name`, in order, e.g. for headers sent more than once. Values that aren't valid
+    /// text are skipped.
+    fn headers_all(&self, name: &str) -> Vec<&str>;
+    /// Typed values carried with the response, e.g. `res.ext().get::<ConnectionInfo>()`.
+    /// See [`crate::Middleware`] for how far they travel.
+    fn ext(&self) -> &Extensions;
+    fn ext_mut(&mut self) -> &mut Extensions;
+    /// The URL the response came from after [`crate::Follow`] followed any redirects. `None` without `Follow`.
+    fn final_url(&self) -> Option<&Uri>;
+    /// The redirects [`crate::Follow`] followed to reach the response, in order. Empty without `Follow`.
+    fn redirect_history(&self) -> &[Redirect];
+    /// Read the body into memory, using the content type to decide how to store it.
+    async fn into_memory(self) -> ProtocolResult<InMemoryResponse>;
+    /// Links from the `Link` header, keyed by relation type, e.g. `next`, `prev`, `last`.
+    fn links(&self) -> HashMap<String, String>;
+    /// Language tags from the `Content-Language` header.
+    fn content_language(&self) -> Vec<String>;
+    /// The `Content-Length` header, e.g. to check a download's size from a `HEAD` request before making it.
+    /// `None` if it's missing or invalid, e.g. for a chunked body.
+    fn content_length(&self) -> Option<u64>;
+    /// The parsed `Content-Type` header, e.g. to check `res.content_type().is_some_and(|t| t.is_json())`.
+    fn content_type(&self) -> Option<MediaType>;
+    /// A sanitized in-memory copy of the response, e.g. for error reporters and audit logs.
+    /// A body still streaming from the network is read into memory first and kept there, so the response stays readable.
+    async fn cloned_sanitized(&mut self) -> ProtocolResult<InMemoryResponse>;
+    /// HTTP trailers sent after the body, e.g. `grpc-status` from gRPC-web. The rest of the body is read to reach them
+    /// and kept in memory, so the response stays readable. `None` if there were none, or the body is already in memory.
+    /// Streams wrapped by [`crate::Timeouts`]'s `idle_read` or a streaming [`crate::Logger`] don't carry trailers.
+    async fn trailers(&mut self) -> ProtocolResult<Option<HeaderMap>>;
+    /// Stream the body's data chunks as they arrive, instead of buffering it.
+    ///
+    /// Reading from the returned [`Body`] is cancel-safe: a chunk is only taken from the connection when it's returned,
+    /// so dropping a pending `next()`, e.g. in a `select!`, loses nothing. The connection is read only as fast as the stream
+    /// is polled, so slow consumers apply backpressure to the server.
+    fn chunks(self) -> Body;
+    /// Stream the body as text chunks as they arrive, e.g. to tail logs or LLM token streams.
+    /// Code points split across chunks are held back until complete. See [`TextStream`].
+    #[cfg(feature = "stream")]
+    fn text_stream(self) -> TextStream;
 }
 
 #[async_trait]
@@ -54,10 +109,184 @@ impl ResponseExt for Response<Body> {
     }
 
     fn get_cookie(&self, name: &str) -> Option<&str> {
-        let value = self.headers().get("set-cookie")?;
-        let value = value.to_str().ok()?;
-        let cookie = cookie::Cookie::split_parse_encoded(value);
-        let cookie = cookie.into_iter().filter_map(std::result::Result::ok).find(|c| c.name() == name)?;
-        cookie.value_raw()
+        get_cookie(self.headers(), name)
+    }
+
+    fn cookies(&self) -> Vec<Cookie<'_>> {
+        cookies(self.headers())
+    }
+
+    fn headers_all(&self, name: &str) -> Vec<&str> {
+        headers_all(self.headers(), name)
+    }
+
+    fn ext(&self) -> &Extensions {
+        self.extensions()
+    }
+
+    fn ext_mut(&mut self) -> &mut Extensions {
+        self.extensions_mut()
+    }
+
+    fn final_url(&self) -> Option<&Uri> {
+        self.extensions().get::<RedirectHistory>().map(|history| &history.final_url)
+    }
+
+    fn redirect_history(&self) -> &[Redirect] {
+        self.extensions().get::<RedirectHistory>().map_or(&[], |history| &history.redirects)
+    }
+
+    async fn into_memory(self) -> ProtocolResult<InMemoryResponse> {
+        let (parts, body) = self.into_parts();
+        let content_type = parts.headers.get(http::header::CONTENT_TYPE);
+        let body = body.into_content_type(content_type).await?;
+        Ok(InMemoryResponse::from_parts(parts, body))
+    }
+
+    fn links(&self) -> HashMap<String, String> {
+        crate::link::links(self.headers())
+    }
+
+    fn content_language(&self) -> Vec<String> {
+        crate::language::content_language(self.headers())
+    }
+
+    fn content_length(&self) -> Option<u64> {
+        crate::middleware::content_length(self.headers())
+    }
+
+    fn content_type(&self) -> Option<MediaType> {
+        crate::media::content_type(self.headers())
+    }
+
+    async fn cloned_sanitized(&mut self) -> ProtocolResult<InMemoryResponse> {
+        let body = match std::mem::take(self.body_mut()) {
+            Body::InMemory(body) => body,
+            body @ Body::Hyper(_) => body.into_content_type(self.headers().get(http::header::CONTENT_TYPE)).await?,
+        };
+        let mut copy = self.try_clone().expect("Response body is in memory").map(|_| body.clone());
+        *self.body_mut() = Body::InMemory(body);
+        sanitize_response(&mut copy);
+        Ok(copy)
+    }
+
+    fn chunks(self) -> Body {
+        self.into_body()
+    }
+
+    #[cfg(feature = "stream")]
+    fn text_stream(self) -> TextStream {
+        TextStream::new(self.into_body())
+    }
+
+    async fn trailers(&mut self) -> ProtocolResult<Option<HeaderMap>> {
+        let Body::Hyper(body) = self.body_mut() else {
+            return Ok(None);
+        };
+        let mut data = Vec::new();
+        while let Some(chunk) = body.data().await {
+            data.extend_from_slice(&chunk?);
+        }
+        let trailers = body.trailers().await?;
+        *body = hyper::Body::from(data);
+        // hyper's header types are from an older `http`, so convert them.
+        Ok(trailers.map(|trailers| {
+            trailers
+                .iter()
+                .filter_map(|(k, v)| Some((HeaderName::from_bytes(k.as_str().as_bytes()).ok()?, HeaderValue::from_bytes(v.as_bytes()).ok()?)))
+                .collect()
+        }))
+    }
+}
+
+/// The cookies set by each `Set-Cookie` header.
+fn cookies(headers: &HeaderMap) -> Vec<Cookie<'_>> {
+    headers_all(headers, SET_COOKIE.as_str())
+        .into_iter()
+        .filter_map(|value| Cookie::parse_encoded(value).ok())
+        .collect()
+}
+
+/// The value of the cookie `name` as sent, still percent-encoded.
+fn get_cookie<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    let mut cookies = headers_all(headers, SET_COOKIE.as_str()).into_iter().filter_map(|value| Cookie::parse(value).ok());
+    cookies.find(|c| c.name() == name)?.value_raw()
+}
+
+fn headers_all<'a>(headers: &'a HeaderMap, name: &str) -> Vec<&'a str> {
+    headers.get_all(name).iter().filter_map(|v| v.to_str().ok()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::InMemoryBody;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_cloned_sanitized() {
+        let mut res = Response::builder()
+            .header("content-type", "application/json")
+            .header("set-cookie", "session=abc")
+            .body(Body::Hyper(hyper::Body::from(r#"{"token":"abc","name":"Ada"}"#)))
+            .unwrap();
+        let copy = res.cloned_sanitized().await.unwrap();
+        assert_eq!(copy.headers()["set-cookie"], crate::sanitize::SANITIZED_VALUE);
+        assert_eq!(copy.body().clone().json::<serde_json::Value>().unwrap(), json!({"token": "**********", "name": "Ada"}));
+
+        assert_eq!(res.headers()["set-cookie"], "session=abc");
+        assert_eq!(res.json::<serde_json::Value>().await.unwrap()["token"], "abc");
+    }
+
+    #[test]
+    fn test_cookies() {
+        let res = Response::builder()
+            .header("set-cookie", "session=abc; Path=/; HttpOnly")
+            .header("set-cookie", "theme=dark%20mode; Max-Age=60")
+            .body(Body::default())
+            .unwrap();
+        assert_eq!(res.headers_all("Set-Cookie").len(), 2);
+        assert_eq!(res.get_cookie("theme"), Some("dark%20mode"));
+        assert_eq!(res.get_cookie("Path"), None);
+        let cookies = res.cookies();
+        assert_eq!(
+            cookies.iter().map(|c| (c.name(), c.value())).collect::<Vec<_>>(),
+            [("session", "abc"), ("theme", "dark mode")]
+        );
+        assert_eq!(cookies[0].http_only(), Some(true));
+    }
+
+    #[tokio::test]
+    async fn test_trailers() {
+        let (mut sender, body) = hyper::Body::channel();
+        tokio::spawn(async move {
+            sender.send_data(Bytes::from("hello")).await.unwrap();
+            let mut trailers = hyper::HeaderMap::new();
+            trailers.insert("grpc-status", "0".parse().unwrap());
+            sender.send_trailers(trailers).await.unwrap();
+        });
+        let mut res = Response::new(Body::Hyper(body));
+        let trailers = res.trailers().await.unwrap().unwrap();
+        assert_eq!(trailers["grpc-status"], "0");
+        assert_eq!(res.text().await.unwrap(), "hello");
+
+        let mut res = Response::new(Body::InMemory(InMemoryBody::Text("hi".into())));
+        assert!(res.trailers().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_chunks_cancel_safe() {
+        use futures::StreamExt;
+
+        let (mut sender, body) = hyper::Body::channel();
+        let mut chunks = Response::new(Body::Hyper(body)).chunks();
+        let timed_out = tokio::time::timeout(std::time::Duration::from_millis(10), chunks.next()).await;
+        assert!(timed_out.is_err());
+        sender.send_data(Bytes::from("late")).await.unwrap();
+        drop(sender);
+        assert_eq!(chunks.next().await.unwrap().unwrap(), "late");
+        assert!(chunks.next().await.is_none());
     }
 }
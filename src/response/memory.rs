@@ -1,8 +1,15 @@
-use http::{HeaderMap, Response, StatusCode};
+use std::collections::HashMap;
+
+use cookie::Cookie;
+use http::header::CONTENT_TYPE;
+use http::{Extensions, HeaderMap, Response, StatusCode};
 use hyper::body::Bytes;
-use serde::de::{DeserializeOwned, Error};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::Value;
 
-use crate::{InMemoryBody, InMemoryResult, Result};
+use crate::media::MediaType;
+use crate::{InMemoryBody, InMemoryResult, TryClone};
 
 pub type InMemoryResponse = Response<InMemoryBody>;
 
@@ -12,10 +19,35 @@ pub trait InMemoryResponseExt {
     fn new(status: StatusCode, headers: HeaderMap, body: InMemoryBody) -> Self;
     fn text(self) -> InMemoryResult<String>;
     fn json<U: DeserializeOwned>(self) -> serde_json::Result<U>;
+    /// Deserialize the body borrowing from the response, e.g. into structs with `&str` fields. See [`InMemoryBody::json_borrowed`].
+    fn json_borrowed<'de, U: Deserialize<'de>>(&'de self) -> serde_json::Result<U>;
+    /// Parse a JSON body into a `serde_json::Value` in place, for middlewares that read or edit it.
+    /// JSON responses are otherwise kept as bytes until deserialized. `None` if the response isn't JSON.
+    fn json_value_mut(&mut self) -> serde_json::Result<Option<&mut Value>>;
     fn bytes(self) -> InMemoryResult<Bytes>;
 
+    /// See [`crate::ResponseExt::get_cookie`].
     fn get_cookie(&self, name: &str) -> Option<&str>;
+    /// See [`crate::ResponseExt::cookies`].
+    fn cookies(&self) -> Vec<Cookie<'_>>;
     fn header(&self, name: &str) -> Option<&str>;
+    /// See [`crate::ResponseExt::headers_all`].
+    fn headers_all(&self, name: &str) -> Vec<&str>;
+    /// Typed values carried with the response. See [`crate::ResponseExt::ext`].
+    fn ext(&self) -> &Extensions;
+    fn ext_mut(&mut self) -> &mut Extensions;
+    /// Links from the `Link` header, keyed by relation type, e.g. `next`, `prev`, `last`.
+    fn links(&self) -> HashMap<String, String>;
+    /// Language tags from the `Content-Language` header.
+    fn content_language(&self) -> Vec<String>;
+    /// The parsed `Content-Type` header, e.g. to check `res.content_type().is_some_and(|t| t.is_json())`.
+    fn content_type(&self) -> Option<MediaType>;
+    /// A sanitized copy of the response, e.g. for error reporters and audit logs.
+    #[must_use]
+    fn cloned_sanitized(&self) -> Self;
+    /// Convert into a plain `http::Response` with the bytes sent on the wire, keeping the version and extensions,
+    /// e.g. to hand to code written against `http` types.
+    fn into_http_bytes(self) -> serde_json::Result<Response<Bytes>>;
 }
 
 impl InMemoryResponseExt for InMemoryResponse {
@@ -36,43 +68,113 @@ impl InMemoryResponseExt for InMemoryResponse {
         body.json()
     }
 
+    fn json_borrowed<'de, U: Deserialize<'de>>(&'de self) -> serde_json::Result<U> {
+        self.body().json_borrowed()
+    }
+
+    fn json_value_mut(&mut self) -> serde_json::Result<Option<&mut Value>> {
+        if crate::body::is_json(self.headers().get(CONTENT_TYPE)) {
+            let value = match self.body() {
+                InMemoryBody::Bytes(b) => Some(serde_json::from_slice(b)?),
+                InMemoryBody::Text(t) => Some(serde_json::from_str(t)?),
+                InMemoryBody::Empty | InMemoryBody::Json(_) => None,
+            };
+            if let Some(value) = value {
+                *self.body_mut() = InMemoryBody::Json(value);
+            }
+        }
+        match self.body_mut() {
+            InMemoryBody::Json(value) => Ok(Some(value)),
+            _ => Ok(None),
+        }
+    }
+
     fn bytes(self) -> InMemoryResult<Bytes> {
         let (_, body) = self.into_parts();
         body.bytes()
     }
 
     fn get_cookie(&self, name: &str) -> Option<&str> {
-        let value = self.headers().get("set-cookie")?;
-        let value = value.to_str().ok()?;
-        let cookie = cookie::Cookie::split_parse_encoded(value);
-        let cookie = cookie.into_iter().filter_map(std::result::Result::ok).find(|c| c.name() == name)?;
-        cookie.value_raw()
+        super::get_cookie(self.headers(), name)
+    }
+
+    fn cookies(&self) -> Vec<Cookie<'_>> {
+        super::cookies(self.headers())
     }
 
     fn header(&self, name: &str) -> Option<&str> {
         self.headers().get(name).and_then(|v| v.to_str().ok())
     }
+
+    fn headers_all(&self, name: &str) -> Vec<&str> {
+        super::headers_all(self.headers(), name)
+    }
+
+    fn ext(&self) -> &Extensions {
+        self.extensions()
+    }
+
+    fn ext_mut(&mut self) -> &mut Extensions {
+        self.extensions_mut()
+    }
+
+    fn links(&self) -> HashMap<String, String> {
+        crate::link::links(self.headers())
+    }
+
+    fn content_language(&self) -> Vec<String> {
+        crate::language::content_language(self.headers())
+    }
+
+    fn content_type(&self) -> Option<MediaType> {
+        crate::media::content_type(self.headers())
+    }
+
+    fn cloned_sanitized(&self) -> Self {
+        let mut copy = self.try_clone().expect("In-memory responses can always be cloned");
+        crate::sanitize::sanitize_response(&mut copy);
+        copy
+    }
+
+    fn into_http_bytes(self) -> serde_json::Result<Response<Bytes>> {
+        let (parts, body) = self.into_parts();
+        Ok(Response::from_parts(parts, body.into_wire_bytes()?))
+    }
 }
 
+#[cfg(feature = "recorder")]
 pub mod serde_response {
     use std::collections::BTreeMap;
     use std::str::FromStr;
 
+    use serde::de::Error;
     use serde::ser::SerializeStruct;
     use serde::Deserializer;
 
-    use super::{Error, HeaderMap, InMemoryBody, InMemoryResponse, Result, StatusCode};
+    use super::{HeaderMap, InMemoryBody, InMemoryResponse, StatusCode};
+    use crate::body::BASE64_ENCODING;
+    use crate::recorder::BodyFile;
+    use crate::Result;
 
     pub fn serialize<S>(v: &InMemoryResponse, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: serde::Serializer,
     {
-        let size = 2 + usize::from(!v.body().is_empty());
+        let body_file = v.extensions().get::<BodyFile>();
+        let base64 = v.body().recorded_base64().filter(|_| body_file.is_none());
+        let size = 2 + usize::from(!v.body().is_empty()) + usize::from(base64.is_some());
         let mut map = serializer.serialize_struct("InMemoryResponse", size)?;
         map.serialize_field("status", &v.status().as_u16())?;
         let ordered: BTreeMap<_, _> = v.headers().iter().map(|(k, v)| (k.as_str(), v.to_str().unwrap())).collect();
         map.serialize_field("headers", &ordered)?;
-        map.serialize_field("body", &v.body())?;
+        if let Some(BodyFile(name)) = body_file {
+            map.serialize_field("body_file", name)?;
+        } else if let Some(base64) = base64 {
+            map.serialize_field("body_encoding", BASE64_ENCODING)?;
+            map.serialize_field("body", &base64)?;
+        } else {
+            map.serialize_field("body", &v.body())?;
+        }
         map.end()
     }
 
@@ -95,6 +197,8 @@ pub mod serde_response {
             let mut status = None;
             let mut headers = None;
             let mut body = None;
+            let mut encoding = None;
+            let mut body_file = None;
             while let Some(key) = map.next_key::<Cow<str>>()? {
                 match key.as_ref() {
                     "status" => {
@@ -116,6 +220,8 @@ pub mod serde_response {
                         }
                         body = Some(map.next_value::<InMemoryBody>()?);
                     }
+                    "body_encoding" => encoding = Some(map.next_value::<String>()?),
+                    "body_file" => body_file = Some(map.next_value::<String>()?),
                     _ => {
                         map.next_value::<serde::de::IgnoredAny>()?;
                     }
@@ -130,8 +236,15 @@ pub mod serde_response {
                     .map(|(k, v)| (HeaderName::from_str(k).unwrap(), HeaderValue::from_str(v).unwrap())),
             );
 
-            let body = body.ok_or_else(|| Error::missing_field("body"))?;
+            let body = match body {
+                Some(body) => body.decode_recorded(encoding.as_deref())?,
+                None if body_file.is_some() => InMemoryBody::Empty,
+                None => return Err(Error::missing_field("body")),
+            };
             let mut b = http::response::Builder::new().status(status);
+            if let Some(name) = body_file {
+                b = b.extension(BodyFile(name));
+            }
             let h = b.headers_mut().unwrap();
             *h = headers;
             Ok(b.body(body).unwrap())
@@ -148,16 +261,17 @@ pub mod serde_response {
 
 #[cfg(test)]
 mod tests {
-    use std::io::BufWriter;
-
     use serde_json::json;
 
-    use crate::sanitize::sanitize_response;
-
     use super::*;
 
+    #[cfg(feature = "recorder")]
     #[test]
     fn test_serialize() {
+        use std::io::BufWriter;
+
+        use crate::sanitize::sanitize_response;
+
         let mut res = http::response::Builder::new()
             .body(InMemoryBody::Json(json!({
                 "Password": "hunter2",
@@ -172,6 +286,53 @@ mod tests {
         assert_eq!(serialized, r#"{"status":200,"headers":{},"body":{"Password":"**********","email":"amazing"}}"#);
     }
 
+    #[test]
+    fn test_into_http_bytes() {
+        let mut res = http::Response::builder()
+            .version(http::Version::HTTP_2)
+            .extension(7u8)
+            .body(InMemoryBody::Json(json!({"a": 1})))
+            .unwrap();
+        res.headers_mut().insert("x-a", "1".parse().unwrap());
+        let res = res.into_http_bytes().unwrap();
+        assert_eq!(res.version(), http::Version::HTTP_2);
+        assert_eq!(res.extensions().get::<u8>(), Some(&7));
+        assert_eq!(res.headers()["x-a"], "1");
+        assert_eq!(res.body().as_ref(), br#"{"a":1}"#);
+    }
+
+    #[test]
+    fn test_json_borrowed() {
+        #[derive(Deserialize)]
+        struct User<'a> {
+            name: &'a str,
+        }
+        let res = InMemoryResponse::new(InMemoryBody::Bytes(Bytes::from_static(br#"{"name":"Ada"}"#)));
+        let user: User = res.json_borrowed().unwrap();
+        assert_eq!(user.name, "Ada");
+        let InMemoryBody::Bytes(b) = res.body() else { unreachable!() };
+        assert!(b.as_ptr_range().contains(&user.name.as_ptr()));
+
+        let res = InMemoryResponse::new(InMemoryBody::Json(json!({"name": "Ada"})));
+        assert_eq!(res.json_borrowed::<User>().unwrap().name, "Ada");
+        let res = InMemoryResponse::new(InMemoryBody::Empty);
+        assert!(res.json_borrowed::<User>().is_err());
+    }
+
+    #[test]
+    fn test_json_value_mut() {
+        let mut res = http::Response::builder()
+            .header(CONTENT_TYPE, "application/json")
+            .body(InMemoryBody::Bytes(Bytes::from_static(br#"{"a":1}"#)))
+            .unwrap();
+        res.json_value_mut().unwrap().unwrap()["a"] = json!(2);
+        assert!(matches!(res.body(), InMemoryBody::Json(v) if *v == json!({"a": 2})));
+
+        let mut res = InMemoryResponse::new(InMemoryBody::Bytes(Bytes::from_static(br#"{"a":1}"#)));
+        assert!(res.json_value_mut().unwrap().is_none());
+    }
+
+    #[cfg(feature = "recorder")]
     #[test]
     fn test_deserialize_json_array() {
         let data = r#"
@@ -190,6 +351,20 @@ mod tests {
         assert!(body.is_array());
     }
 
+    #[cfg(feature = "recorder")]
+    #[test]
+    fn test_binary_roundtrip() {
+        let res = InMemoryResponse::new(InMemoryBody::Bytes(Bytes::from_static(&[0x89, b'P', b'N', b'G', 0, 0xff])));
+        let mut serializer = serde_json::Serializer::new(Vec::new());
+        serde_response::serialize(&res, &mut serializer).unwrap();
+        let serialized = String::from_utf8(serializer.into_inner()).unwrap();
+        assert_eq!(serialized, r#"{"status":200,"headers":{},"body_encoding":"base64","body":"iVBORwD/"}"#);
+
+        let deserialized = serde_response::deserialize(&mut serde_json::Deserializer::from_str(&serialized)).unwrap();
+        assert_eq!(deserialized.bytes().unwrap(), res.bytes().unwrap());
+    }
+
+    #[cfg(feature = "recorder")]
     #[test]
     fn test_deserialize_string() {
         let data = r#"
@@ -208,6 +383,7 @@ mod tests {
         assert_eq!(body, "foo");
     }
 
+    #[cfg(feature = "recorder")]
     #[test]
     fn test_deserialize_bytes() {
         let data = r#"
@@ -0,0 +1,89 @@
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use futures::Stream;
+
+use crate::error::{ProtocolError, ProtocolResult};
+use crate::Body;
+
+/// A response body decoded as UTF-8 text chunk by chunk. Made by `ResponseExt::text_stream`.
+///
+/// Each item is the text decoded so far; bytes of a code point split across network chunks are carried over
+/// to the next item. Invalid UTF-8, or a body that ends mid code point, fails the stream.
+#[derive(Debug)]
+pub struct TextStream {
+    body: Body,
+    partial: Vec<u8>,
+}
+
+impl TextStream {
+    pub(crate) fn new(body: Body) -> Self {
+        Self { body, partial: Vec::new() }
+    }
+
+    /// Take the longest complete UTF-8 prefix of the buffered bytes.
+    fn decode(&mut self) -> ProtocolResult<String> {
+        let valid = match std::str::from_utf8(&self.partial) {
+            Ok(_) => self.partial.len(),
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(_) => return Err(ProtocolError::invalid_input("Response body is not valid UTF-8")),
+        };
+        let rest = self.partial.split_off(valid);
+        let text = std::mem::replace(&mut self.partial, rest);
+        Ok(String::from_utf8(text)?)
+    }
+}
+
+impl Stream for TextStream {
+    type Item = ProtocolResult<String>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            match ready!(Pin::new(&mut this.body).poll_next(cx)) {
+                Some(Ok(chunk)) => {
+                    this.partial.extend_from_slice(&chunk);
+                    match this.decode() {
+                        Ok(text) if text.is_empty() => {}
+                        result => return Poll::Ready(Some(result)),
+                    }
+                }
+                Some(Err(e)) => return Poll::Ready(Some(Err(e))),
+                None if this.partial.is_empty() => return Poll::Ready(None),
+                None => {
+                    this.partial.clear();
+                    return Poll::Ready(Some(Err(ProtocolError::invalid_input("Response body ends mid UTF-8 code point"))));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::StreamExt;
+    use hyper::body::Bytes;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_text_stream() {
+        let (mut sender, body) = hyper::Body::channel();
+        tokio::spawn(async move {
+            // "héllo 👋", with both multi-byte characters split across chunks
+            for chunk in [&b"h\xc3"[..], b"\xa9llo \xf0\x9f", b"\x91", b"\x8b"] {
+                sender.send_data(Bytes::from_static(chunk)).await.unwrap();
+            }
+        });
+        let chunks: Vec<String> = TextStream::new(Body::Hyper(body)).map(Result::unwrap).collect().await;
+        assert_eq!(chunks, vec!["h", "\u{e9}llo ", "\u{1f44b}"]);
+
+        let mut stream = TextStream::new(Body::Hyper(hyper::Body::from(&b"ok\xf0\x9f"[..])));
+        assert_eq!(stream.next().await.unwrap().unwrap(), "ok");
+        assert!(stream.next().await.unwrap().is_err());
+        assert!(stream.next().await.is_none());
+
+        let mut stream = TextStream::new(Body::Hyper(hyper::Body::from(&b"a\xffb"[..])));
+        assert!(stream.next().await.unwrap().is_err());
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{InMemoryRequest, InMemoryResponse};
+use crate::{InMemoryRequest, InMemoryResponse, Request};
 use http::{HeaderMap, HeaderValue};
 use regex::Regex;
 use serde_json::Value;
@@ -36,6 +36,16 @@ fn regex() -> &'static Regex {
     })
 }
 
+/// Request extension that turns off sanitization when the request is recorded, e.g. for a fixture
+/// that must contain a test token byte-exact on replay. Set it with `RequestBuilder::no_sanitize`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoSanitize;
+
+/// Whether the request has opted out of sanitization with [`NoSanitize`].
+pub fn is_sanitize_disabled<B>(req: &Request<B>) -> bool {
+    req.extensions().get::<NoSanitize>().is_some()
+}
+
 pub static SANITIZED_VALUE: &str = "**********";
 pub static SANITIZED_HEADER_VALUE: HeaderValue = HeaderValue::from_static(SANITIZED_VALUE);
 
@@ -0,0 +1,282 @@
+use std::collections::BTreeMap;
+use std::ops::Deref;
+use std::sync::{Arc, Mutex, MutexGuard, RwLock};
+
+use async_trait::async_trait;
+use http::header::COOKIE;
+use http::HeaderValue;
+
+pub use jar::CookieJar;
+
+use crate::clock::Clock;
+use crate::error::ProtocolResult;
+use crate::middleware::Next;
+use crate::{Client, InMemoryRequest, InMemoryResult, Middleware, Response};
+
+mod jar;
+
+#[derive(Debug, Default)]
+struct State {
+    cookies: Mutex<CookieJar>,
+    auth: RwLock<Option<Arc<dyn Middleware>>>,
+}
+
+/// Sends the jar's cookies, runs the session's auth, and stores the cookies responses set.
+#[derive(Debug)]
+struct SessionMiddleware(Arc<State>);
+
+#[async_trait]
+impl Middleware for SessionMiddleware {
+    async fn handle(&self, mut request: InMemoryRequest, next: Next<'_>) -> ProtocolResult<Response> {
+        let uri = request.uri().clone();
+        if let Some(cookies) = self.0.cookies.lock().expect("Session cookie lock poisoned").header(&uri) {
+            let value = match request.headers().get(COOKIE).and_then(|v| v.to_str().ok()) {
+                Some(existing) => format!("{existing}; {cookies}"),
+                None => cookies,
+            };
+            if let Ok(value) = HeaderValue::from_str(&value) {
+                request.headers_mut().insert(COOKIE, value);
+            }
+        }
+        let auth = self.0.auth.read().expect("Session auth lock poisoned").clone();
+        let res = match auth {
+            Some(auth) => auth.handle(request, next).await?,
+            None => next.run(request).await?,
+        };
+        self.0.cookies.lock().expect("Session cookie lock poisoned").store(&uri, res.headers());
+        Ok(res)
+    }
+}
+
+/// A client that keeps state between requests: a cookie jar, and the auth set up by logging in.
+/// The base URL and default headers are the wrapped client's.
+///
+/// A session derefs to its [`Client`], so requests are made the same way. Clones share the cookies and auth.
+/// ```no_run
+/// # use httpclient::{Client, FormLogin, InMemoryResponseExt, Session};
+/// # async fn f() -> httpclient::InMemoryResult<()> {
+/// let session = Session::new(Client::new().base_url("https://example.com").user_agent("scraper/1.0"));
+/// session.login(&FormLogin::new("/login").field("username", "kurt").field("password", "hunter2")).await?;
+/// let page = session.get("/account").await?.text()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Session {
+    client: Client,
+    state: Arc<State>,
+}
+
+impl Session {
+    /// Wrap `client`. The session's middleware runs before the client's, so they see its cookies and auth.
+    /// If the client follows redirects, it runs right after [`crate::Follow`] instead, so each hop sends the cookies
+    /// for its own URL and stores the ones its response sets.
+    #[must_use]
+    pub fn new(mut client: Client) -> Self {
+        let state = Arc::new(State::default());
+        let index = client.middlewares.iter().rposition(|m| m.follows_redirects()).map_or(0, |i| i + 1);
+        client.middlewares.insert(index, Arc::new(SessionMiddleware(state.clone())));
+        Self { client, state }
+    }
+
+    /// Expire cookies by this clock, e.g. a [`crate::clock::TestClock`].
+    #[must_use]
+    pub fn clock<C: Clock + 'static>(self, clock: C) -> Self {
+        self.cookies().set_clock(Arc::new(clock));
+        self
+    }
+
+    #[must_use]
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// Lock the cookie jar, e.g. to read a cookie or add one by hand.
+    pub fn cookies(&self) -> MutexGuard<'_, CookieJar> {
+        self.state.cookies.lock().expect("Session cookie lock poisoned")
+    }
+
+    /// Authenticate later requests with `auth`, e.g. a token middleware obtained while logging in.
+    /// Replaces any auth set before.
+    pub fn set_auth<T: Middleware + 'static>(&self, auth: T) {
+        *self.state.auth.write().expect("Session auth lock poisoned") = Some(Arc::new(auth));
+    }
+
+    /// Log in with `login`. Cookies set along the way are kept, and it may call [`Session::set_auth`].
+    pub async fn login<L: Login + ?Sized>(&self, login: &L) -> InMemoryResult<()> {
+        login.login(self).await
+    }
+
+    /// Forget the session's cookies and auth.
+    pub fn logout(&self) {
+        self.cookies().clear();
+        *self.state.auth.write().expect("Session auth lock poisoned") = None;
+    }
+}
+
+impl Deref for Session {
+    type Target = Client;
+
+    fn deref(&self) -> &Client {
+        &self.client
+    }
+}
+
+/// A way of logging a [`Session`] in, e.g. submitting a form, or exchanging credentials for a token.
+#[async_trait]
+pub trait Login: Send + Sync {
+    async fn login(&self, session: &Session) -> InMemoryResult<()>;
+}
+
+/// Log in by posting a URL-encoded form, keeping the session cookie the response sets.
+/// Fails if the response is an error status.
+#[derive(Debug, Clone)]
+pub struct FormLogin {
+    url: String,
+    fields: BTreeMap<String, String>,
+}
+
+impl FormLogin {
+    /// Post to `url`, a path relative to the session's base URL or an absolute URL.
+    #[must_use]
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            fields: BTreeMap::new(),
+        }
+    }
+
+    #[must_use]
+    pub fn field(mut self, name: &str, value: &str) -> Self {
+        self.fields.insert(name.to_string(), value.to_string());
+        self
+    }
+}
+
+#[async_trait]
+impl Login for FormLogin {
+    async fn login(&self, session: &Session) -> InMemoryResult<()> {
+        session.post(&self.url).form(&self.fields).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::header::AUTHORIZATION;
+    use http::{StatusCode, Uri};
+
+    use std::time::Duration;
+
+    use crate::clock::TestClock;
+    use crate::middleware::FakeTransport;
+    use crate::{Cookie, Follow, InMemoryBody};
+
+    use super::*;
+
+    #[derive(Debug)]
+    struct TokenAuth;
+
+    #[async_trait]
+    impl Middleware for TokenAuth {
+        async fn handle(&self, mut request: InMemoryRequest, next: Next<'_>) -> ProtocolResult<Response> {
+            request.headers_mut().insert(AUTHORIZATION, HeaderValue::from_static("Bearer t"));
+            next.run(request).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_session() {
+        let login = http::Response::builder()
+            .header("set-cookie", "sid=abc; Path=/; HttpOnly")
+            .header("set-cookie", "pref=dark; Domain=example.com; Path=/app")
+            .body(InMemoryBody::Empty)
+            .unwrap();
+        let transport = FakeTransport::new()
+            .respond(login)
+            .respond_with(StatusCode::OK, InMemoryBody::Empty)
+            .respond_with(StatusCode::OK, InMemoryBody::Empty)
+            .respond_with(StatusCode::OK, InMemoryBody::Empty);
+        let session = Session::new(Client::new().base_url("https://www.example.com").with_middleware(transport.clone()));
+        session.login(&FormLogin::new("/login").field("user", "kurt")).await.unwrap();
+        assert_eq!(session.cookies().get("sid"), Some("abc"));
+
+        session.set_auth(TokenAuth);
+        session.get("/app/settings").cookie("extra", "1").await.unwrap();
+        session.logout();
+        session.get("/app/settings").await.unwrap();
+
+        let requests = transport.requests();
+        assert!(matches!(requests[0].body(), InMemoryBody::Text(t) if t == "user=kurt"));
+        assert_eq!(requests[1].headers()[COOKIE], "extra=1; pref=dark; sid=abc");
+        assert_eq!(requests[1].headers()[AUTHORIZATION], "Bearer t");
+        assert!(!requests[2].headers().contains_key(COOKIE));
+        assert!(!requests[2].headers().contains_key(AUTHORIZATION));
+    }
+
+    #[tokio::test]
+    async fn test_session_redirects() {
+        let redirect = |to: &str, cookie: Option<&str>| {
+            let mut res = http::Response::builder().status(StatusCode::FOUND).header("location", to);
+            if let Some(cookie) = cookie {
+                res = res.header("set-cookie", cookie);
+            }
+            res.body(InMemoryBody::Empty).unwrap()
+        };
+        let transport = FakeTransport::new()
+            .respond(redirect("/home", Some("sid=abc; Path=/")))
+            .respond(redirect("https://other.org/", None))
+            .respond_with(StatusCode::OK, InMemoryBody::Empty);
+        let client = Client::new().base_url("https://www.example.com").with_middleware(Follow).with_middleware(transport.clone());
+        let session = Session::new(client);
+        session.get("/login").await.unwrap();
+
+        let requests = transport.requests();
+        assert_eq!(requests.len(), 3);
+        assert!(!requests[0].headers().contains_key(COOKIE));
+        // The cookie set by the first hop is sent on the next one, to the same host, but not to another host.
+        assert_eq!(requests[1].headers()[COOKIE], "sid=abc");
+        assert!(!requests[2].headers().contains_key(COOKIE));
+        assert_eq!(session.cookies().header(&Uri::from_static("https://www.example.com/")).as_deref(), Some("sid=abc"));
+    }
+
+    #[test]
+    fn test_huge_max_age() {
+        let uri = Uri::from_static("https://example.com/");
+        let mut jar = CookieJar::new();
+        jar.insert(&uri, Cookie::parse("sid=abc; Max-Age=99999999999").unwrap());
+        jar.insert(&uri, Cookie::parse(format!("pref=dark; Max-Age={}", i64::MAX)).unwrap());
+        assert_eq!(jar.header(&uri).as_deref(), Some("sid=abc; pref=dark"));
+    }
+
+    #[tokio::test]
+    async fn test_cookie_expiry_clock() {
+        let transport = FakeTransport::new().respond(http::Response::builder().header("set-cookie", "sid=abc; Max-Age=60").body(InMemoryBody::Empty).unwrap());
+        let clock = TestClock::new();
+        let session = Session::new(Client::new().with_middleware(transport)).clock(clock.clone());
+        session.get("https://example.com/").await.unwrap();
+        let uri = Uri::from_static("https://example.com/");
+        clock.advance(Duration::from_secs(59));
+        assert_eq!(session.cookies().header(&uri).as_deref(), Some("sid=abc"));
+        clock.advance(Duration::from_secs(2));
+        assert_eq!(session.cookies().header(&uri), None);
+    }
+
+    #[test]
+    fn test_public_suffix_domain() {
+        let mut jar = CookieJar::new();
+        jar.insert(&Uri::from_static("https://evil.com/"), Cookie::parse("a=1; Domain=com").unwrap());
+        jar.insert(&Uri::from_static("https://evil.co.uk/"), Cookie::parse("b=1; Domain=.co.uk").unwrap());
+        jar.insert(&Uri::from_static("https://site.github.io/"), Cookie::parse("c=1; Domain=github.io").unwrap());
+        assert_eq!(jar.iter().count(), 0);
+
+        // A host that is itself a public suffix gets a host-only cookie.
+        let uri = Uri::from_static("http://localhost/");
+        jar.insert(&uri, Cookie::parse("d=1; Domain=localhost").unwrap());
+        assert_eq!(jar.header(&uri).as_deref(), Some("d=1"));
+        assert_eq!(jar.header(&Uri::from_static("http://sub.localhost/")), None);
+
+        jar.insert(&Uri::from_static("https://www.example.co.uk/"), Cookie::parse("e=1; Domain=example.co.uk").unwrap());
+        assert_eq!(jar.header(&Uri::from_static("https://api.example.co.uk/")).as_deref(), Some("e=1"));
+    }
+}
@@ -0,0 +1,171 @@
+use std::sync::Arc;
+
+use cookie::time::OffsetDateTime;
+use cookie::{Cookie, Expiration};
+use http::header::SET_COOKIE;
+use http::{HeaderMap, Uri};
+
+use crate::clock::{Clock, SystemClock};
+
+#[derive(Debug, Clone)]
+struct StoredCookie {
+    cookie: Cookie<'static>,
+    domain: String,
+    /// Without a `Domain` attribute, a cookie is only sent back to the exact host that set it.
+    host_only: bool,
+    path: String,
+    expires: Option<OffsetDateTime>,
+}
+
+impl StoredCookie {
+    fn matches(&self, uri: &Uri, now: OffsetDateTime) -> bool {
+        let host = uri.host().unwrap_or_default().to_ascii_lowercase();
+        let domain_match = host == self.domain || (!self.host_only && host.ends_with(&format!(".{}", self.domain)));
+        let secure_ok = !self.cookie.secure().unwrap_or(false) || uri.scheme_str() == Some("https");
+        domain_match && secure_ok && path_matches(&self.path, uri.path()) && self.expires.is_none_or(|e| e > now)
+    }
+}
+
+/// RFC 6265 path matching: `/docs` matches `/docs`, `/docs/` and `/docs/web`, but not `/docsearch`.
+fn path_matches(cookie_path: &str, path: &str) -> bool {
+    path == cookie_path || (path.starts_with(cookie_path) && (cookie_path.ends_with('/') || path[cookie_path.len()..].starts_with('/')))
+}
+
+/// The path a cookie applies to when `Set-Cookie` doesn't give one: the request path up to its last `/`.
+fn default_path(path: &str) -> String {
+    match path.rfind('/') {
+        Some(0) | None => "/".to_string(),
+        Some(i) => path[..i].to_string(),
+    }
+}
+
+/// Whether `domain` is a public suffix like `com` or `co.uk`, which no site may set cookies for.
+/// Single-label domains count as one.
+fn is_public_suffix(domain: &str) -> bool {
+    !domain.contains('.') || psl::suffix_str(domain) == Some(domain)
+}
+
+/// Cookies set by responses, sent back with later requests to matching URLs. Used by [`crate::Session`].
+///
+/// Follows the RFC 6265 rules for `Domain`, `Path`, `Secure`, `Max-Age` and `Expires`.
+/// Cookies with a `Domain` that doesn't match the host setting them, or that is a public suffix, are ignored.
+#[derive(Debug, Clone)]
+pub struct CookieJar {
+    cookies: Vec<StoredCookie>,
+    clock: Arc<dyn Clock>,
+}
+
+impl Default for CookieJar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CookieJar {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            cookies: Vec::new(),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Expire cookies by this clock, e.g. a [`crate::clock::TestClock`].
+    #[must_use]
+    pub fn clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.set_clock(Arc::new(clock));
+        self
+    }
+
+    pub(crate) fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    fn now(&self) -> OffsetDateTime {
+        OffsetDateTime::from(self.clock.now())
+    }
+
+    /// Store a cookie as if a response from `uri` had set it. An expired cookie removes the stored one instead.
+    pub fn insert(&mut self, uri: &Uri, cookie: Cookie<'static>) {
+        let host = uri.host().unwrap_or_default().to_ascii_lowercase();
+        let (domain, host_only) = match cookie.domain() {
+            Some(d) => {
+                let d = d.trim_start_matches('.').to_ascii_lowercase();
+                if host != d && !host.ends_with(&format!(".{d}")) {
+                    return;
+                }
+                // A public suffix can only name the host itself, so the cookie is host-only (RFC 6265, section 5.3).
+                if is_public_suffix(&d) {
+                    if host != d {
+                        return;
+                    }
+                    (d, true)
+                } else {
+                    (d, false)
+                }
+            }
+            None => (host, true),
+        };
+        let path = cookie.path().filter(|p| p.starts_with('/')).map_or_else(|| default_path(uri.path()), str::to_string);
+        let now = self.now();
+        let expires = match (cookie.max_age(), cookie.expires()) {
+            // A max age too large to represent never expires.
+            (Some(max_age), _) => now.checked_add(max_age),
+            (None, Some(Expiration::DateTime(at))) => Some(at),
+            _ => None,
+        };
+        self.cookies.retain(|c| !(c.cookie.name() == cookie.name() && c.domain == domain && c.path == path));
+        if expires.is_none_or(|e| e > now) {
+            self.cookies.push(StoredCookie {
+                cookie,
+                domain,
+                host_only,
+                path,
+                expires,
+            });
+        }
+    }
+
+    /// Store the cookies set by the `Set-Cookie` headers of a response from `uri`.
+    pub fn store(&mut self, uri: &Uri, headers: &HeaderMap) {
+        for value in headers.get_all(SET_COOKIE) {
+            if let Some(cookie) = value.to_str().ok().and_then(|v| Cookie::parse(v.to_string()).ok()) {
+                self.insert(uri, cookie);
+            }
+        }
+    }
+
+    /// The `Cookie` header value for a request to `uri`, if any cookies apply.
+    /// Cookies with longer paths come first.
+    #[must_use]
+    pub fn header(&self, uri: &Uri) -> Option<String> {
+        let now = self.now();
+        let mut matching: Vec<&StoredCookie> = self.cookies.iter().filter(|c| c.matches(uri, now)).collect();
+        if matching.is_empty() {
+            return None;
+        }
+        matching.sort_by_key(|c| std::cmp::Reverse(c.path.len()));
+        let pairs: Vec<String> = matching.iter().map(|c| format!("{}={}", c.cookie.name(), c.cookie.value())).collect();
+        Some(pairs.join("; "))
+    }
+
+    /// The value of the first stored cookie named `name`, for any domain.
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.cookies.iter().find(|c| c.cookie.name() == name).map(|c| c.cookie.value())
+    }
+
+    /// All stored cookies, including any that have expired but not yet been replaced.
+    pub fn iter(&self) -> impl Iterator<Item = &Cookie<'static>> {
+        self.cookies.iter().map(|c| &c.cookie)
+    }
+
+    /// Remove every stored cookie named `name`.
+    pub fn remove(&mut self, name: &str) {
+        self.cookies.retain(|c| c.cookie.name() != name);
+    }
+
+    pub fn clear(&mut self) {
+        self.cookies.clear();
+    }
+}
@@ -0,0 +1,241 @@
+//! Build SOAP 1.1 and 1.2 envelopes and read their responses, for legacy integrations.
+//! ```no_run
+//! # use httpclient::soap::{self, Envelope};
+//! # use httpclient::{Client, Error, InMemoryResponseExt};
+//! # async fn f(client: Client) -> Result<(), Box<dyn std::error::Error>> {
+//! let envelope = Envelope::new("<GetPrice xmlns=\"urn:stock\"><Symbol>ACME</Symbol></GetPrice>");
+//! let res = client.post("/StockService").soap("urn:stock#GetPrice", &envelope).await;
+//! let xml = match res {
+//!     Ok(res) => res.text()?,
+//!     Err(Error::HttpError(res)) => res.text()?, // faults are usually sent with a 500
+//!     Err(e) => return Err(e.into()),
+//! };
+//! let price = soap::body(&xml)?;
+//! # Ok(())
+//! # }
+//! ```
+use std::fmt::{Display, Formatter};
+
+use http::header::CONTENT_TYPE;
+use http::HeaderValue;
+use roxmltree::{Document, Node};
+
+use crate::{InMemoryBody, RequestBuilder};
+
+const SOAP_11: &str = "http://schemas.xmlsoap.org/soap/envelope/";
+const SOAP_12: &str = "http://www.w3.org/2003/05/soap-envelope";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SoapVersion {
+    /// SOAP 1.1: `text/xml`, with the action in a `SOAPAction` header.
+    #[default]
+    V11,
+    /// SOAP 1.2: `application/soap+xml`, with the action as a content type parameter.
+    V12,
+}
+
+impl SoapVersion {
+    fn namespace(self) -> &'static str {
+        match self {
+            SoapVersion::V11 => SOAP_11,
+            SoapVersion::V12 => SOAP_12,
+        }
+    }
+}
+
+/// A SOAP envelope. The header blocks and body are XML fragments, inserted as they are.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Envelope {
+    pub version: SoapVersion,
+    pub headers: Vec<String>,
+    pub body: String,
+}
+
+impl Envelope {
+    #[must_use]
+    pub fn new(body: &str) -> Self {
+        Self {
+            version: SoapVersion::default(),
+            headers: Vec::new(),
+            body: body.to_string(),
+        }
+    }
+
+    #[must_use]
+    pub fn version(mut self, version: SoapVersion) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Add a header block, e.g. a WS-Security `<wsse:Security>` element.
+    #[must_use]
+    pub fn header(mut self, xml: &str) -> Self {
+        self.headers.push(xml.to_string());
+        self
+    }
+
+    #[must_use]
+    pub fn to_xml(&self) -> String {
+        let mut xml = format!(r#"<?xml version="1.0" encoding="utf-8"?><soap:Envelope xmlns:soap="{}">"#, self.version.namespace());
+        if !self.headers.is_empty() {
+            xml.push_str("<soap:Header>");
+            xml.extend(self.headers.iter().map(String::as_str));
+            xml.push_str("</soap:Header>");
+        }
+        xml.push_str("<soap:Body>");
+        xml.push_str(&self.body);
+        xml.push_str("</soap:Body></soap:Envelope>");
+        xml
+    }
+}
+
+/// A SOAP fault, read from either version's layout.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fault {
+    /// `faultcode` in 1.1, `Code/Value` in 1.2, e.g. `soap:Server`.
+    pub code: String,
+    /// `faultstring` in 1.1, the first `Reason/Text` in 1.2.
+    pub reason: String,
+    /// The `detail` element, as XML.
+    pub detail: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum SoapError {
+    InvalidXml(String),
+    /// The response isn't a SOAP envelope with a body.
+    MissingBody,
+    Fault(Fault),
+}
+
+impl std::error::Error for SoapError {}
+
+impl Display for SoapError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SoapError::InvalidXml(msg) => write!(f, "Invalid XML: {msg}"),
+            SoapError::MissingBody => write!(f, "Response is not a SOAP envelope"),
+            SoapError::Fault(fault) => write!(f, "SOAP fault {}: {}", fault.code, fault.reason),
+        }
+    }
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|n| n.is_element() && n.tag_name().name() == name)
+}
+
+fn text(node: Option<Node>) -> String {
+    node.and_then(|n| n.text()).unwrap_or_default().trim().to_string()
+}
+
+/// The XML of everything inside `node`.
+fn inner_xml<'a>(xml: &'a str, node: Node) -> &'a str {
+    match (node.first_child(), node.last_child()) {
+        (Some(first), Some(last)) => &xml[first.range().start..last.range().end],
+        _ => "",
+    }
+}
+
+fn read_fault(xml: &str, fault: Node) -> Fault {
+    if fault.tag_name().namespace() == Some(SOAP_12) {
+        Fault {
+            code: text(child(fault, "Code").and_then(|c| child(c, "Value"))),
+            reason: text(child(fault, "Reason").and_then(|r| child(r, "Text"))),
+            detail: child(fault, "Detail").map(|d| inner_xml(xml, d).to_string()),
+        }
+    } else {
+        Fault {
+            code: text(child(fault, "faultcode")),
+            reason: text(child(fault, "faultstring")),
+            detail: child(fault, "detail").map(|d| inner_xml(xml, d).to_string()),
+        }
+    }
+}
+
+/// The contents of the envelope's body, as XML, or the fault it holds.
+pub fn body(xml: &str) -> Result<String, SoapError> {
+    let doc = Document::parse(xml).map_err(|e| SoapError::InvalidXml(e.to_string()))?;
+    let envelope = doc.root_element();
+    if envelope.tag_name().name() != "Envelope" || !matches!(envelope.tag_name().namespace(), Some(SOAP_11 | SOAP_12)) {
+        return Err(SoapError::MissingBody);
+    }
+    let body = child(envelope, "Body").ok_or(SoapError::MissingBody)?;
+    if let Some(fault) = child(body, "Fault") {
+        return Err(SoapError::Fault(read_fault(xml, fault)));
+    }
+    Ok(inner_xml(xml, body).trim().to_string())
+}
+
+/// The fault in a SOAP response, if it has one.
+#[must_use]
+pub fn fault(xml: &str) -> Option<Fault> {
+    match body(xml) {
+        Err(SoapError::Fault(fault)) => Some(fault),
+        _ => None,
+    }
+}
+
+impl<C> RequestBuilder<'_, C> {
+    /// Send `envelope` as the body, with the content type and action header for its SOAP version.
+    #[must_use]
+    pub fn soap(mut self, action: &str, envelope: &Envelope) -> Self {
+        let action = action.replace('"', "");
+        match envelope.version {
+            SoapVersion::V11 => {
+                self.headers.insert(CONTENT_TYPE, HeaderValue::from_static("text/xml; charset=utf-8"));
+                self.headers
+                    .insert("soapaction", HeaderValue::from_str(&format!("\"{action}\"")).expect("Invalid SOAP action"));
+            }
+            SoapVersion::V12 => {
+                let content_type = format!("application/soap+xml; charset=utf-8; action=\"{action}\"");
+                self.headers.insert(CONTENT_TYPE, HeaderValue::from_str(&content_type).expect("Invalid SOAP action"));
+            }
+        }
+        self.body = Some(InMemoryBody::Text(envelope.to_xml().into()));
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Client;
+
+    use super::*;
+
+    #[test]
+    fn test_soap() {
+        let envelope = Envelope::new("<m:GetPrice xmlns:m=\"urn:stock\"><m:Symbol>ACME</m:Symbol></m:GetPrice>").header("<t:Trace xmlns:t=\"urn:t\">1</t:Trace>");
+        let client = Client::new();
+        let req = client.post("https://example.com/stock").soap("urn:stock#GetPrice", &envelope).build();
+        assert_eq!(req.headers()["soapaction"], "\"urn:stock#GetPrice\"");
+        assert_eq!(req.headers()[CONTENT_TYPE], "text/xml; charset=utf-8");
+        let InMemoryBody::Text(xml) = req.body() else { panic!("Expected a text body") };
+        assert!(xml.contains("<soap:Header><t:Trace"));
+        assert_eq!(body(xml).unwrap(), envelope.body);
+
+        let req = client
+            .post("https://example.com/stock")
+            .soap("urn:stock#GetPrice", &envelope.clone().version(SoapVersion::V12))
+            .build();
+        assert_eq!(req.headers()[CONTENT_TYPE], "application/soap+xml; charset=utf-8; action=\"urn:stock#GetPrice\"");
+        assert!(!req.headers().contains_key("soapaction"));
+
+        let fault11 = r#"<s:Envelope xmlns:s="http://schemas.xmlsoap.org/soap/envelope/"><s:Body><s:Fault>
+            <faultcode>s:Client</faultcode><faultstring>Unknown symbol</faultstring><detail><code>42</code></detail>
+        </s:Fault></s:Body></s:Envelope>"#;
+        assert_eq!(
+            fault(fault11),
+            Some(Fault {
+                code: "s:Client".to_string(),
+                reason: "Unknown symbol".to_string(),
+                detail: Some("<code>42</code>".to_string()),
+            })
+        );
+        let fault12 = r#"<env:Envelope xmlns:env="http://www.w3.org/2003/05/soap-envelope"><env:Body><env:Fault>
+            <env:Code><env:Value>env:Receiver</env:Value></env:Code><env:Reason><env:Text xml:lang="en">Down</env:Text></env:Reason>
+        </env:Fault></env:Body></env:Envelope>"#;
+        assert!(matches!(body(fault12), Err(SoapError::Fault(f)) if f.code == "env:Receiver" && f.reason == "Down" && f.detail.is_none()));
+        assert!(matches!(body("<html/>"), Err(SoapError::MissingBody)));
+        assert!(matches!(body("<a>"), Err(SoapError::InvalidXml(_))));
+    }
+}
@@ -0,0 +1,64 @@
+//! Counts the bytes allocated while a text body is received, cloned, and sent, to check they are shared, not copied.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use http::HeaderValue;
+use httpclient::{Body, InMemoryBody};
+use hyper::body::Bytes;
+
+struct Counting;
+
+thread_local! {
+    static ALLOCATED: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.with(|a| a.set(a.get() + layout.size()));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATED.with(|a| a.set(a.get() + new_size));
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+/// Bytes allocated on this thread while running `f`.
+fn allocated<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    let before = ALLOCATED.with(Cell::get);
+    let value = f();
+    (value, ALLOCATED.with(Cell::get) - before)
+}
+
+const SIZE: usize = 1 << 20;
+
+#[tokio::test(flavor = "current_thread")]
+async fn test_text_body_allocations() {
+    // Like a buffer read from the network, the received bytes are shared, so taking ownership of them copies.
+    let bytes = Bytes::from("a".repeat(SIZE));
+    let _buffer = bytes.clone();
+    let content_type = HeaderValue::from_static("text/plain");
+
+    let before = ALLOCATED.with(Cell::get);
+    let body = Body::Hyper(hyper::Body::from(bytes)).into_content_type(Some(&content_type)).await.unwrap();
+    let received = ALLOCATED.with(Cell::get) - before;
+    assert!(matches!(body, InMemoryBody::Text(_)));
+
+    let (copy, cloned) = allocated(|| body.clone());
+    let (wire, sent) = allocated(|| copy.into_wire_bytes().unwrap());
+    assert_eq!(wire.len(), SIZE);
+
+    // Small bookkeeping allocations are fine; a copy of the body is not.
+    assert!(received < SIZE / 100, "receiving copied the body: {received} bytes");
+    assert!(cloned < SIZE / 100, "cloning copied the body: {cloned} bytes");
+    assert!(sent < SIZE / 100, "sending copied the body: {sent} bytes");
+}